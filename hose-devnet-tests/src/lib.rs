@@ -1,10 +1,19 @@
 #[cfg(test)]
 mod test {
     use anyhow::{Context, ensure};
-    use hose::builder::TxBuilder;
+    use hose::builder::tx::TxBuilderError;
+    use hose::builder::{
+        BatchSubmitter, BuiltTx, ExUnitsCache, OnFailure, Phase, SequentialSpender, SubmitError,
+        TxBuilder,
+    };
+    use hose::contract::{Contract, DatumSource};
+    use hose::ogmios::OgmiosQueries;
     use hose::primitives::{
-        Asset, AssetId, Hash, Output, PubKeyHash, RedeemerPurpose, Script, ScriptKind,
+        Asset, AssetId, AssetsDeltaExt, Datum, ExUnits, Hash, Input, Output, PoolId, PubKeyHash,
+        RedeemerPurpose, Script, ScriptExt, ScriptKind, StakeCredential,
     };
+    use hose::wallet::Wallet;
+    use hose_devnet::chaos::{ChaosConfig, Chaotic};
     use hose_devnet::prelude::*;
     use hose_devnet::{
         empty_redeemer, network_from_network_id, nonced_always_succeeds_script,
@@ -17,6 +26,7 @@ mod test {
     };
     use pallas::ledger::primitives::Fragment;
     use pallas::ledger::primitives::alonzo::NativeScript;
+    use pallas::ledger::primitives::conway::{PlutusData, RedeemerTag, Redeemers, Tx};
     use pallas::ledger::traverse::ComputeHash;
     use tracing::info;
 
@@ -32,6 +42,57 @@ mod test {
         }
     }
 
+    /// Submits `tx` signed by `wallet` only, unlike `DevnetContext::sign_and_submit_tx`, and
+    /// waits until it's included.
+    async fn submit_signed_by(
+        context: &DevnetContext,
+        tx: BuiltTx,
+        wallet: &Wallet,
+    ) -> anyhow::Result<()> {
+        let signed = tx.sign(wallet)?;
+        let (indexer, ogmios) = (
+            context.chaotic(&context.indexer),
+            context.chaotic(&context.ogmios),
+        );
+        BatchSubmitter::new(&indexer, &ogmios)
+            .wait_for_acceptance(true)
+            .confirmation_timeout(context.config.utxo_wait_timeout)
+            .submit_batch(&[signed])
+            .await
+            .remove(0)
+            .map_err(|err| anyhow::anyhow!("failed to submit transaction: {err}"))?;
+        Ok(())
+    }
+
+    /// Checks that the only certificate of `tx` has a redeemer with a budget filled in from
+    /// evaluation.
+    fn ensure_evaluated_cert_redeemer(tx: &[u8]) -> anyhow::Result<()> {
+        let tx = Tx::decode_fragment(tx)?;
+        let redeemers = tx
+            .transaction_witness_set
+            .redeemer
+            .as_ref()
+            .context("transaction has no redeemers")?;
+        let Redeemers::List(redeemers) = &**redeemers else {
+            anyhow::bail!("unexpected redeemer format");
+        };
+        let redeemer = redeemers
+            .iter()
+            .find(|redeemer| redeemer.tag == RedeemerTag::Cert)
+            .context("transaction has no cert redeemer")?;
+        ensure!(
+            redeemer.index == 0,
+            "cert redeemer points to {}",
+            redeemer.index
+        );
+        ensure!(
+            redeemer.ex_units.mem > 0 && redeemer.ex_units.steps > 0,
+            "cert redeemer budget wasn't evaluated: {:?}",
+            redeemer.ex_units
+        );
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn basic_tx(context: &mut DevnetContext) -> anyhow::Result<()> {
         let tx = TxBuilder::new(context.network_id, context.wallet.address())
@@ -44,6 +105,72 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn seeded_utxos_are_indexed(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let script = nonced_always_succeeds_script()?;
+        let datum = minicbor::to_vec(42u64)?;
+        let seeded = context
+            .seed_utxos(&[
+                Output::new(validator_to_address(context, &script), MIN_ADA)
+                    .set_datum(datum.clone()),
+                Output::new(context.wallet.address(), 10_000_000)
+                    .set_script(script.kind, script.bytes.clone()),
+            ])
+            .await?;
+        ensure!(seeded.len() == 2);
+
+        let indexer = context.indexer.lock().await;
+        let with_datum = indexer
+            .utxo(TxOutputPointer::from(&seeded[0]))?
+            .context("seeded datum UTxO not indexed")?;
+        ensure!(with_datum.datum_hash == Some(Datum::new(datum).hash));
+        let with_script = indexer
+            .utxo(TxOutputPointer::from(&seeded[1]))?
+            .context("seeded script UTxO not indexed")?;
+        ensure!(with_script.script.is_some());
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn hook_adds_output_proportional_to_selection(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        const FEE_PER_INPUT: u64 = 1_000;
+        let fee_address = context.wallet.address();
+
+        let hook_address = fee_address.clone();
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_output(Output::new(context.wallet.address(), MIN_ADA))
+            .on_phase(Phase::AfterSelection, move |body, phase_context| {
+                let protocol_fee =
+                    MIN_ADA + FEE_PER_INPUT * phase_context.selected_inputs.len() as u64;
+                body.outputs
+                    .push(Output::new(hook_address.clone(), protocol_fee));
+                Ok(())
+            })
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        // No inputs were added explicitly, so every input was picked by coin selection
+        let expected_fee_output = MIN_ADA + FEE_PER_INPUT * tx.body().inputs().len() as u64;
+        ensure!(
+            tx.body()
+                .outputs()
+                .iter()
+                .filter(|output| output.address == fee_address
+                    && output.lovelace == expected_fee_output)
+                .count()
+                == 1,
+            "expected exactly one hook output for the final selection"
+        );
+
+        // The ledger rejects unbalanced transactions
+        context.sign_and_submit_tx(tx).await?;
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn utxo_with_datum(context: &mut DevnetContext) -> anyhow::Result<()> {
         let cbor = minicbor::to_vec(42)?;
@@ -57,6 +184,21 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn utxo_with_datum_hash(context: &mut DevnetContext) -> anyhow::Result<()> {
+        // The output only holds the hash, the datum goes along as a supplemental witness
+        let (builder, datum_hash) = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_datum_typed(&hose::primitives::BigInt::from(42))?;
+        let tx = builder
+            .add_output(Output::new(context.wallet.address(), MIN_ADA).set_datum_hash(datum_hash))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        context.sign_and_submit_tx(tx).await?;
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn reference_input(context: &DevnetContext) -> anyhow::Result<()> {
         let validator = nonced_always_succeeds_script()?;
@@ -76,8 +218,8 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
 
-        let (signed, res) = context.sign_and_submit_tx(deploy_tx).await?;
-        info!("deployment transaction id: {:#?}", res.transaction.id);
+        let (signed, hash) = context.sign_and_submit_tx(deploy_tx).await?;
+        info!("deployment transaction id: {hash}");
 
         let (ref_output_pointer, spend_output_pointer) = (
             TxOutputPointer::new(signed.hash()?, 0),
@@ -97,11 +239,112 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
 
+        // No script is attached inline, but the language view must still be part of the script
+        // data hash.
+        let decoded =
+            pallas::ledger::primitives::conway::Tx::decode_fragment(&ref_and_spend_tx.cbor())?;
+        ensure!(decoded.transaction_witness_set.plutus_v3_script.is_none());
+        ensure!(decoded.transaction_body.script_data_hash.is_some());
+
         context.sign_and_submit_tx(ref_and_spend_tx).await?;
 
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn referenced_script_is_not_attached_again(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let validator = nonced_always_succeeds_script()?;
+        let validator_address = validator_to_address(context, &validator);
+
+        let deploy_tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_output(
+                Output::new(validator_address.clone(), MIN_ADA)
+                    .set_script(validator.kind, validator.bytes.clone()),
+            )
+            .add_output(Output::new(validator_address, MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let (signed, _) = context.sign_and_submit_tx(deploy_tx).await?;
+        let (ref_output_pointer, spend_output_pointer) = (
+            TxOutputPointer::new(signed.hash()?, 0),
+            TxOutputPointer::new(signed.hash()?, 1),
+        );
+        hose_devnet::wait_until_utxo_exists(context, ref_output_pointer.clone()).await?;
+
+        // The script is both referenced and attached, only the reference must remain
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_reference_input(ref_output_pointer.into())
+            .add_script_input(
+                spend_output_pointer.into(),
+                empty_redeemer(),
+                validator.kind,
+            )
+            .add_script(validator.kind, validator.bytes)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        ensure!(tx.body().scripts().is_empty());
+        let decoded = pallas::ledger::primitives::conway::Tx::decode_fragment(&tx.cbor())?;
+        ensure!(decoded.transaction_witness_set.plutus_v3_script.is_none());
+
+        context.sign_and_submit_tx(tx).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn batched_script_inputs_share_one_script(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        const BATCH_SIZE: usize = 10;
+        let validator = nonced_always_succeeds_script()?;
+        let validator_address = validator_to_address(context, &validator);
+        let seeded = context
+            .seed_utxos(&vec![Output::new(validator_address, MIN_ADA); BATCH_SIZE])
+            .await?;
+
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_script(validator.kind, validator.bytes.clone())
+            .add_script_inputs(
+                validator.kind,
+                seeded
+                    .into_iter()
+                    .map(|input| (input, empty_redeemer()))
+                    .collect(),
+            )
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        let decoded = Tx::decode_fragment(&tx.cbor())?;
+        let scripts = decoded
+            .transaction_witness_set
+            .plutus_v3_script
+            .as_ref()
+            .context("script wasn't attached")?;
+        ensure!(scripts.len() == 1, "{} scripts attached", scripts.len());
+        let redeemers = decoded
+            .transaction_witness_set
+            .redeemer
+            .as_ref()
+            .context("transaction has no redeemers")?;
+        let Redeemers::List(redeemers) = &**redeemers else {
+            anyhow::bail!("unexpected redeemer format");
+        };
+        let spend_redeemers = redeemers
+            .iter()
+            .filter(|redeemer| redeemer.tag == RedeemerTag::Spend)
+            .count();
+        ensure!(
+            spend_redeemers == BATCH_SIZE,
+            "{spend_redeemers} spend redeemers"
+        );
+
+        context.sign_and_submit_tx(tx).await?;
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn register_and_withdraw_zero_script_reward(
         context: &mut DevnetContext,
@@ -112,6 +355,7 @@ mod test {
             .add_script(script.kind, script.bytes.clone())
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
+        ensure_evaluated_cert_redeemer(&registration_tx.cbor())?;
 
         context.sign_and_submit_tx(registration_tx).await?;
 
@@ -121,14 +365,15 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
 
-        let (_, withdrawal_tx_id) = context.sign_and_submit_tx(withdrawal_tx).await?;
-        info!("Withdrawal tx hash: {}", withdrawal_tx_id.transaction.id);
+        let (_, withdrawal_tx_hash) = context.sign_and_submit_tx(withdrawal_tx).await?;
+        info!("Withdrawal tx hash: {withdrawal_tx_hash}");
 
         let deregistration_tx = TxBuilder::new(context.network_id, context.wallet.address())
             .deregister_script_stake(script.hash, script.kind, empty_redeemer())
             .add_script(script.kind, script.bytes.clone())
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
+        ensure_evaluated_cert_redeemer(&deregistration_tx.cbor())?;
 
         context.sign_and_submit_tx(deregistration_tx).await?;
 
@@ -178,18 +423,14 @@ mod test {
 
         let (signed, _res) = context.sign_and_submit_tx(mint_tx).await?;
         let asset_id = AssetId::new(policy, asset_name.clone());
-        let output_idx = signed
-            .body()
-            .outputs
-            .iter()
-            .position(|output| {
+        let output_pointer = signed
+            .find_output(|output| {
                 output
                     .assets
                     .as_ref()
                     .is_some_and(|assets| assets.get(&asset_id) == Some(&mint_amount))
             })
             .context("minted output not found")?;
-        let output_pointer = TxOutputPointer::new(signed.hash()?.0.into(), output_idx as u64);
         hose_devnet::wait_until_utxo_exists(context, output_pointer.clone()).await?;
 
         let burn_tx = TxBuilder::new(context.network_id, context.wallet.address())
@@ -256,6 +497,206 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn minted_assets_partly_kept_as_change(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let asset_name = b"qAda".to_vec();
+
+        // Only part of the new asset is paid out, the rest has to end up in the change output
+        // without coin selection looking for it in the wallet
+        let mint_tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .mint_asset(
+                Asset {
+                    policy,
+                    name: asset_name.clone(),
+                    quantity: 10,
+                },
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(Output::new(context.wallet.address(), MIN_ADA).add_asset(
+                policy,
+                asset_name.clone(),
+                4,
+            )?)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        let asset_id = AssetId::new(policy, asset_name);
+        ensure!(
+            mint_tx
+                .find_output(|output| {
+                    output
+                        .assets
+                        .as_ref()
+                        .is_some_and(|assets| assets.get(&asset_id) == Some(&6))
+                })
+                .is_some(),
+            "change output doesn't hold the rest of the minted asset"
+        );
+        context.sign_and_submit_tx(mint_tx).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn quote_brackets_built_fee(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let builder = || {
+            TxBuilder::new(context.network_id, context.wallet.address())
+                .add_output(Output::new(context.wallet.address(), MIN_ADA))
+        };
+
+        let quote = builder()
+            .quote(&context.indexer, None, &context.protocol_params)
+            .await?;
+        ensure!(!quote.requires_collateral);
+        ensure!(quote.script_budget == Some(ExUnits { mem: 0, steps: 0 }));
+
+        let tx = builder()
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let fee = tx.body().fee().context("built transaction has no fee")?;
+        let (low, high) = quote.estimated_fee_range;
+        ensure!(
+            (low..=high).contains(&fee),
+            "fee {fee} outside of quoted range {low}..={high}"
+        );
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn rebuild_rebalances_a_modified_tx(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_output(Output::new(context.wallet.address(), MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        let rebuilt = tx
+            .rebuild()?
+            .add_output(Output::new(context.wallet.address(), MIN_ADA + 1))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        // The first output, the added one and a single change output
+        ensure!(
+            rebuilt.body().outputs().len() == 3,
+            "expected 3 outputs, got {:?}",
+            rebuilt.body().outputs()
+        );
+        ensure!(rebuilt.body().fee() > tx.body().fee());
+        ensure!(rebuilt.hash()? != tx.hash()?);
+
+        let (signed, _res) = context.sign_and_submit_tx(rebuilt).await?;
+        hose_devnet::wait_until_tx_is_included(context, signed.hash()?).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn evaluations_resolve_to_redeemer_purposes(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .mint_asset(
+                Asset::new(policy, b"EVALUATED".to_vec(), 1),
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(Output::new(context.wallet.address(), MIN_ADA).add_asset(
+                policy,
+                b"EVALUATED".to_vec(),
+                1,
+            )?)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        let evaluation = context
+            .ogmios
+            .evaluate(&tx.cbor())
+            .await
+            .context("failed to evaluate transaction")?;
+        let evaluations = tx.body().script_evaluations(&evaluation)?;
+        ensure!(
+            evaluations.len() == 1 && evaluations[0].purpose == RedeemerPurpose::Mint(policy),
+            "unexpected evaluations {evaluations:?}"
+        );
+        ensure!(evaluations[0].ex_units.mem > 0 && evaluations[0].ex_units.steps > 0);
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn quote_with_warm_cache_skips_evaluation(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let cache = ExUnitsCache::new();
+        let mint_builder = |name: &[u8]| -> anyhow::Result<TxBuilder> {
+            Ok(TxBuilder::new(context.network_id, context.wallet.address())
+                .ex_units_cache(cache.clone())
+                .mint_asset(
+                    Asset::new(policy, name.to_vec(), 1),
+                    policy_script.kind,
+                    empty_redeemer(),
+                )?
+                .add_script(policy_script.kind, policy_script.bytes.clone())
+                .add_output(Output::new(context.wallet.address(), MIN_ADA).add_asset(
+                    policy,
+                    name.to_vec(),
+                    1,
+                )?))
+        };
+
+        let cold_quote = mint_builder(b"FIRST")?
+            .quote(&context.indexer, None, &context.protocol_params)
+            .await?;
+        ensure!(cold_quote.requires_collateral);
+        ensure!(cold_quote.script_budget.is_none());
+
+        let first = mint_builder(b"FIRST")?
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        ensure!(
+            !cache.is_empty(),
+            "build didn't record the evaluated budget"
+        );
+        let (first, _) = context.sign_and_submit_tx(first).await?;
+        hose_devnet::wait_until_utxo_exists(context, TxOutputPointer::new(first.hash()?, 0))
+            .await?;
+
+        // Same script and redeemer, so no evaluation is needed
+        let warm_quote = mint_builder(b"SECOND")?
+            .quote(&context.indexer, None, &context.protocol_params)
+            .await?;
+        let budget = warm_quote
+            .script_budget
+            .context("warm cache didn't provide a budget")?;
+        ensure!(budget.mem > 0 && budget.steps > 0);
+
+        let second = mint_builder(b"SECOND")?
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let fee = second
+            .body()
+            .fee()
+            .context("built transaction has no fee")?;
+        let (low, high) = warm_quote.estimated_fee_range;
+        ensure!(
+            (low..=high).contains(&fee),
+            "fee {fee} outside of quoted range {low}..={high}"
+        );
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn mint_and_burn_same_asset_is_noop(context: &mut DevnetContext) -> anyhow::Result<()> {
         let policy_script = nonced_always_succeeds_script()?;
@@ -263,6 +704,8 @@ mod test {
         let asset_name = b"NETZERO".to_vec();
 
         let built = TxBuilder::new(context.network_id, context.wallet.address())
+            // Attached up front, as it would be for a mint that doesn't cancel out
+            .add_script(policy_script.kind, policy_script.bytes.clone())
             .mint_asset(
                 Asset {
                     policy,
@@ -286,15 +729,20 @@ mod test {
             .await?;
 
         ensure!(
-            built.body().mint.is_empty(),
-            "expected zero mint entries for net-zero mint"
+            built.body().mint().is_net_zero(),
+            "expected a net-zero mint, got {:?}",
+            built.body().mint()
         );
-        if let Some(redeemers) = built.body().redeemers.as_ref() {
+        if let Some(redeemers) = built.body().redeemers() {
             ensure!(
                 !redeemers.contains_key(&RedeemerPurpose::Mint(policy)),
                 "expected no mint redeemer for net-zero mint"
             );
         }
+        ensure!(
+            !built.body().scripts().contains_key(&policy),
+            "expected no policy script witness for net-zero mint"
+        );
 
         context.sign_and_submit_tx(built).await?;
 
@@ -311,16 +759,10 @@ mod test {
 
             let (signed, _res) = context.sign_and_submit_tx(tx).await?;
 
-            let output_idx = signed
-                .body()
-                .outputs
-                .iter()
-                .position(|output| output.lovelace == 42_000_000)
+            let output_pointer = signed
+                .find_output(|output| output.lovelace == 42_000_000)
                 .context("output with 42 ada not found")?;
 
-            let output_pointer: TxOutputPointer =
-                TxOutputPointer::new(signed.hash()?.0.into(), output_idx as u64);
-
             hose_devnet::wait_until_utxo_exists(context, output_pointer.clone()).await?;
             (signed, output_pointer)
         };
@@ -339,9 +781,16 @@ mod test {
 
     #[hose_devnet::test]
     async fn spend_from_always_succeeds_script(context: &mut DevnetContext) -> anyhow::Result<()> {
-        let script_bytes =
-            hex::decode("5101010023259800a518a4d136564004ae69").expect("invalid script bytes");
+        let script_bytes = Script::normalize(
+            ScriptKind::PlutusV3,
+            &hex::decode("5101010023259800a518a4d136564004ae69")?,
+        )?;
         let script = Script::new(ScriptKind::PlutusV3, script_bytes.clone());
+        ensure!(
+            hex::encode(script.hash.0)
+                == "bd3ae991b5aafccafe5ca70758bd36a9b2f872f57f6d3a1ffa0eb777",
+            "unexpected always-succeeds script hash"
+        );
         let script_address = validator_to_address(context, &script);
 
         // Create a transaction that sends some Ada to the script address.
@@ -353,16 +802,10 @@ mod test {
 
             let (signed, _res) = context.sign_and_submit_tx(tx).await?;
 
-            let output_idx = signed
-                .body()
-                .outputs
-                .iter()
-                .position(|output| output.address == script_address)
+            let output_pointer = signed
+                .find_output(|output| output.address == script_address)
                 .context("output with script address not found")?;
 
-            let output_pointer: TxOutputPointer =
-                TxOutputPointer::new(signed.hash()?.0.into(), output_idx as u64);
-
             (signed, output_pointer)
         };
 
@@ -385,25 +828,133 @@ mod test {
     }
 
     #[hose_devnet::test]
-    async fn chain_spend(context: &mut DevnetContext) -> anyhow::Result<()> {
-        const NUM_TXS: u64 = 10;
-        const AMOUNT_STEP: u64 = 1_000_000;
-        // Start with enough to cover decreasing amounts + a buffer for tx fees
-        let start_amount = (NUM_TXS * AMOUNT_STEP) + AMOUNT_STEP;
+    async fn spend_from_always_succeeds_contract(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let script_bytes = Script::normalize(
+            ScriptKind::PlutusV3,
+            &hex::decode("5101010023259800a518a4d136564004ae69")?,
+        )?;
+        let contract: Contract<PlutusData, PlutusData> = Contract::new(
+            Script::new(ScriptKind::PlutusV3, script_bytes),
+            network_from_network_id(context.network_id),
+        );
+        let unit: PlutusData = minicbor::decode(&[0xd8, 0x79, 0x80])?;
+
+        let output_pointer = {
+            let tx = contract
+                .pay(
+                    TxBuilder::new(context.network_id, context.wallet.address()),
+                    42_000_000,
+                    Default::default(),
+                    &unit,
+                )?
+                .build(&context.indexer, &context.ogmios, &context.protocol_params)
+                .await?;
 
-        // 1. Grab initial UTXO from wallet
-        let mut current_pointer = {
-            let indexer = context.indexer.lock().await;
-            let utxos = indexer.address_utxos(&context.wallet.address().to_vec())?;
+            let (signed, _res) = context.sign_and_submit_tx(tx).await?;
+            signed
+                .find_output(|output| output.address == contract.address())
+                .context("output with script address not found")?
+        };
 
-            // Find the UTXO with the most funds to ensure we can cover the chain
-            let output = utxos
-                .into_iter()
-                .max_by_key(|output| output.lovelace)
-                .context("no utxos found in wallet")?;
+        let tx = contract
+            .spend(
+                TxBuilder::new(context.network_id, context.wallet.address()),
+                output_pointer.into(),
+                &unit,
+                DatumSource::Inline,
+            )?
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        context.sign_and_submit_tx(tx).await?;
 
-            anyhow::ensure!(
-                output.lovelace >= start_amount,
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn chain_script_spend_before_inclusion(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let ogmios = OgmiosQueries::new(context.config.ogmios_url.clone());
+        let script_bytes = Script::normalize(
+            ScriptKind::PlutusV3,
+            &hex::decode("5101010023259800a518a4d136564004ae69")?,
+        )?;
+        let script = Script::new(ScriptKind::PlutusV3, script_bytes);
+        let script_address = validator_to_address(context, &script);
+        let policy_script = nonced_always_succeeds_script()?;
+
+        // Lock a freshly minted token at the script, plus a separate UTxO to use as collateral
+        let locked = Output::new(script_address.clone(), 42_000_000)
+            .add_asset(policy_script.hash, b"STATE".to_vec(), 1)?
+            .set_datum(vec![0xd8, 0x79, 0x80]);
+        let collateral = Output::new(context.wallet.address(), 5_000_000);
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .mint_asset(
+                Asset {
+                    policy: policy_script.hash,
+                    name: b"STATE".to_vec(),
+                    quantity: 1,
+                },
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(locked.clone())
+            .add_output(collateral.clone())
+            .build(&context.indexer, &ogmios, &context.protocol_params)
+            .await?;
+        let (first, _) = context.sign_and_submit_tx(tx).await?;
+        let locked_pointer = first
+            .find_output(|output| output.address == script_address)
+            .context("locked output not found")?;
+        let collateral_pointer = first
+            .find_output(|output| {
+                output.address == context.wallet.address() && output.lovelace == 5_000_000
+            })
+            .context("collateral output not found")?;
+
+        // Spend it right away: neither the indexer nor the ledger knows the first transaction's
+        // outputs yet, so evaluation only succeeds if they're passed to Ogmios
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_script_input_resolved(
+                locked_pointer.into(),
+                locked,
+                empty_redeemer(),
+                ScriptKind::PlutusV3,
+            )
+            .add_script(ScriptKind::PlutusV3, script.bytes.clone())
+            .add_known_utxo(collateral_pointer.clone(), collateral)
+            .add_collateral_input(collateral_pointer.into())
+            .build(&context.indexer, &ogmios, &context.protocol_params)
+            .await?;
+        let (second, _) = context.sign_and_submit_tx(tx).await?;
+        hose_devnet::wait_until_tx_is_included(context, second.hash()?).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn chain_spend(context: &mut DevnetContext) -> anyhow::Result<()> {
+        const NUM_TXS: u64 = 10;
+        const AMOUNT_STEP: u64 = 1_000_000;
+        // Start with enough to cover decreasing amounts + a buffer for tx fees
+        let start_amount = (NUM_TXS * AMOUNT_STEP) + AMOUNT_STEP;
+
+        // 1. Grab initial UTXO from wallet
+        let mut current_pointer = {
+            let indexer = context.indexer.lock().await;
+            let utxos = indexer.address_utxos(&context.wallet.address().to_vec())?;
+
+            // Find the UTXO with the most funds to ensure we can cover the chain
+            let output = utxos
+                .into_iter()
+                .max_by_key(|output| output.lovelace)
+                .context("no utxos found in wallet")?;
+
+            anyhow::ensure!(
+                output.lovelace >= start_amount,
                 "wallet does not have enough funds: has {}, needs {}",
                 output.lovelace,
                 start_amount
@@ -441,14 +992,9 @@ mod test {
             // Identify the output to spend in the next iteration.
             // We look for the output with the specific amount we set.
             // Since `next_amount` is distinct and decreased each time, this is safe.
-            let output_idx = signed
-                .body()
-                .outputs
-                .iter()
-                .position(|output| output.lovelace == next_amount)
+            current_pointer = signed
+                .find_output(|output| output.lovelace == next_amount)
                 .context("chained output not found in transaction")?;
-
-            current_pointer = TxOutputPointer::new(signed.hash()?.0.into(), output_idx as u64);
         }
 
         let elapsed = start_time.elapsed();
@@ -462,15 +1008,225 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn sequential_payouts(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let (payee, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        let start: Input = {
+            let indexer = context.indexer.lock().await;
+            let utxos = indexer.address_utxos(&context.wallet.address().to_vec())?;
+            utxos
+                .into_iter()
+                .max_by_key(|output| output.lovelace)
+                .context("no utxos found in wallet")?
+                .into()
+        };
+
+        let spender = SequentialSpender::new(
+            &context.wallet,
+            context.network_id,
+            &context.indexer,
+            &context.ogmios,
+            &context.protocol_params,
+            b"payouts",
+            start.clone(),
+        );
+        let payout = |i: u64| {
+            let address = payee.address();
+            spender.next_tx(move |builder| builder.add_output(Output::new(address, MIN_ADA + i)))
+        };
+
+        // Requested in order but awaited in reverse, links must still follow the request order
+        let (p0, p1, p2, p3, p4) = (payout(0), payout(1), payout(2), payout(3), payout(4));
+        let (r4, r3, r2, r1, r0) = tokio::join!(p4, p3, p2, p1, p0);
+        let links = [r0?, r1?, r2?, r3?, r4?];
+
+        let mut previous_tip = start;
+        for (i, link) in links.iter().enumerate() {
+            let body = link.body();
+            ensure!(
+                body.inputs().contains(&previous_tip),
+                "payout {i} doesn't spend the previous tip"
+            );
+            ensure!(
+                body.outputs()
+                    .iter()
+                    .any(|output| output.address == payee.address()
+                        && output.lovelace == MIN_ADA + i as u64),
+                "payout {i} doesn't pay the requested amount"
+            );
+
+            let tip_index = body
+                .outputs()
+                .iter()
+                .position(|output| {
+                    output.address == context.wallet.address() && output.datum.is_some()
+                })
+                .context("payout has no tip output")?;
+            previous_tip = Input::new(link.hash()?, tip_index as u64);
+        }
+        ensure!(spender.tip().await == previous_tip);
+
+        hose_devnet::wait_until_tx_is_included(context, links[4].hash()?).await?;
+
+        // A request dropped before its turn is skipped rather than stalling the later ones
+        drop(payout(5));
+        let link = tokio::time::timeout(std::time::Duration::from_secs(120), payout(6))
+            .await
+            .context("link requested after a dropped one never resolved")??;
+        ensure!(
+            link.body().inputs().contains(&previous_tip),
+            "link after the dropped one doesn't spend the previous tip"
+        );
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn chained_batch_is_submitted_in_order(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (payee, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+
+        let first = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_output(Output::new(context.wallet.address(), 10 * MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?
+            .sign(&context.wallet)?;
+        let first_hash = first.hash()?;
+        // Spends the first output of `first` before the indexer has seen it
+        let second = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_input_resolved(Input::new(first_hash, 0), first.body().outputs()[0].clone())
+            .add_output(Output::new(payee.address(), MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?
+            .sign(&context.wallet)?;
+        let second_hash = second.hash()?;
+
+        // Listed before what it spends from, the submitter has to reorder them
+        let batch = [second, first];
+        let results = BatchSubmitter::new(&context.indexer, &context.ogmios)
+            .wait_for_acceptance(true)
+            .submit_batch(&batch)
+            .await;
+        ensure!(
+            results == vec![Ok(second_hash), Ok(first_hash)],
+            "unexpected results: {results:?}"
+        );
+
+        // Submitting the batch again is recognized as a duplicate, the change output of `first`
+        // being indexed although `second` spent its first output
+        let results = BatchSubmitter::new(&context.indexer, &context.ogmios)
+            .submit_batch(&batch)
+            .await;
+        ensure!(
+            results
+                == vec![
+                    Err(SubmitError::AlreadySubmitted { hash: second_hash }),
+                    Err(SubmitError::AlreadySubmitted { hash: first_hash }),
+                ],
+            "unexpected results: {results:?}"
+        );
+
+        // Spends the output `second` spent, so it's rejected and what spends from it isn't even
+        // submitted
+        let conflicting = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_input_resolved(
+                Input::new(first_hash, 0),
+                batch[1].body().outputs()[0].clone(),
+            )
+            .add_output(Output::new(payee.address(), 2 * MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?
+            .sign(&context.wallet)?;
+        let conflicting_hash = conflicting.hash()?;
+        let dependent = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_input_resolved(
+                Input::new(conflicting_hash, 0),
+                conflicting.body().outputs()[0].clone(),
+            )
+            .add_output(Output::new(payee.address(), MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?
+            .sign(&context.wallet)?;
+        let dependent_hash = dependent.hash()?;
+
+        let results = BatchSubmitter::new(&context.indexer, &context.ogmios)
+            .on_failure(OnFailure::Continue)
+            .submit_batch(&[dependent, conflicting])
+            .await;
+        ensure!(
+            matches!(
+                results[1],
+                Err(SubmitError::Rejected { hash, .. }) if hash == conflicting_hash
+            ),
+            "unexpected result for the conflicting transaction: {:?}",
+            results[1]
+        );
+        ensure!(
+            results[0]
+                == Err(SubmitError::DependencyFailed {
+                    hash: dependent_hash,
+                    dependency: conflicting_hash,
+                }),
+            "unexpected result for the dependent transaction: {:?}",
+            results[0]
+        );
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn ambiguous_submissions_are_included_exactly_once(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (payee, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        let chaos = ChaosConfig {
+            ambiguous_submit_percent: 50,
+            ..context.config.chaos
+        };
+        let (indexer, ogmios) = (
+            Chaotic::new(&context.indexer, chaos),
+            Chaotic::new(&context.ogmios, chaos),
+        );
+
+        for payment in 0..20 {
+            let tx = TxBuilder::new(context.network_id, context.wallet.address())
+                .add_output(Output::new(payee.address(), MIN_ADA))
+                .build(&indexer, &ogmios, &context.protocol_params)
+                .await?
+                .sign(&context.wallet)?;
+            let hash = tx.hash()?;
+            let results = BatchSubmitter::new(&indexer, &ogmios)
+                .wait_for_acceptance(true)
+                .submit_batch(&[tx])
+                .await;
+            ensure!(
+                results == vec![Ok(hash)],
+                "unexpected result for payment {payment}: {results:?}"
+            );
+        }
+
+        let received = {
+            let indexer = context.indexer.lock().await;
+            indexer.address_utxos(&payee.address().to_vec())?
+        };
+        ensure!(
+            received.len() == 20 && received.iter().all(|utxo| utxo.lovelace == MIN_ADA),
+            "payee received {} payments instead of 20",
+            received.len()
+        );
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn multi_witness_tx(context: &mut DevnetContext) -> anyhow::Result<()> {
         // 1. Create a second wallet
-        // Just increment the last byte of the configured key to get a new one
-        let original_key_hex = &context.config.private_key_hex;
-        let mut key_bytes = hex::decode(original_key_hex)?;
-        key_bytes[0] = key_bytes[0].wrapping_add(1); // Simple perturbation
-        let wallet2 = hose::wallet::WalletBuilder::new(context.config.network)
-            .from_hex(hex::encode(key_bytes))?;
+        let (wallet2, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
 
         // 2. Fund Wallet 2 (send 10 ADA)
         {
@@ -521,6 +1277,300 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn consolidate_faucet_merges_small_utxos(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let dust = vec![Output::new(context.wallet.address(), 1_000_000); 5];
+        context.seed_utxos(&dust).await?;
+        let before = context.faucet_utxo_count().await?;
+
+        // Five of the smallest UTxOs, the seeded ones or as small, are merged into one
+        let merged = context.consolidate_faucet(5).await?;
+        ensure!(merged == 5);
+        ensure!(context.faucet_utxo_count().await? == before - merged + 1);
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn address_pool_spends_and_routes_change(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (pool_wallet, _mnemonic) = hose::wallet::WalletBuilder::new(context.config.network)
+            .address_pool_size(3)
+            .generate()?;
+        let addresses = pool_wallet.all_addresses();
+
+        // Only the second address of the pool is funded
+        let funded = context
+            .seed_utxos(&[Output::new(addresses[1].clone(), 10_000_000)])
+            .await?;
+
+        let tx = TxBuilder::new(context.network_id, pool_wallet.address())
+            .address_pool(addresses.clone())
+            .add_output(Output::new(context.wallet.address(), MIN_ADA))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        ensure!(tx.body().inputs() == funded);
+        // The first address holds nothing yet, so change goes there
+        ensure!(
+            tx.body()
+                .outputs()
+                .iter()
+                .any(|output| output.address == addresses[0]),
+            "change didn't go to the unused address"
+        );
+
+        let signed = tx.sign(&pool_wallet)?;
+        ensure!(signed.missing_signatures().is_empty());
+        context
+            .ogmios
+            .submit(&signed.cbor())
+            .await
+            .map_err(|err| anyhow::anyhow!("failed to submit transaction: {err:?}"))?;
+        hose_devnet::wait_until_tx_is_included(context, signed.hash()?).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn multi_wallet_spends_from_raw_and_derived_keys(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        // A raw key unique to this run, so that the address starts out empty
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos();
+        let raw_key = hose::wallet::WalletBuilder::new(context.config.network)
+            .from_hex(hex::encode([nanos.to_le_bytes(), [7; 16]].concat()))?;
+        let (derived, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        let funded = context
+            .seed_utxos(&[
+                Output::new(raw_key.address(), 10_000_000),
+                Output::new(derived.address(), 10_000_000),
+            ])
+            .await?;
+
+        let wallets = hose::wallet::MultiWallet::new(derived, vec![raw_key])?;
+        // Neither address holds enough on its own
+        let tx = TxBuilder::new(context.network_id, wallets.change_address())
+            .spend_from(wallets.addresses())
+            .add_output(Output::new(context.wallet.address(), 15_000_000))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        ensure!(
+            funded
+                .iter()
+                .all(|input| tx.body().inputs().contains(input))
+        );
+        ensure!(
+            tx.body()
+                .outputs()
+                .iter()
+                .any(|output| output.address == wallets.change_address()),
+            "change didn't go to the primary address"
+        );
+
+        let signed = wallets.sign_all(tx)?;
+        ensure!(signed.witness_count() == 2);
+        ensure!(signed.missing_signatures().is_empty());
+        context
+            .ogmios
+            .submit(&signed.cbor())
+            .await
+            .map_err(|err| anyhow::anyhow!("failed to submit transaction: {err:?}"))?;
+        hose_devnet::wait_until_tx_is_included(context, signed.hash()?).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn multisig_signature_bundles(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let wallets = (0..3)
+            .map(|_| {
+                hose::wallet::WalletBuilder::new(context.config.network)
+                    .generate()
+                    .map(|(wallet, _mnemonic)| wallet)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let script = NativeScript::ScriptNOfK(
+            2,
+            wallets
+                .iter()
+                .map(|wallet| {
+                    NativeScript::ScriptPubkey(address_to_pub_key_hash(wallet.address()).into())
+                })
+                .collect(),
+        );
+        let script_address = Address::Shelley(ShelleyAddress::new(
+            network_from_network_id(context.network_id),
+            ShelleyPaymentPart::Script(script.compute_hash().into()),
+            ShelleyDelegationPart::Null,
+        ));
+        let locked = context
+            .seed_utxos(&[Output::new(script_address, MIN_ADA)])
+            .await?
+            .remove(0);
+
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .add_input(locked)
+            .add_script(
+                ScriptKind::Native,
+                script
+                    .encode_fragment()
+                    .expect("failed to encode native script as cbor"),
+            )
+            // The multisig witnesses aren't part of the fee estimate
+            .fee_padding_lovelace(20_000)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        // Circulated as JSON, like it would be between key holders
+        let request: hose::builder::SigningRequest =
+            serde_json::from_str(&serde_json::to_string(&tx.to_signing_request()?)?)?;
+        let bundles = wallets
+            .iter()
+            .map(|wallet| wallet.sign_request(&request))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let tx = tx
+            .apply_signature_bundle(&bundles[0])?
+            .apply_signature_bundle(&bundles[2])?;
+        // The wallet paying the fee hasn't signed yet
+        ensure!(!tx.is_fully_signed()?);
+        let tx = tx.sign(&context.wallet)?;
+        ensure!(tx.is_fully_signed()?);
+
+        context.sign_and_submit_tx(tx).await?;
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn collateral_from_separate_wallet(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let (collateral_wallet, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        let collateral = context
+            .seed_utxos(&[Output::new(collateral_wallet.address(), 10_000_000)])
+            .await?
+            .remove(0);
+
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let asset_name = b"COLLATERAL".to_vec();
+        let tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .mint_asset(
+                Asset {
+                    policy,
+                    name: asset_name.clone(),
+                    quantity: 1,
+                },
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(
+                Output::new(context.wallet.address(), MIN_ADA).add_asset(policy, asset_name, 1)?,
+            )
+            .collateral_from(collateral_wallet.address())
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        ensure!(
+            tx.body().collateral_inputs() == vec![collateral],
+            "expected the collateral wallet's utxo as collateral, got {:?}",
+            tx.body().collateral_inputs()
+        );
+        let collateral_return = tx
+            .body()
+            .collateral_output()
+            .context("no collateral return output")?;
+        ensure!(
+            collateral_return.address == collateral_wallet.address(),
+            "collateral return doesn't go back to the collateral wallet"
+        );
+        ensure!(
+            tx.missing_signatures()
+                == std::collections::HashSet::from([
+                    address_to_pub_key_hash(context.wallet.address()),
+                    address_to_pub_key_hash(collateral_wallet.address()),
+                ]),
+            "both wallets should be required to sign"
+        );
+
+        let (signed, _res) = context
+            .sign_and_submit_tx_with(tx, &[&collateral_wallet])
+            .await?;
+        let witnesses = Tx::decode_fragment(&signed.cbor())?
+            .transaction_witness_set
+            .vkeywitness
+            .iter()
+            .flat_map(|witnesses| witnesses.iter())
+            .count();
+        ensure!(witnesses == 2, "expected 2 vkey witnesses, got {witnesses}");
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn collateral_is_reserved_before_coin_selection(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (wallet, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        // Exactly two ADA-only UTxOs: one to spend, one to back the collateral
+        let seeded = context
+            .seed_utxos(&[
+                Output::new(wallet.address(), 5_000_000),
+                Output::new(wallet.address(), 20_000_000),
+            ])
+            .await?;
+
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let asset_name = b"RESERVED".to_vec();
+        let tx = TxBuilder::new(context.network_id, wallet.address())
+            .mint_asset(
+                Asset {
+                    policy,
+                    name: asset_name.clone(),
+                    quantity: 1,
+                },
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(
+                Output::new(context.wallet.address(), 12_000_000)
+                    .add_asset(policy, asset_name, 1)?,
+            )
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+
+        ensure!(
+            tx.body().inputs() == vec![seeded[1].clone()],
+            "expected only the larger utxo to be spent, got {:?}",
+            tx.body().inputs()
+        );
+        ensure!(
+            tx.body().collateral_inputs() == vec![seeded[0].clone()],
+            "expected the smaller utxo as collateral, got {:?}",
+            tx.body().collateral_inputs()
+        );
+
+        let signed = tx.sign(&wallet)?;
+        context
+            .ogmios
+            .submit(&signed.cbor())
+            .await
+            .map_err(|err| anyhow::anyhow!("failed to submit transaction: {err:?}"))?;
+        hose_devnet::wait_until_tx_is_included(context, signed.hash()?).await?;
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn delegate_to_unknown_pool(context: &mut DevnetContext) -> anyhow::Result<()> {
         let pub_key_hash = address_to_pub_key_hash(context.wallet.address());
@@ -563,6 +1613,24 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn stake_pools_are_queried(context: &mut DevnetContext) -> anyhow::Result<()> {
+        let ogmios = OgmiosQueries::new(context.config.ogmios_url.clone());
+        let known_pool_id =
+            PoolId::from_bech32("pool13gsek6vd8dhqxsu346zvae30r4mtd77yth07fcc7p49kqc3fd09")?;
+
+        let pools = ogmios.stake_pools().await?;
+        ensure!(
+            pools.contains_key(&known_pool_id),
+            "devnet pool missing from {:?}",
+            pools.keys().collect::<Vec<_>>()
+        );
+        ensure!(ogmios.pool_exists(known_pool_id).await?);
+        ensure!(!ogmios.pool_exists(PoolId(Hash([0xAA; 28]))).await?);
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn delegate_to_known_pool(context: &mut DevnetContext) -> anyhow::Result<()> {
         let pub_key_hash = address_to_pub_key_hash(context.wallet.address());
@@ -585,10 +1653,8 @@ mod test {
         }
 
         // 2. Delegate to valid pool
-        let pool_hex = "8a219b698d3b6e034391ae84cee62f1d76b6fbc45ddfe4e31e0d4b60";
-        let pool_bytes = hex::decode(pool_hex)?;
         let valid_pool_id =
-            Hash::from(TryInto::<[u8; 28]>::try_into(pool_bytes).expect("invalid pool id length"));
+            PoolId::from_bech32("pool13gsek6vd8dhqxsu346zvae30r4mtd77yth07fcc7p49kqc3fd09")?;
 
         let delegation_tx = TxBuilder::new(context.network_id, context.wallet.address())
             .delegate_stake(pub_key_hash, valid_pool_id)
@@ -605,10 +1671,8 @@ mod test {
         context: &mut DevnetContext,
     ) -> anyhow::Result<()> {
         let script = nonced_always_succeeds_script()?;
-        let pool_hex = "8a219b698d3b6e034391ae84cee62f1d76b6fbc45ddfe4e31e0d4b60";
-        let pool_bytes = hex::decode(pool_hex)?;
         let valid_pool_id =
-            Hash::from(TryInto::<[u8; 28]>::try_into(pool_bytes).expect("invalid pool id length"));
+            PoolId::from_bech32("pool13gsek6vd8dhqxsu346zvae30r4mtd77yth07fcc7p49kqc3fd09")?;
 
         // 1. Register Script Stake
         let registration_tx = TxBuilder::new(context.network_id, context.wallet.address())
@@ -646,6 +1710,176 @@ mod test {
         Ok(())
     }
 
+    #[hose_devnet::test]
+    async fn stake_info_follows_registration_and_delegation(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let ogmios = OgmiosQueries::new(context.config.ogmios_url.clone());
+        let script = nonced_always_succeeds_script()?;
+        let credential = StakeCredential::Script(script.hash);
+        let pool_id =
+            PoolId::from_bech32("pool13gsek6vd8dhqxsu346zvae30r4mtd77yth07fcc7p49kqc3fd09")?;
+
+        let info = ogmios.stake_info(&[credential]).await?.remove(0);
+        ensure!(!info.registered, "fresh credential is registered: {info:?}");
+
+        let registration_tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .register_script_stake(script.hash, script.kind, Some(empty_redeemer()))
+            .add_script(script.kind, script.bytes.clone())
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let (registration_tx, _) = context.sign_and_submit_tx(registration_tx).await?;
+        hose_devnet::wait_until_tx_is_included(context, registration_tx.hash()?).await?;
+
+        let info = ogmios.stake_info(&[credential]).await?.remove(0);
+        ensure!(info.registered, "credential isn't registered: {info:?}");
+        ensure!(
+            info.deposit.is_none_or(|deposit| {
+                deposit == context.protocol_params.stake_credential_deposit.lovelace
+            }),
+            "unexpected deposit: {info:?}"
+        );
+        ensure!(
+            info.pool.is_none(),
+            "credential delegates already: {info:?}"
+        );
+        ensure!(info.rewards == 0);
+
+        let delegation_tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .delegate_script_stake(
+                script.hash,
+                pool_id,
+                script.kind,
+                Some(empty_redeemer()),
+                None,
+            )
+            .add_script(script.kind, script.bytes.clone())
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let (delegation_tx, _) = context.sign_and_submit_tx(delegation_tx).await?;
+        hose_devnet::wait_until_tx_is_included(context, delegation_tx.hash()?).await?;
+
+        let info = ogmios.stake_info(&[credential]).await?.remove(0);
+        ensure!(info.registered, "credential isn't registered: {info:?}");
+        ensure!(
+            info.pool == Some(pool_id),
+            "delegation not reflected: {info:?}"
+        );
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn utxos_holding_a_state_token_are_found(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let ogmios = OgmiosQueries::new(context.config.ogmios_url.clone());
+        let policy_script = nonced_always_succeeds_script()?;
+        let policy = policy_script.hash;
+        let name = b"STATE".to_vec();
+
+        let state_output =
+            || Output::new(context.wallet.address(), MIN_ADA).add_asset(policy, name.clone(), 1);
+        let mint_tx = TxBuilder::new(context.network_id, context.wallet.address())
+            .mint_asset(
+                Asset {
+                    policy,
+                    name: name.clone(),
+                    quantity: 3,
+                },
+                policy_script.kind,
+                empty_redeemer(),
+            )?
+            .add_script(policy_script.kind, policy_script.bytes.clone())
+            .add_output(state_output()?)
+            .add_output(state_output()?)
+            .add_output(state_output()?)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        let (mint_tx, _) = context.sign_and_submit_tx(mint_tx).await?;
+        let tx_hash = mint_tx.hash()?;
+        hose_devnet::wait_until_tx_is_included(context, tx_hash).await?;
+
+        let mut found = ogmios
+            .utxos_by_asset(policy, Some(&name))
+            .await?
+            .into_iter()
+            .map(|(input, _)| input)
+            .collect::<Vec<_>>();
+        found.sort_by_key(|input| input.index);
+        let expected = (0..3)
+            .map(|index| Input::new(tx_hash, index))
+            .collect::<Vec<_>>();
+        ensure!(found == expected, "found {found:?}, expected {expected:?}");
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn underfunded_stake_registration_reports_the_deposit(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (wallet, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        // Enough for the fee, but not for the deposit on top of it
+        context
+            .seed_utxos(&[Output::new(wallet.address(), 2_000_000)])
+            .await?;
+
+        let result = TxBuilder::new(context.network_id, wallet.address())
+            .register_stake(address_to_pub_key_hash(wallet.address()))
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await;
+
+        let err = result.err().context("registration should be underfunded")?;
+        let Some(TxBuilderError::InsufficientFundsForDeposit {
+            deposit, available, ..
+        }) = err.downcast_ref::<TxBuilderError>()
+        else {
+            anyhow::bail!("unexpected error: {err:?}");
+        };
+        ensure!(*deposit == context.protocol_params.stake_credential_deposit.lovelace);
+        ensure!(*available == 2_000_000);
+
+        Ok(())
+    }
+
+    #[hose_devnet::test]
+    async fn deregistration_refund_funds_the_transaction(
+        context: &mut DevnetContext,
+    ) -> anyhow::Result<()> {
+        let (wallet, _mnemonic) =
+            hose::wallet::WalletBuilder::new(context.config.network).generate()?;
+        let pub_key_hash = address_to_pub_key_hash(wallet.address());
+        let seeded = context
+            .seed_utxos(&[
+                Output::new(wallet.address(), 5_000_000),
+                Output::new(wallet.address(), 1_000_000),
+            ])
+            .await?;
+        let registration_tx = TxBuilder::new(context.network_id, wallet.address())
+            .add_input(seeded[0].clone())
+            .register_stake(pub_key_hash)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        submit_signed_by(context, registration_tx, &wallet).await?;
+
+        // 1 ADA doesn't cover the fee and the change output, only the refund makes up for it.
+        // Without it, the larger change UTxO of the registration would be selected as well.
+        let deregistration_tx = TxBuilder::new(context.network_id, wallet.address())
+            .add_input(seeded[1].clone())
+            .deregister_stake(pub_key_hash)
+            .build(&context.indexer, &context.ogmios, &context.protocol_params)
+            .await?;
+        ensure!(deregistration_tx.body().inputs() == vec![seeded[1].clone()]);
+        let fee = deregistration_tx.body().fee().context("fee is set")?;
+        let deposit = context.protocol_params.stake_credential_deposit.lovelace;
+        ensure!(deregistration_tx.body().outputs()[0].lovelace == 1_000_000 + deposit - fee);
+        submit_signed_by(context, deregistration_tx, &wallet).await?;
+
+        Ok(())
+    }
+
     #[hose_devnet::test]
     async fn register_and_deregister_stake_key(context: &mut DevnetContext) -> anyhow::Result<()> {
         let pub_key_hash = address_to_pub_key_hash(context.wallet.address());
@@ -690,14 +1924,9 @@ mod test {
             .await?;
         let (signed_setup, _) = context.sign_and_submit_tx(setup_tx).await?;
 
-        let output_idx = signed_setup
-            .body()
-            .outputs
-            .iter()
-            .position(|output| output.address == script_address)
+        let script_input = signed_setup
+            .find_output(|output| output.address == script_address)
             .context("script output not found")?;
-        let script_input: TxOutputPointer =
-            TxOutputPointer::new(signed_setup.hash()?.0.into(), output_idx as u64);
 
         hose_devnet::wait_until_utxo_exists(context, script_input.clone()).await?;
 
@@ -708,7 +1937,7 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
 
-        let collateral_inputs = &spend_tx.body().collateral_inputs;
+        let collateral_inputs = spend_tx.body().collateral_inputs();
 
         anyhow::ensure!(
             !collateral_inputs.is_empty(),
@@ -738,8 +1967,7 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
         let (signed, _res) = context.sign_and_submit_tx(pay_to_script_tx).await?;
-        let script_output_pointer =
-            hydrant::primitives::TxOutputPointer::new(signed.hash()?.into(), 0);
+        let script_output_pointer = hydrant::primitives::TxOutputPointer::new(signed.hash()?, 0);
         hose_devnet::wait_until_utxo_exists(context, script_output_pointer.clone()).await?;
 
         let spend_from_script_tx =
@@ -749,6 +1977,16 @@ mod test {
                 .build(&context.indexer, &context.ogmios, &context.protocol_params)
                 .await?;
 
+        // Native scripts are validated in phase-1, so there's nothing for collateral to back
+        ensure!(
+            spend_from_script_tx.body().collateral_inputs().is_empty(),
+            "native script spend should not select collateral"
+        );
+        ensure!(
+            spend_from_script_tx.body().collateral_output().is_none(),
+            "native script spend should not have a collateral return"
+        );
+
         context.sign_and_submit_tx(spend_from_script_tx).await?;
 
         Ok(())
@@ -769,7 +2007,7 @@ mod test {
 
         match context.sign_and_submit_tx(registration_tx).await {
             Ok((signed, _res)) => {
-                hose_devnet::wait_until_tx_is_included(context, signed.hash()?.into()).await?;
+                hose_devnet::wait_until_tx_is_included(context, signed.hash()?).await?;
             }
             Err(e) => {
                 let err_msg = e.to_string();
@@ -786,6 +2024,15 @@ mod test {
             .build(&context.indexer, &context.ogmios, &context.protocol_params)
             .await?;
 
+        ensure!(
+            withdrawal_tx.body().collateral_inputs().is_empty(),
+            "native script withdrawal should not select collateral"
+        );
+        ensure!(
+            withdrawal_tx.body().collateral_output().is_none(),
+            "native script withdrawal should not have a collateral return"
+        );
+
         context.sign_and_submit_tx(withdrawal_tx).await?;
 
         Ok(())
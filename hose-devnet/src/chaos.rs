@@ -0,0 +1,99 @@
+//! Failure injection, to test retry and confirmation logic against an unreliable node
+//!
+//! `Chaotic` wraps the providers hose builds and submits with, so the code under test runs
+//! unchanged: `DevnetContext::chaotic` wraps them with the settings of the profile, see
+//! `Config::chaos`.
+//!
+//! Rollbacks aren't injected: the node is managed outside of the tests, which can't restart it
+//! from an earlier chain db snapshot.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use hose::builder::{Evaluator, Rejection, Submitter, UtxoSource};
+use hose::primitives::{Input, Output};
+use hydrant::primitives::{TxOutput, TxOutputPointer};
+use ogmios_client::method::evaluate::Evaluation;
+use rand::Rng as _;
+
+/// What goes wrong, nothing by default.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ChaosConfig {
+    /// Chance, in percent, that a submission the node accepted is reported as a transport error
+    pub ambiguous_submit_percent: u8,
+    /// Delay added to every evaluation and query
+    pub latency: Duration,
+}
+
+impl ChaosConfig {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// A provider that fails as configured by `ChaosConfig`.
+pub struct Chaotic<'a, T> {
+    inner: &'a T,
+    chaos: ChaosConfig,
+}
+
+impl<'a, T> Chaotic<'a, T> {
+    pub fn new(inner: &'a T, chaos: ChaosConfig) -> Self {
+        Self { inner, chaos }
+    }
+
+    async fn delay(&self) {
+        if !self.chaos.latency.is_zero() {
+            tokio::time::sleep(self.chaos.latency).await;
+        }
+    }
+}
+
+/// Submits for real before reporting a transport error, which leaves the caller unable to tell
+/// whether the transaction went through.
+impl<T: Submitter> Submitter for Chaotic<'_, T> {
+    async fn submit(&self, tx: &[u8]) -> Result<(), Rejection> {
+        self.inner.submit(tx).await?;
+        let percent = self.chaos.ambiguous_submit_percent.min(100);
+        if rand::thread_rng().gen_ratio(percent.into(), 100) {
+            tracing::info!("Chaos: reporting a submitted transaction as a transport error");
+            return Err(Rejection::Transport(
+                "connection dropped by the chaos layer".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Evaluator> Evaluator for Chaotic<'_, T> {
+    async fn evaluate(
+        &self,
+        tx: &[u8],
+        additional_utxos: &[(Input, Output)],
+    ) -> Result<Vec<Evaluation>> {
+        self.delay().await;
+        self.inner.evaluate(tx, additional_utxos).await
+    }
+
+    async fn tip_slot(&self) -> Result<u64> {
+        self.delay().await;
+        self.inner.tip_slot().await
+    }
+
+    async fn treasury_value(&self) -> Result<Option<u64>> {
+        self.delay().await;
+        self.inner.treasury_value().await
+    }
+}
+
+impl<T: UtxoSource> UtxoSource for Chaotic<'_, T> {
+    async fn utxos(&self, pointers: &[TxOutputPointer]) -> Result<Vec<TxOutput>> {
+        self.delay().await;
+        self.inner.utxos(pointers).await
+    }
+
+    async fn address_utxos(&self, address: &[u8]) -> Result<Vec<TxOutput>> {
+        self.delay().await;
+        self.inner.address_utxos(address).await
+    }
+}
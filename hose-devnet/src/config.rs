@@ -1,37 +1,366 @@
-use std::path::PathBuf;
+//! Devnet configuration
+//!
+//! Settings are read from a TOML profile, at the path in `HOSE_DEVNET_PROFILE` or at
+//! `hose-devnet.toml` in the working directory, and every setting can be overridden by its
+//! environment variable:
+//!
+//! ```toml
+//! network = "testnet"
+//! db_path = "./db"
+//! node_host = "localhost:3001"
+//! ogmios_url = "http://localhost:1337"
+//! genesis_byron_path = "config/byron-genesis.json"
+//! genesis_shelley_path = "config/shelley-genesis.json"
+//! # optional, see `DevnetContext::consolidate_faucet`
+//! consolidate_faucet_above = 200
+//! # optional, how long and how often `wait_until_utxo_exists` polls, in milliseconds
+//! utxo_wait_timeout_ms = 60000
+//! utxo_poll_interval_ms = 250
+//! # optional, first script nonce, `HOSE_DEVNET_NONCE` in the environment
+//! nonce = 1234
+//! # optional, see `chaos`: chance in percent of a submission being reported as a transport
+//! # error after going through, and delay added to evaluations and queries in milliseconds
+//! chaos_ambiguous_submit_percent = 50
+//! chaos_latency_ms = 200
+//!
+//! [faucet_key]
+//! hex = "..."
+//! # or `bech32 = "..."`, or `keystore = { path = "...", passphrase = "..." }`
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Context as _;
-use clap::Parser;
+use hose::wallet::{Wallet, WalletBuilder};
 use hydrant::GenesisConfig;
 use pallas::ledger::addresses::Network;
+use serde::Deserialize;
+use url::Url;
+
+use crate::chaos::ChaosConfig;
 
-#[derive(Parser, Debug, PartialEq, Eq, Clone)]
+/// Environment variable holding the path to the profile
+pub const PROFILE_ENV: &str = "HOSE_DEVNET_PROFILE";
+/// Profile read when `HOSE_DEVNET_PROFILE` isn't set, if it exists
+pub const DEFAULT_PROFILE_PATH: &str = "hose-devnet.toml";
+/// How long `wait_until_utxo_exists` waits when the profile doesn't say
+pub const DEFAULT_UTXO_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often `wait_until_utxo_exists` polls the indexer when the profile doesn't say
+pub const DEFAULT_UTXO_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Validated configuration, see `DevnetProfile`
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Config {
-    #[arg(long, env)]
-    pub private_key_hex: String,
+    pub faucet_key: FaucetKey,
 
     /// The network to use. Either `testnet` or `mainnet`. For devnet tests, you should set this to `testnet`.
-    #[arg(long, env, value_parser = parse_network)]
     pub network: Network,
 
-    #[arg(long, env)]
     pub db_path: PathBuf,
 
-    #[arg(long, env)]
     pub node_host: String,
 
-    #[arg(long, env)]
     pub ogmios_url: String,
 
     /// Path to the byron genesis file. This file can be found in the local-testnet repository.
-    #[arg(long, env)]
     pub genesis_byron_path: Option<PathBuf>,
 
     /// Path to the shelley genesis file. This file can be found in the local-testnet repository.
-    #[arg(long, env)]
     pub genesis_shelley_path: Option<PathBuf>,
+
+    /// When the faucet holds more UTxOs than this, they're consolidated during setup.
+    pub consolidate_faucet_above: Option<usize>,
+
+    /// How long to wait for a UTxO to be indexed before giving up.
+    pub utxo_wait_timeout: Duration,
+
+    /// Delay between two lookups while waiting for a UTxO.
+    pub utxo_poll_interval: Duration,
+
+    /// First nonce of `nonced_always_succeeds_script`, seeded from the time when unset.
+    pub nonce: Option<u64>,
+
+    /// Failures injected by `DevnetContext::chaotic`, none by default.
+    pub chaos: ChaosConfig,
+}
+
+impl Config {
+    /// Reads the profile, applies the environment overrides and validates the result.
+    pub fn load() -> anyhow::Result<Self> {
+        let profile = match std::env::var(PROFILE_ENV) {
+            Ok(path) => DevnetProfile::read(path)?,
+            Err(_) if Path::new(DEFAULT_PROFILE_PATH).exists() => {
+                DevnetProfile::read(DEFAULT_PROFILE_PATH)?
+            }
+            Err(_) => DevnetProfile::default(),
+        };
+        Ok(profile
+            .with_env_overrides(|name| std::env::var(name).ok())
+            .validate()?)
+    }
+
+    /// Wallet holding the funds the tests spend
+    pub fn faucet_wallet(&self) -> anyhow::Result<Wallet> {
+        let builder = WalletBuilder::new(self.network);
+        let wallet = match &self.faucet_key {
+            FaucetKey::Hex(hex) => builder.from_hex(hex.clone()),
+            FaucetKey::Bech32(bech32) => builder.from_bech32(bech32.clone()),
+            FaucetKey::Keystore { path, passphrase } => builder.from_keystore(path, passphrase),
+        };
+        wallet.context("failed to load the faucet key")
+    }
+}
+
+/// Key of the wallet funding the tests
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum FaucetKey {
+    /// Raw payment key, 32 or 64 bytes
+    Hex(String),
+    Bech32(String),
+    /// Keystore written by `Wallet::save_keystore`
+    Keystore {
+        path: PathBuf,
+        passphrase: String,
+    },
+}
+
+/// Devnet settings as written in a profile, before validation. Every field can be left out of
+/// the file and set through its environment variable instead.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DevnetProfile {
+    pub faucet_key: Option<FaucetKey>,
+    pub network: Option<String>,
+    pub db_path: Option<PathBuf>,
+    pub node_host: Option<String>,
+    pub ogmios_url: Option<String>,
+    pub genesis_byron_path: Option<PathBuf>,
+    pub genesis_shelley_path: Option<PathBuf>,
+    pub consolidate_faucet_above: Option<usize>,
+    pub utxo_wait_timeout_ms: Option<u64>,
+    pub utxo_poll_interval_ms: Option<u64>,
+    pub nonce: Option<u64>,
+    pub chaos_ambiguous_submit_percent: Option<u8>,
+    pub chaos_latency_ms: Option<u64>,
+    /// Environment overrides that couldn't be parsed, reported by `validate`
+    #[serde(skip)]
+    malformed_env: Vec<String>,
+}
+
+impl DevnetProfile {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read devnet profile {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("failed to parse devnet profile {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Replaces the fields whose environment variable is set, as looked up by `var`.
+    ///
+    /// The faucet key is taken from `PRIVATE_KEY_HEX` or `PRIVATE_KEY_BECH32`, the other fields
+    /// from their name in upper case, e.g. `OGMIOS_URL`, except for the nonce which is taken from
+    /// `HOSE_DEVNET_NONCE`.
+    pub fn with_env_overrides(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(hex) = var("PRIVATE_KEY_HEX") {
+            self.faucet_key = Some(FaucetKey::Hex(hex));
+        } else if let Some(bech32) = var("PRIVATE_KEY_BECH32") {
+            self.faucet_key = Some(FaucetKey::Bech32(bech32));
+        }
+        if let Some(network) = var("NETWORK") {
+            self.network = Some(network);
+        }
+        if let Some(db_path) = var("DB_PATH") {
+            self.db_path = Some(db_path.into());
+        }
+        if let Some(node_host) = var("NODE_HOST") {
+            self.node_host = Some(node_host);
+        }
+        if let Some(ogmios_url) = var("OGMIOS_URL") {
+            self.ogmios_url = Some(ogmios_url);
+        }
+        if let Some(path) = var("GENESIS_BYRON_PATH") {
+            self.genesis_byron_path = Some(path.into());
+        }
+        if let Some(path) = var("GENESIS_SHELLEY_PATH") {
+            self.genesis_shelley_path = Some(path.into());
+        }
+        const INTEGER: &str = "a non-negative integer";
+        const MILLIS: &str = "a number of milliseconds";
+        const PERCENT: &str = "a percentage";
+        if let Some(threshold) = self.parse_env(&var, "CONSOLIDATE_FAUCET_ABOVE", INTEGER) {
+            self.consolidate_faucet_above = Some(threshold);
+        }
+        if let Some(timeout) = self.parse_env(&var, "UTXO_WAIT_TIMEOUT_MS", MILLIS) {
+            self.utxo_wait_timeout_ms = Some(timeout);
+        }
+        if let Some(interval) = self.parse_env(&var, "UTXO_POLL_INTERVAL_MS", MILLIS) {
+            self.utxo_poll_interval_ms = Some(interval);
+        }
+        if let Some(nonce) = self.parse_env(&var, "HOSE_DEVNET_NONCE", INTEGER) {
+            self.nonce = Some(nonce);
+        }
+        if let Some(percent) = self.parse_env(&var, "CHAOS_AMBIGUOUS_SUBMIT_PERCENT", PERCENT) {
+            self.chaos_ambiguous_submit_percent = Some(percent);
+        }
+        if let Some(latency) = self.parse_env(&var, "CHAOS_LATENCY_MS", MILLIS) {
+            self.chaos_latency_ms = Some(latency);
+        }
+        self
+    }
+
+    /// Parses the environment variable `name`, if set. Values that aren't `expected` are
+    /// reported by `validate` instead, leaving the profile's value in place.
+    fn parse_env<T: FromStr>(
+        &mut self,
+        var: &impl Fn(&str) -> Option<String>,
+        name: &str,
+        expected: &str,
+    ) -> Option<T> {
+        let value = var(name)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            self.malformed_env
+                .push(format!("`{name}` must be {expected}, got {value}"));
+        }
+        parsed
+    }
+
+    /// Checks every field, reporting all the problems found rather than only the first one.
+    pub fn validate(self) -> Result<Config, ProfileErrors> {
+        let mut errors = self.malformed_env;
+        let mut require = |field: &str, env: &str| {
+            errors.push(format!(
+                "`{field}` is missing, set it in the profile or with {env}"
+            ));
+        };
+
+        if self.faucet_key.is_none() {
+            require("faucet_key", "PRIVATE_KEY_HEX");
+        }
+        if self.network.is_none() {
+            require("network", "NETWORK");
+        }
+        if self.db_path.is_none() {
+            require("db_path", "DB_PATH");
+        }
+        if self.node_host.is_none() {
+            require("node_host", "NODE_HOST");
+        }
+        if self.ogmios_url.is_none() {
+            require("ogmios_url", "OGMIOS_URL");
+        }
+
+        let network = match self.network.as_deref().map(parse_network) {
+            Some(Ok(network)) => Some(network),
+            Some(Err(err)) => {
+                errors.push(format!("`network`: {err}"));
+                None
+            }
+            None => None,
+        };
+        let malformed_hex =
+            |hex: &str| !matches!(hex::decode(hex).map(|key| key.len()), Ok(32 | 64));
+        if matches!(&self.faucet_key, Some(FaucetKey::Hex(hex)) if malformed_hex(hex)) {
+            errors.push("`faucet_key.hex` must be 32 or 64 bytes of hex".to_string());
+        }
+        if let Some(Err(err)) = self.ogmios_url.as_deref().map(Url::parse) {
+            errors.push(format!("`ogmios_url` is not a valid URL: {err}"));
+        }
+        if self
+            .consolidate_faucet_above
+            .is_some_and(|threshold| threshold < 2)
+        {
+            errors.push("`consolidate_faucet_above` must be a number of at least 2".to_string());
+        }
+        for (field, millis) in [
+            ("utxo_wait_timeout_ms", self.utxo_wait_timeout_ms),
+            ("utxo_poll_interval_ms", self.utxo_poll_interval_ms),
+        ] {
+            if millis == Some(0) {
+                errors.push(format!(
+                    "`{field}` must be a positive number of milliseconds"
+                ));
+            }
+        }
+        if self
+            .chaos_ambiguous_submit_percent
+            .is_some_and(|percent| percent > 100)
+        {
+            errors.push("`chaos_ambiguous_submit_percent` must be at most 100".to_string());
+        }
+        for (field, path) in [
+            ("genesis_byron_path", &self.genesis_byron_path),
+            ("genesis_shelley_path", &self.genesis_shelley_path),
+        ] {
+            if let Some(path) = path.as_ref().filter(|path| !path.is_file()) {
+                errors.push(format!("`{field}` {} does not exist", path.display()));
+            }
+        }
+
+        match (
+            self.faucet_key,
+            network,
+            self.db_path,
+            self.node_host,
+            self.ogmios_url,
+        ) {
+            (Some(faucet_key), Some(network), Some(db_path), Some(node_host), Some(ogmios_url))
+                if errors.is_empty() =>
+            {
+                Ok(Config {
+                    faucet_key,
+                    network,
+                    db_path,
+                    node_host,
+                    ogmios_url,
+                    genesis_byron_path: self.genesis_byron_path,
+                    genesis_shelley_path: self.genesis_shelley_path,
+                    consolidate_faucet_above: self.consolidate_faucet_above,
+                    utxo_wait_timeout: self
+                        .utxo_wait_timeout_ms
+                        .map_or(DEFAULT_UTXO_WAIT_TIMEOUT, Duration::from_millis),
+                    utxo_poll_interval: self
+                        .utxo_poll_interval_ms
+                        .map_or(DEFAULT_UTXO_POLL_INTERVAL, Duration::from_millis),
+                    nonce: self.nonce,
+                    chaos: ChaosConfig {
+                        ambiguous_submit_percent: self
+                            .chaos_ambiguous_submit_percent
+                            .unwrap_or_default(),
+                        latency: Duration::from_millis(self.chaos_latency_ms.unwrap_or_default()),
+                    },
+                })
+            }
+            _ => Err(ProfileErrors(errors)),
+        }
+    }
+}
+
+/// Every problem found while validating a `DevnetProfile`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProfileErrors(pub Vec<String>);
+
+impl fmt::Display for ProfileErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid devnet profile:")?;
+        for error in &self.0 {
+            writeln!(f, "  - {error}")?;
+        }
+        Ok(())
+    }
 }
 
+impl std::error::Error for ProfileErrors {}
+
 fn parse_network(s: &str) -> Result<Network, String> {
     let s = s.to_lowercase();
     match s.as_ref() {
@@ -71,3 +400,165 @@ pub fn genesis_config(config: &Config) -> anyhow::Result<GenesisConfig> {
     )?;
     GenesisConfig::new(Some(&byron_str), Some(&shelley_str)).map_err(anyhow::Error::from)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"
+        network = "testnet"
+        db_path = "./db"
+        node_host = "localhost:3001"
+        ogmios_url = "http://localhost:1337"
+
+        [faucet_key]
+        hex = "0000000000000000000000000000000000000000000000000000000000000000"
+    "#;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn valid_profile() {
+        let config = DevnetProfile::parse(VALID)
+            .unwrap()
+            .with_env_overrides(no_env)
+            .validate()
+            .unwrap();
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.db_path, PathBuf::from("./db"));
+        assert_eq!(config.node_host, "localhost:3001");
+        assert_eq!(config.ogmios_url, "http://localhost:1337");
+        assert_eq!(config.faucet_key, FaucetKey::Hex("00".repeat(32)));
+        assert_eq!(config.genesis_byron_path, None);
+        assert_eq!(config.utxo_wait_timeout, DEFAULT_UTXO_WAIT_TIMEOUT);
+        assert_eq!(config.utxo_poll_interval, DEFAULT_UTXO_POLL_INTERVAL);
+        assert!(!config.chaos.is_enabled());
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let profile = DevnetProfile::parse(
+            r#"
+            network = "devnet"
+            db_path = "./db"
+            node_host = "localhost:3001"
+            ogmios_url = "localhost"
+
+            [faucet_key]
+            hex = "not hex"
+            "#,
+        )
+        .unwrap();
+
+        let ProfileErrors(errors) = profile
+            .with_env_overrides(|name| match name {
+                "CONSOLIDATE_FAUCET_ABOVE" => Some("many".to_string()),
+                "UTXO_WAIT_TIMEOUT_MS" => Some("1m".to_string()),
+                "UTXO_POLL_INTERVAL_MS" => Some("-5".to_string()),
+                _ => None,
+            })
+            .validate()
+            .unwrap_err();
+        assert_eq!(errors.len(), 6, "{errors:?}");
+        assert_eq!(
+            errors[..3],
+            [
+                "`CONSOLIDATE_FAUCET_ABOVE` must be a non-negative integer, got many",
+                "`UTXO_WAIT_TIMEOUT_MS` must be a number of milliseconds, got 1m",
+                "`UTXO_POLL_INTERVAL_MS` must be a number of milliseconds, got -5",
+            ]
+        );
+        assert!(errors[3].starts_with("`network`: Invalid network: devnet"));
+        assert!(errors[4].starts_with("`faucet_key.hex`"));
+        assert!(errors[5].starts_with("`ogmios_url` is not a valid URL"));
+    }
+
+    #[test]
+    fn env_overrides_the_profile() {
+        let config = DevnetProfile::parse(VALID)
+            .unwrap()
+            .with_env_overrides(|name| {
+                (name == "OGMIOS_URL").then(|| "http://ogmios:1337".to_string())
+            })
+            .validate()
+            .unwrap();
+        assert_eq!(config.ogmios_url, "http://ogmios:1337");
+        assert_eq!(config.node_host, "localhost:3001");
+    }
+
+    #[test]
+    fn utxo_wait_settings_are_configurable() {
+        let config = DevnetProfile::parse(&format!("utxo_wait_timeout_ms = 5000\n{VALID}"))
+            .unwrap()
+            .with_env_overrides(|name| (name == "UTXO_POLL_INTERVAL_MS").then(|| "10".to_string()))
+            .validate()
+            .unwrap();
+        assert_eq!(config.utxo_wait_timeout, Duration::from_secs(5));
+        assert_eq!(config.utxo_poll_interval, Duration::from_millis(10));
+
+        let ProfileErrors(errors) = DevnetProfile::parse(VALID)
+            .unwrap()
+            .with_env_overrides(|name| (name == "UTXO_WAIT_TIMEOUT_MS").then(|| "soon".to_string()))
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            ["`UTXO_WAIT_TIMEOUT_MS` must be a number of milliseconds, got soon"]
+        );
+    }
+
+    #[test]
+    fn chaos_is_configurable() {
+        let config = DevnetProfile::parse(&format!("chaos_latency_ms = 200\n{VALID}"))
+            .unwrap()
+            .with_env_overrides(|name| {
+                (name == "CHAOS_AMBIGUOUS_SUBMIT_PERCENT").then(|| "50".to_string())
+            })
+            .validate()
+            .unwrap();
+        assert_eq!(
+            config.chaos,
+            ChaosConfig {
+                ambiguous_submit_percent: 50,
+                latency: Duration::from_millis(200),
+            }
+        );
+
+        let ProfileErrors(errors) =
+            DevnetProfile::parse(&format!("chaos_ambiguous_submit_percent = 150\n{VALID}"))
+                .unwrap()
+                .validate()
+                .unwrap_err();
+        assert_eq!(
+            errors,
+            ["`chaos_ambiguous_submit_percent` must be at most 100"]
+        );
+    }
+
+    #[test]
+    fn nonce_is_read_from_hose_devnet_nonce() {
+        let nonce_env = |value: &'static str| {
+            move |name: &str| (name == "HOSE_DEVNET_NONCE").then(|| value.to_string())
+        };
+        let profile = DevnetProfile::parse(&format!("nonce = 1\n{VALID}")).unwrap();
+        assert_eq!(profile.clone().validate().unwrap().nonce, Some(1));
+
+        let config = profile
+            .clone()
+            .with_env_overrides(nonce_env("1700000000000"))
+            .validate()
+            .unwrap();
+        assert_eq!(config.nonce, Some(1_700_000_000_000));
+
+        let ProfileErrors(errors) = profile
+            .with_env_overrides(nonce_env("-1"))
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            ["`HOSE_DEVNET_NONCE` must be a non-negative integer, got -1"]
+        );
+    }
+}
@@ -1,13 +1,12 @@
 use std::sync::Arc;
 
 use anyhow::Context as _;
-use clap::Parser as _;
-use hose::builder::BuiltTx;
-use hose::wallet::{Wallet, WalletBuilder};
+use hose::builder::{BatchSubmitter, BuiltTx, TxBuilder};
+use hose::primitives::{Input, Output, TxHash};
+use hose::wallet::Wallet;
 use hydrant::UtxoIndexer;
 use ogmios_client::OgmiosHttpClient;
 use ogmios_client::method::pparams::ProtocolParams;
-use ogmios_client::method::submit::SubmitResult;
 use pallas::ledger::addresses::Network;
 use pallas::ledger::primitives::NetworkId;
 use pallas::network::facades::PeerClient;
@@ -18,6 +17,7 @@ use tracing_subscriber::util::SubscriberInitExt as _;
 use tracing_subscriber::{EnvFilter, Layer as _};
 use url::Url;
 
+use crate::chaos::Chaotic;
 use crate::config::{self, Config};
 
 pub struct DevnetContext {
@@ -47,7 +47,8 @@ impl DevnetContext {
             .unwrap();
         init_tracing();
 
-        let config = config::Config::parse_from::<_, String>(vec![]);
+        let config = Config::load().unwrap();
+        crate::nonce::NONCES.start(config.nonce);
         let network_id = NetworkId::try_from(config.network.value())
             .expect("failed to convert network to network id");
 
@@ -55,9 +56,7 @@ impl DevnetContext {
 
         let protocol_params = ogmios.protocol_params().await.unwrap();
 
-        let wallet = WalletBuilder::new(config.network)
-            .from_hex(config.private_key_hex.clone())
-            .unwrap();
+        let wallet = config.faucet_wallet().unwrap();
 
         let db = hydrant::Db::new(config.db_path.to_str().unwrap()).expect("failed to open db");
 
@@ -93,7 +92,7 @@ impl DevnetContext {
             }
         });
 
-        Self {
+        let context = Self {
             config,
             network_id,
             ogmios,
@@ -101,27 +100,155 @@ impl DevnetContext {
             wallet,
             sync_handle,
             indexer,
+        };
+        if context.config.chaos.is_enabled() {
+            tracing::info!("Injecting failures: {:?}", context.config.chaos);
+        }
+        if let Some(threshold) = context.config.consolidate_faucet_above {
+            let utxo_count = context.faucet_utxo_count().await.unwrap();
+            if utxo_count > threshold {
+                tracing::info!("Faucet holds {utxo_count} UTxOs, consolidating...");
+                context
+                    .consolidate_faucet(MAX_CONSOLIDATION_INPUTS)
+                    .await
+                    .expect("failed to consolidate the faucet");
+            }
         }
+        context
+    }
+
+    /// Wraps `inner`, e.g. `self.ogmios` or `self.indexer`, to fail as set in `Config::chaos`.
+    pub fn chaotic<'a, T>(&self, inner: &'a T) -> Chaotic<'a, T> {
+        Chaotic::new(inner, self.config.chaos)
+    }
+
+    /// Number of UTxOs at the faucet address
+    pub async fn faucet_utxo_count(&self) -> anyhow::Result<usize> {
+        let indexer = self.indexer.lock().await;
+        Ok(indexer
+            .address_utxos(&self.wallet.address().to_vec())?
+            .len())
+    }
+
+    /// Merges up to `max_inputs` of the faucet's smallest UTxOs into a single one, so that
+    /// long-lived devnets don't slow coin selection down with thousands of dust outputs. Does
+    /// nothing when there is less than two UTxOs to merge.
+    ///
+    /// Returns the number of UTxOs that were merged.
+    pub async fn consolidate_faucet(&self, max_inputs: usize) -> anyhow::Result<usize> {
+        let mut utxos = {
+            let indexer = self.indexer.lock().await;
+            indexer.address_utxos(&self.wallet.address().to_vec())?
+        };
+        if utxos.len() < 2 || max_inputs < 2 {
+            return Ok(0);
+        }
+        utxos.sort_by_key(|utxo| utxo.lovelace);
+        utxos.truncate(max_inputs);
+
+        // Without outputs, everything goes to the change output
+        let tx = utxos
+            .iter()
+            .map(Input::from)
+            .fold(
+                TxBuilder::new(self.network_id, self.wallet.address()),
+                TxBuilder::add_input,
+            )
+            .build(
+                &self.chaotic(&self.indexer),
+                &self.chaotic(&self.ogmios),
+                &self.protocol_params,
+            )
+            .await
+            .context("failed to build consolidation transaction")?;
+        let merged = tx.body().inputs().len();
+        self.sign_and_submit_tx(tx).await?;
+        Ok(merged)
+    }
+
+    /// Signs `tx` with the faucet wallet, submits it through `chaotic` providers and waits until
+    /// it's included.
+    pub async fn sign_and_submit_tx(&self, tx: BuiltTx) -> anyhow::Result<(BuiltTx, TxHash)> {
+        self.sign_and_submit_tx_with(tx, &[]).await
     }
 
-    pub async fn sign_and_submit_tx(&self, tx: BuiltTx) -> anyhow::Result<(BuiltTx, SubmitResult)> {
-        let signed = tx.sign(&self.wallet)?;
+    /// Same as `sign_and_submit_tx`, but also signs with `cosigners`, e.g. a separate collateral
+    /// wallet, and checks that no required signature is missing before submitting.
+    pub async fn sign_and_submit_tx_with(
+        &self,
+        tx: BuiltTx,
+        cosigners: &[&Wallet],
+    ) -> anyhow::Result<(BuiltTx, TxHash)> {
+        let mut signed = tx.sign(&self.wallet)?;
+        for cosigner in cosigners {
+            signed = signed.sign(cosigner)?;
+        }
+        if !cosigners.is_empty() {
+            let missing = signed.missing_signatures();
+            anyhow::ensure!(
+                missing.is_empty(),
+                "transaction is missing signatures from {}",
+                missing
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
         tracing::info!("Submitting transaction: {}", signed.hash()?);
-        match self.ogmios.submit(&signed.cbor()).await {
-            Ok(res) => {
-                tracing::debug!("Submitted transaction: {:?}", res.transaction.id);
-                assert_eq!(res.transaction.id, signed.hash()?.to_string());
-                crate::wait_until_tx_is_included(self, signed.hash()?).await?;
-                Ok((signed, res))
+        let (indexer, ogmios) = (self.chaotic(&self.indexer), self.chaotic(&self.ogmios));
+        let result = BatchSubmitter::new(&indexer, &ogmios)
+            .wait_for_acceptance(true)
+            .confirmation_timeout(self.config.utxo_wait_timeout)
+            .submit_batch(std::slice::from_ref(&signed))
+            .await
+            .remove(0);
+        match result {
+            Ok(hash) => {
+                tracing::debug!("Submitted transaction: {hash}");
+                Ok((signed, hash))
             }
             Err(e) => {
                 tracing::info!("Failed transaction CBOR: {:?}", signed.cbor_hex());
-                Err(anyhow::anyhow!("Failed to submit transaction: {:?}", e))
+                Err(anyhow::anyhow!("Failed to submit transaction: {e}"))
             }
         }
     }
+
+    /// Creates `outputs` from the wallet in a single transaction and waits until it's indexed,
+    /// so a test can start from a known on-chain state (e.g. a script UTxO holding a datum).
+    ///
+    /// Returns the seeded UTxOs, in the same order as `outputs`.
+    pub async fn seed_utxos(&self, outputs: &[Output]) -> anyhow::Result<Vec<Input>> {
+        anyhow::ensure!(!outputs.is_empty(), "no outputs to seed");
+        let tx = outputs
+            .iter()
+            .cloned()
+            .fold(
+                TxBuilder::new(self.network_id, self.wallet.address()),
+                TxBuilder::add_output,
+            )
+            .build(
+                &self.chaotic(&self.indexer),
+                &self.chaotic(&self.ogmios),
+                &self.protocol_params,
+            )
+            .await
+            .context("failed to build seeding transaction")?;
+
+        let (signed, _) = self.sign_and_submit_tx(tx).await?;
+        let hash = signed.hash()?;
+        // Outputs keep the order they were added in, the change output comes after them
+        Ok((0..outputs.len() as u64)
+            .map(|index| Input::new(hash, index))
+            .collect())
+    }
 }
 
+/// Inputs merged at once by the automatic consolidation, keeping the transaction well below the
+/// size limit
+const MAX_CONSOLIDATION_INPUTS: usize = 100;
+
 fn init_tracing() {
     let fmt = tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env());
     let _ = tracing_subscriber::registry().with(fmt).try_init();
@@ -1,9 +1,11 @@
+pub mod chaos;
 pub mod config;
 pub mod context;
-use std::time::{SystemTime, UNIX_EPOCH};
+mod nonce;
+use std::time::Instant;
 
 pub use context::DevnetContext;
-use hose::primitives::{Address, Script, ScriptKind, TxHash};
+use hose::primitives::{Address, Script, ScriptExt, ScriptKind, TxHash};
 pub use hose_devnet_macros::test;
 use hydrant::primitives::TxOutputPointer;
 use pallas::ledger::addresses::{
@@ -29,10 +31,13 @@ pub async fn wait_n_slots(_context: &DevnetContext, n: u64) -> anyhow::Result<()
     Ok(())
 }
 
+/// Polls the indexer until the output exists, giving up after `Config::utxo_wait_timeout`.
 pub async fn wait_until_utxo_exists(
     context: &DevnetContext,
     output_pointer: TxOutputPointer,
 ) -> anyhow::Result<()> {
+    let timeout = context.config.utxo_wait_timeout;
+    let deadline = Instant::now() + timeout;
     loop {
         debug!(
             "Waiting for utxo to exist: {}#{}",
@@ -45,7 +50,14 @@ pub async fn wait_until_utxo_exists(
                 return Ok(());
             }
         }
-        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "UTxO {}#{} did not appear within {timeout:?}",
+                output_pointer.hash.to_hex(),
+                output_pointer.index
+            );
+        }
+        tokio::time::sleep(context.config.utxo_poll_interval).await;
     }
 }
 
@@ -79,15 +91,17 @@ pub fn validator_to_address(context: &DevnetContext, validator: &Script) -> Addr
     ))
 }
 
+/// Always succeeding script with a new nonce on every call, which avoids problems with rewards
+/// accounts (that cannot be registered twice in a row). Nonces are reproducible from the logs,
+/// see `HOSE_DEVNET_NONCE`.
 pub fn nonced_always_succeeds_script() -> anyhow::Result<Script> {
+    nonced_always_succeeds_script_with(nonce::NONCES.next())
+}
+
+/// Same as `nonced_always_succeeds_script`, with the nonce given explicitly.
+pub fn nonced_always_succeeds_script_with(nonce: u64) -> anyhow::Result<Script> {
     // This is just an always succeeds that takes an integer as a parameter and ignores it.
     let base_script_bytes = hex::decode("5601010022332259800a518a4d136564008ae68dd68011")?;
-    // We apply the unix time as the nonce just so we have a different script for each run,
-    // which avoids problems with rewards accounts (that cannot be registered twice in a row).
-    let nonce = SystemTime::now()
-        .duration_since(UNIX_EPOCH)?
-        // Theoretically unsafe, but  will fit into a u64 for the next few million years :)
-        .as_millis() as u64;
 
     let params = vec![nonce].to_plutus_data();
     let params_bytes = params
@@ -95,5 +109,8 @@ pub fn nonced_always_succeeds_script() -> anyhow::Result<Script> {
         .map_err(|err| anyhow::anyhow!("failed to encode params: {err:?}"))?;
     let script_bytes = apply_params_to_script(&params_bytes, &base_script_bytes)
         .map_err(|err| anyhow::anyhow!("failed to apply params to script: {err:?}"))?;
-    Ok(Script::new(ScriptKind::PlutusV3, script_bytes))
+    Ok(Script::new(
+        ScriptKind::PlutusV3,
+        Script::normalize(ScriptKind::PlutusV3, &script_bytes)?,
+    ))
 }
@@ -0,0 +1,117 @@
+//! Nonces of the scripts made by `nonced_always_succeeds_script`
+//!
+//! Every script gets a new nonce, so that tests don't run into chain state left by earlier runs,
+//! like reward accounts that are still registered. Nonces count up from a seed, which is
+//! `HOSE_DEVNET_NONCE` when set and the time of the first context setup otherwise. Each context
+//! logs the nonce its test starts at, so a failed run can be reproduced against the same script
+//! addresses.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) static NONCES: NonceSource = NonceSource::new();
+
+pub(crate) struct NonceSource {
+    seed: OnceLock<u64>,
+    /// Nonces handed out so far
+    issued: AtomicU64,
+}
+
+impl NonceSource {
+    const fn new() -> Self {
+        Self {
+            seed: OnceLock::new(),
+            issued: AtomicU64::new(0),
+        }
+    }
+
+    /// Fixes the seed on first call, to `configured` or else the time, and logs the nonce the
+    /// starting test gets first.
+    pub(crate) fn start(&self, configured: Option<u64>) -> u64 {
+        let seed = *self
+            .seed
+            .get_or_init(|| configured.unwrap_or_else(time_millis));
+        let next = seed.wrapping_add(self.issued.load(Ordering::SeqCst));
+        tracing::info!(
+            "Script nonce seed is {seed}, this test starts at nonce {next}, set HOSE_DEVNET_NONCE={next} to reproduce it"
+        );
+        next
+    }
+
+    /// Next nonce, or the time when no context was set up yet.
+    pub(crate) fn next(&self) -> u64 {
+        match self.seed.get() {
+            Some(seed) => seed.wrapping_add(self.issued.fetch_add(1, Ordering::SeqCst)),
+            None => time_millis(),
+        }
+    }
+}
+
+fn time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the clock is past the epoch")
+        // Theoretically unsafe, but will fit into a u64 for the next few million years :)
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::NonceSource;
+    use crate::nonced_always_succeeds_script_with;
+
+    /// Log output, shared with the subscriber
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn nonces_count_up_from_the_logged_seed() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+
+        let nonces = NonceSource::new();
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(nonces.start(Some(42)), 42);
+            assert_eq!((nonces.next(), nonces.next()), (42, 43));
+            // Later contexts keep the seed and carry on from there
+            assert_eq!(nonces.start(Some(7)), 44);
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("seed is 42"), "{logs}");
+        assert!(logs.contains("HOSE_DEVNET_NONCE=44"), "{logs}");
+    }
+
+    #[test]
+    fn the_same_nonce_gives_the_same_script() {
+        let script = nonced_always_succeeds_script_with(42).unwrap();
+        assert_eq!(
+            script.hash,
+            nonced_always_succeeds_script_with(42).unwrap().hash
+        );
+        assert_ne!(
+            script.hash,
+            nonced_always_succeeds_script_with(43).unwrap().hash
+        );
+    }
+}
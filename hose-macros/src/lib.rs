@@ -0,0 +1,263 @@
+extern crate proc_macro;
+extern crate quote;
+extern crate syn;
+
+mod blake2b;
+
+use std::path::PathBuf;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, LitStr, Token};
+
+/// Embeds a compiled script at compile time, failing compilation if it doesn't decode as the
+/// declared kind or if its hash doesn't match the expected one.
+///
+/// The script is read from a hex file, relative to the crate's `Cargo.toml`, or given inline with
+/// `hex = "..."`. Plutus scripts may be flat or CBOR-wrapped any number of times, they're
+/// normalized like `Script::normalize` does. `expect_hash` is optional.
+///
+/// The macro doesn't depend on `hose`, so the checks are lighter than `Script::normalize`'s:
+/// native scripts must be a CBOR array and plutus scripts must have a UPLC version header that
+/// the declared kind supports, but their bodies aren't decoded.
+///
+/// Expands to a `SCRIPT_BYTES` const holding the normalized bytes, a `SCRIPT_HASH` const, and a
+/// `script()` function returning the `Script`, so it's best invoked in a dedicated module.
+///
+/// Example usage:
+/// ```ignore
+/// mod always_succeeds {
+///     hose_macros::script!(
+///         "scripts/always_succeeds.hex",
+///         kind = PlutusV3,
+///         expect_hash = "bd3ae991b5aafccafe5ca70758bd36a9b2f872f57f6d3a1ffa0eb777",
+///     );
+/// }
+///
+/// let script = always_succeeds::script();
+/// ```
+#[proc_macro]
+pub fn script(input: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(input as ScriptArgs);
+    match expand_script(args) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum ScriptSource {
+    Path(LitStr),
+    Hex(LitStr),
+}
+
+struct ScriptArgs {
+    source: ScriptSource,
+    kind: Ident,
+    expect_hash: Option<LitStr>,
+}
+
+impl Parse for ScriptArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = if input.peek(LitStr) {
+            ScriptSource::Path(input.parse()?)
+        } else {
+            let key: Ident = input.parse()?;
+            if key != "hex" {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected a path literal or `hex = \"...\"`",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            ScriptSource::Hex(input.parse()?)
+        };
+
+        let mut kind = None;
+        let mut expect_hash = None;
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "kind" => kind = Some(input.parse()?),
+                "expect_hash" => expect_hash = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "unknown argument, expected `kind` or `expect_hash`",
+                    ));
+                }
+            }
+        }
+
+        let kind = kind.ok_or_else(|| input.error("missing `kind = ...` argument"))?;
+        Ok(Self {
+            source,
+            kind,
+            expect_hash,
+        })
+    }
+}
+
+fn expand_script(args: ScriptArgs) -> syn::Result<TokenStream2> {
+    // Tags prefixed to the script bytes before hashing, as the ledger does
+    let kind = args.kind.to_string();
+    let tag = match kind.as_str() {
+        "Native" => 0,
+        "PlutusV1" => 1,
+        "PlutusV2" => 2,
+        "PlutusV3" => 3,
+        _ => {
+            return Err(syn::Error::new(
+                args.kind.span(),
+                "expected one of `Native`, `PlutusV1`, `PlutusV2` or `PlutusV3`",
+            ));
+        }
+    };
+
+    let (hex, span, tracked_file) = match &args.source {
+        ScriptSource::Path(path) => {
+            let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+            let full_path = PathBuf::from(manifest_dir).join(path.value());
+            let hex = std::fs::read_to_string(&full_path).map_err(|err| {
+                syn::Error::new(
+                    path.span(),
+                    format!("failed to read {}: {err}", full_path.display()),
+                )
+            })?;
+            (hex, path.span(), Some(full_path.display().to_string()))
+        }
+        ScriptSource::Hex(hex) => (hex.value(), hex.span(), None),
+    };
+
+    let bytes = decode_hex(hex.trim())
+        .map_err(|err| syn::Error::new(span, format!("invalid script hex: {err}")))?;
+    let bytes = normalize(&kind, &bytes)
+        .map_err(|err| syn::Error::new(span, format!("script doesn't decode as {kind}: {err}")))?;
+    let hash: [u8; 28] = blake2b::hash(&[&[tag][..], &bytes].concat());
+
+    if let Some(expect_hash) = &args.expect_hash {
+        let computed: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+        if expect_hash.value().to_lowercase() != computed {
+            return Err(syn::Error::new(
+                expect_hash.span(),
+                format!(
+                    "script hash mismatch: expected {}, computed {computed}",
+                    expect_hash.value()
+                ),
+            ));
+        }
+    }
+
+    // Makes cargo rebuild the crate when the script file changes
+    let tracked_file = tracked_file.map(|path| quote! { const _: &[u8] = include_bytes!(#path); });
+    let kind = &args.kind;
+    let len = bytes.len();
+
+    Ok(quote! {
+        #tracked_file
+
+        pub const SCRIPT_BYTES: [u8; #len] = [#(#bytes),*];
+
+        pub const SCRIPT_HASH: [u8; 28] = [#(#hash),*];
+
+        pub fn script() -> ::hose::primitives::Script {
+            ::hose::primitives::Script::new(
+                ::hose::primitives::ScriptKind::#kind,
+                SCRIPT_BYTES.to_vec(),
+            )
+        }
+    })
+}
+
+/// Mirrors `Script::normalize`: native scripts are left untouched, plutus scripts are wrapped in
+/// exactly one CBOR bytestring.
+fn normalize(kind: &str, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if kind == "Native" {
+        if !matches!(bytes.first(), Some(0x80..=0x9f)) {
+            return Err("invalid native script: not a CBOR array".to_string());
+        }
+        return Ok(bytes.to_vec());
+    }
+
+    let mut flat = bytes;
+    while let Some(inner) = cbor_bytes(flat) {
+        flat = inner;
+    }
+
+    let mut header = flat;
+    let version = [(); 3].map(|_| flat_natural(&mut header));
+    let [Some(major), Some(minor), Some(patch)] = version else {
+        return Err("invalid plutus script: truncated UPLC version".to_string());
+    };
+    if kind != "PlutusV3" && (major, minor, patch) >= (1, 1, 0) {
+        return Err(format!(
+            "UPLC {major}.{minor}.{patch} programs are only supported by Plutus V3"
+        ));
+    }
+
+    let len = flat.len() as u64;
+    let mut wrapped = match len {
+        0..=23 => vec![0x40 | len as u8],
+        24..=0xff => vec![0x58, len as u8],
+        0x100..=0xffff => [&[0x59][..], &(len as u16).to_be_bytes()].concat(),
+        0x10000..=0xffff_ffff => [&[0x5a][..], &(len as u32).to_be_bytes()].concat(),
+        _ => [&[0x5b][..], &len.to_be_bytes()].concat(),
+    };
+    wrapped.extend_from_slice(flat);
+    Ok(wrapped)
+}
+
+/// Contents of `bytes` if they're exactly one definite-length CBOR bytestring.
+fn cbor_bytes(bytes: &[u8]) -> Option<&[u8]> {
+    let (&head, rest) = bytes.split_first()?;
+    if head >> 5 != 2 {
+        return None;
+    }
+    let (len, rest) = match head & 0x1f {
+        len @ 0..=23 => (len as u64, rest),
+        info @ 24..=27 => {
+            let size = 1 << (info - 24);
+            if rest.len() < size {
+                return None;
+            }
+            let (len, rest) = rest.split_at(size);
+            (
+                len.iter().fold(0, |acc, &byte| acc << 8 | byte as u64),
+                rest,
+            )
+        }
+        _ => return None,
+    };
+    (rest.len() as u64 == len).then_some(rest)
+}
+
+/// Reads a flat-encoded natural from a byte-aligned position: 7 bits per byte, least
+/// significant first, with the high bit set on every byte but the last.
+fn flat_natural(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if let Some(invalid) = hex.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("invalid character `{invalid}`"));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err("odd number of digits".to_string());
+    }
+    Ok(hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+        .collect())
+}
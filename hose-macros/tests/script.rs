@@ -0,0 +1,38 @@
+use hose::primitives::{Hash, ScriptKind};
+
+const ALWAYS_SUCCEEDS_HASH: &str = "bd3ae991b5aafccafe5ca70758bd36a9b2f872f57f6d3a1ffa0eb777";
+
+mod from_file {
+    hose_macros::script!(
+        "tests/fixtures/always_succeeds.plutus-v3.hex",
+        kind = PlutusV3,
+        expect_hash = "bd3ae991b5aafccafe5ca70758bd36a9b2f872f57f6d3a1ffa0eb777",
+    );
+}
+
+mod from_flat_hex {
+    hose_macros::script!(hex = "01010023259800a518a4d136564004ae69", kind = PlutusV3);
+}
+
+fn expected_hash() -> Hash<28> {
+    Hash(
+        hex::decode(ALWAYS_SUCCEEDS_HASH)
+            .unwrap()
+            .try_into()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn embedded_script_hash_matches_expected() {
+    let script = from_file::script();
+    assert_eq!(script.kind, ScriptKind::PlutusV3);
+    assert_eq!(script.hash, expected_hash());
+    assert_eq!(Hash(from_file::SCRIPT_HASH), expected_hash());
+}
+
+#[test]
+fn inline_hex_is_normalized() {
+    assert_eq!(from_flat_hex::SCRIPT_BYTES, from_file::SCRIPT_BYTES);
+    assert_eq!(from_flat_hex::script().hash, expected_hash());
+}
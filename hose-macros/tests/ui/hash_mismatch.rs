@@ -0,0 +1,7 @@
+hose_macros::script!(
+    hex = "5101010023259800a518a4d136564004ae69",
+    kind = PlutusV3,
+    expect_hash = "00000000000000000000000000000000000000000000000000000000",
+);
+
+fn main() {}
@@ -0,0 +1,6 @@
+hose_macros::script!(
+    hex = "5101010023259800a518a4d136564004ae69",
+    kind = PlutusV2,
+);
+
+fn main() {}
@@ -1,31 +1,91 @@
 //! Public API for building transactions
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use hydrant::primitives::{Asset, AssetId};
+use chrono::{DateTime, Utc};
+use hydrant::primitives::{Asset, TxOutputPointer};
 use intervals_general::Interval;
+use pallas::codec::minicbor;
 use pallas::ledger::addresses::Address;
 use pallas::ledger::primitives::NetworkId;
 
-use super::TxBuilder;
-use super::tx::StagingTransaction;
+use super::assertions::BalanceAssertion;
+use super::coin_selection::DEFAULT_NO_CHANGE_THRESHOLD;
+use super::lint::LintRule;
+use super::tx::{Era, StagingTransaction};
+use super::validity::WallClockWindow;
+use super::{ExUnitsCache, PersistentExUnits, Phase, PhaseContext, TxBuilder, ValidityMargin};
 use crate::builder::tx::TxBuilderError;
+use crate::ogmios::OgmiosQueries;
 use crate::primitives::{
-    Certificate, DatumOption, ExUnits, Hash, Input, Output, RewardAccount, ScriptKind,
+    Anchor, Assets, AssetsDelta, AssetsDeltaExt as _, Certificate, Datum, DatumHash, DatumOption,
+    ExUnits, GovAction, Hash, Input, Output, ProposalProcedure, PubKeyHash, RedeemerPurpose,
+    RewardAccount, ScriptKind, SlotConfig,
 };
+use crate::registry::TokenRegistry;
 
 impl TxBuilder {
     pub fn new(network: NetworkId, change_address: Address) -> Self {
+        Self::with_body(
+            StagingTransaction::new().network_id(network.into()),
+            Some(change_address),
+        )
+    }
+
+    /// A builder for transactions that balance exactly, e.g. sweeps spending everything they're
+    /// given: `build` makes no change output and fails with `UnbalancedWithoutChangeAddress`
+    /// unless inputs cover outputs and fee to the lovelace. Inputs are only selected from
+    /// addresses given with `address_pool` or `spend_from`.
+    pub fn without_change_address(network: NetworkId) -> Self {
+        Self::with_body(StagingTransaction::new().network_id(network.into()), None)
+    }
+
+    /// Starts over from `body`, see `BuiltTx::rebuild`.
+    pub(super) fn with_body(body: StagingTransaction, change_address: Option<Address>) -> Self {
         Self {
-            body: StagingTransaction::new().network_id(network.into()),
+            body,
             collateral_address: None,
+            collateral_source: None,
             change_address,
+            no_change_threshold: None,
             change_datum: None,
+            address_pool: Vec::new(),
+            used_addresses: Vec::new(),
+            source_addresses: Vec::new(),
+            avoided_inputs: HashSet::new(),
             validity_interval: Interval::Unbounded,
             script_kinds: HashSet::new(),
+            mint_script_kinds: HashMap::new(),
+            known_utxos: HashMap::new(),
+            known_outputs: HashMap::new(),
+            fee_padding_lovelace: 0,
+            fee_padding_percent: 0.0,
+            assertions: Vec::new(),
+            split_outputs: Vec::new(),
+            hooks: Vec::new(),
+            token_registry: None,
+            redeemer_budgets: None,
+            lint_rules: Vec::new(),
+            deny_lints: false,
+            datum_size_threshold: None,
+            script_size_threshold: None,
+            script_input_batches: Vec::new(),
+            keep_duplicate_scripts: false,
+            ex_units_cache: None,
+            ex_units_store: None,
+            verify_script_data_hash: false,
+            wall_clock_windows: Vec::new(),
+            slot_config: None,
+            validity_margin: ValidityMargin::default(),
         }
     }
 
+    /// The transaction body staged so far.
+    pub fn body(&self) -> &StagingTransaction {
+        &self.body
+    }
+
     /// Manually add an input to the transaction for consumption.
     ///
     /// Note that when no inputs are specified, the balancing algorithm will automatically select
@@ -35,6 +95,16 @@ impl TxBuilder {
         self
     }
 
+    /// Same as `add_input`, but with the output the input points to already resolved by the
+    /// caller, so the builder doesn't need to look it up in the indexer. Useful when working
+    /// ahead of the indexer tip.
+    ///
+    /// If the indexer does know the UTxO and disagrees with the supplied output, `build` fails.
+    pub fn add_input_resolved(self, input: Input, output: Output) -> Self {
+        self.add_known_utxo(TxOutputPointer::from(&input), output)
+            .add_input(input)
+    }
+
     // TODO: Use a `Script` type
     pub fn add_script_input(
         mut self,
@@ -48,18 +118,61 @@ impl TxBuilder {
         self
     }
 
+    /// Spends several UTxOs locked by the same script, each with its own redeemer. The script is
+    /// attached once with `add_script`, or carried by a reference input, and covers all of them.
+    ///
+    /// `build` fails if the inputs turn out to be locked by different scripts.
+    pub fn add_script_inputs(
+        mut self,
+        script_kind: ScriptKind,
+        inputs: Vec<(Input, Vec<u8>)>,
+    ) -> Self {
+        self.script_input_batches
+            .push(inputs.iter().map(|(input, _)| input.clone()).collect());
+        inputs
+            .into_iter()
+            .fold(self, |builder, (input, plutus_data)| {
+                builder.add_script_input(input, plutus_data, script_kind)
+            })
+    }
+
+    /// Same as `add_script_input`, but with the output the input points to already resolved by
+    /// the caller. See `add_input_resolved`.
+    pub fn add_script_input_resolved(
+        self,
+        input: Input,
+        output: Output,
+        plutus_data: Vec<u8>,
+        script_kind: ScriptKind,
+    ) -> Self {
+        self.add_known_utxo(TxOutputPointer::from(&input), output)
+            .add_script_input(input, plutus_data, script_kind)
+    }
+
+    /// Registers the output `pointer` refers to without spending it, for UTxOs the indexer hasn't
+    /// seen yet, e.g. outputs of a transaction that's still in flight. Balancing, fee estimation
+    /// and required signers then resolve the UTxO from here, whether it ends up as an input, a
+    /// reference input or collateral. `add_input_resolved` is this and `add_input` in one go.
+    ///
+    /// Evaluating with `OgmiosQueries` passes the UTxO on to Ogmios, so that scripts of a
+    /// transaction spending it can be evaluated before the node knows it.
+    ///
+    /// If the indexer does know the UTxO and disagrees with the supplied output, `build` fails.
+    pub fn add_known_utxo(mut self, pointer: TxOutputPointer, output: Output) -> Self {
+        let input = Input::from(pointer);
+        self.known_utxos
+            .insert(input.clone(), output.to_tx_output(&input));
+        self.known_outputs.insert(input, output);
+        self
+    }
+
     pub fn mint_asset(
         self,
         asset: Asset,
         policy_script_kind: ScriptKind,
         redeemer: Vec<u8>,
     ) -> Result<Self, TxBuilderError> {
-        if asset.quantity == 0 {
-            return Err(TxBuilderError::InvalidMintAmount);
-        }
-        let amount =
-            i64::try_from(asset.quantity).map_err(|_| TxBuilderError::InvalidMintAmount)?;
-        self.mint_or_burn_asset(asset.into(), policy_script_kind, amount, redeemer)
+        self.apply_delta(AssetsDelta::mint(asset)?, policy_script_kind, redeemer)
     }
 
     pub fn burn_asset(
@@ -68,41 +181,49 @@ impl TxBuilder {
         policy_script_kind: ScriptKind,
         redeemer: Vec<u8>,
     ) -> Result<Self, TxBuilderError> {
-        if asset.quantity == 0 {
-            return Err(TxBuilderError::InvalidMintAmount);
-        }
-        let amount =
-            -i64::try_from(asset.quantity).map_err(|_| TxBuilderError::InvalidMintAmount)?;
-        self.mint_or_burn_asset(asset.into(), policy_script_kind, amount, redeemer)
+        self.apply_delta(AssetsDelta::burn(asset)?, policy_script_kind, redeemer)
     }
 
-    fn mint_or_burn_asset(
+    /// Adds `delta` to the mint field, positive amounts being minted and negative ones burned.
+    ///
+    /// Every policy of `delta` is run as a `policy_script_kind` script with `redeemer`.
+    pub fn apply_delta(
         mut self,
-        asset: AssetId,
+        delta: AssetsDelta,
         policy_script_kind: ScriptKind,
-        amount: i64,
         redeemer: Vec<u8>,
     ) -> Result<Self, TxBuilderError> {
-        if amount == 0 {
+        if delta.is_empty() || delta.iter().any(|(_, amount)| *amount == 0) {
             return Err(TxBuilderError::InvalidMintAmount);
         }
 
-        let asset_id = asset.clone();
-        self.body = self.body.mint_asset(asset.policy, asset.name, amount)?;
+        for (asset_id, amount) in delta.iter() {
+            self.body = self
+                .body
+                .mint_asset(asset_id.policy, asset_id.name.clone(), *amount)?;
+        }
 
         // if minting + burning results in a mint value of 0, the minting policy is not invoked and
-        // the redeemer must be removed to avoid an integrity hash mismatch.
-        let has_policy_mint = self.body.mint.keys().any(|id| id.policy == asset_id.policy);
-        if has_policy_mint {
-            self.body = self.body.add_mint_redeemer(asset_id.policy, redeemer, None);
-        } else {
-            self.body = self.body.remove_mint_redeemer(asset_id.policy);
+        // the redeemer must be removed to avoid an integrity hash mismatch. Its script kind and
+        // attached script are dropped during `build`, see `used_script_kinds`.
+        for policy in delta.by_policy().into_keys() {
+            let has_policy_mint = self.body.mint.keys().any(|id| id.policy == policy);
+            if has_policy_mint {
+                self.body = self.body.add_mint_redeemer(policy, redeemer.clone(), None);
+            } else {
+                self.body = self.body.remove_mint_redeemer(policy);
+            }
+            self.mint_script_kinds.insert(policy, policy_script_kind);
         }
 
-        self.script_kinds.insert(policy_script_kind);
         Ok(self)
     }
 
+    /// Assets minted (positive) and burned (negative) by the transaction so far.
+    pub fn current_mint_delta(&self) -> &AssetsDelta {
+        &self.body.mint
+    }
+
     /// Manually add a collateral input to the transaction for consumption by the chain, if our
     /// scripts fail to execute after submission. The input must contain only ADA (no assets).
     ///
@@ -162,11 +283,11 @@ impl TxBuilder {
         self
     }
 
-    /// Delegate a script's stake to a stake pool.
+    /// Delegate a script's stake to a stake pool. `pool_id` is a `PoolId` or its raw hash.
     pub fn delegate_script_stake(
         mut self,
         script_hash: Hash<28>,
-        pool_id: Hash<28>,
+        pool_id: impl Into<Hash<28>>,
         script_kind: ScriptKind,
         redeemer: Option<Vec<u8>>,
         ex_units: Option<ExUnits>,
@@ -175,7 +296,7 @@ impl TxBuilder {
             .body
             .add_certificate(Certificate::StakeDelegationScript {
                 script_hash,
-                pool_id,
+                pool_id: pool_id.into(),
             });
 
         if let Some(redeemer) = redeemer {
@@ -203,11 +324,11 @@ impl TxBuilder {
         self
     }
 
-    /// Delegate a key's stake to a stake pool.
-    pub fn delegate_stake(mut self, pub_key_hash: Hash<28>, pool_id: Hash<28>) -> Self {
+    /// Delegate a key's stake to a stake pool. `pool_id` is a `PoolId` or its raw hash.
+    pub fn delegate_stake(mut self, pub_key_hash: Hash<28>, pool_id: impl Into<Hash<28>>) -> Self {
         self.body = self.body.add_certificate(Certificate::StakeDelegation {
             pub_key_hash,
-            pool_id,
+            pool_id: pool_id.into(),
         });
         self
     }
@@ -259,6 +380,64 @@ impl TxBuilder {
         self.script_kinds.insert(script_kind);
         Ok(self)
     }
+
+    /// Propose a governance action. `deposit` defaults to the governance action deposit of the
+    /// protocol params, and is returned to `return_account` once the action is enacted or expires.
+    ///
+    /// Actions checked by the constitution's guardrails script must come with a redeemer for it,
+    /// the script itself has to be attached with `add_script` or carried by a reference input.
+    pub fn propose(
+        mut self,
+        gov_action: GovAction,
+        deposit: Option<u64>,
+        return_account: RewardAccount,
+        anchor: Anchor,
+        redeemer: Option<Vec<u8>>,
+    ) -> Result<Self, TxBuilderError> {
+        let index = self.body.proposals.len();
+        match (gov_action.guardrails_script(), redeemer) {
+            (Some(_), None) => return Err(TxBuilderError::RedeemerMissing),
+            (None, Some(_)) => return Err(TxBuilderError::RedeemerTargetMissing),
+            (Some(_), Some(redeemer)) => {
+                self.body = self.body.add_propose_redeemer(index, redeemer, None);
+                // Guardrails scripts are Plutus V3, the only version able to see proposals
+                self.script_kinds.insert(ScriptKind::PlutusV3);
+            }
+            (None, None) => {}
+        }
+
+        self.body = self.body.proposal(ProposalProcedure {
+            gov_action,
+            deposit,
+            return_account,
+            anchor,
+        });
+        Ok(self)
+    }
+
+    /// Declares the lovelace the treasury holds, which the ledger rejects the transaction over
+    /// if it's not the current value. Treasury withdrawal proposals are checked against it.
+    ///
+    /// When a treasury withdrawal is proposed and no value is set, `build` queries it through its
+    /// evaluator, and fails with `TxBuilderError::TreasuryValueUnavailable` if it can't.
+    pub fn current_treasury_value(mut self, lovelace: u64) -> Self {
+        self.body = self.body.treasury_value(lovelace);
+        self
+    }
+
+    /// Same as `current_treasury_value`, with the value Ogmios reports. A value set already is
+    /// kept, and Ogmios isn't queried. Fails if the query does.
+    pub async fn current_treasury_value_from(
+        self,
+        ogmios: &OgmiosQueries,
+    ) -> Result<Self, crate::ogmios::Error> {
+        if self.body.treasury_value.is_some() {
+            return Ok(self);
+        }
+        let treasury = ogmios.treasury_and_reserves().await?.treasury;
+        Ok(self.current_treasury_value(treasury))
+    }
+
     /// Add a read-only input to the transaction which won't be consumed, but can be inspected by
     /// scripts. Perfect for oracles, shared state, etc.
     pub fn add_reference_input(mut self, input: Input) -> Self {
@@ -272,6 +451,28 @@ impl TxBuilder {
         self
     }
 
+    /// Same as `add_output`, but an inline datum is replaced by its hash, with the datum itself
+    /// attached to the witness set. For validators expecting datum hashes on their outputs.
+    pub fn add_output_with_datum_hashed(mut self, output: Output) -> Self {
+        let (output, datum) = output.with_datum_hashed();
+        if let Some(datum) = datum {
+            self.body = self.body.datum(datum.bytes);
+        }
+        self.body = self.body.output(output);
+        self
+    }
+
+    /// Same as `add_output`, but if the value of the output exceeds the `max_value_size` protocol
+    /// parameter, it's split into several outputs to the same address at build time. The extra
+    /// outputs are funded from the output's own lovelace, and building fails if it's not enough.
+    ///
+    /// Split outputs are added after the ones from `add_output`, so they can't be referenced by
+    /// index in assertions.
+    pub fn add_output_split(mut self, output: Output) -> Self {
+        self.split_outputs.push(output);
+        self
+    }
+
     /// Sets the address to which the collateral change will be sent when script validation fails.
     ///
     /// Note that by default, no collateral output is added to save on transaction size.
@@ -280,6 +481,243 @@ impl TxBuilder {
         self
     }
 
+    /// Selects collateral from the pure-ADA UTxOs of `address` instead of the change address,
+    /// e.g. a low-value wallet kept apart so a failing script can never take treasury funds.
+    /// Collateral change goes back to `address`, unless `collateral_output_address` says
+    /// otherwise.
+    ///
+    /// The owner of `address` has to sign as well, which `BuiltTx::missing_signatures` reports
+    /// and the fee estimate accounts for.
+    pub fn collateral_from(mut self, address: Address) -> Self {
+        if self.collateral_address.is_none() {
+            self.collateral_address = Some(address.clone());
+        }
+        self.collateral_source = Some(address);
+        self
+    }
+
+    /// Adds a fixed amount of lovelace on top of the computed minimum fee.
+    ///
+    /// This trades a tiny overpayment for submission reliability, e.g. to avoid `FeeTooSmall`
+    /// rejections caused by rounding at parameter boundaries. Defaults to zero.
+    pub fn fee_padding_lovelace(mut self, lovelace: u64) -> Self {
+        self.fee_padding_lovelace = lovelace;
+        self
+    }
+
+    /// Adds a percentage of the computed minimum fee on top of it (e.g. `1.5` for 1.5%), rounded
+    /// up to the next lovelace. Can be combined with `fee_padding_lovelace`. Defaults to zero.
+    ///
+    /// Negative or non-finite values are treated as zero.
+    pub fn fee_padding_percent(mut self, percent: f64) -> Self {
+        self.fee_padding_percent = if percent.is_finite() {
+            percent.max(0.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Registers a hook that `build` calls at `phase`, with mutable access to the staging
+    /// transaction and a read-only view of the balancing state. Hooks run in registration order,
+    /// and an error aborts the build with `TxBuilderError::HookFailed`.
+    ///
+    /// Hooks run on every iteration of the balancing loop, each time on the body without the
+    /// changes of previous runs, so e.g. an output proportional to the selected inputs can be
+    /// added without piling up. See `Phase` for what can be changed at each point.
+    pub fn on_phase(
+        mut self,
+        phase: Phase,
+        hook: impl Fn(&mut StagingTransaction, &PhaseContext) -> anyhow::Result<()>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.hooks.push((phase, Box::new(hook)));
+        self
+    }
+
+    /// Makes `build` fail with `TxBuilderError::ExUnitsBelowEvaluated` when explicit ex units
+    /// (e.g. from `delegate_script_stake`) are below what evaluation says the script needs.
+    ///
+    /// By default, explicit ex units always win and only a warning is logged.
+    pub fn strict_ex_units(mut self) -> Self {
+        self.body = self.body.strict_ex_units(true);
+        self
+    }
+
+    /// Selects inputs from the UTxOs at any of `addresses` on top of the change address, e.g.
+    /// from `Wallet::all_addresses`, and sends change to the first of them that holds no UTxOs,
+    /// so that it lands on a fresh address. If all of them are in use, change goes to the change
+    /// address.
+    ///
+    /// The indexer only knows the current UTxO set, so an address that received funds and was
+    /// emptied since looks fresh again. Pass the addresses that were ever paid to
+    /// `used_addresses` to keep them from receiving change a second time.
+    ///
+    /// Inputs spent from the pool must be signed by their own key, which `BuiltTx::sign` does
+    /// for a wallet whose pool they come from.
+    pub fn address_pool(mut self, addresses: Vec<Address>) -> Self {
+        self.address_pool = addresses;
+        self
+    }
+
+    /// Pool addresses that have received funds before, which change never goes to even once
+    /// they're emptied, e.g. every pool address earlier transactions paid to (see
+    /// `BuiltBody::outputs`), as recorded by the application.
+    pub fn used_addresses(mut self, addresses: Vec<Address>) -> Self {
+        self.used_addresses = addresses;
+        self
+    }
+
+    /// Selects inputs from the UTxOs at any of `addresses` on top of the change address, e.g.
+    /// from `MultiWallet::addresses`. Unlike `address_pool`, change always goes to the change
+    /// address.
+    ///
+    /// Inputs spent from these addresses must be signed by their own key, see
+    /// `MultiWallet::sign_all`.
+    pub fn spend_from(mut self, addresses: Vec<Address>) -> Self {
+        self.source_addresses = addresses;
+        self
+    }
+
+    /// Keeps coin selection from picking `inputs`, e.g. those of transactions still in flight
+    /// after a restart, see `TxJournal::in_flight_inputs`. Inputs added explicitly are spent
+    /// regardless.
+    pub fn avoid_inputs(mut self, inputs: impl IntoIterator<Item = Input>) -> Self {
+        self.avoided_inputs.extend(inputs);
+        self
+    }
+
+    /// Builds the transaction in the format of `era`, Conway by default. Targeting Babbage is
+    /// meant for networks that haven't hard-forked into Conway yet, and makes `build` fail with
+    /// `TxBuilderError::ConwayOnlyFeature` if the transaction relies on a Conway-only feature.
+    pub fn era(mut self, era: Era) -> Self {
+        self.body = self.body.era(era);
+        self
+    }
+
+    /// Pins the ex units of every redeemer to budgets evaluated ahead of time, e.g. by a local
+    /// evaluator or a previous `build`, so that `build` never asks Ogmios to evaluate the
+    /// transaction. Script fees are computed from the pinned budgets instead.
+    ///
+    /// Every redeemer of the final transaction needs a budget, either from `budgets` or given
+    /// explicitly when it was added, and their total must fit in the protocol's per transaction
+    /// limit, otherwise `build` fails.
+    pub fn with_redeemer_budgets(mut self, budgets: HashMap<RedeemerPurpose, ExUnits>) -> Self {
+        self.redeemer_budgets = Some(budgets);
+        self
+    }
+
+    /// Registers a rule run by `lint` after the built-in ones.
+    pub fn lint_rule(mut self, rule: impl LintRule + 'static) -> Self {
+        self.lint_rules.push(Box::new(rule));
+        self
+    }
+
+    /// Makes `build` fail with `TxBuilderError::LintFailed` before doing anything else when
+    /// `lint` reports an error. Warnings never fail the build.
+    pub fn deny_lints(mut self) -> Self {
+        self.deny_lints = true;
+        self
+    }
+
+    /// Sizes in bytes above which `lint` warns about inline datums and reference scripts of
+    /// outputs, instead of `DEFAULT_DATUM_SIZE_THRESHOLD` and `DEFAULT_SCRIPT_SIZE_THRESHOLD`.
+    pub fn lint_size_thresholds(mut self, datum: usize, script: usize) -> Self {
+        self.datum_size_threshold = Some(datum);
+        self.script_size_threshold = Some(script);
+        self
+    }
+
+    /// By default, `build` drops attached scripts that a reference input or a spent input
+    /// already provides, since the ledger may reject them as extraneous and they cost fees
+    /// either way. This keeps them attached.
+    pub fn keep_duplicate_scripts(mut self) -> Self {
+        self.keep_duplicate_scripts = true;
+        self
+    }
+
+    /// Shares `cache` with this builder: `build` records the evaluated script budgets into it,
+    /// and `quote` reads them instead of evaluating the transaction.
+    pub fn ex_units_cache(mut self, cache: ExUnitsCache) -> Self {
+        self.ex_units_cache = Some(cache);
+        self
+    }
+
+    /// Writes `hash` as the script data hash instead of the computed one, e.g. to reproduce the
+    /// hash of a transaction built elsewhere. Combine with `verify_script_data_hash` to find out
+    /// whether `build` would have computed the same hash.
+    pub fn script_data_hash(mut self, hash: Hash<32>) -> Self {
+        self.body = self.body.script_data_hash(hash);
+        self
+    }
+
+    /// Makes `build` fail with `TxBuilderError::ScriptDataHashMismatch` when the hash set with
+    /// `script_data_hash` isn't the one computed from the final redeemers, datums and language
+    /// view, which helps tracking down `ScriptIntegrityHashMismatch` rejections. Without a hash
+    /// set, `build` fails with `TxBuilderError::ScriptDataHashNotPinned` rather than skip the
+    /// check.
+    pub fn verify_script_data_hash(mut self) -> Self {
+        self.verify_script_data_hash = true;
+        self
+    }
+
+    /// Shares `store` with this builder: when it holds a budget for every redeemer without
+    /// explicit ex units, `build` pins them and skips evaluation, otherwise it records the
+    /// evaluated budgets into it. Builders with hooks always evaluate, as hooks may change the
+    /// redeemers.
+    pub fn ex_units_store(mut self, store: PersistentExUnits) -> Self {
+        self.ex_units_store = Some(store);
+        self
+    }
+
+    /// Asserts that the output at `index` holds exactly `lovelace` and `assets` in the final
+    /// transaction. Checked right before `build` returns, after change and collateral are
+    /// computed, failing with `TxBuilderError::AssertionFailed`.
+    pub fn assert_output_exact(mut self, index: usize, lovelace: u64, assets: Assets) -> Self {
+        self.assertions.push(BalanceAssertion::OutputExact {
+            index,
+            lovelace,
+            assets,
+        });
+        self
+    }
+
+    /// Asserts that the outputs to `address` add up to at least `lovelace` in the final
+    /// transaction. See `assert_output_exact`.
+    pub fn assert_address_receives_at_least(mut self, address: Address, lovelace: u64) -> Self {
+        self.assertions
+            .push(BalanceAssertion::AddressReceivesAtLeast { address, lovelace });
+        self
+    }
+
+    /// Asserts that the final fee, including any padding, is strictly below `lovelace`. See
+    /// `assert_output_exact`.
+    pub fn assert_total_fee_below(mut self, lovelace: u64) -> Self {
+        self.assertions
+            .push(BalanceAssertion::TotalFeeBelow(lovelace));
+        self
+    }
+
+    /// Asserts that no output of the final transaction, collateral return included, pays to an
+    /// address matching `predicate`. See `assert_output_exact`.
+    pub fn assert_no_value_to(
+        mut self,
+        predicate: impl Fn(&Address) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.assertions
+            .push(BalanceAssertion::NoValueTo(Box::new(predicate)));
+        self
+    }
+
+    /// Renders asset amounts in assertion failures with the tickers and decimals known to
+    /// `registry`, e.g. "12.5 tDJED (12500000)" instead of the raw amount and asset id.
+    pub fn token_registry(mut self, registry: Arc<dyn TokenRegistry>) -> Self {
+        self.token_registry = Some(registry);
+        self
+    }
+
     /// Sets the start of the validity interval for the transaction.
     ///
     /// Inclusive. If you care about different inclusivity, use `validity_interval` instead.
@@ -298,6 +736,37 @@ impl TxBuilder {
         Ok(self)
     }
 
+    /// Makes the transaction valid for `duration` from the moment it is built.
+    ///
+    /// The window is converted into slots during `build` against the chain tip, see
+    /// `validity_margin` for how it is rounded.
+    pub fn valid_for(mut self, duration: std::time::Duration) -> Self {
+        self.wall_clock_windows.push(WallClockWindow::For(duration));
+        self
+    }
+
+    /// Makes the transaction valid from `from` until `to`, intersected with any other bound.
+    ///
+    /// Fails during `build` if the window doesn't span a whole slot or is already over.
+    pub fn valid_between(mut self, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        self.wall_clock_windows
+            .push(WallClockWindow::Between(from, to));
+        self
+    }
+
+    /// Slot timing used to convert `valid_for`/`valid_between` windows. Defaults to mainnet's on
+    /// mainnet, other networks have to set it.
+    pub fn slot_config(mut self, slot_config: SlotConfig) -> Self {
+        self.slot_config = Some(slot_config);
+        self
+    }
+
+    /// Slack applied to `valid_for`/`valid_between` windows, one slot at each end by default.
+    pub fn validity_margin(mut self, margin: ValidityMargin) -> Self {
+        self.validity_margin = margin;
+        self
+    }
+
     /// Bounds the validity interval of the transaction by a given slot interval. Note that
     /// if you have previously bounded it, they will be intersected. If they are disjoint,
     /// the result will be the empty interval, leading to a InvalidValidityInterval error.
@@ -337,15 +806,55 @@ impl TxBuilder {
         self.body = self.body.datum(datum);
         self
     }
+
+    /// Same as `add_datum`, encoding `value` (e.g. a type generated from a blueprint) as the
+    /// datum. Returns the datum hash along with the builder, for an output to refer to.
+    pub fn add_datum_typed<T: minicbor::Encode<()>>(
+        self,
+        value: &T,
+    ) -> Result<(Self, DatumHash), TxBuilderError> {
+        let datum = minicbor::to_vec(value).map_err(|_| TxBuilderError::PlutusDataEncoding)?;
+        let hash = Datum::new(datum.clone()).hash;
+        Ok((self.add_datum(datum), hash))
+    }
     pub fn add_signer(mut self, pub_key_hash: Hash<28>) -> Self {
         self.body = self.body.disclosed_signer(pub_key_hash);
         self
     }
 
+    /// Declares the keys that will sign the native scripts of the transaction. The fee is
+    /// estimated for the fewest of them that satisfy each script, rather than for any of the
+    /// scripts' keys, and `build` fails with `UnsatisfiableNativeScript` if they can't.
+    pub fn expected_available_signers(mut self, signers: &[PubKeyHash]) -> Self {
+        self.body = self.body.expected_signers(signers.to_vec());
+        self
+    }
+
+    /// Attaches CBOR encoded auxiliary data (transaction metadata), replacing any set before.
+    /// Data that doesn't decode as auxiliary data is ignored.
+    pub fn add_auxiliary_data(mut self, data: Vec<u8>) -> Self {
+        self.body = self.body.add_auxiliary_data(data);
+        self
+    }
+
     pub fn change_datum(mut self, datum: DatumOption) -> Self {
         self.change_datum = Some(datum);
         self
     }
+
+    /// Makes no change output, even with a change address: whatever the inputs hold beyond
+    /// outputs and fee is added to the fee, as long as that's at most
+    /// `DEFAULT_NO_CHANGE_THRESHOLD` lovelace. Anything more, or less than the fee, fails `build`
+    /// with `UnbalancedWithoutChangeAddress`.
+    pub fn no_change(self) -> Self {
+        self.no_change_threshold(DEFAULT_NO_CHANGE_THRESHOLD)
+    }
+
+    /// Same as `no_change`, adding at most `lovelace` to the fee.
+    pub fn no_change_threshold(mut self, lovelace: u64) -> Self {
+        self.no_change_threshold = Some(lovelace);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -397,4 +906,22 @@ mod tests {
 
         assert_validity_interval_closed!(builder.validity_interval, 500, 1000);
     }
+
+    #[test]
+    fn typed_datums_are_encoded_and_hashed() {
+        let builder = TxBuilder::new(
+            NetworkId::Mainnet,
+            Address::from_bech32("addr1q9ct3v9ru6j8my2f6twme6gxsus670ul7pnnn4ervc0wylww7949sr6lj64c0u8ej9apt36czqm0umgd2qgjnxyrhnpqeeqvsy").unwrap(),
+        );
+        let (builder, hash) = builder
+            .add_datum_typed(&crate::primitives::BigInt::from(42))
+            .unwrap();
+
+        let bytes = minicbor::to_vec(42).unwrap();
+        assert_eq!(hash, Datum::new(bytes.clone()).hash);
+        assert_eq!(
+            builder.body.datums.get(&hash).map(|datum| &datum.bytes),
+            Some(&bytes)
+        );
+    }
 }
@@ -0,0 +1,278 @@
+//! Declarative checks on the value flows of the final transaction
+
+use std::fmt::Write as _;
+
+use pallas::ledger::addresses::Address;
+
+use super::TxBuilder;
+use super::tx::{StagingTransaction, TxBuilderError};
+use crate::primitives::{Assets, Output};
+use crate::registry::{TokenRegistry, display_assets};
+
+/// An expectation about the final transaction, checked right before `build` returns.
+pub(crate) enum BalanceAssertion {
+    OutputExact {
+        index: usize,
+        lovelace: u64,
+        assets: Assets,
+    },
+    AddressReceivesAtLeast {
+        address: Address,
+        lovelace: u64,
+    },
+    TotalFeeBelow(u64),
+    NoValueTo(Box<dyn Fn(&Address) -> bool + Send + Sync>),
+}
+
+impl TxBuilder {
+    /// Checks every registered assertion against the final transaction body, which includes the
+    /// change output and collateral.
+    pub(crate) fn check_assertions(&self, body: &StagingTransaction) -> Result<(), TxBuilderError> {
+        for assertion in &self.assertions {
+            assertion.check(body, self.token_registry.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+impl BalanceAssertion {
+    fn check(
+        &self,
+        body: &StagingTransaction,
+        registry: Option<&dyn TokenRegistry>,
+    ) -> Result<(), TxBuilderError> {
+        let failed = |which: String, expected: String, actual: String| {
+            Err(TxBuilderError::AssertionFailed {
+                which,
+                expected,
+                actual,
+                summary: summary(body, registry),
+            })
+        };
+
+        match self {
+            BalanceAssertion::OutputExact {
+                index,
+                lovelace,
+                assets,
+            } => {
+                let which = format!("output #{index} is exact");
+                let expected = format!("{lovelace} lovelace, assets {assets:?}");
+                match body.outputs.get(*index) {
+                    None => failed(which, expected, "no such output".to_string()),
+                    Some(output) => {
+                        let output_assets = output.assets.clone().unwrap_or_default();
+                        if output.lovelace == *lovelace && output_assets == *assets {
+                            Ok(())
+                        } else {
+                            let actual =
+                                format!("{} lovelace, assets {output_assets:?}", output.lovelace);
+                            failed(which, expected, actual)
+                        }
+                    }
+                }
+            }
+            BalanceAssertion::AddressReceivesAtLeast { address, lovelace } => {
+                let received: u64 = body
+                    .outputs
+                    .iter()
+                    .filter(|output| output.address == *address)
+                    .map(|output| output.lovelace)
+                    .sum();
+                if received >= *lovelace {
+                    Ok(())
+                } else {
+                    failed(
+                        format!("{} receives at least", display_address(address)),
+                        format!("{lovelace} lovelace"),
+                        format!("{received} lovelace"),
+                    )
+                }
+            }
+            BalanceAssertion::TotalFeeBelow(max_fee) => {
+                let fee = body.fee.unwrap_or_default();
+                if fee < *max_fee {
+                    Ok(())
+                } else {
+                    failed(
+                        "total fee is below".to_string(),
+                        format!("less than {max_fee} lovelace"),
+                        format!("{fee} lovelace"),
+                    )
+                }
+            }
+            BalanceAssertion::NoValueTo(predicate) => {
+                let offending = body
+                    .outputs
+                    .iter()
+                    .chain(body.collateral_output.iter())
+                    .find(|output| predicate(&output.address));
+                match offending {
+                    None => Ok(()),
+                    Some(output) => failed(
+                        "no value to matching addresses".to_string(),
+                        "no outputs".to_string(),
+                        format!(
+                            "{} lovelace to {}",
+                            output.lovelace,
+                            display_address(&output.address)
+                        ),
+                    ),
+                }
+            }
+        }
+    }
+}
+
+fn display_address(address: &Address) -> String {
+    address.to_bech32().unwrap_or_else(|_| address.to_hex())
+}
+
+/// Human readable overview of the fee and outputs, attached to assertion failures.
+pub(super) fn summary(body: &StagingTransaction, registry: Option<&dyn TokenRegistry>) -> String {
+    let mut summary = format!("fee: {} lovelace", body.fee.unwrap_or_default());
+    let describe = |output: &Output| {
+        let assets = output.assets.clone().unwrap_or_default();
+        let assets = match registry {
+            Some(registry) => format!("[{}]", display_assets(registry, &assets)),
+            None => format!("{assets:?}"),
+        };
+        format!(
+            "{} lovelace, assets {assets} to {}",
+            output.lovelace,
+            display_address(&output.address)
+        )
+    };
+    for (index, output) in body.outputs.iter().enumerate() {
+        let _ = write!(summary, "\noutput #{index}: {}", describe(output));
+    }
+    if let Some(output) = &body.collateral_output {
+        let _ = write!(summary, "\ncollateral return: {}", describe(output));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::NetworkId;
+
+    use crate::builder::TxBuilder;
+    use crate::builder::tx::{StagingTransaction, TxBuilderError};
+    use crate::primitives::{Asset, Assets, Hash, Output};
+    use crate::registry::FileTokenRegistry;
+
+    fn address(byte: u8) -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([byte; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    /// A final body as `build` would produce it: one payment and the change output.
+    fn final_body(change_lovelace: u64) -> StagingTransaction {
+        StagingTransaction::new()
+            .fee(170_000)
+            .output(
+                Output::new(address(1), 5_000_000)
+                    .add_asset(Hash([7u8; 28]), b"token".to_vec(), 10)
+                    .unwrap(),
+            )
+            .output(Output::new(address(2), change_lovelace))
+    }
+
+    fn token_assets() -> Assets {
+        let mut assets = Assets::default();
+        assets.add_asset(Asset::new(Hash([7u8; 28]), b"token".to_vec(), 10));
+        assets
+    }
+
+    fn new_builder() -> TxBuilder {
+        TxBuilder::new(NetworkId::Testnet, address(2))
+    }
+
+    #[test]
+    fn output_exact_holds() {
+        let builder = new_builder()
+            .assert_output_exact(0, 5_000_000, token_assets())
+            .assert_output_exact(1, 3_000_000, Assets::default());
+        assert_eq!(builder.check_assertions(&final_body(3_000_000)), Ok(()));
+    }
+
+    #[test]
+    fn perturbed_change_trips_output_exact() {
+        let builder = new_builder().assert_output_exact(1, 3_000_000, Assets::default());
+
+        // Simulates a change computation that shifted value away from the expected output
+        let mut body = final_body(3_000_000);
+        body.outputs[1].lovelace -= 1;
+
+        match builder.check_assertions(&body) {
+            Err(TxBuilderError::AssertionFailed {
+                which,
+                actual,
+                summary,
+                ..
+            }) => {
+                assert_eq!(which, "output #1 is exact");
+                assert!(actual.starts_with("2999999 lovelace"));
+                assert!(summary.contains("output #1: 2999999 lovelace"));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn summary_uses_the_token_registry() {
+        let registry = FileTokenRegistry::from_json(&format!(
+            r#"{{ "{}746f6b656e": {{ "ticker": "TKN", "decimals": 1 }} }}"#,
+            hex::encode([7u8; 28])
+        ))
+        .unwrap();
+        let builder = new_builder()
+            .token_registry(Arc::new(registry))
+            .assert_total_fee_below(0);
+
+        match builder.check_assertions(&final_body(3_000_000)) {
+            Err(TxBuilderError::AssertionFailed { summary, .. }) => {
+                assert!(summary.contains("output #0: 5000000 lovelace, assets [1 TKN (10)]"));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn address_receives_at_least() {
+        let body = final_body(3_000_000);
+        let builder = new_builder().assert_address_receives_at_least(address(1), 5_000_000);
+        assert_eq!(builder.check_assertions(&body), Ok(()));
+
+        let builder = new_builder().assert_address_receives_at_least(address(1), 5_000_001);
+        assert!(builder.check_assertions(&body).is_err());
+    }
+
+    #[test]
+    fn total_fee_below() {
+        let body = final_body(3_000_000);
+        let builder = new_builder().assert_total_fee_below(200_000);
+        assert_eq!(builder.check_assertions(&body), Ok(()));
+
+        let builder = new_builder().assert_total_fee_below(170_000);
+        assert!(builder.check_assertions(&body).is_err());
+    }
+
+    #[test]
+    fn no_value_to() {
+        let body = final_body(3_000_000);
+        let builder = new_builder().assert_no_value_to(|candidate| *candidate == address(3));
+        assert_eq!(builder.check_assertions(&body), Ok(()));
+
+        let builder = new_builder().assert_no_value_to(|candidate| *candidate == address(1));
+        assert!(builder.check_assertions(&body).is_err());
+    }
+}
@@ -1,19 +1,76 @@
 use std::cmp::Reverse;
-use std::sync::Arc;
 
 use anyhow::{Context, Result, ensure};
-use hydrant::UtxoIndexer;
 use hydrant::primitives::{Assets, AssetsDelta, TxOutput};
 use ogmios_client::method::pparams::ProtocolParams;
-use tokio::sync::Mutex;
+use pallas::ledger::addresses::Address;
 
-use super::{Output, TxBuilder};
-use crate::primitives::Certificate;
+use super::resolve::UtxoSource;
+use super::tx::TxBuilderError;
+use super::{Output, TxBuilder, min_utxo_lovelace};
+use crate::primitives::{Certificate, Input};
+
+/// Lovelace `no_change` lets `build` add to the fee rather than fail
+pub const DEFAULT_NO_CHANGE_THRESHOLD: u64 = 100_000;
 
 impl TxBuilder {
+    /// Fails when the transaction spends nothing and coin selection has nothing to pick from,
+    /// instead of going on to build a transaction the ledger rejects with `EmptyInputSet`.
+    pub(crate) fn ensure_fundable(&self, address_utxos: &[TxOutput]) -> Result<(), TxBuilderError> {
+        if self.body.inputs.is_empty() && address_utxos.is_empty() {
+            return Err(TxBuilderError::NoSpendableUtxos);
+        }
+        Ok(())
+    }
+
+    /// UTxOs coin selection picks from: those at the change address, and at the addresses set
+    /// with `address_pool` and `spend_from`, except the ones passed to `avoid_inputs`.
+    pub(crate) async fn spendable_utxos(&self, indexer: &impl UtxoSource) -> Result<Vec<TxOutput>> {
+        let mut utxos = vec![];
+        let mut seen = vec![];
+        for address in self
+            .change_address
+            .iter()
+            .chain(self.address_pool.iter())
+            .chain(self.source_addresses.iter())
+        {
+            if seen.contains(&address) {
+                continue;
+            }
+            seen.push(address);
+            utxos.extend(
+                indexer
+                    .address_utxos(&address.to_vec())
+                    .await?
+                    .into_iter()
+                    .filter(|utxo| !self.avoided_inputs.contains(&Input::from(utxo))),
+            );
+        }
+        Ok(utxos)
+    }
+
+    /// First address of the pool that holds none of `utxos`, isn't paid by the transaction
+    /// already and wasn't marked with `used_addresses`, where change goes to keep addresses
+    /// single-use. Emptied addresses that weren't marked can't be told apart from fresh ones.
+    pub(crate) fn next_unused_address(&self, utxos: &[TxOutput]) -> Option<Address> {
+        self.address_pool
+            .iter()
+            .find(|address| {
+                let bytes = address.to_vec();
+                !self.used_addresses.contains(address)
+                    && !utxos.iter().any(|utxo| utxo.address == bytes)
+                    && !self
+                        .body
+                        .outputs
+                        .iter()
+                        .any(|output| output.address == **address)
+            })
+            .cloned()
+    }
+
     pub(crate) async fn select_coins(
         &self,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
+        indexer: &impl UtxoSource,
         possible_utxos: &[TxOutput],
         fee: u64,
         pparams: &ProtocolParams,
@@ -23,31 +80,38 @@ impl TxBuilder {
         let input_lovelace = self.get_input_lovelace(indexer).await?;
         let input_assets = self.get_input_assets(indexer).await?;
 
-        // Filter utxos already used as inputs
+        // Filter utxos already used as inputs or reference inputs, since spending a referenced
+        // utxo is rejected by the ledger
         // TODO: should also filter out utxos with scripts? utxos with datums?
         let mut possible_utxos = possible_utxos
             .iter()
-            .filter(|utxo| !self.body.inputs.iter().any(|input| input == *utxo))
+            .filter(|utxo| {
+                !self
+                    .body
+                    .inputs
+                    .iter()
+                    .chain(self.body.reference_inputs.iter())
+                    .any(|input| input == *utxo)
+            })
             .collect::<Vec<_>>();
 
-        // TODO: consider minted assets
         // TODO: for simplicity, we assume that all assets are included in the change output
-        let mut change_output =
-            Output::new(self.change_address.clone(), 0).add_assets(input_assets.clone())?;
-        change_output.datum = self.change_datum.clone();
-        let min_change_lovelace = change_output.min_deposit(pparams)?;
+        let min_change_lovelace = match self.change_target() {
+            Some(address) => {
+                let mut change_output = Output::new(address.clone(), 0)
+                    .add_assets(self.available_assets(&input_assets).only_positive().into())?;
+                change_output.datum = self.change_datum.clone();
+                min_utxo_lovelace(&change_output, pparams)?
+            }
+            None => 0,
+        };
 
         let registration_deposit = self.get_registration_deposit();
-        let deregistration_refund = self.get_deregistration_refund();
-        let withdrawal_lovelace = self.get_withdrawal_lovelace();
+        let balance = self.lovelace_balance(input_lovelace, fee);
         let mut required_lovelace =
-            (self.get_output_lovelace() + fee + min_change_lovelace + registration_deposit)
-                .saturating_sub(input_lovelace + withdrawal_lovelace + deregistration_refund);
+            (balance.required + min_change_lovelace).saturating_sub(balance.available);
 
-        let output_assets: AssetsDelta = self.get_output_assets().into();
-        let input_assets: AssetsDelta = input_assets.into();
-        let mut required_assets: AssetsDelta =
-            output_assets - input_assets - self.body.mint.clone();
+        let mut required_assets = self.required_assets(&input_assets);
 
         // Select for assets
         while !possible_utxos.is_empty()
@@ -76,6 +140,22 @@ impl TxBuilder {
             selected_utxos.push(utxo.clone());
         }
 
+        if required_lovelace > 0 && registration_deposit > 0 {
+            let available =
+                balance.available + selected_utxos.iter().map(|utxo| utxo.lovelace).sum::<u64>();
+            return Err(TxBuilderError::InsufficientFundsForDeposit {
+                needed: self.get_output_lovelace() + fee + min_change_lovelace,
+                deposit: registration_deposit,
+                available,
+            }
+            .into());
+        }
+        if required_lovelace > 0 && self.change_target().is_none() {
+            return Err(TxBuilderError::UnbalancedWithoutChangeAddress {
+                surplus: -(required_lovelace as i64),
+            }
+            .into());
+        }
         ensure!(
             required_lovelace == 0,
             "failed to select coins, wallet doesn't contain enough lovelace (needs {} more)",
@@ -93,22 +173,16 @@ impl TxBuilder {
     /// Create change output if needed because transaction is not balanced.
     pub(crate) async fn change_output(
         &self,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
+        indexer: &impl UtxoSource,
         fee: u64,
         pparams: &ProtocolParams,
     ) -> Result<Option<Output>> {
-        // TODO: consider minted assets
         let input_lovelace = self.get_input_lovelace(indexer).await?;
-        let registration_deposit = self.get_registration_deposit();
-        let deregistration_refund = self.get_deregistration_refund();
-        let withdrawal_lovelace = self.get_withdrawal_lovelace();
-        let output_lovelace = self.get_output_lovelace();
-        let change_lovelace = (input_lovelace + withdrawal_lovelace + deregistration_refund)
-            .saturating_sub(output_lovelace + fee + registration_deposit);
-
-        let input_assets: AssetsDelta = self.get_input_assets(indexer).await?.into();
-        let output_assets: AssetsDelta = self.get_output_assets().into();
-        let change_assets = input_assets + self.body.mint.clone() - output_assets;
+        let change_lovelace = self.lovelace_balance(input_lovelace, fee).surplus();
+
+        let input_assets = self.get_input_assets(indexer).await?;
+        let change_assets =
+            self.available_assets(&input_assets) - AssetsDelta::from(self.get_output_assets());
         if !change_assets.only_negative().is_empty() {
             tracing::error!(
                 "Negative change assets: {:#?}",
@@ -122,38 +196,87 @@ impl TxBuilder {
             return Ok(None);
         }
 
-        let mut change_output = Output::new(self.change_address.clone(), change_lovelace)
+        let address = self
+            .change_target()
+            .context("transaction is built without a change output")?;
+        let mut change_output = Output::new(address.clone(), change_lovelace)
             .add_assets(change_assets.into())
             .context("failed to create change output")?;
         change_output.datum = self.change_datum.clone();
 
-        if change_output.min_deposit(pparams)? > change_output.lovelace {
+        if min_utxo_lovelace(&change_output, pparams)? > change_output.lovelace {
             return Ok(None);
         }
         Ok(Some(change_output))
     }
 
-    pub(crate) async fn get_input_lovelace(
-        &self,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
-    ) -> Result<u64> {
-        let indexer = indexer.lock().await;
-        Ok(indexer
-            .utxos(&self.body.inputs)?
+    /// Where the change output goes, `None` when the transaction is built without one.
+    pub(crate) fn change_target(&self) -> Option<&Address> {
+        self.change_address
+            .as_ref()
+            .filter(|_| self.no_change_threshold.is_none())
+    }
+
+    /// Lovelace added to `fee` in place of a change output, see `no_change`. Fails when the
+    /// transaction doesn't balance within the threshold, or leaves assets over.
+    pub(crate) async fn fee_surplus(&self, indexer: &impl UtxoSource, fee: u64) -> Result<u64> {
+        let input_assets = self.get_input_assets(indexer).await?;
+        let leftover_assets = self.required_assets(&input_assets).only_negative();
+        ensure!(
+            leftover_assets.is_empty(),
+            "assets are left over without a change output: {:?}",
+            leftover_assets
+        );
+
+        let input_lovelace = self.get_input_lovelace(indexer).await?;
+        let surplus = self
+            .lovelace_balance(input_lovelace, fee)
+            .fee_surplus(self.no_change_threshold.unwrap_or(0))?;
+        Ok(surplus)
+    }
+
+    /// Lovelace in and out of the transaction when it spends `input_lovelace` and pays `fee`.
+    ///
+    /// Withdrawals and deposit refunds are available just like inputs, so a transaction without
+    /// outputs that only withdraws pays its fee out of them and gets the rest back as change.
+    pub(crate) fn lovelace_balance(&self, input_lovelace: u64, fee: u64) -> LovelaceBalance {
+        LovelaceBalance {
+            available: input_lovelace
+                + self.get_withdrawal_lovelace()
+                + self.get_deregistration_refund(),
+            required: self.get_output_lovelace() + fee + self.get_registration_deposit(),
+        }
+    }
+
+    pub(crate) async fn get_input_lovelace(&self, indexer: &impl UtxoSource) -> Result<u64> {
+        Ok(self
+            .resolve_utxos(indexer, &self.body.inputs)
+            .await?
             .iter()
             .map(|utxo| utxo.lovelace)
             .sum())
     }
 
-    async fn get_input_assets(&self, indexer: &Arc<Mutex<UtxoIndexer>>) -> Result<Assets> {
-        let indexer = indexer.lock().await;
-        Ok(indexer
-            .utxos(&self.body.inputs)?
+    async fn get_input_assets(&self, indexer: &impl UtxoSource) -> Result<Assets> {
+        Ok(self
+            .resolve_utxos(indexer, &self.body.inputs)
+            .await?
             .iter()
             .map(|utxo| utxo.assets.clone())
             .sum())
     }
 
+    /// Assets the transaction can pay out, given the assets of its inputs: what's minted doesn't
+    /// have to be found among the inputs, what's burned does.
+    fn available_assets(&self, input_assets: &Assets) -> AssetsDelta {
+        AssetsDelta::from(input_assets.clone()) + self.body.mint.clone()
+    }
+
+    /// Assets still missing from the inputs, negative for those left over for change.
+    fn required_assets(&self, input_assets: &Assets) -> AssetsDelta {
+        AssetsDelta::from(self.get_output_assets()) - self.available_assets(input_assets)
+    }
+
     pub(crate) fn get_output_lovelace(&self) -> u64 {
         self.body.outputs.iter().map(|output| output.lovelace).sum()
     }
@@ -166,10 +289,11 @@ impl TxBuilder {
             .sum()
     }
 
-    /// Registration certificates consume a deposit from the inputs, while deregistration
-    /// certificates refund them.
+    /// Registration certificates and governance proposals consume a deposit from the inputs,
+    /// while deregistration certificates refund them.
     fn get_registration_deposit(&self) -> u64 {
-        self.body
+        let certificate_deposits = self
+            .body
             .certificates
             .iter()
             .filter_map(|cert| match cert {
@@ -177,7 +301,14 @@ impl TxBuilder {
                 Certificate::StakeRegistrationScript { deposit, .. } => *deposit,
                 _ => None,
             })
-            .sum()
+            .sum::<u64>();
+        let proposal_deposits = self
+            .body
+            .proposals
+            .iter()
+            .filter_map(|proposal| proposal.deposit)
+            .sum::<u64>();
+        certificate_deposits + proposal_deposits
     }
 
     fn get_deregistration_refund(&self) -> u64 {
@@ -196,3 +327,142 @@ impl TxBuilder {
         self.body.withdrawals.values().copied().sum()
     }
 }
+
+/// Lovelace a transaction takes in and pays out, leaving aside the inputs coin selection has yet
+/// to pick. See `TxBuilder::lovelace_balance`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) struct LovelaceBalance {
+    /// Spent inputs, withdrawals and deposit refunds
+    pub available: u64,
+    /// Outputs, fee and deposits
+    pub required: u64,
+}
+
+impl LovelaceBalance {
+    /// Lovelace left over for change
+    pub fn surplus(&self) -> u64 {
+        self.available.saturating_sub(self.required)
+    }
+
+    /// Surplus of a transaction without change output, which goes to the fee. Fails unless it's
+    /// between zero and `threshold`.
+    pub fn fee_surplus(&self, threshold: u64) -> Result<u64, TxBuilderError> {
+        let surplus = self.available as i64 - self.required as i64;
+        u64::try_from(surplus)
+            .ok()
+            .filter(|surplus| *surplus <= threshold)
+            .ok_or(TxBuilderError::UnbalancedWithoutChangeAddress { surplus })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::NetworkId;
+
+    use super::{DEFAULT_NO_CHANGE_THRESHOLD, LovelaceBalance, TxBuilder};
+    use crate::builder::tx::TxBuilderError;
+    use crate::primitives::{Asset, AssetId, Assets, Hash, Output, ScriptKind};
+
+    const POLICY: Hash<28> = Hash([7u8; 28]);
+
+    fn address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    fn paying(name: &[u8], amount: u64) -> TxBuilder {
+        TxBuilder::new(NetworkId::Testnet, address()).add_output(
+            Output::new(address(), 2_000_000)
+                .add_asset(POLICY, name.to_vec(), amount)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn minted_assets_are_not_looked_for_among_the_inputs() {
+        let builder = paying(b"NEW", 4)
+            .mint_asset(
+                Asset::new(POLICY, b"NEW".to_vec(), 10),
+                ScriptKind::PlutusV3,
+                vec![0u8],
+            )
+            .unwrap();
+        let required = builder.required_assets(&Assets::default());
+        assert!(required.only_positive().is_empty(), "{required:?}");
+        // What isn't paid out is left over for change
+        assert_eq!(
+            required.get(&AssetId::new(POLICY, b"NEW".to_vec())),
+            Some(&-6)
+        );
+    }
+
+    #[test]
+    fn burned_assets_are_looked_for_among_the_inputs() {
+        let builder = paying(b"OLD", 4)
+            .burn_asset(
+                Asset::new(POLICY, b"OLD".to_vec(), 3),
+                ScriptKind::PlutusV3,
+                vec![0u8],
+            )
+            .unwrap();
+        let required = builder.required_assets(&Assets::default());
+        assert_eq!(
+            required.get(&AssetId::new(POLICY, b"OLD".to_vec())),
+            Some(&7)
+        );
+    }
+
+    fn balance(available: u64, required: u64) -> LovelaceBalance {
+        LovelaceBalance {
+            available,
+            required,
+        }
+    }
+
+    #[test]
+    fn exact_balance_needs_no_change() {
+        assert_eq!(balance(5_170_000, 5_170_000).fee_surplus(0), Ok(0));
+
+        let builder = TxBuilder::without_change_address(NetworkId::Testnet);
+        assert_eq!(builder.change_target(), None);
+        assert_eq!(builder.no_change_threshold, None);
+    }
+
+    #[test]
+    fn small_surplus_is_folded_into_the_fee() {
+        assert_eq!(balance(5_170_400, 5_170_000).fee_surplus(1_000), Ok(400));
+        assert_eq!(
+            balance(5_170_400, 5_170_000).fee_surplus(0),
+            Err(TxBuilderError::UnbalancedWithoutChangeAddress { surplus: 400 })
+        );
+
+        // A change address is kept for `rebuild`, but gets no output
+        let builder = TxBuilder::new(NetworkId::Testnet, address()).no_change();
+        assert_eq!(builder.change_target(), None);
+        assert_eq!(
+            builder.no_change_threshold,
+            Some(DEFAULT_NO_CHANGE_THRESHOLD)
+        );
+    }
+
+    #[test]
+    fn deficit_without_change_is_reported_precisely() {
+        let err = balance(5_000_000, 5_170_000)
+            .fee_surplus(1_000)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            TxBuilderError::UnbalancedWithoutChangeAddress { surplus: -170_000 }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Transaction without change output has 170000 lovelace missing"
+        );
+    }
+}
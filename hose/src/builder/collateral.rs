@@ -1,60 +1,41 @@
 use std::cmp::Reverse;
-use std::sync::Arc;
 
 use anyhow::{Result, ensure};
-use hydrant::UtxoIndexer;
 use hydrant::primitives::{TxOutput, TxOutputPointer};
 use ogmios_client::method::pparams::ProtocolParams;
-use pallas::ledger::addresses::Address;
-use tokio::sync::Mutex;
 
-use super::TxBuilder;
-use crate::primitives::Input;
+use super::resolve::UtxoSource;
+use super::{TxBuilder, min_utxo_lovelace};
+use crate::primitives::{Assets, Input, Output, ScriptKind};
 
 impl TxBuilder {
-    fn non_collateral_inputs(&self) -> Vec<TxOutputPointer> {
-        self.body
-            .inputs
-            .iter()
-            .chain(self.body.reference_inputs.iter())
-            .map(Into::into)
-            .collect::<Vec<_>>()
-    }
-
-    async fn requires_collateral(&self, indexer: &Arc<Mutex<UtxoIndexer>>) -> Result<bool> {
-        // any mints (minting policy) or scripts (inline)
-        if !self.body.mint.is_empty() || !self.body.scripts.is_empty() {
-            return Ok(true);
-        }
-
-        // any input comes from a script or contains a script (validator)
-        let input_utxos = {
-            let indexer = indexer.lock().await;
-            indexer.utxos(&self.non_collateral_inputs())?
-        };
-        if input_utxos.iter().any(|input| {
-            Address::from_bytes(&input.address).unwrap().has_script() || input.script.is_some()
-        }) {
-            return Ok(true);
-        }
-
-        Ok(false)
+    /// Collateral only backs phase-2 validation, i.e. plutus scripts run through a redeemer.
+    /// Native scripts are validated in phase-1 and never require collateral, even when spending
+    /// from, minting with or withdrawing through them.
+    pub(crate) fn requires_collateral(&self) -> bool {
+        let has_redeemers = self
+            .body
+            .redeemers
+            .as_ref()
+            .is_some_and(|redeemers| !redeemers.is_empty());
+        has_redeemers
+            && self
+                .used_script_kinds()
+                .iter()
+                .any(|kind| *kind != ScriptKind::Native)
     }
 
-    pub(crate) async fn collateral_inputs(
+    pub(crate) fn collateral_inputs(
         &self,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
         possible_utxos: &[TxOutput],
         pparams: &ProtocolParams,
         fee: u64,
     ) -> Result<Vec<Input>> {
-        if !self.body.collateral_inputs.is_empty() || !self.requires_collateral(indexer).await? {
+        if !self.body.collateral_inputs.is_empty() || !self.requires_collateral() {
             return Ok(vec![]);
         }
 
-        // note: collateral_percentage is a percent (e.g., 150), so divide by 100 to get the multiplier
-        let required_lovelace =
-            ((fee as f64) * pparams.collateral_percentage / 100.0).ceil() as u64;
+        let required_lovelace = required_collateral(fee, pparams);
 
         let max_collateral_inputs = if pparams.max_collateral_inputs > 0 {
             pparams.max_collateral_inputs as usize
@@ -62,10 +43,121 @@ impl TxBuilder {
             3 // NOTE: Current Cardano protocol limits this to 3 (Feb 9, 2026)
         };
 
-        select_collateral(possible_utxos, required_lovelace, max_collateral_inputs)
+        select_unspent_collateral(
+            possible_utxos,
+            &self.body.inputs,
+            required_lovelace,
+            max_collateral_inputs,
+        )
+    }
+
+    /// Collateral set aside before coin selection, so that selection doesn't spend the only
+    /// UTxOs eligible for it. Empty when no collateral is needed, when it comes from
+    /// `collateral_from` or was given explicitly, and when nothing is eligible, in which case
+    /// the collateral step reports the error.
+    pub(crate) fn reserve_collateral(
+        &self,
+        address_utxos: &[TxOutput],
+        pparams: &ProtocolParams,
+        fee: u64,
+    ) -> Vec<Input> {
+        if self.collateral_source.is_some() {
+            return vec![];
+        }
+        self.collateral_inputs(address_utxos, pparams, fee)
+            .unwrap_or_default()
+    }
+
+    /// Coin selection out of `address_utxos` minus the `reserved` collateral, falling back to
+    /// all of them when the transaction can't be funded otherwise.
+    pub(crate) async fn select_coins_around(
+        &self,
+        indexer: &impl UtxoSource,
+        address_utxos: &[TxOutput],
+        reserved: &[Input],
+        fee: u64,
+        pparams: &ProtocolParams,
+    ) -> Result<Vec<TxOutput>> {
+        if reserved.is_empty() {
+            return self
+                .select_coins(indexer, address_utxos, fee, pparams)
+                .await;
+        }
+        let unreserved = address_utxos
+            .iter()
+            .filter(|utxo| !reserved.iter().any(|input| input == *utxo))
+            .cloned()
+            .collect::<Vec<_>>();
+        match self.select_coins(indexer, &unreserved, fee, pparams).await {
+            Ok(selected) => Ok(selected),
+            Err(_) => {
+                self.select_coins(indexer, address_utxos, fee, pparams)
+                    .await
+            }
+        }
+    }
+
+    /// Output sending whatever the collateral inputs hold above the required collateral back to
+    /// the collateral address, if one is set. Omitted when that excess doesn't cover the minimum
+    /// deposit of the output, in which case all of the collateral is at stake.
+    pub(crate) async fn collateral_return(
+        &self,
+        indexer: &impl UtxoSource,
+        collateral_inputs: &[Input],
+        fee: u64,
+        pparams: &ProtocolParams,
+    ) -> Result<Option<Output>> {
+        let Some(address) = &self.collateral_address else {
+            return Ok(None);
+        };
+        if collateral_inputs.is_empty() {
+            return Ok(None);
+        }
+
+        let utxos = self.resolve_utxos(indexer, collateral_inputs).await?;
+        let collateral_lovelace = utxos.iter().map(|utxo| utxo.lovelace).sum::<u64>();
+        let Some(excess) = collateral_lovelace.checked_sub(required_collateral(fee, pparams))
+        else {
+            return Ok(None);
+        };
+        let assets = utxos
+            .into_iter()
+            .fold(Assets::default(), |assets, utxo| assets + utxo.assets);
+
+        let mut output = Output::new(address.clone(), excess);
+        if !assets.is_empty() {
+            output = output.add_assets(assets)?;
+        }
+        if min_utxo_lovelace(&output, pparams)? > output.lovelace {
+            return Ok(None);
+        }
+        Ok(Some(output))
     }
 }
 
+/// Lovelace the collateral must cover for a transaction paying `fee`.
+fn required_collateral(fee: u64, pparams: &ProtocolParams) -> u64 {
+    // note: collateral_percentage is a percent (e.g., 150), so divide by 100 to get the multiplier
+    ((fee as f64) * pparams.collateral_percentage / 100.0).ceil() as u64
+}
+
+/// Selects collateral out of the UTxOs that aren't `spent` by the transaction, such as the one
+/// reserved before coin selection, and only out of all of them if those aren't enough.
+fn select_unspent_collateral(
+    possible_utxos: &[TxOutput],
+    spent: &[Input],
+    required_lovelace: u64,
+    max_collateral_inputs: usize,
+) -> Result<Vec<Input>> {
+    let unspent = possible_utxos
+        .iter()
+        .filter(|utxo| !spent.iter().any(|input| input == *utxo))
+        .cloned()
+        .collect::<Vec<_>>();
+    select_collateral(&unspent, required_lovelace, max_collateral_inputs)
+        .or_else(|_| select_collateral(possible_utxos, required_lovelace, max_collateral_inputs))
+}
+
 fn select_collateral(
     possible_utxos: &[TxOutput],
     required_lovelace: u64,
@@ -121,6 +213,11 @@ fn select_collateral(
 
 #[cfg(test)]
 mod tests {
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::NetworkId;
+
     use super::*;
     use crate::primitives::Hash;
 
@@ -202,4 +299,56 @@ mod tests {
         let res = select_collateral(&utxos, 150, 3);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn collateral_avoids_spent_utxos() {
+        let utxo = |index: u64, lovelace: u64| TxOutput {
+            hash: Hash([0u8; 32]),
+            index,
+            address: vec![0; 29],
+            lovelace,
+            assets: Default::default(),
+            script: None,
+            datum_hash: None,
+        };
+        let utxos = vec![utxo(0, 5_000_000), utxo(1, 20_000_000)];
+        let spent = vec![Input::new(Hash([0u8; 32]), 0)];
+
+        // The smallest UTxO would do, but it's spent already
+        let selected = select_unspent_collateral(&utxos, &spent, 1_000_000, 3).unwrap();
+        assert_eq!(selected, vec![Input::new(Hash([0u8; 32]), 1)]);
+
+        // Spent UTxOs still back the collateral when the others can't
+        let selected = select_unspent_collateral(&utxos[..1], &spent, 1_000_000, 3).unwrap();
+        assert_eq!(selected, spent);
+    }
+
+    fn new_builder() -> TxBuilder {
+        let address = Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ));
+        TxBuilder::new(NetworkId::Testnet, address)
+    }
+
+    #[test]
+    fn native_script_spend_and_withdrawal_skip_collateral() {
+        let builder = new_builder()
+            .add_input(Input::new(Hash([2u8; 32]), 0))
+            .add_script(ScriptKind::Native, vec![0x82, 0x00, 0x58, 0x1c])
+            .withdraw_from_script(Hash([3u8; 28]), ScriptKind::Native, 0, None)
+            .unwrap();
+        assert!(!builder.requires_collateral());
+    }
+
+    #[test]
+    fn plutus_script_spend_requires_collateral() {
+        let builder = new_builder().add_script_input(
+            Input::new(Hash([2u8; 32]), 0),
+            vec![0xd8, 0x79, 0x80],
+            ScriptKind::PlutusV3,
+        );
+        assert!(builder.requires_collateral());
+    }
 }
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use ogmios_client::OgmiosHttpClient;
+use ogmios_client::method::evaluate::Evaluation;
+use ogmios_client::method::tip::Tip;
+
+use crate::ogmios::OgmiosQueries;
+use crate::primitives::{Input, Output};
+
+/// Where `build` gets script budgets and the chain tip from, and the treasury value when it's
+/// needed.
+///
+/// Implemented for `OgmiosHttpClient`, and for `OgmiosQueries`, which is the one to use when
+/// spending outputs of transactions that aren't included yet: only it passes them on to the
+/// evaluation. Only `OgmiosQueries` can query the treasury too.
+pub trait Evaluator: Sync {
+    /// Budgets of the scripts the serialized `tx` runs. `additional_utxos` are the UTxOs the
+    /// caller resolved for the transaction, which the node may not know yet.
+    ///
+    /// Evaluators not backed by Ogmios can convert their budgets from `ValidatorBudget`s.
+    fn evaluate(
+        &self,
+        tx: &[u8],
+        additional_utxos: &[(Input, Output)],
+    ) -> impl Future<Output = Result<Vec<Evaluation>>> + Send;
+
+    /// Slot of the chain tip, 0 at the origin.
+    fn tip_slot(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Lovelace in the treasury, declared by transactions proposing treasury withdrawals without
+    /// a value set with `current_treasury_value`. `None` when it can't be queried, the default.
+    fn treasury_value(&self) -> impl Future<Output = Result<Option<u64>>> + Send {
+        async { Ok(None) }
+    }
+}
+
+/// Ignores `additional_utxos`, which the client has no way to send: transactions spending
+/// outputs the node doesn't know yet fail to evaluate. Can't query the treasury either.
+impl Evaluator for OgmiosHttpClient {
+    async fn evaluate(
+        &self,
+        tx: &[u8],
+        _additional_utxos: &[(Input, Output)],
+    ) -> Result<Vec<Evaluation>> {
+        let tx = tx.to_vec();
+        Ok(OgmiosHttpClient::evaluate(self, &tx).await?)
+    }
+
+    async fn tip_slot(&self) -> Result<u64> {
+        Ok(match self.query_tip().await? {
+            Tip::Point { slot, .. } => slot,
+            Tip::Origin => 0,
+        })
+    }
+}
+
+impl Evaluator for OgmiosQueries {
+    async fn evaluate(
+        &self,
+        tx: &[u8],
+        additional_utxos: &[(Input, Output)],
+    ) -> Result<Vec<Evaluation>> {
+        Ok(OgmiosQueries::evaluate(self, tx, additional_utxos).await?)
+    }
+
+    async fn tip_slot(&self) -> Result<u64> {
+        Ok(OgmiosQueries::tip_slot(self).await?)
+    }
+
+    async fn treasury_value(&self) -> Result<Option<u64>> {
+        Ok(Some(self.treasury_and_reserves().await?.treasury))
+    }
+}
@@ -0,0 +1,584 @@
+//! Script budgets persisted across processes
+//!
+//! `ExUnitsCache` only lives as long as the process that filled it. An `ExUnitsStore` keeps
+//! budgets where every builder of a fleet can reach them, so that a script run with a redeemer of
+//! a shape seen before isn't evaluated again. Entries are keyed by script hash and a fingerprint
+//! of the shape of the redeemer and datum: constructor tags, list and map lengths, and byte
+//! string lengths, but neither integer values nor the bytes themselves. Budgets of the same shape
+//! differ little, so the largest one seen is kept and raised by a safety margin when used.
+//!
+//! Entries expire after a time to live, and are ignored once the cost models they were evaluated
+//! under change.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hydrant::primitives::TxOutput;
+use ogmios_client::method::pparams::ProtocolParams;
+use pallas::crypto::hash::Hasher;
+use pallas::ledger::primitives::{Fragment as _, PlutusData};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::quote::redeemer_script_hash;
+use super::tx::StagingTransaction;
+use crate::primitives::{ExUnits, Hash, Input, RedeemerPurpose, hash_hex_serde};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_SAFETY_PERCENT: u64 = 5;
+
+/// Script budget lookup key, see `ExUnitsKey::new`.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Serialize, Deserialize)]
+pub struct ExUnitsKey {
+    #[serde(with = "hash_hex_serde")]
+    pub script_hash: Hash<28>,
+    /// Fingerprint of the shape of the redeemer and datum
+    #[serde(with = "hash_hex_serde")]
+    pub shape: Hash<32>,
+}
+
+impl ExUnitsKey {
+    /// Key of `script_hash` running with the CBOR encoded `redeemer`, and `datum` when spending.
+    ///
+    /// Data that isn't valid plutus data only counts by its length.
+    pub fn new(script_hash: Hash<28>, redeemer: &[u8], datum: Option<&[u8]>) -> Self {
+        let mut shape = Vec::new();
+        push_shape(redeemer, &mut shape);
+        match datum {
+            Some(datum) => {
+                shape.push(1);
+                push_shape(datum, &mut shape);
+            }
+            None => shape.push(0),
+        }
+        let mut hasher = Hasher::<256>::new();
+        hasher.input(&shape);
+        Self {
+            script_hash,
+            shape: hasher.finalize().into(),
+        }
+    }
+}
+
+/// Largest budget recorded for a key.
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub struct StoredExUnits {
+    pub mem: u64,
+    pub steps: u64,
+    /// Cost models the budget was evaluated under, see `cost_models_fingerprint`
+    #[serde(with = "hash_hex_serde")]
+    pub cost_models: Hash<32>,
+    /// Unix time in seconds the budget was last recorded
+    pub recorded_at: u64,
+}
+
+/// Backend keeping script budgets beyond the lifetime of a process, see `FileExUnitsStore`.
+pub trait ExUnitsStore: Send + Sync {
+    fn get(&self, key: &ExUnitsKey) -> Result<Option<StoredExUnits>, ExUnitsStoreError>;
+
+    /// Stores `entry`, replacing any entry of the same key.
+    fn put(&self, key: ExUnitsKey, entry: StoredExUnits) -> Result<(), ExUnitsStoreError>;
+}
+
+#[derive(Error, Debug)]
+pub enum ExUnitsStoreError {
+    #[error("Could not access the ex units store: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Ex units store holds malformed JSON: {0}")]
+    MalformedJson(#[from] serde_json::Error),
+}
+
+/// Store kept in a JSON file, which may be shared by several processes.
+///
+/// Every `put` reads the file, merges the entry in and replaces the file at once with a rename,
+/// so readers never see a partial write. Writers of different processes racing each other may
+/// lose an update, which only costs an evaluation later on.
+pub struct FileExUnitsStore {
+    path: PathBuf,
+    /// Serializes the writes of this process
+    lock: StdMutex<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    entries: Vec<FileEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    #[serde(flatten)]
+    key: ExUnitsKey,
+    #[serde(flatten)]
+    budget: StoredExUnits,
+}
+
+impl FileExUnitsStore {
+    /// Store in the file at `path`, which is created on the first `put`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: StdMutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Result<HashMap<ExUnitsKey, StoredExUnits>, ExUnitsStoreError> {
+        let json = match std::fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let file: StoreFile = serde_json::from_str(&json)?;
+        Ok(file
+            .entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.budget))
+            .collect())
+    }
+}
+
+impl ExUnitsStore for FileExUnitsStore {
+    fn get(&self, key: &ExUnitsKey) -> Result<Option<StoredExUnits>, ExUnitsStoreError> {
+        Ok(self.read()?.remove(key))
+    }
+
+    fn put(&self, key: ExUnitsKey, entry: StoredExUnits) -> Result<(), ExUnitsStoreError> {
+        let _guard = self.lock.lock().expect("ex units store lock poisoned");
+        let mut entries = self.read()?;
+        entries.insert(key, entry);
+        let file = StoreFile {
+            entries: entries
+                .into_iter()
+                .map(|(key, budget)| FileEntry { key, budget })
+                .collect(),
+        };
+        replace_file(&self.path, &serde_json::to_vec(&file)?)?;
+        Ok(())
+    }
+}
+
+/// Replaces the file at `path` with `contents` at once, so that readers never see a partial
+/// write.
+pub(super) fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // Written next to the file so that the rename stays on one filesystem, under a name no other
+    // writer, in this process or another, is using
+    static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(&temp_path, path).inspect_err(|_| {
+        std::fs::remove_file(&temp_path).ok();
+    })
+}
+
+/// Store shared with builders through `TxBuilder::ex_units_store`, along with how its entries
+/// are used.
+///
+/// Entries are tied to the cost models they were evaluated under rather than to an epoch. A
+/// budget only depends on the script, its arguments and the cost models, so a new epoch alone
+/// doesn't change it, while an expiry at each epoch boundary would have the whole fleet evaluate
+/// everything again every epoch. A protocol parameter update that does change the cost models
+/// changes `cost_models_fingerprint`, which invalidates the entries at once.
+#[derive(Clone)]
+pub struct PersistentExUnits {
+    store: Arc<dyn ExUnitsStore>,
+    ttl: Duration,
+    safety_percent: u64,
+}
+
+impl PersistentExUnits {
+    /// Uses `store` with entries expiring after a day and a 5% safety margin.
+    pub fn new(store: impl ExUnitsStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            ttl: DEFAULT_TTL,
+            safety_percent: DEFAULT_SAFETY_PERCENT,
+        }
+    }
+
+    /// How long after being recorded an entry is still used.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Percentage stored budgets are raised by before use, covering the inputs of the same shape
+    /// that cost a bit more than the ones evaluated so far.
+    pub fn safety_percent(mut self, percent: u64) -> Self {
+        self.safety_percent = percent;
+        self
+    }
+
+    /// Budget to give a script with `key`, when a fresh entry evaluated under `cost_models`
+    /// exists.
+    pub fn budget(
+        &self,
+        key: &ExUnitsKey,
+        cost_models: Hash<32>,
+    ) -> Result<Option<ExUnits>, ExUnitsStoreError> {
+        self.budget_at(key, cost_models, unix_time())
+    }
+
+    fn budget_at(
+        &self,
+        key: &ExUnitsKey,
+        cost_models: Hash<32>,
+        now: u64,
+    ) -> Result<Option<ExUnits>, ExUnitsStoreError> {
+        let Some(entry) = self.store.get(key)? else {
+            return Ok(None);
+        };
+        let expired = now.saturating_sub(entry.recorded_at) > self.ttl.as_secs();
+        if expired || entry.cost_models != cost_models {
+            return Ok(None);
+        }
+        Ok(Some(ExUnits {
+            mem: with_margin(entry.mem, self.safety_percent),
+            steps: with_margin(entry.steps, self.safety_percent),
+        }))
+    }
+
+    /// Records an evaluated budget, keeping the largest one seen under the same cost models.
+    pub fn record(
+        &self,
+        key: ExUnitsKey,
+        ex_units: &ExUnits,
+        cost_models: Hash<32>,
+    ) -> Result<(), ExUnitsStoreError> {
+        self.record_at(key, ex_units, cost_models, unix_time())
+    }
+
+    fn record_at(
+        &self,
+        key: ExUnitsKey,
+        ex_units: &ExUnits,
+        cost_models: Hash<32>,
+        now: u64,
+    ) -> Result<(), ExUnitsStoreError> {
+        let mut entry = StoredExUnits {
+            mem: ex_units.mem,
+            steps: ex_units.steps,
+            cost_models,
+            recorded_at: now,
+        };
+        if let Some(previous) = self.store.get(&key)?
+            && previous.cost_models == cost_models
+            && now.saturating_sub(previous.recorded_at) <= self.ttl.as_secs()
+        {
+            entry.mem = entry.mem.max(previous.mem);
+            entry.steps = entry.steps.max(previous.steps);
+        }
+        self.store.put(key, entry)
+    }
+
+    /// Budget of every redeemer of `tx` without explicit ex units, or `None` as soon as one of
+    /// them has no usable entry.
+    pub(crate) fn budgets(
+        &self,
+        tx: &StagingTransaction,
+        known_utxos: &HashMap<Input, TxOutput>,
+        cost_models: Hash<32>,
+    ) -> Result<Option<HashMap<RedeemerPurpose, ExUnits>>, ExUnitsStoreError> {
+        let mut budgets = HashMap::new();
+        let unbudgeted = tx
+            .redeemers
+            .iter()
+            .flat_map(|rdmrs| rdmrs.iter())
+            .filter(|(_, (_, ex_units))| ex_units.is_none());
+        for (purpose, _) in unbudgeted {
+            let Some(key) = redeemer_key(purpose, tx, known_utxos) else {
+                return Ok(None);
+            };
+            let Some(budget) = self.budget(&key, cost_models)? else {
+                return Ok(None);
+            };
+            budgets.insert(purpose.clone(), budget);
+        }
+        Ok(Some(budgets))
+    }
+
+    /// Records the budget of every redeemer of `tx` found in `budgets`.
+    pub(crate) fn record_all(
+        &self,
+        tx: &StagingTransaction,
+        budgets: &HashMap<RedeemerPurpose, ExUnits>,
+        known_utxos: &HashMap<Input, TxOutput>,
+        cost_models: Hash<32>,
+    ) -> Result<(), ExUnitsStoreError> {
+        for (purpose, ex_units) in budgets {
+            if let Some(key) = redeemer_key(purpose, tx, known_utxos) {
+                self.record(key, ex_units, cost_models)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fingerprint of the plutus cost models of `pparams`, which budgets evaluated under other cost
+/// models don't apply to.
+pub fn cost_models_fingerprint(pparams: &ProtocolParams) -> Hash<32> {
+    let models = &pparams.plutus_cost_models;
+    let mut hasher = Hasher::<256>::new();
+    for (language, costs) in [
+        (1u8, models.plutus_v1.as_ref().map(|model| &model.0)),
+        (2, models.plutus_v2.as_ref().map(|model| &model.0)),
+        (3, models.plutus_v3.as_ref().map(|model| &model.0)),
+    ] {
+        if let Some(costs) = costs {
+            hasher.input(&[language]);
+            for cost in costs {
+                hasher.input(&cost.to_be_bytes());
+            }
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Key of the redeemer with `purpose`. Spends include the datum when the transaction carries it,
+/// inline datums aren't known to the indexer so only the redeemer counts for those.
+fn redeemer_key(
+    purpose: &RedeemerPurpose,
+    tx: &StagingTransaction,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> Option<ExUnitsKey> {
+    let script_hash = redeemer_script_hash(purpose, tx, known_utxos)?;
+    let (redeemer, _) = tx.redeemers.as_ref()?.get(purpose)?;
+    let datum = match purpose {
+        RedeemerPurpose::Spend(input) => known_utxos
+            .get(input)
+            .and_then(|utxo| utxo.datum_hash)
+            .and_then(|hash| tx.datums.get(&hash)),
+        _ => None,
+    };
+    Some(ExUnitsKey::new(
+        script_hash,
+        redeemer,
+        datum.map(|datum| datum.bytes.as_slice()),
+    ))
+}
+
+fn push_shape(cbor: &[u8], shape: &mut Vec<u8>) {
+    match PlutusData::decode_fragment(cbor) {
+        Ok(data) => push_data_shape(&data, shape),
+        Err(_) => {
+            shape.push(0xff);
+            push_length(cbor.len(), shape);
+        }
+    }
+}
+
+fn push_data_shape(data: &PlutusData, shape: &mut Vec<u8>) {
+    match data {
+        PlutusData::Constr(constr) => {
+            shape.push(0);
+            shape.extend(constr.tag.to_be_bytes());
+            shape.extend(constr.any_constructor.unwrap_or_default().to_be_bytes());
+            push_length(constr.fields.len(), shape);
+            for field in constr.fields.iter() {
+                push_data_shape(field, shape);
+            }
+        }
+        PlutusData::Map(entries) => {
+            shape.push(1);
+            push_length(entries.len(), shape);
+            for (key, value) in entries.iter() {
+                push_data_shape(key, shape);
+                push_data_shape(value, shape);
+            }
+        }
+        PlutusData::BigInt(_) => shape.push(2),
+        PlutusData::BoundedBytes(bytes) => {
+            shape.push(3);
+            push_length(bytes.len(), shape);
+        }
+        PlutusData::Array(items) => {
+            shape.push(4);
+            push_length(items.len(), shape);
+            for item in items.iter() {
+                push_data_shape(item, shape);
+            }
+        }
+    }
+}
+
+fn push_length(length: usize, shape: &mut Vec<u8>) {
+    shape.extend((length as u64).to_be_bytes());
+}
+
+fn with_margin(budget: u64, percent: u64) -> u64 {
+    budget.saturating_mul(100 + percent).div_ceil(100)
+}
+
+pub(super) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the clock is past the epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{ExUnitsKey, ExUnitsStore, FileExUnitsStore, PersistentExUnits, StoredExUnits};
+    use crate::primitives::{ExUnits, Hash};
+
+    fn temp_store_path() -> PathBuf {
+        std::env::temp_dir().join(format!("hose-ex-units-{}.json", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn keys_only_depend_on_the_shape_of_the_data() {
+        let script = Hash([1u8; 28]);
+        // Constructor 0 with a single integer field
+        let key = ExUnitsKey::new(script, &[0xd8, 0x79, 0x81, 0x05], None);
+
+        assert_eq!(
+            key,
+            ExUnitsKey::new(script, &[0xd8, 0x79, 0x81, 0x06], None)
+        );
+        // Another constructor, a byte string field, or a datum make for other shapes
+        assert_ne!(
+            key,
+            ExUnitsKey::new(script, &[0xd8, 0x7a, 0x81, 0x05], None)
+        );
+        assert_ne!(
+            key,
+            ExUnitsKey::new(script, &[0xd8, 0x79, 0x81, 0x41, 0xaa], None)
+        );
+        assert_ne!(
+            key,
+            ExUnitsKey::new(script, &[0xd8, 0x79, 0x81, 0x05], Some(&[0x05][..]))
+        );
+        assert_ne!(
+            key,
+            ExUnitsKey::new(Hash([2u8; 28]), &[0xd8, 0x79, 0x81, 0x05], None)
+        );
+    }
+
+    #[test]
+    fn stored_budgets_get_a_margin_and_go_stale() {
+        let path = temp_store_path();
+        let store = PersistentExUnits::new(FileExUnitsStore::new(&path))
+            .ttl(Duration::from_secs(60))
+            .safety_percent(10);
+        let key = ExUnitsKey::new(Hash([1u8; 28]), &[0x05], None);
+        let cost_models = Hash([7u8; 32]);
+
+        store
+            .record_at(
+                key.clone(),
+                &ExUnits {
+                    mem: 100,
+                    steps: 2_001,
+                },
+                cost_models,
+                1_000,
+            )
+            .unwrap();
+        store
+            .record_at(
+                key.clone(),
+                &ExUnits {
+                    mem: 150,
+                    steps: 1_000,
+                },
+                cost_models,
+                1_010,
+            )
+            .unwrap();
+        // The largest budget seen, raised by 10% and rounded up
+        assert_eq!(
+            store.budget_at(&key, cost_models, 1_070).unwrap(),
+            Some(ExUnits {
+                mem: 165,
+                steps: 2_202
+            })
+        );
+        assert_eq!(store.budget_at(&key, cost_models, 1_071).unwrap(), None);
+        assert_eq!(store.budget_at(&key, Hash([8u8; 32]), 1_010).unwrap(), None);
+
+        // Budgets from before a cost model change are replaced rather than merged
+        store
+            .record_at(
+                key.clone(),
+                &ExUnits { mem: 50, steps: 50 },
+                Hash([8u8; 32]),
+                1_020,
+            )
+            .unwrap();
+        assert_eq!(store.budget_at(&key, cost_models, 1_020).unwrap(), None);
+        assert_eq!(
+            store.budget_at(&key, Hash([8u8; 32]), 1_020).unwrap(),
+            Some(ExUnits { mem: 55, steps: 55 })
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn concurrent_writers_leave_a_valid_file() {
+        let path = temp_store_path();
+        let shared = Arc::new(FileExUnitsStore::new(&path));
+        let cost_models = Hash([7u8; 32]);
+        let key =
+            |writer: u8, entry: u8| ExUnitsKey::new(Hash([writer * 10 + entry; 28]), &[0x05], None);
+
+        let writers = (0..8u8)
+            .map(|writer| {
+                let shared = shared.clone();
+                // Every other writer has a store of its own, like another process would
+                let own = FileExUnitsStore::new(&path);
+                std::thread::spawn(move || {
+                    for entry in 0..10u8 {
+                        let store: &dyn ExUnitsStore = if writer % 2 == 0 {
+                            shared.as_ref()
+                        } else {
+                            &own
+                        };
+                        store
+                            .put(
+                                key(writer, entry),
+                                StoredExUnits {
+                                    mem: u64::from(entry),
+                                    steps: u64::from(writer),
+                                    cost_models,
+                                    recorded_at: 0,
+                                },
+                            )
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // Writers racing from other stores may have lost entries, but those left are intact
+        let store = FileExUnitsStore::new(&path);
+        let mut found = 0;
+        for writer in 0..8u8 {
+            for entry in 0..10u8 {
+                if let Some(stored) = store.get(&key(writer, entry)).unwrap() {
+                    assert_eq!(
+                        (stored.mem, stored.steps),
+                        (u64::from(entry), u64::from(writer))
+                    );
+                    found += 1;
+                }
+            }
+        }
+        assert!(found > 0);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -1,195 +1,363 @@
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{Context, Result};
-use hydrant::UtxoIndexer;
-use hydrant::primitives::TxOutputPointer;
+use hydrant::primitives::TxOutput;
 use num::{BigRational, ToPrimitive as _};
-use ogmios_client::OgmiosHttpClient;
 use ogmios_client::method::evaluate::Evaluation;
 use ogmios_client::method::pparams::ProtocolParams;
 use pallas::ledger::addresses::{Address, ShelleyPaymentPart};
-use tokio::sync::Mutex;
+use pallas::ledger::primitives::Fragment;
+use pallas::ledger::primitives::conway::NativeScript;
 
-use super::TxBuilder;
-use crate::builder::tx::StagingTransaction;
-use crate::primitives::Certificate;
+use super::resolve::{UtxoSource, resolve_utxos};
+use super::{Evaluator, TxBuilder};
+use crate::builder::tx::{BuiltTransaction, StagingTransaction, TxBuilderError};
+use crate::primitives::{
+    Certificate, Hash, Input, Output, PubKeyHash, ScriptKind, min_signers, timelock_holds,
+};
 
 impl TxBuilder {
     /// Returns the minimum lovelace for a transaction
+    ///
+    /// Inputs found in `known_utxos` are resolved from it instead of the indexer. Those of
+    /// `known_outputs` the transaction spends or refers to are passed on to the evaluation, see
+    /// `Evaluator::evaluate`. Transactions without redeemers run no scripts, so their fee is
+    /// computed from size alone, without asking Ogmios to evaluate them. The same goes for
+    /// transactions with pinned ex units, whose script fee is computed from the pinned budgets.
     pub async fn min_fee(
         tx: &StagingTransaction,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
-        ogmios: &OgmiosHttpClient,
+        indexer: &impl UtxoSource,
+        known_utxos: &HashMap<Input, TxOutput>,
+        known_outputs: &HashMap<Input, Output>,
+        ogmios: &impl Evaluator,
         pparams: &ProtocolParams,
         evaluation: Option<Vec<Evaluation>>,
     ) -> Result<(u64, Vec<Evaluation>)> {
         // Estimate witness count
-        let input_pointers = tx
-            .inputs
-            .iter()
-            .chain(tx.collateral_inputs.iter())
-            .map(|input| TxOutputPointer::new(input.hash, input.index))
-            .collect::<Vec<_>>();
-
-        let inputs = {
-            let indexer = indexer.lock().await;
-            indexer
-                .utxos(&input_pointers)
-                .context("Failed to fetch input UTXOs for witness estimation")?
+        let mut signers = required_signers(tx, indexer, known_utxos).await?;
+        signers.extend(native_script_signers(tx)?);
+        let witness_count = signers.len().max(1);
+
+        // Hooks may add redeemers after the budgets were pinned, which nothing would evaluate
+        if tx.ex_units_pinned
+            && let Some((purpose, _)) = tx
+                .redeemers
+                .iter()
+                .flat_map(|redeemers| redeemers.iter())
+                .find(|(_, (_, ex_units))| ex_units.is_none())
+        {
+            return Err(TxBuilderError::MissingPinnedExUnits {
+                purpose: purpose.clone(),
+            }
+            .into());
+        }
+
+        let built_tx = tx
+            .clone()
+            .build(evaluation.clone())
+            .context("Failed to build transaction for fee calculation")?;
+        let built_tx = with_dummy_witnesses(built_tx, witness_count)?;
+
+        // Only plutus scripts need to be evaluated, and they can't run without a redeemer
+        let has_redeemers = tx
+            .redeemers
+            .as_ref()
+            .is_some_and(|redeemers| !redeemers.is_empty());
+        let (built_tx, evaluation) = if has_redeemers && !tx.ex_units_pinned {
+            let additional_utxos = tx
+                .inputs
+                .iter()
+                .chain(tx.reference_inputs.iter())
+                .chain(tx.collateral_inputs.iter())
+                .filter_map(|input| Some((input.clone(), known_outputs.get(input)?.clone())))
+                .collect::<Vec<_>>();
+            let evaluation = ogmios
+                .evaluate(&built_tx.bytes, &additional_utxos)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to evaluate transaction, redeemers:\n{}",
+                        tx.redeemer_legend()
+                    )
+                })?;
+            let built_tx = tx
+                .clone()
+                .build(Some(evaluation.clone()))
+                .context("Failed to build transaction with evaluation")?;
+            (with_dummy_witnesses(built_tx, witness_count)?, evaluation)
+        } else {
+            (built_tx, vec![])
         };
 
-        let mut signers = HashSet::new();
-        for input in inputs {
-            let address = Address::from_bytes(&input.address).context("Invalid address")?;
+        // Fee from scripts
+        let (total_cpu, total_mem) = if tx.ex_units_pinned {
+            let total = tx.total_ex_units();
+            (
+                BigRational::from_integer(total.steps.into()),
+                BigRational::from_integer(total.mem.into()),
+            )
+        } else {
+            (
+                evaluation
+                    .iter()
+                    .map(|e| e.budget.cpu.0.clone())
+                    .sum::<BigRational>(),
+                evaluation
+                    .iter()
+                    .map(|e| e.budget.memory.0.clone())
+                    .sum::<BigRational>(),
+            )
+        };
+        let total_ref_script_size = reference_script_size(tx, indexer, known_utxos).await?;
 
-            if let Address::Shelley(shelley_addr) = address
-                && let ShelleyPaymentPart::Key(hash) = shelley_addr.payment()
-            {
-                signers.insert(*hash);
-            }
+        let fee = fee_for(
+            built_tx.bytes.len() as u64,
+            total_cpu,
+            total_mem,
+            total_ref_script_size,
+            pparams,
+        )?;
+        Ok((fee, evaluation))
+    }
+}
+
+/// Total size of the reference scripts carried by the inputs and reference inputs of `tx`, which
+/// are charged for on top of the transaction size.
+pub(crate) async fn reference_script_size(
+    tx: &StagingTransaction,
+    indexer: &impl UtxoSource,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> Result<u64> {
+    let inputs_and_ref_inputs = tx
+        .inputs
+        .iter()
+        .chain(tx.reference_inputs.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let resolved_inputs_and_ref_inputs =
+        resolve_utxos(indexer, known_utxos, &inputs_and_ref_inputs)
+            .await
+            .context(
+                "Failed to fetch inputs or reference inputs for reference script fee calculation",
+            )?;
+
+    Ok(resolved_inputs_and_ref_inputs
+        .iter()
+        .flat_map(|utxo| utxo.script.as_ref())
+        .map(|script| script.bytes.len() as u64)
+        .sum::<u64>())
+}
+
+/// Minimum fee of a transaction of `tx_size` bytes running scripts for `cpu` steps and `mem`
+/// memory units in total, with `total_ref_script_size` bytes of reference scripts.
+pub(crate) fn fee_for(
+    tx_size: u64,
+    cpu: BigRational,
+    mem: BigRational,
+    total_ref_script_size: u64,
+    pparams: &ProtocolParams,
+) -> Result<u64> {
+    // Base fee + fee from size
+    let mut min_fee = BigRational::from_integer(pparams.min_fee_constant.lovelace.into());
+    // TODO: for some reason this is off by 1 byte, not that it matters since it's a difference
+    // of 0.000044 ADA...
+    min_fee += BigRational::from_integer(tx_size.into())
+        * BigRational::from_integer(pparams.min_fee_coefficient.into());
+    // Fee from scripts
+    min_fee += cpu * pparams.script_execution_prices.cpu.0.clone();
+    min_fee += mem * pparams.script_execution_prices.memory.0.clone();
+
+    // Fee from reference input script sizes
+    // https://github.com/IntersectMBO/cardano-ledger/blob/master/docs/adr/2024-08-14_009-refscripts-fee-change.md
+    if total_ref_script_size > 0 {
+        // Full chunks
+        let range = pparams.min_fee_reference_scripts.range as u64;
+        let base = pparams.min_fee_reference_scripts.base;
+        let multiplier = pparams.min_fee_reference_scripts.multiplier;
+
+        // to match the ledger's behavior, all tier contributions need to be summed first,
+        // then floored only at the very end. See `tierRefScriptFee`:
+        // https://github.com/IntersectMBO/cardano-ledger/blob/6ef1bf9fa1ca589e706e781fa8c9b4ad8df1e919/eras/conway/impl/src/Cardano/Ledger/Conway/Tx.hs#L122-L130
+        let steps = (total_ref_script_size / range) as i32;
+        let cost_per_step = range as f64 * base;
+        let mut ref_script_fee = 0.0;
+
+        for i in 0..steps {
+            ref_script_fee += cost_per_step * multiplier.powi(i);
         }
 
-        for cert in &tx.certificates {
-            match cert {
-                Certificate::StakeRegistration { pub_key_hash, .. }
-                | Certificate::StakeDeregistration { pub_key_hash, .. }
-                | Certificate::StakeDelegation { pub_key_hash, .. } => {
-                    signers.insert(pub_key_hash.0.into());
-                }
-                _ => {}
-            }
+        // Partial chunk
+        let partial_chunk_bytes = total_ref_script_size % range;
+        if partial_chunk_bytes > 0 {
+            let base_cost = partial_chunk_bytes as f64 * base;
+            ref_script_fee += base_cost * multiplier.powi(steps);
+        }
+
+        min_fee += BigRational::from_integer((ref_script_fee.floor() as u64).into());
+    }
+
+    min_fee
+        .ceil()
+        .to_integer()
+        .to_biguint()
+        .context("Failed to convert fee to BigUint")?
+        .to_u64()
+        .context("Failed to convert fee to u64")
+}
+
+/// Key hashes that must sign `tx`: owners of the spent inputs (collateral included), of the
+/// key certificates and withdrawals, and the disclosed signers. Keys required by native scripts
+/// aren't included.
+pub(crate) async fn required_signers(
+    tx: &StagingTransaction,
+    indexer: &impl UtxoSource,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> Result<HashSet<PubKeyHash>> {
+    let spent_inputs = tx
+        .inputs
+        .iter()
+        .chain(tx.collateral_inputs.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let inputs = resolve_utxos(indexer, known_utxos, &spent_inputs)
+        .await
+        .context("Failed to fetch input UTXOs to find their signers")?;
+
+    let mut signers = HashSet::new();
+    for input in inputs {
+        let address = Address::from_bytes(&input.address).context("Invalid address")?;
+
+        if let Address::Shelley(shelley_addr) = address
+            && let ShelleyPaymentPart::Key(hash) = shelley_addr.payment()
+        {
+            signers.insert(Hash::from(*hash));
         }
+    }
 
-        for account in tx.withdrawals.keys() {
-            let bytes = account.as_ref();
-            if !bytes.is_empty() && (bytes[0] & 0x10) == 0 {
-                // Key-based reward account
-                if bytes.len() >= 29 {
-                    let mut hash = [0u8; 28];
-                    hash.copy_from_slice(&bytes[1..29]);
-                    signers.insert(hash.into());
-                }
+    for cert in &tx.certificates {
+        match cert {
+            Certificate::StakeRegistration { pub_key_hash, .. }
+            | Certificate::StakeDeregistration { pub_key_hash, .. }
+            | Certificate::StakeDelegation { pub_key_hash, .. } => {
+                signers.insert(*pub_key_hash);
             }
+            _ => {}
         }
+    }
 
-        if let Some(disclosed) = &tx.disclosed_signers {
-            for signer in disclosed {
-                signers.insert(signer.0.into());
+    for account in tx.withdrawals.keys() {
+        let bytes = account.as_ref();
+        if !bytes.is_empty() && (bytes[0] & 0x10) == 0 {
+            // Key-based reward account
+            if bytes.len() >= 29 {
+                let mut hash = [0u8; 28];
+                hash.copy_from_slice(&bytes[1..29]);
+                signers.insert(Hash(hash));
             }
         }
+    }
 
-        let witness_count = signers.len().max(1);
+    if let Some(disclosed) = &tx.disclosed_signers {
+        for signer in disclosed {
+            signers.insert(*signer);
+        }
+    }
 
-        let mut built_tx = tx
-            .clone()
-            .build_conway(evaluation.clone())
-            .context("Failed to build transaction for fee calculation")?;
+    Ok(signers)
+}
 
-        for i in 0..witness_count {
-            let mut vkey = [0u8; 32];
-            vkey[0] = (i % 256) as u8;
-            vkey[1] = (i / 256) as u8;
-            let signature = [0u8; 64];
-            built_tx = built_tx
-                .add_signature(vkey.into(), signature)
-                .context("Failed to add dummy witness")?;
-        }
+/// Fewest keys that satisfy the native scripts in the witness set of `tx` within its validity
+/// interval, out of its expected signers or else any of the scripts' keys. Fails when a script
+/// can't be satisfied. Native scripts provided by reference inputs aren't counted.
+pub(crate) fn native_script_signers(
+    tx: &StagingTransaction,
+) -> Result<HashSet<PubKeyHash>, TxBuilderError> {
+    let available = |key: &PubKeyHash| {
+        tx.expected_signers
+            .as_ref()
+            .is_none_or(|expected| expected.contains(key))
+    };
+    let timelock =
+        |script: &NativeScript| timelock_holds(script, tx.valid_from_slot, tx.invalid_from_slot);
 
-        let evaluation = ogmios
-            .evaluate(&built_tx.bytes)
-            .await
-            .context("Failed to evaluate transaction")?;
-        let mut built_tx = tx
-            .clone()
-            .build_conway(Some(evaluation.clone()))
-            .context("Failed to build transaction with evaluation")?;
-
-        for i in 0..witness_count {
-            let mut vkey = [0u8; 32];
-            vkey[0] = (i % 256) as u8;
-            vkey[1] = (i / 256) as u8;
-            let signature = [0u8; 64];
-            built_tx = built_tx
-                .add_signature(vkey.into(), signature)
-                .context("Failed to add dummy witness")?;
-        }
+    let mut signers = HashSet::new();
+    for script in tx
+        .scripts
+        .values()
+        .filter(|script| script.kind == ScriptKind::Native)
+    {
+        let native = NativeScript::decode_fragment(&script.bytes)
+            .map_err(|_| TxBuilderError::MalformedScript)?;
+        let keys = min_signers(&native, &available, &timelock)
+            .ok_or(TxBuilderError::UnsatisfiableNativeScript { hash: script.hash })?;
+        signers.extend(keys);
+    }
+    Ok(signers)
+}
 
-        // Base fee + fee from size
-        let mut min_fee = BigRational::from_integer(pparams.min_fee_constant.lovelace.into());
-        let tx_size = built_tx.bytes.len() as u64;
-        // TODO: for some reason this is off by 1 byte, not that it matters since it's a difference
-        // of 0.000044 ADA...
-        min_fee += BigRational::from_integer(tx_size.into())
-            * BigRational::from_integer(pparams.min_fee_coefficient.into());
-        // Fee from scripts
-        let total_cpu = evaluation
-            .iter()
-            .map(|e| e.budget.cpu.0.clone())
-            .sum::<BigRational>();
-        let total_mem = evaluation
-            .iter()
-            .map(|e| e.budget.memory.0.clone())
-            .sum::<BigRational>();
-        min_fee += total_cpu * pparams.script_execution_prices.cpu.0.clone();
-        min_fee += total_mem * pparams.script_execution_prices.memory.0.clone();
-
-        // Fee from reference input script sizes
-        // https://github.com/IntersectMBO/cardano-ledger/blob/master/docs/adr/2024-08-14_009-refscripts-fee-change.md
-        let inputs_and_ref_input_pointers = tx
-            .inputs
-            .iter()
-            .chain(tx.reference_inputs.iter())
-            .map(|input| TxOutputPointer::new(input.hash, input.index))
-            .collect::<Vec<_>>();
-
-        let resolved_inputs_and_ref_inputs = {
-            let indexer = indexer.lock().await;
-            indexer.utxos(&inputs_and_ref_input_pointers).context(
-                "Failed to fetch inputs or reference inputs for reference script fee calculation",
-            )?
-        };
+/// Signs `tx` with `count` distinct dummy keys, so its size matches the signed transaction.
+pub(crate) fn with_dummy_witnesses(
+    mut tx: BuiltTransaction,
+    count: usize,
+) -> Result<BuiltTransaction> {
+    for i in 0..count {
+        let mut vkey = [0u8; 32];
+        vkey[0] = (i % 256) as u8;
+        vkey[1] = (i / 256) as u8;
+        let signature = [0u8; 64];
+        tx = tx
+            .add_signature(vkey.into(), signature)
+            .context("Failed to add dummy witness")?;
+    }
+    Ok(tx)
+}
 
-        let total_ref_script_size = resolved_inputs_and_ref_inputs
-            .iter()
-            .flat_map(|utxo| utxo.script.as_ref())
-            .map(|script| script.bytes.len() as u64)
-            .sum::<u64>();
-
-        if total_ref_script_size > 0 {
-            // Full chunks
-            let range = pparams.min_fee_reference_scripts.range as u64;
-            let base = pparams.min_fee_reference_scripts.base;
-            let multiplier = pparams.min_fee_reference_scripts.multiplier;
-
-            // to match the ledger's behavior, all tier contributions need to be summed first,
-            // then floored only at the very end. See `tierRefScriptFee`:
-            // https://github.com/IntersectMBO/cardano-ledger/blob/6ef1bf9fa1ca589e706e781fa8c9b4ad8df1e919/eras/conway/impl/src/Cardano/Ledger/Conway/Tx.hs#L122-L130
-            let steps = (total_ref_script_size / range) as i32;
-            let cost_per_step = range as f64 * base;
-            let mut ref_script_fee = 0.0;
-
-            for i in 0..steps {
-                ref_script_fee += cost_per_step * multiplier.powi(i);
-            }
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::primitives::Fragment;
+    use pallas::ledger::primitives::conway::NativeScript;
 
-            // Partial chunk
-            let partial_chunk_bytes = total_ref_script_size % range;
-            if partial_chunk_bytes > 0 {
-                let base_cost = partial_chunk_bytes as f64 * base;
-                ref_script_fee += base_cost * multiplier.powi(steps);
-            }
+    use super::native_script_signers;
+    use crate::builder::tx::{StagingTransaction, TxBuilderError};
+    use crate::primitives::{Hash, ScriptKind};
 
-            min_fee += BigRational::from_integer((ref_script_fee.floor() as u64).into());
-        }
+    fn with_script(script: NativeScript) -> StagingTransaction {
+        StagingTransaction::new().script(ScriptKind::Native, script.encode_fragment().unwrap())
+    }
 
-        let fee = min_fee
-            .ceil()
-            .to_integer()
-            .to_biguint()
-            .context("Failed to convert fee to BigUint")?
-            .to_u64()
-            .context("Failed to convert fee to u64")?;
-        Ok((fee, evaluation))
+    /// `atLeast(2, [sig 1, sig 2, all[sig 3, before slot 100]])`
+    fn treasury() -> NativeScript {
+        NativeScript::ScriptNOfK(
+            2,
+            vec![
+                NativeScript::ScriptPubkey(Hash([1u8; 28]).into()),
+                NativeScript::ScriptPubkey(Hash([2u8; 28]).into()),
+                NativeScript::ScriptAll(vec![
+                    NativeScript::ScriptPubkey(Hash([3u8; 28]).into()),
+                    NativeScript::InvalidHereafter(100),
+                ]),
+            ],
+        )
+    }
+
+    #[test]
+    fn native_script_witnesses_follow_the_expected_signers() {
+        let tx = with_script(treasury());
+        assert_eq!(native_script_signers(&tx).unwrap().len(), 2);
+
+        // Key 2 is away, so key 3 signs, which requires the transaction to expire in time
+        let tx = tx.expected_signers(vec![Hash([1u8; 28]), Hash([3u8; 28])]);
+        let hash = *tx.scripts.keys().next().unwrap();
+        assert_eq!(
+            native_script_signers(&tx),
+            Err(TxBuilderError::UnsatisfiableNativeScript { hash })
+        );
+        let signers = native_script_signers(&tx.invalid_from_slot(90)).unwrap();
+        assert_eq!(
+            signers,
+            [Hash([1u8; 28]), Hash([3u8; 28])].into_iter().collect()
+        );
     }
 }
@@ -0,0 +1,168 @@
+//! Callbacks into the balancing loop of `TxBuilder::build`
+
+use ogmios_client::method::evaluate::Evaluation;
+
+use super::TxBuilder;
+use super::tx::{StagingTransaction, TxBuilderError};
+use crate::primitives::Input;
+
+/// Points of `TxBuilder::build` at which hooks run. Each phase is reached once per iteration of
+/// the balancing loop, in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// After coin selection, before the change output is computed. This is the only phase where
+    /// hooks may change the value balance (e.g. add outputs), since inputs are selected again to
+    /// cover the changes.
+    AfterSelection,
+    /// On the complete body, change and collateral included, before its fee is computed.
+    BeforeFinalize,
+    /// On the complete body, right after its scripts were evaluated. Changes are evaluated again.
+    AfterEvaluation,
+}
+
+/// Read-only view of the balancing state, handed to hooks.
+pub struct PhaseContext<'a> {
+    pub phase: Phase,
+    /// Inputs picked by coin selection so far, on top of the ones added explicitly
+    pub selected_inputs: &'a [Input],
+    /// Fee the body is being balanced for
+    pub fee: u64,
+    /// Latest script evaluation, empty when the transaction runs no scripts
+    pub evaluation: &'a [Evaluation],
+}
+
+pub(crate) type Hook =
+    Box<dyn Fn(&mut StagingTransaction, &PhaseContext) -> anyhow::Result<()> + Send + Sync>;
+
+impl TxBuilder {
+    pub(crate) fn has_hooks(&self, phase: Phase) -> bool {
+        self.hooks
+            .iter()
+            .any(|(hook_phase, _)| *hook_phase == phase)
+    }
+
+    /// Runs the hooks registered for `context.phase` on `body`, in registration order, stopping
+    /// at the first failure.
+    pub(crate) fn run_hooks(
+        &self,
+        body: &mut StagingTransaction,
+        context: &PhaseContext,
+    ) -> Result<(), TxBuilderError> {
+        for (phase, hook) in &self.hooks {
+            if *phase == context.phase {
+                hook(body, context).map_err(|err| TxBuilderError::HookFailed {
+                    phase: *phase,
+                    message: format!("{err:#}"),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::NetworkId;
+
+    use super::{Phase, PhaseContext};
+    use crate::builder::TxBuilder;
+    use crate::builder::tx::{StagingTransaction, TxBuilderError};
+    use crate::primitives::{Hash, Input, Output};
+
+    fn address(byte: u8) -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([byte; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    fn context(phase: Phase, selected_inputs: &[Input]) -> PhaseContext<'_> {
+        PhaseContext {
+            phase,
+            selected_inputs,
+            fee: 170_000,
+            evaluation: &[],
+        }
+    }
+
+    #[test]
+    fn hooks_run_in_registration_order_for_their_phase_only() {
+        let builder = TxBuilder::new(NetworkId::Testnet, address(1))
+            .on_phase(Phase::AfterSelection, |body, _| {
+                body.outputs.push(Output::new(address(2), 1));
+                Ok(())
+            })
+            .on_phase(Phase::AfterEvaluation, |body, _| {
+                body.outputs.push(Output::new(address(3), 1));
+                Ok(())
+            })
+            .on_phase(Phase::AfterSelection, |body, _| {
+                body.outputs.push(Output::new(address(4), 1));
+                Ok(())
+            });
+
+        let mut body = StagingTransaction::new();
+        builder
+            .run_hooks(&mut body, &context(Phase::AfterSelection, &[]))
+            .unwrap();
+
+        let addresses = body
+            .outputs
+            .iter()
+            .map(|output| output.address.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, vec![address(2), address(4)]);
+        assert!(builder.has_hooks(Phase::AfterEvaluation));
+        assert!(!builder.has_hooks(Phase::BeforeFinalize));
+    }
+
+    #[test]
+    fn hooks_see_the_selected_inputs() {
+        let builder = TxBuilder::new(NetworkId::Testnet, address(1)).on_phase(
+            Phase::AfterSelection,
+            |body, context| {
+                let protocol_fee = 1_000_000 * context.selected_inputs.len() as u64;
+                body.outputs.push(Output::new(address(2), protocol_fee));
+                Ok(())
+            },
+        );
+
+        let selected = [
+            Input::new(Hash([1u8; 32]), 0),
+            Input::new(Hash([2u8; 32]), 1),
+        ];
+        let mut body = StagingTransaction::new();
+        builder
+            .run_hooks(&mut body, &context(Phase::AfterSelection, &selected))
+            .unwrap();
+        assert_eq!(body.outputs[0].lovelace, 2_000_000);
+    }
+
+    #[test]
+    fn failing_hook_aborts_with_its_phase() {
+        let builder = TxBuilder::new(NetworkId::Testnet, address(1))
+            .on_phase(Phase::BeforeFinalize, |_, _| {
+                bail!("fee oracle unavailable")
+            })
+            .on_phase(Phase::BeforeFinalize, |_, _| {
+                panic!("hooks after a failure must not run")
+            });
+
+        let result = builder.run_hooks(
+            &mut StagingTransaction::new(),
+            &context(Phase::BeforeFinalize, &[]),
+        );
+        assert_eq!(
+            result,
+            Err(TxBuilderError::HookFailed {
+                phase: Phase::BeforeFinalize,
+                message: "fee oracle unavailable".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Transactions in flight, persisted across restarts
+//!
+//! A process that stops between submitting a transaction and seeing it on chain loses track of
+//! it, and may spend its inputs again once restarted. A `TxJournal` records every transaction
+//! `BatchSubmitter` submits, with its inputs and the states it went through. After a restart,
+//! `recover` works out what became of the transactions still in flight, and `in_flight_inputs`
+//! tells builders which UTxOs not to select, see `TxBuilder::avoid_inputs`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex as StdMutex;
+
+use anyhow::Context as _;
+use hydrant::primitives::TxOutputPointer;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::ex_units_store::{replace_file, unix_time};
+use super::{BuiltTx, UtxoSource};
+use crate::primitives::{Hash, HashExt as _, Input, TxHash};
+
+/// Stage of a journaled transaction.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxState {
+    /// About to be submitted
+    Built,
+    /// Accepted by the node
+    Submitted,
+    /// Seen by the indexer
+    Confirmed,
+    /// Rejected by the node, or can't be included anymore
+    Failed,
+    /// Was seen by the indexer, until a rollback undid it, e.g. as reported by `TxTracker`
+    RolledBack,
+}
+
+impl TxState {
+    /// Whether nothing more happens to the transaction.
+    pub fn is_final(self) -> bool {
+        matches!(self, TxState::Confirmed | TxState::Failed)
+    }
+}
+
+/// A journaled transaction.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct JournalEntry {
+    pub hash: TxHash,
+    /// The signed transaction, to submit it again
+    pub cbor: Vec<u8>,
+    /// Inputs it spends
+    pub inputs: Vec<Input>,
+    /// Number of outputs, which tell whether it's on chain
+    pub outputs: u64,
+    /// States it went through, oldest first, with the unix time in seconds they were entered
+    pub transitions: Vec<(TxState, u64)>,
+}
+
+impl JournalEntry {
+    pub fn state(&self) -> TxState {
+        self.transitions
+            .last()
+            .map(|(state, _)| *state)
+            .unwrap_or(TxState::Built)
+    }
+}
+
+/// What to do about a transaction that was in flight, see `TxJournal::recover`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Recovery {
+    /// The indexer has its outputs, it's marked confirmed
+    Confirmed,
+    /// Not on chain and its inputs are unspent: it can be submitted again. A copy still in the
+    /// mempool is then reported as `SubmitError::AlreadySubmitted` once included.
+    Resubmit,
+    /// Not on chain and some of its inputs are spent, it's marked failed. A transaction whose
+    /// outputs were all spent already since it was included looks the same.
+    Abandon,
+}
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("Could not access the transaction journal: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Transaction journal holds malformed JSON: {0}")]
+    MalformedJson(#[from] serde_json::Error),
+    #[error("Transaction journal holds a malformed entry: {0}")]
+    MalformedEntry(String),
+    #[error("Transaction {0} is not in the journal")]
+    UnknownTransaction(TxHash),
+}
+
+/// Journal kept in a JSON file, replaced at once on every change like `FileExUnitsStore`.
+///
+/// Meant for a single process: writers of different processes racing each other lose updates.
+pub struct TxJournal {
+    path: PathBuf,
+    /// Serializes the writes of this process
+    lock: StdMutex<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalFile {
+    entries: Vec<FileEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    hash: String,
+    cbor: String,
+    /// As `hash#index`
+    inputs: Vec<String>,
+    outputs: u64,
+    transitions: Vec<(TxState, u64)>,
+}
+
+impl TxJournal {
+    /// Journal in the file at `path`, which is created on the first write.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: StdMutex::new(()),
+        }
+    }
+
+    /// Every journaled transaction, in the order they were first recorded.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        let json = match std::fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let file: JournalFile = serde_json::from_str(&json)?;
+        file.entries.into_iter().map(parse_entry).collect()
+    }
+
+    /// Inputs of the transactions not in a final state, which builders shouldn't select again.
+    pub fn in_flight_inputs(&self) -> Result<Vec<Input>, JournalError> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| !entry.state().is_final())
+            .flat_map(|entry| entry.inputs)
+            .collect())
+    }
+
+    /// Records `tx` as built, unless it's journaled already.
+    pub fn record(&self, tx: &BuiltTx) -> Result<(), JournalError> {
+        let hash = tx.tx.hash;
+        let entry = JournalEntry {
+            hash,
+            cbor: tx.cbor(),
+            inputs: tx.body().inputs().to_vec(),
+            outputs: tx.body().outputs().len() as u64,
+            transitions: vec![(TxState::Built, unix_time())],
+        };
+        self.update(|entries| {
+            if !entries.iter().any(|entry| entry.hash == hash) {
+                entries.push(entry);
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves the transaction `hash` to `state`.
+    pub fn transition(&self, hash: TxHash, state: TxState) -> Result<(), JournalError> {
+        self.update(|entries| {
+            let entry = entries
+                .iter_mut()
+                .find(|entry| entry.hash == hash)
+                .ok_or(JournalError::UnknownTransaction(hash))?;
+            if entry.state() != state {
+                entry.transitions.push((state, unix_time()));
+            }
+            Ok(())
+        })
+    }
+
+    /// Checks every transaction not in a final state against the indexer, e.g. after a restart,
+    /// marking those found on chain as confirmed and those that can't be included anymore as
+    /// failed.
+    pub async fn recover(
+        &self,
+        indexer: &impl UtxoSource,
+    ) -> anyhow::Result<Vec<(JournalEntry, Recovery)>> {
+        let mut plan = Vec::new();
+        for entry in self.entries()? {
+            if entry.state().is_final() {
+                continue;
+            }
+            let hash = entry.hash;
+            let outputs = (0..entry.outputs)
+                .map(|index| TxOutputPointer::new(hash, index))
+                .collect::<Vec<_>>();
+            let on_chain = !indexer
+                .utxos(&outputs)
+                .await
+                .with_context(|| format!("failed to look up the outputs of {hash}"))?
+                .is_empty();
+            let inputs = entry
+                .inputs
+                .iter()
+                .map(TxOutputPointer::from)
+                .collect::<Vec<_>>();
+            let unspent = indexer
+                .utxos(&inputs)
+                .await
+                .with_context(|| format!("failed to look up the inputs of {hash}"))?
+                .len()
+                == inputs.len();
+
+            let recovery = if on_chain {
+                self.transition(hash, TxState::Confirmed)?;
+                Recovery::Confirmed
+            } else if unspent {
+                Recovery::Resubmit
+            } else {
+                self.transition(hash, TxState::Failed)?;
+                Recovery::Abandon
+            };
+            plan.push((entry, recovery));
+        }
+        Ok(plan)
+    }
+
+    fn update(
+        &self,
+        change: impl FnOnce(&mut Vec<JournalEntry>) -> Result<(), JournalError>,
+    ) -> Result<(), JournalError> {
+        let _guard = self.lock.lock().expect("transaction journal lock poisoned");
+        let mut entries = self.entries()?;
+        change(&mut entries)?;
+        let file = JournalFile {
+            entries: entries.into_iter().map(file_entry).collect(),
+        };
+        replace_file(&self.path, &serde_json::to_vec(&file)?)?;
+        Ok(())
+    }
+}
+
+fn file_entry(entry: JournalEntry) -> FileEntry {
+    FileEntry {
+        hash: entry.hash.to_hex(),
+        cbor: hex::encode(entry.cbor),
+        inputs: entry
+            .inputs
+            .iter()
+            .map(|input| format!("{}#{}", input.hash.to_hex(), input.index))
+            .collect(),
+        outputs: entry.outputs,
+        transitions: entry.transitions,
+    }
+}
+
+fn parse_entry(entry: FileEntry) -> Result<JournalEntry, JournalError> {
+    let malformed = |what: &str| JournalError::MalformedEntry(format!("{what} of {}", entry.hash));
+    let inputs = entry
+        .inputs
+        .iter()
+        .map(|input| {
+            let (hash, index) = input.split_once('#')?;
+            Some(Input::new(Hash::from_hex(hash).ok()?, index.parse().ok()?))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| malformed("inputs"))?;
+    Ok(JournalEntry {
+        hash: Hash::from_hex(&entry.hash).map_err(|_| malformed("hash"))?,
+        cbor: hex::decode(&entry.cbor).map_err(|_| malformed("cbor"))?,
+        inputs,
+        outputs: entry.outputs,
+        transitions: entry.transitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hydrant::primitives::{TxOutput, TxOutputPointer};
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::{Recovery, TxJournal, TxState};
+    use crate::builder::tx::StagingTransaction;
+    use crate::builder::{BuiltTx, UtxoSource};
+    use crate::primitives::{Hash, Input, Output};
+
+    struct Indexed(Vec<TxOutput>);
+
+    impl UtxoSource for Indexed {
+        async fn utxos(&self, pointers: &[TxOutputPointer]) -> anyhow::Result<Vec<TxOutput>> {
+            let inputs = pointers.iter().map(Input::from).collect::<Vec<_>>();
+            Ok(self
+                .0
+                .iter()
+                .filter(|utxo| inputs.contains(&Input::from(*utxo)))
+                .cloned()
+                .collect())
+        }
+
+        async fn address_utxos(&self, _address: &[u8]) -> anyhow::Result<Vec<TxOutput>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    fn spending(input: &Input) -> BuiltTx {
+        let staging = StagingTransaction::new()
+            .fee(0)
+            .input(input.clone())
+            .output(Output::new(address(), 1));
+        let tx = staging.clone().build_conway(None).unwrap();
+        BuiltTx::new(staging, tx)
+    }
+
+    fn utxo(input: &Input) -> TxOutput {
+        Output::new(address(), 1).to_tx_output(input)
+    }
+
+    fn journal_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hose-journal-{}.json", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn entries_survive_the_journal_being_dropped() {
+        let path = journal_path();
+        let input = Input::new(Hash([9u8; 32]), 3);
+        let tx = spending(&input);
+        let hash = tx.hash().unwrap();
+
+        let journal = TxJournal::new(&path);
+        journal.record(&tx).unwrap();
+        journal.record(&tx).unwrap();
+        journal.transition(hash, TxState::Submitted).unwrap();
+        drop(journal);
+
+        let entries = TxJournal::new(&path).entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].hash, hash);
+        assert_eq!(entries[0].cbor, tx.cbor());
+        assert_eq!(entries[0].inputs, vec![input.clone()]);
+        assert_eq!(entries[0].outputs, 1);
+        assert_eq!(
+            entries[0]
+                .transitions
+                .iter()
+                .map(|(state, _)| *state)
+                .collect::<Vec<_>>(),
+            vec![TxState::Built, TxState::Submitted]
+        );
+        assert_eq!(
+            TxJournal::new(&path).in_flight_inputs().unwrap(),
+            vec![input]
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn recovery_classifies_what_became_of_each_transaction() {
+        let path = journal_path();
+        let journal = TxJournal::new(&path);
+        let inputs = (0..5)
+            .map(|index| Input::new(Hash([9u8; 32]), index))
+            .collect::<Vec<_>>();
+        let txs = inputs.iter().map(spending).collect::<Vec<_>>();
+        let hashes = txs.iter().map(|tx| tx.hash().unwrap()).collect::<Vec<_>>();
+        for tx in &txs {
+            journal.record(tx).unwrap();
+        }
+        // 0 was included, 1 is still in flight, 2 lost its input to another transaction, 3 was
+        // rolled back and 4 failed before the crash
+        journal.transition(hashes[1], TxState::Submitted).unwrap();
+        journal.transition(hashes[2], TxState::Submitted).unwrap();
+        journal.transition(hashes[3], TxState::Confirmed).unwrap();
+        journal.transition(hashes[3], TxState::RolledBack).unwrap();
+        journal.transition(hashes[4], TxState::Failed).unwrap();
+        drop(journal);
+        let indexer = Indexed(vec![
+            utxo(&Input::new(hashes[0], 0)),
+            utxo(&inputs[1]),
+            utxo(&inputs[3]),
+            utxo(&inputs[4]),
+        ]);
+
+        let journal = TxJournal::new(&path);
+        let plan = journal
+            .recover(&indexer)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(entry, recovery)| (entry.hash, recovery))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            plan,
+            vec![
+                (hashes[0], Recovery::Confirmed),
+                (hashes[1], Recovery::Resubmit),
+                (hashes[2], Recovery::Abandon),
+                (hashes[3], Recovery::Resubmit),
+            ]
+        );
+        assert_eq!(
+            journal
+                .entries()
+                .unwrap()
+                .iter()
+                .map(|entry| entry.state())
+                .collect::<Vec<_>>(),
+            vec![
+                TxState::Confirmed,
+                TxState::Submitted,
+                TxState::Failed,
+                TxState::RolledBack,
+                TxState::Failed,
+            ]
+        );
+        assert_eq!(
+            journal.in_flight_inputs().unwrap(),
+            vec![inputs[1].clone(), inputs[3].clone()]
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,977 @@
+//! Pre-flight checks on a staged transaction
+//!
+//! Lints look at the transaction as configured on a `TxBuilder`, before coin selection and
+//! without any network access, and report what the ledger is known to reject (errors) or what
+//! is most likely a mistake (warnings). Codes are stable, so they can be matched on or silenced
+//! by applications. Custom rules are registered with `TxBuilder::lint_rule`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use hydrant::primitives::TxOutput;
+use ogmios_client::method::pparams::ProtocolParams;
+use pallas::ledger::addresses::{Address, ShelleyPaymentPart};
+
+use super::TxBuilder;
+use crate::builder::tx::{StagingTransaction, TxBuilderError};
+use crate::primitives::{
+    AssetId, Certificate, DatumHash, DatumOption, Hash, Input, PubKeyHash, RewardAccount,
+    ScriptHash,
+};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Severity {
+    /// Likely a mistake, but the ledger accepts it
+    Warning,
+    /// The ledger rejects the transaction
+    Error,
+}
+
+/// Part of the transaction a finding is about
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum LintLocation {
+    Output(usize),
+    Mint(AssetId),
+    CollateralInputs,
+    ValidityInterval,
+    Withdrawal(RewardAccount),
+    DisclosedSigner(PubKeyHash),
+    Datum(DatumHash),
+    Script(ScriptHash),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct LintFinding {
+    pub severity: Severity,
+    /// Stable identifier of the kind of problem, e.g. `output-below-min-utxo`
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<LintLocation>,
+}
+
+impl LintFinding {
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    pub fn at(mut self, location: LintLocation) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}[{}]: {}", self.code, self.message)
+    }
+}
+
+/// Inline datums larger than this many bytes are reported by default, see `LargeDatum`
+pub const DEFAULT_DATUM_SIZE_THRESHOLD: usize = 4_096;
+/// Reference scripts larger than this many bytes are reported by default, see `LargeScript`
+pub const DEFAULT_SCRIPT_SIZE_THRESHOLD: usize = 8_192;
+
+/// Protocol parameters the built-in rules check against, along with the soft size thresholds
+#[derive(Debug, Clone)]
+pub struct LintParams {
+    pub min_utxo_deposit_constant: u64,
+    pub min_utxo_deposit_coefficient: u64,
+    pub max_collateral_inputs: usize,
+    pub max_value_size: usize,
+    pub datum_size_threshold: usize,
+    pub script_size_threshold: usize,
+}
+
+impl From<&ProtocolParams> for LintParams {
+    fn from(pparams: &ProtocolParams) -> Self {
+        Self {
+            min_utxo_deposit_constant: pparams.min_utxo_deposit_constant.lovelace,
+            min_utxo_deposit_coefficient: pparams.min_utxo_deposit_coefficient,
+            max_collateral_inputs: pparams.max_collateral_inputs as usize,
+            max_value_size: pparams.max_value_size.bytes as usize,
+            datum_size_threshold: DEFAULT_DATUM_SIZE_THRESHOLD,
+            script_size_threshold: DEFAULT_SCRIPT_SIZE_THRESHOLD,
+        }
+    }
+}
+
+/// What a rule gets to look at
+pub struct LintContext<'a> {
+    pub tx: &'a StagingTransaction,
+    /// UTxOs resolved by the caller, the only inputs lints can look into
+    pub known_utxos: &'a HashMap<Input, TxOutput>,
+    pub params: &'a LintParams,
+}
+
+impl LintContext<'_> {
+    /// The spent inputs, if all of them can be resolved without the indexer
+    pub fn resolved_inputs(&self) -> Option<Vec<&TxOutput>> {
+        self.tx
+            .inputs
+            .iter()
+            .map(|input| self.known_utxos.get(input))
+            .collect()
+    }
+}
+
+/// A check run by `TxBuilder::lint`
+pub trait LintRule: Send + Sync {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding>;
+}
+
+impl<F> LintRule for F
+where
+    F: Fn(&LintContext<'_>) -> Vec<LintFinding> + Send + Sync,
+{
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        self(ctx)
+    }
+}
+
+/// Rules every `TxBuilder::lint` runs
+pub fn builtin_rules() -> Vec<Box<dyn LintRule>> {
+    vec![
+        Box::new(OutputBelowMinUtxo),
+        Box::new(OutputValueTooLarge),
+        Box::new(LargeDatum),
+        Box::new(LargeScript),
+        Box::new(AssetNameTooLong),
+        Box::new(TooManyCollateralInputs),
+        Box::new(InvertedValidityInterval),
+        Box::new(MintWithoutOutput),
+        Box::new(WithdrawalBeforeRegistration),
+        Box::new(DuplicateDisclosedSigner),
+        Box::new(UnusedDatum),
+        Box::new(UnusedScript),
+    ]
+}
+
+/// Runs the built-in rules followed by `extra_rules`, errors first.
+pub fn run(ctx: &LintContext<'_>, extra_rules: &[Box<dyn LintRule>]) -> Vec<LintFinding> {
+    let mut findings = builtin_rules()
+        .iter()
+        .chain(extra_rules)
+        .flat_map(|rule| rule.check(ctx))
+        .collect::<Vec<_>>();
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    findings
+}
+
+impl TxBuilder {
+    /// Runs the built-in lint rules and those registered with `lint_rule` on the transaction as
+    /// configured so far, errors first. Nothing is fetched: inputs are only looked into when
+    /// added with `add_input_resolved` or `add_script_input_resolved`.
+    pub fn lint(&self, pparams: &ProtocolParams) -> Vec<LintFinding> {
+        let mut params = LintParams::from(pparams);
+        if let Some(threshold) = self.datum_size_threshold {
+            params.datum_size_threshold = threshold;
+        }
+        if let Some(threshold) = self.script_size_threshold {
+            params.script_size_threshold = threshold;
+        }
+        let ctx = LintContext {
+            tx: &self.body,
+            known_utxos: &self.known_utxos,
+            params: &params,
+        };
+        run(&ctx, &self.lint_rules)
+    }
+
+    /// Fails with `TxBuilderError::LintFailed` if `deny_lints` is set and any finding is an
+    /// error.
+    pub(crate) fn check_lints(&self, pparams: &ProtocolParams) -> Result<(), TxBuilderError> {
+        if !self.deny_lints {
+            return Ok(());
+        }
+        let errors = self
+            .lint(pparams)
+            .into_iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TxBuilderError::LintFailed { findings: errors })
+        }
+    }
+}
+
+/// An output holds less lovelace than its minimum deposit.
+pub struct OutputBelowMinUtxo;
+
+impl OutputBelowMinUtxo {
+    pub const CODE: &'static str = "output-below-min-utxo";
+}
+
+impl LintRule for OutputBelowMinUtxo {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        ctx.tx
+            .outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, output)| {
+                let min_deposit = output
+                    .min_deposit_with(
+                        ctx.params.min_utxo_deposit_constant,
+                        ctx.params.min_utxo_deposit_coefficient,
+                    )
+                    .ok()?;
+                (output.lovelace < min_deposit).then(|| {
+                    LintFinding::error(
+                        Self::CODE,
+                        format!(
+                            "output #{index} holds {} lovelace, below its minimum deposit of {min_deposit}",
+                            output.lovelace
+                        ),
+                    )
+                    .at(LintLocation::Output(index))
+                })
+            })
+            .collect()
+    }
+}
+
+/// The value of an output takes more than `max_value_size` bytes once serialized.
+pub struct OutputValueTooLarge;
+
+impl OutputValueTooLarge {
+    pub const CODE: &'static str = "output-value-too-large";
+}
+
+impl LintRule for OutputValueTooLarge {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        ctx.tx
+            .outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, output)| {
+                let size = output.value_size().ok()?;
+                (size > ctx.params.max_value_size).then(|| {
+                    LintFinding::error(
+                        Self::CODE,
+                        format!(
+                            "value of output #{index} takes {size} bytes, above max_value_size of {}",
+                            ctx.params.max_value_size
+                        ),
+                    )
+                    .at(LintLocation::Output(index))
+                })
+            })
+            .collect()
+    }
+}
+
+/// An inline datum is larger than `LintParams::datum_size_threshold`. The ledger accepts it, but
+/// it's paid for in the minimum deposit and every reader of the output has to deal with it.
+pub struct LargeDatum;
+
+impl LargeDatum {
+    pub const CODE: &'static str = "large-datum";
+}
+
+impl LintRule for LargeDatum {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        ctx.tx
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.datum_size() > ctx.params.datum_size_threshold)
+            .filter_map(|(index, output)| {
+                let deposit = output
+                    .datum_deposit_with(
+                        ctx.params.min_utxo_deposit_constant,
+                        ctx.params.min_utxo_deposit_coefficient,
+                    )
+                    .ok()?;
+                Some(
+                    LintFinding::warning(
+                        Self::CODE,
+                        format!(
+                            "inline datum of output #{index} takes {} bytes, above the threshold of {}, and accounts for {deposit} lovelace of its minimum deposit",
+                            output.datum_size(),
+                            ctx.params.datum_size_threshold
+                        ),
+                    )
+                    .at(LintLocation::Output(index)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A reference script is larger than `LintParams::script_size_threshold`.
+pub struct LargeScript;
+
+impl LargeScript {
+    pub const CODE: &'static str = "large-script";
+}
+
+impl LintRule for LargeScript {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        ctx.tx
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.script_size() > ctx.params.script_size_threshold)
+            .map(|(index, output)| {
+                LintFinding::warning(
+                    Self::CODE,
+                    format!(
+                        "reference script of output #{index} takes {} bytes, above the threshold of {}",
+                        output.script_size(),
+                        ctx.params.script_size_threshold
+                    ),
+                )
+                .at(LintLocation::Output(index))
+            })
+            .collect()
+    }
+}
+
+/// An asset name, in an output or in the mint field, is longer than 32 bytes.
+pub struct AssetNameTooLong;
+
+impl AssetNameTooLong {
+    pub const CODE: &'static str = "asset-name-too-long";
+
+    fn finding(asset_id: &AssetId, location: LintLocation) -> LintFinding {
+        LintFinding::error(
+            Self::CODE,
+            format!(
+                "asset name {} of policy {} is {} bytes long, at most 32 are allowed",
+                hex::encode(&asset_id.name),
+                asset_id.policy,
+                asset_id.name.len()
+            ),
+        )
+        .at(location)
+    }
+}
+
+impl LintRule for AssetNameTooLong {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let in_outputs = ctx
+            .tx
+            .outputs
+            .iter()
+            .enumerate()
+            .flat_map(|(index, output)| {
+                output
+                    .assets
+                    .iter()
+                    .flat_map(|assets| assets.iter())
+                    .filter(|(asset_id, _)| asset_id.name.len() > 32)
+                    .map(move |(asset_id, _)| Self::finding(asset_id, LintLocation::Output(index)))
+            });
+        let in_mint = ctx
+            .tx
+            .mint
+            .iter()
+            .filter(|(asset_id, _)| asset_id.name.len() > 32)
+            .map(|(asset_id, _)| Self::finding(asset_id, LintLocation::Mint(asset_id.clone())));
+        in_outputs.chain(in_mint).collect()
+    }
+}
+
+/// More collateral inputs than `max_collateral_inputs`.
+pub struct TooManyCollateralInputs;
+
+impl TooManyCollateralInputs {
+    pub const CODE: &'static str = "too-many-collateral-inputs";
+}
+
+impl LintRule for TooManyCollateralInputs {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let count = ctx.tx.collateral_inputs.len();
+        let max = ctx.params.max_collateral_inputs;
+        if count <= max {
+            return vec![];
+        }
+        vec![
+            LintFinding::error(
+                Self::CODE,
+                format!("{count} collateral inputs, at most {max} are allowed"),
+            )
+            .at(LintLocation::CollateralInputs),
+        ]
+    }
+}
+
+/// The validity interval ends before it starts, so no slot is valid.
+pub struct InvertedValidityInterval;
+
+impl InvertedValidityInterval {
+    pub const CODE: &'static str = "inverted-validity-interval";
+}
+
+impl LintRule for InvertedValidityInterval {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        match (ctx.tx.valid_from_slot, ctx.tx.invalid_from_slot) {
+            (Some(valid_from), Some(invalid_from)) if valid_from >= invalid_from => vec![
+                LintFinding::error(
+                    Self::CODE,
+                    format!("validity interval [{valid_from}, {invalid_from}) contains no slot"),
+                )
+                .at(LintLocation::ValidityInterval),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+/// Minted tokens that no output holds, which end up in the change output.
+pub struct MintWithoutOutput;
+
+impl MintWithoutOutput {
+    pub const CODE: &'static str = "mint-without-output";
+}
+
+impl LintRule for MintWithoutOutput {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        ctx.tx
+            .mint
+            .iter()
+            .filter(|(_, amount)| **amount > 0)
+            .filter(|(asset_id, _)| {
+                !ctx.tx.outputs.iter().any(|output| {
+                    output
+                        .assets
+                        .as_ref()
+                        .is_some_and(|assets| assets.iter().any(|(id, _)| id == *asset_id))
+                })
+            })
+            .map(|(asset_id, amount)| {
+                LintFinding::warning(
+                    Self::CODE,
+                    format!(
+                        "{amount} of {}.{} minted, but no output holds it, so it goes to change",
+                        asset_id.policy,
+                        hex::encode(&asset_id.name)
+                    ),
+                )
+                .at(LintLocation::Mint(asset_id.clone()))
+            })
+            .collect()
+    }
+}
+
+/// A withdrawal from a reward account the same transaction registers.
+///
+/// The ledger checks withdrawals against the accounts registered before the certificates of the
+/// transaction are applied, so such an account can't have rewards to withdraw yet.
+pub struct WithdrawalBeforeRegistration;
+
+impl WithdrawalBeforeRegistration {
+    pub const CODE: &'static str = "withdrawal-before-registration";
+}
+
+impl LintRule for WithdrawalBeforeRegistration {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let registered = ctx
+            .tx
+            .certificates
+            .iter()
+            .filter(|cert| {
+                matches!(
+                    cert,
+                    Certificate::StakeRegistration { .. }
+                        | Certificate::StakeRegistrationScript { .. }
+                )
+            })
+            .map(Certificate::credential_hash)
+            .collect::<HashSet<_>>();
+        ctx.tx
+            .withdrawals
+            .keys()
+            .filter(|account| credential_hash(account).is_some_and(|hash| registered.contains(&hash)))
+            .map(|account| {
+                LintFinding::error(
+                    Self::CODE,
+                    format!(
+                        "withdrawal from reward account {}, which is only registered by this transaction",
+                        hex::encode(account.as_ref())
+                    ),
+                )
+                .at(LintLocation::Withdrawal(account.clone()))
+            })
+            .collect()
+    }
+}
+
+/// The same key is disclosed as a required signer more than once.
+pub struct DuplicateDisclosedSigner;
+
+impl DuplicateDisclosedSigner {
+    pub const CODE: &'static str = "duplicate-disclosed-signer";
+}
+
+impl LintRule for DuplicateDisclosedSigner {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let mut seen = HashSet::new();
+        let mut reported = HashSet::new();
+        ctx.tx
+            .disclosed_signers
+            .iter()
+            .flatten()
+            .filter(|signer| !seen.insert(**signer) && reported.insert(**signer))
+            .map(|signer| {
+                LintFinding::warning(
+                    Self::CODE,
+                    format!("signer {signer} is disclosed more than once"),
+                )
+                .at(LintLocation::DisclosedSigner(*signer))
+            })
+            .collect()
+    }
+}
+
+/// A datum is attached to the witness set, but neither an output nor a spent input refers to
+/// its hash.
+///
+/// Only checked when every spent input is known, since an input from the indexer could be the
+/// one needing it.
+pub struct UnusedDatum;
+
+impl UnusedDatum {
+    pub const CODE: &'static str = "unused-datum";
+}
+
+impl LintRule for UnusedDatum {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let Some(inputs) = ctx.resolved_inputs() else {
+            return vec![];
+        };
+        let referenced = ctx
+            .tx
+            .outputs
+            .iter()
+            .filter_map(|output| match &output.datum {
+                Some(DatumOption::Hash(hash)) => Some(*hash),
+                _ => None,
+            })
+            .chain(inputs.iter().filter_map(|utxo| utxo.datum_hash))
+            .collect::<HashSet<_>>();
+        ctx.tx
+            .datums
+            .keys()
+            .filter(|hash| !referenced.contains(*hash))
+            .map(|hash| {
+                LintFinding::warning(
+                    Self::CODE,
+                    format!("datum {hash} is attached, but nothing refers to it"),
+                )
+                .at(LintLocation::Datum(*hash))
+            })
+            .collect()
+    }
+}
+
+/// A script is attached to the witness set, but nothing in the transaction runs it.
+///
+/// Only checked when every spent input is known, since an input from the indexer could be
+/// locked by it.
+pub struct UnusedScript;
+
+impl UnusedScript {
+    pub const CODE: &'static str = "unused-script";
+}
+
+impl LintRule for UnusedScript {
+    fn check(&self, ctx: &LintContext<'_>) -> Vec<LintFinding> {
+        let Some(inputs) = ctx.resolved_inputs() else {
+            return vec![];
+        };
+        let spent =
+            inputs
+                .iter()
+                .filter_map(|utxo| match Address::from_bytes(&utxo.address).ok()? {
+                    Address::Shelley(address) => match address.payment() {
+                        ShelleyPaymentPart::Script(hash) => Some(Hash::from(*hash)),
+                        ShelleyPaymentPart::Key(_) => None,
+                    },
+                    _ => None,
+                });
+        let minted = ctx.tx.mint.keys().map(|asset_id| asset_id.policy);
+        let certified = ctx
+            .tx
+            .certificates
+            .iter()
+            .filter_map(|cert| cert.script_hash());
+        let withdrawn = ctx
+            .tx
+            .withdrawals
+            .keys()
+            .filter(|account| is_script_account(account))
+            .filter_map(credential_hash);
+        let proposed = ctx
+            .tx
+            .proposals
+            .iter()
+            .filter_map(|proposal| proposal.gov_action.guardrails_script());
+        let used = spent
+            .chain(minted)
+            .chain(certified)
+            .chain(withdrawn)
+            .chain(proposed)
+            .collect::<HashSet<_>>();
+        ctx.tx
+            .scripts
+            .keys()
+            .filter(|hash| !used.contains(*hash))
+            .map(|hash| {
+                LintFinding::warning(
+                    Self::CODE,
+                    format!("script {hash} is attached, but nothing in the transaction runs it"),
+                )
+                .at(LintLocation::Script(*hash))
+            })
+            .collect()
+    }
+}
+
+fn credential_hash(account: &RewardAccount) -> Option<Hash<28>> {
+    let bytes: [u8; 28] = account.as_ref().get(1..29)?.try_into().ok()?;
+    Some(Hash(bytes))
+}
+
+fn is_script_account(account: &RewardAccount) -> bool {
+    account
+        .as_ref()
+        .first()
+        .is_some_and(|header| header & 0x10 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{Network, ShelleyAddress, ShelleyDelegationPart};
+
+    use super::*;
+    use crate::primitives::{
+        Anchor, Asset, Datum, GovAction, Output, ProposalProcedure, ScriptKind,
+    };
+
+    const NATIVE_SCRIPT: [u8; 3] = [0x82, 0x04, 0x00];
+
+    fn params() -> LintParams {
+        LintParams {
+            min_utxo_deposit_constant: 0,
+            min_utxo_deposit_coefficient: 4_310,
+            max_collateral_inputs: 3,
+            max_value_size: 5_000,
+            datum_size_threshold: DEFAULT_DATUM_SIZE_THRESHOLD,
+            script_size_threshold: DEFAULT_SCRIPT_SIZE_THRESHOLD,
+        }
+    }
+
+    fn address(payment: ShelleyPaymentPart) -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            payment,
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    fn key_address() -> Address {
+        address(ShelleyPaymentPart::Key(Hash([1u8; 28]).into()))
+    }
+
+    fn funded_output() -> Output {
+        Output::new(key_address(), 5_000_000)
+    }
+
+    fn input(index: u64) -> Input {
+        Input::new(Hash([9u8; 32]), index)
+    }
+
+    fn codes_with(
+        tx: &StagingTransaction,
+        known_utxos: &HashMap<Input, TxOutput>,
+    ) -> Vec<&'static str> {
+        let params = params();
+        let ctx = LintContext {
+            tx,
+            known_utxos,
+            params: &params,
+        };
+        run(&ctx, &[])
+            .into_iter()
+            .map(|finding| finding.code)
+            .collect()
+    }
+
+    fn codes(tx: &StagingTransaction) -> Vec<&'static str> {
+        codes_with(tx, &HashMap::new())
+    }
+
+    #[test]
+    fn clean_transaction_has_no_findings() {
+        let tx = StagingTransaction::new().output(funded_output());
+        assert!(codes(&tx).is_empty());
+    }
+
+    #[test]
+    fn output_below_min_utxo() {
+        let tx = StagingTransaction::new().output(Output::new(key_address(), 1));
+        assert_eq!(codes(&tx), vec![OutputBelowMinUtxo::CODE]);
+    }
+
+    #[test]
+    fn asset_name_too_long() {
+        let mut output = funded_output();
+        let mut assets = output.assets.take().unwrap_or_default();
+        assets.add_asset(Asset::new(Hash([2u8; 28]), vec![0; 33], 1));
+        output.assets = Some(assets);
+        let tx = StagingTransaction::new().output(output);
+        assert_eq!(codes(&tx), vec![AssetNameTooLong::CODE]);
+    }
+
+    #[test]
+    fn too_many_collateral_inputs() {
+        let tx = (0..4).fold(
+            StagingTransaction::new().output(funded_output()),
+            |tx, index| tx.collateral_input(input(index)),
+        );
+        assert_eq!(codes(&tx), vec![TooManyCollateralInputs::CODE]);
+    }
+
+    #[test]
+    fn inverted_validity_interval() {
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .valid_from_slot(100)
+            .invalid_from_slot(100);
+        assert_eq!(codes(&tx), vec![InvertedValidityInterval::CODE]);
+    }
+
+    #[test]
+    fn mint_without_output() {
+        let policy = ScriptKind::Native.hash(&NATIVE_SCRIPT);
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .script(ScriptKind::Native, NATIVE_SCRIPT.to_vec())
+            .mint_asset(policy, b"token".to_vec(), 10)
+            .unwrap();
+        assert_eq!(codes(&tx), vec![MintWithoutOutput::CODE]);
+
+        let held = funded_output()
+            .add_asset(policy, b"token".to_vec(), 10)
+            .unwrap();
+        let tx = StagingTransaction::new()
+            .output(held)
+            .script(ScriptKind::Native, NATIVE_SCRIPT.to_vec())
+            .mint_asset(policy, b"token".to_vec(), 10)
+            .unwrap();
+        assert!(codes(&tx).is_empty());
+    }
+
+    #[test]
+    fn withdrawal_before_registration() {
+        let pub_key_hash = Hash([3u8; 28]);
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .add_certificate(Certificate::StakeRegistration {
+                pub_key_hash,
+                deposit: Some(2_000_000),
+            })
+            .withdrawal(
+                RewardAccount::from_key_hash(Network::Testnet, pub_key_hash),
+                0,
+            );
+        assert_eq!(codes(&tx), vec![WithdrawalBeforeRegistration::CODE]);
+    }
+
+    #[test]
+    fn duplicate_disclosed_signer() {
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .disclosed_signer(Hash([4u8; 28]))
+            .disclosed_signer(Hash([4u8; 28]))
+            .disclosed_signer(Hash([4u8; 28]));
+        assert_eq!(codes(&tx), vec![DuplicateDisclosedSigner::CODE]);
+    }
+
+    #[test]
+    fn unused_datum() {
+        let datum = vec![0xd8, 0x79, 0x80];
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .datum(datum.clone());
+        assert_eq!(codes(&tx), vec![UnusedDatum::CODE]);
+
+        // Spending an input nothing is known about might need it
+        let tx = tx.input(input(0));
+        assert!(codes(&tx).is_empty());
+
+        let tx = StagingTransaction::new()
+            .output(funded_output().set_datum_hash(Datum::new(datum.clone()).hash))
+            .datum(datum);
+        assert!(codes(&tx).is_empty());
+    }
+
+    #[test]
+    fn unused_script() {
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .script(ScriptKind::Native, NATIVE_SCRIPT.to_vec());
+        assert_eq!(codes(&tx), vec![UnusedScript::CODE]);
+
+        // Spending a known input locked by the script uses it
+        let script_hash = ScriptKind::Native.hash(&NATIVE_SCRIPT);
+        let locked = Output::new(
+            address(ShelleyPaymentPart::Script(script_hash.into())),
+            5_000_000,
+        );
+        let known_utxos = HashMap::from([(input(0), locked.to_tx_output(&input(0)))]);
+        let tx = tx.input(input(0));
+        assert!(codes_with(&tx, &known_utxos).is_empty());
+    }
+
+    #[test]
+    fn guardrails_script_of_a_proposal_is_used() {
+        let script_hash = ScriptKind::PlutusV3.hash(&NATIVE_SCRIPT);
+        let return_account = RewardAccount::from_key_hash(Network::Testnet, Hash([7u8; 28]));
+        let tx = StagingTransaction::new()
+            .output(funded_output())
+            .script(ScriptKind::PlutusV3, NATIVE_SCRIPT.to_vec())
+            .proposal(ProposalProcedure {
+                gov_action: GovAction::TreasuryWithdrawals {
+                    withdrawals: [(return_account.clone(), 1_000_000)].into(),
+                    guardrails_script: Some(script_hash),
+                },
+                deposit: Some(100_000_000_000),
+                return_account,
+                anchor: Anchor::new("https://dao.example/p1", Hash([9u8; 32])),
+            });
+        assert!(codes(&tx).is_empty());
+    }
+
+    #[test]
+    fn custom_rules_run_after_builtin_ones_and_errors_come_first() {
+        let custom = |ctx: &LintContext<'_>| -> Vec<LintFinding> {
+            ctx.tx
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(index, _)| {
+                    LintFinding::warning("custom", "custom rule").at(LintLocation::Output(index))
+                })
+                .collect()
+        };
+        let tx = StagingTransaction::new().output(Output::new(key_address(), 1));
+        let params = params();
+        let known_utxos = HashMap::new();
+        let ctx = LintContext {
+            tx: &tx,
+            known_utxos: &known_utxos,
+            params: &params,
+        };
+        let findings = run(&ctx, &[Box::new(custom)]);
+        assert_eq!(
+            findings
+                .iter()
+                .map(|finding| finding.code)
+                .collect::<Vec<_>>(),
+            vec![OutputBelowMinUtxo::CODE, "custom"]
+        );
+        assert_eq!(
+            findings[0].to_string().split(':').next(),
+            Some("error[output-below-min-utxo]")
+        );
+    }
+
+    fn check_with(
+        rule: &dyn LintRule,
+        tx: &StagingTransaction,
+        params: &LintParams,
+    ) -> Vec<String> {
+        let known_utxos = HashMap::new();
+        let ctx = LintContext {
+            tx,
+            known_utxos: &known_utxos,
+            params,
+        };
+        rule.check(&ctx)
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn output_value_too_large_at_the_boundary() {
+        let output = (0..10u8).fold(funded_output(), |output, index| {
+            output.add_asset(Hash([index; 28]), vec![index], 1).unwrap()
+        });
+        let size = output.value_size().unwrap();
+        let tx = StagingTransaction::new().output(output);
+
+        let mut params = params();
+        params.max_value_size = size;
+        assert!(check_with(&OutputValueTooLarge, &tx, &params).is_empty());
+
+        params.max_value_size = size - 1;
+        assert_eq!(
+            check_with(&OutputValueTooLarge, &tx, &params),
+            vec![format!(
+                "value of output #0 takes {size} bytes, above max_value_size of {}",
+                size - 1
+            )]
+        );
+    }
+
+    #[test]
+    fn large_datum_reports_its_deposit() {
+        let output = funded_output().set_datum(vec![0x44, 0xde, 0xad, 0xbe, 0xef]);
+        let tx = StagingTransaction::new().output(output);
+
+        let mut params = params();
+        params.datum_size_threshold = 5;
+        assert!(check_with(&LargeDatum, &tx, &params).is_empty());
+
+        // 11 bytes at 4310 lovelace each, see `Output::datum_deposit`
+        params.datum_size_threshold = 4;
+        assert_eq!(
+            check_with(&LargeDatum, &tx, &params),
+            vec![
+                "inline datum of output #0 takes 5 bytes, above the threshold of 4, and accounts for 47410 lovelace of its minimum deposit"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn large_script() {
+        let output = funded_output().set_script(ScriptKind::PlutusV3, vec![0; 100]);
+        let tx = StagingTransaction::new().output(output);
+
+        let mut params = params();
+        params.script_size_threshold = 100;
+        assert!(check_with(&LargeScript, &tx, &params).is_empty());
+
+        params.script_size_threshold = 99;
+        assert_eq!(
+            check_with(&LargeScript, &tx, &params),
+            vec![
+                "reference script of output #0 takes 100 bytes, above the threshold of 99"
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -1,35 +1,128 @@
 //! High-level transaction builder API
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
 use anyhow::{Context, Result, bail, ensure};
-use hydrant::UtxoIndexer;
+use hydrant::primitives::{TxOutput, TxOutputPointer};
 use intervals_general::Interval;
-use ogmios_client::OgmiosHttpClient;
+use num::{BigRational, ToPrimitive as _};
+use ogmios_client::method::evaluate::Evaluation;
 use ogmios_client::method::pparams::ProtocolParams;
 use pallas::ledger::addresses::Address;
 use pallas::ledger::primitives::conway::LanguageView;
-use tokio::sync::Mutex;
 
-use crate::primitives::{DatumOption, Output, ScriptKind, TxHash};
+use crate::primitives::{
+    AssetsDelta, Certificate, DatumOption, ExUnits, GovAction, Hash, Input, Output,
+    ProposalProcedure, PubKeyHash, RedeemerPurpose, Redeemers, RewardAccount, Script,
+    ScriptEvaluation, ScriptHash, ScriptKind, SlotConfig, TxHash,
+};
+use crate::registry::TokenRegistry;
 use crate::wallet::Wallet;
 
 mod api;
+mod assertions;
 pub mod coin_selection;
 mod collateral;
+mod evaluator;
+mod ex_units_store;
 pub mod fee;
+mod hooks;
+mod journal;
+pub mod lint;
+mod quote;
+mod resolve;
+mod scripts;
+mod sequential;
+mod signing;
+mod status;
+mod submission;
 pub mod tx;
+mod validity;
 
-use tx::{BuiltTransaction, StagingTransaction};
+use assertions::BalanceAssertion;
+pub use evaluator::Evaluator;
+use ex_units_store::cost_models_fingerprint;
+pub use ex_units_store::{
+    ExUnitsKey, ExUnitsStore, ExUnitsStoreError, FileExUnitsStore, PersistentExUnits, StoredExUnits,
+};
+use hooks::Hook;
+pub use hooks::{Phase, PhaseContext};
+pub use journal::{JournalEntry, JournalError, Recovery, TxJournal, TxState};
+use lint::LintRule;
+pub use quote::{ExUnitsCache, FeeQuote};
+pub use resolve::UtxoSource;
+use scripts::{check_script_batches, dedupe_scripts, drop_cancelled_policies};
+pub use sequential::SequentialSpender;
+pub use signing::{RequestedSigner, SignatureBundle, SigningRequest};
+pub use status::{Status, TxStatus, TxTracker};
+pub use submission::{BatchSubmitter, OnFailure, Rejection, SubmitError, Submitter};
+pub use tx::Era;
+use tx::{BuiltTransaction, StagingTransaction, TxBuilderError};
+pub use validity::ValidityMargin;
+use validity::WallClockWindow;
 
 pub struct TxBuilder {
     body: StagingTransaction,
     collateral_address: Option<Address>,
-    change_address: Address,
+    /// Address collateral is selected from instead of the change address, see `collateral_from`
+    collateral_source: Option<Address>,
+    /// `None` for transactions that balance exactly, see `without_change_address`
+    change_address: Option<Address>,
+    /// Lovelace `build` may add to the fee instead of making a change output, see `no_change`
+    no_change_threshold: Option<u64>,
     change_datum: Option<DatumOption>,
+    /// Further addresses inputs are selected from, see `address_pool`
+    address_pool: Vec<Address>,
+    /// Pool addresses never picked for change, see `used_addresses`
+    used_addresses: Vec<Address>,
+    /// Addresses inputs are selected from without receiving change, see `spend_from`
+    source_addresses: Vec<Address>,
+    /// UTxOs coin selection leaves alone, see `avoid_inputs`
+    avoided_inputs: HashSet<Input>,
     script_kinds: HashSet<ScriptKind>,
+    /// Kind of each policy passed to `apply_delta`, which only counts while the policy still
+    /// mints or burns something, see `used_script_kinds`
+    mint_script_kinds: HashMap<Hash<28>, ScriptKind>,
+    /// UTxOs resolved by the caller, used instead of indexer lookups
+    known_utxos: HashMap<Input, TxOutput>,
+    /// The same UTxOs as the caller gave them, inline datums included, for evaluation
+    known_outputs: HashMap<Input, Output>,
+    fee_padding_lovelace: u64,
+    fee_padding_percent: f64,
+    assertions: Vec<BalanceAssertion>,
+    /// Outputs to be split according to `max_value_size` once the protocol parameters are known
+    split_outputs: Vec<Output>,
+    /// Callbacks into `build`, see `on_phase`
+    hooks: Vec<(Phase, Hook)>,
+    /// Used to render asset amounts in assertion failures
+    token_registry: Option<Arc<dyn TokenRegistry>>,
+    /// Budgets pinned with `with_redeemer_budgets`, applied at the start of `build`
+    redeemer_budgets: Option<HashMap<RedeemerPurpose, ExUnits>>,
+    /// Rules run by `lint` on top of the built-in ones
+    lint_rules: Vec<Box<dyn LintRule>>,
+    /// Whether `build` fails on lint errors, see `deny_lints`
+    deny_lints: bool,
+    /// Overrides of the default lint thresholds, see `lint_size_thresholds`
+    datum_size_threshold: Option<usize>,
+    script_size_threshold: Option<usize>,
+    /// Inputs added together with `add_script_inputs`, which must all be locked by one script
+    script_input_batches: Vec<Vec<Input>>,
+    /// Whether attached scripts are kept when a reference input carries them too, see
+    /// `keep_duplicate_scripts`
+    keep_duplicate_scripts: bool,
+    /// Evaluated budgets are recorded here for `quote`, see `ex_units_cache`
+    ex_units_cache: Option<ExUnitsCache>,
+    /// Budgets shared with other builders and processes, see `ex_units_store`
+    ex_units_store: Option<PersistentExUnits>,
+    /// Checked on the final transaction only, see `verify_script_data_hash`
+    verify_script_data_hash: bool,
     pub validity_interval: Interval<u64>,
+    /// Windows set with `valid_for`/`valid_between`, converted into slots during `build`
+    wall_clock_windows: Vec<WallClockWindow>,
+    /// Used to convert wall-clock windows, see `slot_config`
+    slot_config: Option<SlotConfig>,
+    validity_margin: ValidityMargin,
 }
 
 // TODO: redeemers, auxillary data, language view, delegation, governance
@@ -41,31 +134,87 @@ impl TxBuilder {
     /// 5. BUILD
     pub async fn build(
         mut self,
-        indexer: &Arc<Mutex<UtxoIndexer>>,
-        ogmios: &OgmiosHttpClient,
+        indexer: &impl UtxoSource,
+        ogmios: &impl Evaluator,
         pparams: &ProtocolParams,
     ) -> Result<BuiltTx> {
+        if !self.wall_clock_windows.is_empty() {
+            let tip = ogmios
+                .tip_slot()
+                .await
+                .context("failed to query the chain tip")?;
+            self = self.apply_wall_clock_windows(tip)?;
+        }
         let validity_interval = self.validity_interval;
         self = self.apply_validity_interval(&validity_interval)?;
         // TODO: language view can only be set once per transaction, so this doens't make sense
-        for script_kind in self.script_kinds.iter() {
+        for script_kind in self.used_script_kinds().iter() {
             if let Some(language_view) = language_view_for_script_kind(*script_kind, pparams) {
                 self.body = self.body.language_view(*script_kind, language_view.1);
             }
         }
-        self.body = self
-            .body
-            .apply_stake_credential_deposit(pparams.stake_credential_deposit.lovelace);
+        self.body = apply_deposits(self.body, pparams);
+        self.apply_treasury_value(ogmios).await?;
+        for output in std::mem::take(&mut self.split_outputs) {
+            for chunk in output.split(pparams)? {
+                self.body = self.body.output(chunk);
+            }
+        }
+        check_value_sizes(&self.body, pparams)?;
+        if let Some(budgets) = self.redeemer_budgets.take() {
+            self.body = self.body.pin_ex_units(budgets)?;
+            check_ex_units_limit(&self.body, pparams)?;
+        }
+        self.check_lints(pparams)?;
 
-        let address_utxos = {
-            let indexer = indexer.lock().await;
-            indexer.address_utxos(&self.change_address.to_vec())?
+        let address_utxos = self.spendable_utxos(indexer).await?;
+        if let Some(address) = self.next_unused_address(&address_utxos) {
+            self.change_address = Some(address);
+        }
+        self.ensure_fundable(&address_utxos)?;
+        let collateral_utxos = match &self.collateral_source {
+            Some(address) => Some(indexer.address_utxos(&address.to_vec()).await?),
+            None => None,
         };
+        self.cache_utxos(indexer, &address_utxos).await?;
+        if let Some(collateral_utxos) = &collateral_utxos {
+            self.cache_utxos(indexer, collateral_utxos).await?;
+        }
+        check_script_batches(&self.script_input_batches, &self.body, &self.known_utxos)?;
+        self.body = drop_cancelled_policies(
+            std::mem::take(&mut self.body),
+            &self.cancelled_policies(),
+            &self.known_utxos,
+        );
+        self.body = dedupe_scripts(
+            std::mem::take(&mut self.body),
+            &self.known_utxos,
+            self.keep_duplicate_scripts,
+        )?;
+        let cost_models = cost_models_fingerprint(pparams);
+        if let Some(budgets) = self.stored_budgets(cost_models) {
+            self.body = self.body.pin_ex_units(budgets)?;
+            check_ex_units_limit(&self.body, pparams)?;
+        }
 
         // balance inputs/outputs with fee in a loop until stable
-        let (mut fee, mut evaluation) =
-            TxBuilder::min_fee(&self.body, indexer, ogmios, pparams, None).await?;
-        self.body = self.body.fee(fee);
+        let (mut fee, mut evaluation) = TxBuilder::min_fee(
+            &self.body,
+            indexer,
+            &self.known_utxos,
+            &self.known_outputs,
+            ogmios,
+            pparams,
+            None,
+        )
+        .await?;
+        fee = self.pad_fee(fee);
+        let reserved_collateral = self.reserve_collateral(&address_utxos, pparams, fee);
+
+        // Every iteration starts over from the body as configured plus the inputs selected so
+        // far, so that hooks always see the current state and their changes don't pile up.
+        let base_body = std::mem::take(&mut self.body);
+        let mut selected_inputs: Vec<Input> = vec![];
 
         let mut loop_count = 0;
         const MAX_ITERATIONS: usize = 20;
@@ -77,38 +226,124 @@ impl TxBuilder {
                 MAX_ITERATIONS
             );
 
+            self.body = base_body.clone().fee(fee);
+            for input in &selected_inputs {
+                self.body = self.body.input(input.clone());
+            }
+
             for input in self
-                .select_coins(indexer, &address_utxos, fee, pparams)
+                .select_coins_around(indexer, &address_utxos, &reserved_collateral, fee, pparams)
                 .await?
             {
-                self.body = self.body.input(input.into());
+                let input: Input = input.into();
+                self.body = self.body.input(input.clone());
+                selected_inputs.push(input);
+            }
+
+            if self.has_hooks(Phase::AfterSelection) {
+                let mut body = self.body.clone();
+                self.run_hooks(
+                    &mut body,
+                    &PhaseContext {
+                        phase: Phase::AfterSelection,
+                        selected_inputs: &selected_inputs,
+                        fee,
+                        evaluation: &evaluation,
+                    },
+                )?;
+                if body != self.body {
+                    // Hooks may have added outputs (or inputs), so selection has to cover them
+                    self.body = body;
+                    let extra_inputs = self
+                        .select_coins_around(
+                            indexer,
+                            &address_utxos,
+                            &reserved_collateral,
+                            fee,
+                            pparams,
+                        )
+                        .await?;
+                    if !extra_inputs.is_empty() {
+                        selected_inputs.extend(extra_inputs.into_iter().map(Input::from));
+                        continue;
+                    }
+                }
             }
 
             // Recalculate fee with the change output and collateral input included
-            let finalized_body = {
+            let mut finalized_body = {
                 let mut body = self.body.clone();
-                for collateral_input in self
-                    .collateral_inputs(indexer, &address_utxos, pparams, fee)
-                    .await?
-                {
+                let collateral_utxos = collateral_utxos.as_deref().unwrap_or(&address_utxos);
+                for collateral_input in self.collateral_inputs(collateral_utxos, pparams, fee)? {
                     body = body.collateral_input(collateral_input);
                 }
-                // TODO: if change output not present, must burn it in fee. perhaps disallow this?
-                let change_output = self
-                    .change_output(indexer, fee, pparams)
+                if let Some(collateral_return) = self
+                    .collateral_return(indexer, &body.collateral_inputs, fee, pparams)
                     .await?
-                    .context("failed to create change output")?;
-                body = body.output(change_output);
+                {
+                    body = body.collateral_output(collateral_return);
+                }
+                if self.change_target().is_some() {
+                    // TODO: if change output not present, must burn it in fee. perhaps disallow
+                    // this?
+                    let change_output = self
+                        .change_output(indexer, fee, pparams)
+                        .await?
+                        .context("failed to create change output")?;
+                    body = body.output(change_output);
+                } else {
+                    body = body.fee(fee + self.fee_surplus(indexer, fee).await?);
+                }
                 body
             };
-            let (next_fee, next_evaluation) = TxBuilder::min_fee(
+            self.run_hooks(
+                &mut finalized_body,
+                &PhaseContext {
+                    phase: Phase::BeforeFinalize,
+                    selected_inputs: &selected_inputs,
+                    fee,
+                    evaluation: &evaluation,
+                },
+            )?;
+
+            let (mut next_fee, mut next_evaluation) = TxBuilder::min_fee(
                 &finalized_body,
                 indexer,
+                &self.known_utxos,
+                &self.known_outputs,
                 ogmios,
                 pparams,
                 Some(evaluation.clone()),
             )
             .await?;
+            next_fee = self.pad_fee(next_fee);
+
+            if self.has_hooks(Phase::AfterEvaluation) {
+                let mut body = finalized_body.clone();
+                self.run_hooks(
+                    &mut body,
+                    &PhaseContext {
+                        phase: Phase::AfterEvaluation,
+                        selected_inputs: &selected_inputs,
+                        fee: next_fee,
+                        evaluation: &next_evaluation,
+                    },
+                )?;
+                if body != finalized_body {
+                    finalized_body = body;
+                    (next_fee, next_evaluation) = TxBuilder::min_fee(
+                        &finalized_body,
+                        indexer,
+                        &self.known_utxos,
+                        &self.known_outputs,
+                        ogmios,
+                        pparams,
+                        Some(next_evaluation),
+                    )
+                    .await?;
+                    next_fee = self.pad_fee(next_fee);
+                }
+            }
 
             // Same as the last iteration, fully balanced
             if next_fee == fee {
@@ -116,18 +351,110 @@ impl TxBuilder {
                 break;
             }
 
-            self.body = self.body.fee(next_fee);
             fee = next_fee;
             evaluation = next_evaluation;
         }
 
+        if let Some(cache) = &self.ex_units_cache {
+            let budgets = self.body.evaluated_ex_units(&evaluation)?;
+            cache.record_all(&self.body, &budgets, &self.known_utxos);
+        }
+        if let Some(store) = &self.ex_units_store
+            && !self.body.ex_units_pinned
+        {
+            let budgets = self.body.evaluated_ex_units(&evaluation)?;
+            if let Err(err) = store.record_all(&self.body, &budgets, &self.known_utxos, cost_models)
+            {
+                tracing::warn!("Could not record script budgets in the ex units store: {err}");
+            }
+        }
+
         // serialize to CBOR
         let tx = self
             .body
             .clone()
-            .build_conway(Some(evaluation))
+            .verify_script_data_hash(self.verify_script_data_hash)
+            .build(Some(evaluation))
             .context("failed to build transaction")?;
-        Ok(BuiltTx::new(self.body, tx))
+        self.check_assertions(&self.body)?;
+        let required_signers =
+            fee::required_signers(&self.body, indexer, &self.known_utxos).await?;
+        Ok(BuiltTx {
+            required_signers,
+            input_owners: signing::input_owners(&self.body, &self.known_utxos),
+            configured: base_body,
+            change_address: self.change_address,
+            ..BuiltTx::new(self.body, tx)
+        })
+    }
+
+    /// Declares the treasury value queried through `ogmios` when a treasury withdrawal is
+    /// proposed and none was set, as the ledger requires one then.
+    async fn apply_treasury_value(&mut self, ogmios: &impl Evaluator) -> Result<()> {
+        let withdraws_from_treasury =
+            self.body.proposals.iter().any(|proposal| {
+                matches!(proposal.gov_action, GovAction::TreasuryWithdrawals { .. })
+            });
+        if !withdraws_from_treasury || self.body.treasury_value.is_some() {
+            return Ok(());
+        }
+        let treasury = ogmios
+            .treasury_value()
+            .await
+            .context("failed to query the treasury value")?
+            .ok_or(TxBuilderError::TreasuryValueUnavailable)?;
+        self.body = std::mem::take(&mut self.body).treasury_value(treasury);
+        Ok(())
+    }
+
+    /// Script kinds the language view is made of: those of scripts run by the transaction, and
+    /// of the policies that still mint or burn something once mints and burns cancelled out.
+    fn used_script_kinds(&self) -> HashSet<ScriptKind> {
+        let mut kinds = self.script_kinds.clone();
+        kinds.extend(
+            self.mint_script_kinds
+                .iter()
+                .filter(|(policy, _)| self.body.mint.keys().any(|id| id.policy == **policy))
+                .map(|(_, kind)| *kind),
+        );
+        kinds
+    }
+
+    /// Budgets from the ex units store for the redeemers without explicit ex units, when it
+    /// covers all of them. Store errors are logged and count as misses.
+    fn stored_budgets(&self, cost_models: Hash<32>) -> Option<HashMap<RedeemerPurpose, ExUnits>> {
+        let store = self.ex_units_store.as_ref()?;
+        // Hooks may add redeemers or change them, so their budgets have to be evaluated
+        let no_redeemers = self
+            .body
+            .redeemers
+            .as_ref()
+            .is_none_or(|rdmrs| rdmrs.is_empty());
+        if !self.hooks.is_empty() || self.body.ex_units_pinned || no_redeemers {
+            return None;
+        }
+        store
+            .budgets(&self.body, &self.known_utxos, cost_models)
+            .inspect_err(|err| {
+                tracing::warn!("Could not read script budgets from the ex units store: {err}");
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Policies given to `apply_delta` whose mints and burns cancelled out.
+    fn cancelled_policies(&self) -> HashSet<Hash<28>> {
+        self.mint_script_kinds
+            .keys()
+            .filter(|policy| !self.body.mint.keys().any(|id| id.policy == **policy))
+            .copied()
+            .collect()
+    }
+
+    /// Adds the configured fee padding on top of the computed minimum fee.
+    fn pad_fee(&self, fee: u64) -> u64 {
+        let percent_padding = (fee as f64 * self.fee_padding_percent / 100.0).ceil() as u64;
+        fee + self.fee_padding_lovelace + percent_padding
     }
 
     pub fn apply_validity_interval(mut self, validity_interval: &Interval<u64>) -> Result<Self> {
@@ -190,6 +517,59 @@ impl TxBuilder {
     }
 }
 
+/// Fails if the pinned ex units of `tx` don't fit in a single transaction
+fn check_ex_units_limit(tx: &StagingTransaction, pparams: &ProtocolParams) -> Result<()> {
+    let limit = max_tx_ex_units(pparams);
+    let total = tx.total_ex_units();
+    if !total.fits_within(&limit) {
+        return Err(TxBuilderError::ExUnitsAboveLimit { total, limit }.into());
+    }
+    Ok(())
+}
+
+/// Fails if the value of an output of `tx` doesn't fit in `max_value_size` bytes, which the
+/// ledger would reject
+fn check_value_sizes(tx: &StagingTransaction, pparams: &ProtocolParams) -> Result<()> {
+    let max = pparams.max_value_size.bytes as usize;
+    for (index, output) in tx.outputs.iter().enumerate() {
+        let size = output.value_size()?;
+        if size > max {
+            return Err(TxBuilderError::OutputValueTooLarge { index, size, max }.into());
+        }
+    }
+    Ok(())
+}
+
+/// Fills in the deposits of certificates and proposals that weren't given one explicitly
+fn apply_deposits(tx: StagingTransaction, pparams: &ProtocolParams) -> StagingTransaction {
+    let tx = tx.apply_stake_credential_deposit(pparams.stake_credential_deposit.lovelace);
+    // Only Conway protocol params have a governance action deposit
+    match &pparams.governance_action_deposit {
+        Some(deposit) => tx.apply_gov_action_deposit(deposit.lovelace),
+        None => tx,
+    }
+}
+
+/// Most ex units all scripts of a transaction may use together
+fn max_tx_ex_units(pparams: &ProtocolParams) -> ExUnits {
+    let max = &pparams.max_execution_units_per_transaction;
+    let to_u64 = |value: &BigRational| value.to_integer().to_u64().unwrap_or(u64::MAX);
+    ExUnits {
+        mem: to_u64(&max.memory.0),
+        steps: to_u64(&max.cpu.0),
+    }
+}
+
+/// Minimum lovelace `output` must hold to be accepted by the ledger, per the Babbage/Conway
+/// `coinsPerUTxOByte` rule: the serialized size of the output plus a constant 160 byte overhead,
+/// times the per byte coefficient.
+///
+/// This is what `build` uses for change and collateral return outputs, so it can be used to tell
+/// ahead of time how much lovelace an output needs.
+pub fn min_utxo_lovelace(output: &Output, pparams: &ProtocolParams) -> Result<u64> {
+    Ok(output.min_deposit(pparams)?)
+}
+
 pub fn language_view_for_script_kind(
     script_kind: ScriptKind,
     pparams: &ProtocolParams,
@@ -229,22 +609,194 @@ pub fn language_view_for_script_kind(
     }
 }
 
+/// Body of a built transaction, as it was serialized.
+///
+/// Only exposes its fields for reading. It can't be cloned nor turned back into a
+/// `StagingTransaction`: use `BuiltTx::rebuild` to derive a modified transaction, so it's
+/// balanced and serialized again.
+///
+/// ```
+/// fn fee(tx: &hose::builder::BuiltTx) -> Option<u64> {
+///     tx.body().fee()
+/// }
+/// ```
+///
+/// ```compile_fail
+/// fn bump_fee(tx: &hose::builder::BuiltTx) {
+///     let mut body = (*tx.body()).clone();
+///     body.fee = Some(1_000_000);
+/// }
+/// ```
+///
+/// ```compile_fail
+/// fn copy(tx: &hose::builder::BuiltTx) -> hose::builder::BuiltBody {
+///     tx.body().clone()
+/// }
+/// ```
+#[derive(PartialEq, Eq, Debug)]
+pub struct BuiltBody(StagingTransaction);
+
+impl BuiltBody {
+    pub fn inputs(&self) -> &[Input] {
+        &self.0.inputs
+    }
+
+    pub fn reference_inputs(&self) -> &[Input] {
+        &self.0.reference_inputs
+    }
+
+    pub fn outputs(&self) -> &[Output] {
+        &self.0.outputs
+    }
+
+    pub fn fee(&self) -> Option<u64> {
+        self.0.fee
+    }
+
+    pub fn mint(&self) -> &AssetsDelta {
+        &self.0.mint
+    }
+
+    pub fn valid_from_slot(&self) -> Option<u64> {
+        self.0.valid_from_slot
+    }
+
+    pub fn invalid_from_slot(&self) -> Option<u64> {
+        self.0.invalid_from_slot
+    }
+
+    pub fn network_id(&self) -> Option<u8> {
+        self.0.network_id
+    }
+
+    pub fn collateral_inputs(&self) -> &[Input] {
+        &self.0.collateral_inputs
+    }
+
+    pub fn collateral_output(&self) -> Option<&Output> {
+        self.0.collateral_output.as_ref()
+    }
+
+    pub fn scripts(&self) -> &HashMap<ScriptHash, Script> {
+        &self.0.scripts
+    }
+
+    pub fn redeemers(&self) -> Option<&Redeemers> {
+        self.0.redeemers.as_ref()
+    }
+
+    pub fn script_data_hash(&self) -> Option<Hash<32>> {
+        self.0.script_data_hash
+    }
+
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.0.certificates
+    }
+
+    pub fn withdrawals(&self) -> &BTreeMap<RewardAccount, u64> {
+        &self.0.withdrawals
+    }
+
+    pub fn proposals(&self) -> &[ProposalProcedure] {
+        &self.0.proposals
+    }
+
+    /// See `StagingTransaction::script_evaluations`.
+    pub fn script_evaluations(
+        &self,
+        evaluations: &[Evaluation],
+    ) -> Result<Vec<ScriptEvaluation>, TxBuilderError> {
+        self.0.script_evaluations(evaluations)
+    }
+}
+
+/// A built transaction.
+///
+/// The body, hash and serialized bytes are fixed once built. Signing only adds witnesses to the
+/// bytes, and never changes the hash.
 pub struct BuiltTx {
-    staging: StagingTransaction,
+    staging: BuiltBody,
     tx: BuiltTransaction,
+    /// Keys that must sign, known once inputs are resolved, see `to_signing_request`
+    required_signers: HashSet<PubKeyHash>,
+    /// Key controlling each key-locked input, see `input_owners`
+    input_owners: HashMap<Input, PubKeyHash>,
+    /// Body as configured before coin selection and balancing, see `rebuild`
+    configured: StagingTransaction,
+    change_address: Option<Address>,
 }
 
 impl BuiltTx {
+    /// Pairs a body with the transaction built from it. `rebuild` starts over from the body
+    /// without its fee and collateral, and needs the body to have a change address.
     pub fn new(staging: StagingTransaction, tx: BuiltTransaction) -> Self {
-        Self { staging, tx }
+        let mut configured = staging.clone();
+        configured.fee = None;
+        configured.collateral_inputs.clear();
+        configured.collateral_output = None;
+        Self {
+            change_address: staging.change_address.clone(),
+            staging: BuiltBody(staging),
+            tx,
+            required_signers: HashSet::new(),
+            input_owners: HashMap::new(),
+            configured,
+        }
     }
 
-    pub fn body(&self) -> &StagingTransaction {
+    pub fn body(&self) -> &BuiltBody {
         &self.staging
     }
 
+    /// A builder to derive a modified transaction from this one, e.g. to add an output.
+    ///
+    /// It starts over from the body as it was before balancing: inputs, outputs, mint,
+    /// certificates, withdrawals, etc. are carried over, while the fee, the inputs selected to
+    /// cover it, the change output and collateral are left for the next `build` to work out.
+    /// Settings of the original builder that aren't part of the body, such as hooks, assertions,
+    /// fee padding or known UTxOs, are not carried over.
+    pub fn rebuild(&self) -> Result<TxBuilder> {
+        let change_address = self
+            .change_address
+            .clone()
+            .context("transaction has no change address to rebuild with")?;
+        Ok(TxBuilder::with_body(
+            self.configured.clone(),
+            Some(change_address),
+        ))
+    }
+
+    /// Pairs each input, in the order it was added to the builder, with its index in the
+    /// serialized transaction. The ledger requires inputs to be sorted by (tx hash, index), and
+    /// spend redeemers point at these sorted indices.
+    pub fn input_order(&self) -> Vec<(Input, usize)> {
+        let mut sorted = self.staging.inputs().to_vec();
+        sorted.sort_unstable_by_key(|input| (input.hash.0, input.index));
+        sorted.dedup();
+
+        self.staging
+            .inputs()
+            .iter()
+            .map(|input| {
+                let index = sorted
+                    .binary_search_by_key(&(input.hash.0, input.index), |x| (x.hash.0, x.index))
+                    .expect("input is in the sorted list");
+                (input.clone(), index)
+            })
+            .collect()
+    }
+
+    /// Index of `input` in the serialized transaction, if it's spent by it. See `input_order`.
+    pub fn input_index(&self, input: &Input) -> Option<usize> {
+        self.input_order()
+            .into_iter()
+            .find_map(|(candidate, index)| (candidate == *input).then_some(index))
+    }
+
+    /// Signs with the keys of `wallet` that must sign, which may be several when inputs come
+    /// from its address pool, or with its main key if none must.
     pub fn sign(mut self, wallet: &Wallet) -> Result<Self> {
-        let tx = wallet.sign(&self.tx)?;
+        let tx = wallet.sign_required(&self.tx, &self.required_signers)?;
         self.tx = tx;
         Ok(self)
     }
@@ -258,20 +810,93 @@ impl BuiltTx {
     }
 
     pub fn hash(&self) -> Result<TxHash> {
-        Ok(self.tx.hash.0.into())
+        Ok(self.tx.hash)
+    }
+
+    /// Pointer to the first output matching `predicate`, e.g. to spend it in a follow-up
+    /// transaction.
+    pub fn find_output(&self, predicate: impl FnMut(&Output) -> bool) -> Option<TxOutputPointer> {
+        self.staging
+            .outputs()
+            .iter()
+            .position(predicate)
+            .map(|index| TxOutputPointer::new(self.tx.hash, index as u64))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use hydrant::primitives::{TxOutput, TxOutputPointer};
+    use ogmios_client::OgmiosHttpClient;
+    use ogmios_client::method::evaluate::Evaluation;
+    use ogmios_client::method::pparams::ProtocolParams;
     use pallas::ledger::addresses::{
         Address as PallasAddress, Network, ShelleyAddress, ShelleyDelegationPart,
         ShelleyPaymentPart,
     };
     use pallas::ledger::primitives::NetworkId;
 
-    use super::TxBuilder;
-    use crate::primitives::{Asset, Hash, RedeemerPurpose, ScriptKind};
+    use super::coin_selection::LovelaceBalance;
+    use super::{
+        BuiltTx, Evaluator, FileExUnitsStore, PersistentExUnits, Phase, StagingTransaction,
+        TxBuilder, TxBuilderError, UtxoSource, drop_cancelled_policies,
+    };
+    use crate::mock_http::MockServer;
+    use crate::ogmios::OgmiosQueries;
+    use crate::primitives::{
+        Anchor, Asset, AssetsDelta, AssetsDeltaExt as _, ExUnits, GovAction, Hash, Input, Output,
+        RedeemerPurpose, RewardAccount, ScriptKind, ValidatorBudget, ValidatorPurpose,
+    };
+
+    /// Fails the test on any lookup, for builds that must not need the indexer.
+    struct NoIndexer;
+
+    impl UtxoSource for NoIndexer {
+        async fn utxos(&self, pointers: &[TxOutputPointer]) -> anyhow::Result<Vec<TxOutput>> {
+            panic!("unexpected indexer lookup of {} utxos", pointers.len())
+        }
+
+        async fn address_utxos(&self, address: &[u8]) -> anyhow::Result<Vec<TxOutput>> {
+            panic!("unexpected indexer lookup of {}", hex::encode(address))
+        }
+    }
+
+    /// Evaluator that isn't backed by Ogmios, answering every evaluation with the same budgets.
+    struct FixedBudgets(Vec<ValidatorBudget>);
+
+    impl Evaluator for FixedBudgets {
+        async fn evaluate(
+            &self,
+            _tx: &[u8],
+            _additional_utxos: &[(Input, Output)],
+        ) -> anyhow::Result<Vec<Evaluation>> {
+            Ok(self
+                .0
+                .iter()
+                .map(Evaluation::try_from)
+                .collect::<Result<_, _>>()?)
+        }
+
+        async fn tip_slot(&self) -> anyhow::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    /// Protocol parameters as Ogmios reports them, with the mainnet values of the genesis files
+    /// in `configs/mainnet`.
+    fn protocol_params() -> ProtocolParams {
+        serde_json::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/protocol_params.json"
+        )))
+        .expect("parse protocol params")
+    }
+
+    fn ogmios_at(server: &MockServer) -> OgmiosHttpClient {
+        OgmiosHttpClient::new(reqwest::Url::parse(&server.url).expect("mock server url"))
+    }
 
     fn dummy_address() -> PallasAddress {
         let payment_hash = Hash([1u8; 28]);
@@ -291,6 +916,143 @@ mod tests {
             .unwrap_or(false)
     }
 
+    #[test]
+    fn input_order_maps_to_sorted_indices() {
+        let first_added = Input::new(Hash([9u8; 32]), 0);
+        let second_added = Input::new(Hash([1u8; 32]), 5);
+        let third_added = Input::new(Hash([1u8; 32]), 2);
+        let staging = StagingTransaction::new()
+            .fee(0)
+            .input(first_added.clone())
+            .input(second_added.clone())
+            .input(third_added.clone())
+            .output(Output::new(dummy_address(), 1));
+        let tx = staging.clone().build_conway(None).unwrap();
+        let built = BuiltTx::new(staging, tx);
+
+        assert_eq!(
+            built.input_order(),
+            vec![
+                (first_added.clone(), 2),
+                (second_added.clone(), 1),
+                (third_added.clone(), 0)
+            ]
+        );
+        assert_eq!(built.input_index(&third_added), Some(0));
+        assert_eq!(built.input_index(&Input::new(Hash([2u8; 32]), 0)), None);
+    }
+
+    #[test]
+    fn find_output_points_into_the_built_tx() {
+        let staging = StagingTransaction::new()
+            .fee(0)
+            .input(Input::new(Hash([9u8; 32]), 0))
+            .output(Output::new(dummy_address(), 1))
+            .output(Output::new(dummy_address(), 2))
+            .output(Output::new(dummy_address(), 2));
+        let tx = staging.clone().build_conway(None).unwrap();
+        let built = BuiltTx::new(staging, tx);
+
+        let pointer = built
+            .find_output(|output| output.lovelace == 2)
+            .expect("output found");
+        assert_eq!(Input::from(pointer), Input::new(built.hash().unwrap(), 1));
+        assert!(built.find_output(|output| output.lovelace == 3).is_none());
+    }
+
+    #[test]
+    fn change_goes_to_the_first_unused_pool_address() {
+        let address = |seed: u8| {
+            PallasAddress::Shelley(ShelleyAddress::new(
+                Network::Testnet,
+                ShelleyPaymentPart::Key(Hash([seed; 28]).into()),
+                ShelleyDelegationPart::Null,
+            ))
+        };
+        let builder = TxBuilder::new(NetworkId::Testnet, address(1))
+            .address_pool(vec![address(1), address(2), address(3)])
+            .add_output(Output::new(address(2), 1_000_000));
+        let utxo_at = |seed: u8| {
+            Output::new(address(seed), 5_000_000).to_tx_output(&Input::new(Hash([seed; 32]), 0))
+        };
+
+        assert_eq!(builder.next_unused_address(&[]), Some(address(1)));
+        assert_eq!(builder.next_unused_address(&[utxo_at(1)]), Some(address(3)));
+        assert_eq!(builder.next_unused_address(&[utxo_at(1), utxo_at(3)]), None);
+        assert_eq!(
+            TxBuilder::new(NetworkId::Testnet, address(1)).next_unused_address(&[]),
+            None
+        );
+
+        // Once emptied, address 1 looks fresh again, unless it's known to have been used
+        let builder = builder.used_addresses(vec![address(1)]);
+        assert_eq!(builder.next_unused_address(&[]), Some(address(3)));
+        assert_eq!(builder.next_unused_address(&[utxo_at(3)]), None);
+    }
+
+    #[test]
+    fn rebuild_starts_over_without_balancing_artifacts() {
+        let input = Input::new(Hash([9u8; 32]), 0);
+        let staging = StagingTransaction::new()
+            .network_id(0)
+            .fee(170_000)
+            .input(input.clone())
+            .collateral_input(Input::new(Hash([8u8; 32]), 0))
+            .collateral_output(Output::new(dummy_address(), 4_000_000))
+            .output(Output::new(dummy_address(), 2_000_000))
+            .change_address(dummy_address());
+        let tx = staging.clone().build_conway(None).unwrap();
+        let built = BuiltTx::new(staging, tx);
+
+        let builder = built.rebuild().unwrap();
+        assert_eq!(builder.change_address, Some(dummy_address()));
+        assert_eq!(builder.body().fee, None);
+        assert!(builder.body().collateral_inputs.is_empty());
+        assert_eq!(builder.body().collateral_output, None);
+        assert_eq!(builder.body().inputs, vec![input]);
+        assert_eq!(builder.body().outputs, built.body().outputs());
+        assert_eq!(builder.body().network_id, Some(0));
+
+        // The built body itself is left as it was
+        assert_eq!(built.body().fee(), Some(170_000));
+
+        let without_change_address = StagingTransaction::new()
+            .fee(0)
+            .input(Input::new(Hash([9u8; 32]), 0))
+            .output(Output::new(dummy_address(), 1));
+        let tx = without_change_address.clone().build_conway(None).unwrap();
+        assert!(BuiltTx::new(without_change_address, tx).rebuild().is_err());
+    }
+
+    #[test]
+    fn known_utxos_are_registered_without_being_spent() {
+        let output = Output::new(dummy_address(), 3_000_000);
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .add_known_utxo(TxOutputPointer::new(Hash([7u8; 32]), 1), output.clone());
+
+        let input = Input::new(Hash([7u8; 32]), 1);
+        assert!(builder.body().inputs.is_empty());
+        assert_eq!(
+            builder.known_utxos.get(&input),
+            Some(&output.to_tx_output(&input))
+        );
+    }
+
+    #[test]
+    fn fee_padding_defaults_to_zero() {
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address());
+        assert_eq!(builder.pad_fee(170_000), 170_000);
+    }
+
+    #[test]
+    fn fee_padding_combines_lovelace_and_percent() {
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .fee_padding_lovelace(1_000)
+            .fee_padding_percent(1.5);
+        // 1.5% of 170_001 is 2_550.015, which is rounded up
+        assert_eq!(builder.pad_fee(170_001), 170_001 + 1_000 + 2_551);
+    }
+
     #[test]
     fn mint_then_burn_same_asset_removes_redeemer() {
         let policy = Hash([4u8; 28]);
@@ -318,5 +1080,439 @@ mod tests {
 
         assert!(builder.body.mint.is_empty());
         assert!(!has_mint_redeemer(&builder, policy));
+        assert!(builder.current_mint_delta().is_net_zero());
+    }
+
+    #[test]
+    fn net_zero_mint_leaves_no_policy_script_behind() {
+        let script = vec![0x46, 0x01, 0x00];
+        let policy = ScriptKind::PlutusV3.hash(&script);
+        let other_policy = Hash([6u8; 28]);
+        let asset = |policy, quantity| Asset::new(policy, b"NETZERO".to_vec(), quantity);
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .add_script(ScriptKind::PlutusV3, script)
+            .mint_asset(asset(policy, 5), ScriptKind::PlutusV3, vec![0u8])
+            .expect("mint")
+            .mint_asset(asset(other_policy, 1), ScriptKind::PlutusV2, vec![0u8])
+            .expect("mint other")
+            .burn_asset(asset(policy, 5), ScriptKind::PlutusV3, vec![1u8])
+            .expect("burn");
+
+        // Only the policy still minting counts towards the language view
+        assert_eq!(
+            builder.used_script_kinds(),
+            HashSet::from([ScriptKind::PlutusV2])
+        );
+        assert_eq!(builder.cancelled_policies(), HashSet::from([policy]));
+
+        let body = drop_cancelled_policies(
+            builder.body.clone(),
+            &builder.cancelled_policies(),
+            &HashMap::new(),
+        );
+        assert!(body.scripts.is_empty());
+    }
+
+    #[test]
+    fn a_full_ex_units_store_pins_every_budget() {
+        let policy = Hash([4u8; 28]);
+        let other_policy = Hash([5u8; 28]);
+        let asset = |policy| Asset::new(policy, b"STORED".to_vec(), 1);
+        let path =
+            std::env::temp_dir().join(format!("hose-ex-units-{}.json", rand::random::<u64>()));
+        let store = PersistentExUnits::new(FileExUnitsStore::new(&path));
+        let cost_models = Hash([7u8; 32]);
+        let explicit = ExUnits { mem: 1, steps: 1 };
+        let mut builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .mint_asset(asset(policy), ScriptKind::PlutusV3, vec![0x05])
+            .expect("mint")
+            .mint_asset(asset(other_policy), ScriptKind::PlutusV3, vec![0x05])
+            .expect("mint other")
+            .ex_units_store(store.clone());
+        builder.body =
+            builder
+                .body
+                .add_mint_redeemer(other_policy, vec![0x05], Some(explicit.clone()));
+
+        // Nothing recorded yet, so the transaction has to be evaluated
+        assert_eq!(builder.stored_budgets(cost_models), None);
+
+        let evaluated = HashMap::from([(
+            RedeemerPurpose::Mint(policy),
+            ExUnits {
+                mem: 100,
+                steps: 1_000,
+            },
+        )]);
+        store
+            .record_all(&builder.body, &evaluated, &HashMap::new(), cost_models)
+            .unwrap();
+        assert_eq!(builder.stored_budgets(Hash([8u8; 32])), None);
+        // Explicit ex units are left alone
+        let budgets = builder.stored_budgets(cost_models).expect("stored budgets");
+        assert_eq!(
+            budgets,
+            HashMap::from([(
+                RedeemerPurpose::Mint(policy),
+                ExUnits {
+                    mem: 105,
+                    steps: 1_050
+                }
+            )])
+        );
+
+        // Pinned budgets are what `min_fee` skips the evaluation for
+        builder.body = builder.body.pin_ex_units(budgets).expect("pin budgets");
+        assert!(builder.body.ex_units_pinned);
+        assert_eq!(
+            builder.body.redeemers.as_ref().unwrap()[&RedeemerPurpose::Mint(other_policy)].1,
+            Some(explicit)
+        );
+
+        // Hooks may change the redeemers, so builders with hooks always evaluate
+        let with_hook = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .mint_asset(asset(policy), ScriptKind::PlutusV3, vec![0x05])
+            .expect("mint")
+            .ex_units_store(store)
+            .on_phase(Phase::BeforeFinalize, |_, _| Ok(()));
+        assert_eq!(with_hook.stored_budgets(cost_models), None);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn mint_delta_signs_reach_the_serialized_mint() {
+        let policy = Hash([5u8; 28]);
+        let delta = AssetsDelta::mint(Asset::new(policy, b"MINTED".to_vec(), 3))
+            .unwrap()
+            .checked_add(&AssetsDelta::burn(Asset::new(policy, b"BURNED".to_vec(), 2)).unwrap())
+            .unwrap();
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .apply_delta(delta.clone(), ScriptKind::PlutusV3, vec![0u8])
+            .expect("apply delta");
+        assert_eq!(builder.current_mint_delta(), &delta);
+        assert!(has_mint_redeemer(&builder, policy));
+
+        let build = |body: StagingTransaction| {
+            body.fee(0)
+                .output(Output::new(dummy_address(), 1))
+                .build_conway(None)
+                .expect("build conway")
+                .bytes
+        };
+        let expected = StagingTransaction::new()
+            .network_id(NetworkId::Testnet.into())
+            .mint_asset(policy, b"MINTED".to_vec(), 3)
+            .and_then(|body| body.mint_asset(policy, b"BURNED".to_vec(), -2))
+            .expect("mint asset")
+            .add_mint_redeemer(policy, vec![0u8], None);
+        assert_eq!(build(builder.body.clone()), build(expected));
+    }
+
+    #[test]
+    fn unfunded_change_address_is_reported_upfront() {
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .add_output(Output::new(dummy_address(), 2_000_000));
+        assert_eq!(
+            builder.ensure_fundable(&[]),
+            Err(TxBuilderError::NoSpendableUtxos)
+        );
+        assert_eq!(
+            TxBuilderError::NoSpendableUtxos.to_string(),
+            "transaction has no inputs; the change address has no spendable UTxOs"
+        );
+
+        let builder = builder.add_input(Input::new(Hash([2u8; 32]), 0));
+        assert_eq!(builder.ensure_fundable(&[]), Ok(()));
+    }
+
+    #[test]
+    fn withdrawal_only_transactions_pay_fee_and_change_out_of_the_withdrawal() {
+        let builder = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .withdraw_rewards(Hash([3u8; 28]), 5_000_000);
+        assert!(builder.body.outputs.is_empty());
+
+        let fee = 180_000;
+        assert_eq!(
+            builder.lovelace_balance(0, fee),
+            LovelaceBalance {
+                available: 5_000_000,
+                required: fee,
+            }
+        );
+        // The ledger still wants an input, whose lovelace goes to change along with the rewards
+        assert_eq!(
+            builder.ensure_fundable(&[]),
+            Err(TxBuilderError::NoSpendableUtxos)
+        );
+        assert_eq!(
+            builder.lovelace_balance(2_000_000, fee).surplus(),
+            2_000_000 + 5_000_000 - fee
+        );
+    }
+
+    #[tokio::test]
+    async fn treasury_value_is_queried_only_when_unset() {
+        let server = MockServer::serving(
+            r#"{
+                "jsonrpc": "2.0",
+                "result": {
+                    "treasury": { "ada": { "lovelace": 42 } },
+                    "reserves": { "ada": { "lovelace": 7 } }
+                }
+            }"#
+            .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let queried = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .current_treasury_value_from(&ogmios)
+            .await
+            .expect("query treasury");
+        assert_eq!(queried.body.treasury_value, Some(42));
+        assert_eq!(server.requests().len(), 1);
+
+        let pinned = TxBuilder::new(NetworkId::Testnet, dummy_address())
+            .current_treasury_value(5)
+            .current_treasury_value_from(&ogmios)
+            .await
+            .expect("keep pinned value");
+        assert_eq!(pinned.body.treasury_value, Some(5));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    fn proposing_treasury_withdrawal() -> TxBuilder {
+        let beneficiary = RewardAccount::from_key_hash(Network::Testnet, Hash([7u8; 28]));
+        TxBuilder::without_change_address(NetworkId::Testnet)
+            .propose(
+                GovAction::TreasuryWithdrawals {
+                    withdrawals: [(beneficiary.clone(), 1_000_000)].into(),
+                    guardrails_script: None,
+                },
+                Some(100_000_000_000),
+                beneficiary,
+                Anchor::new("https://dao.example/p1", Hash([9u8; 32])),
+                None,
+            )
+            .expect("propose treasury withdrawal")
+    }
+
+    #[tokio::test]
+    async fn treasury_withdrawals_get_the_queried_treasury_value() {
+        let server = MockServer::serving(
+            r#"{
+                "jsonrpc": "2.0",
+                "result": {
+                    "treasury": { "ada": { "lovelace": 42 } },
+                    "reserves": { "ada": { "lovelace": 7 } }
+                }
+            }"#
+            .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let mut builder = proposing_treasury_withdrawal();
+        builder
+            .apply_treasury_value(&ogmios)
+            .await
+            .expect("query treasury");
+        assert_eq!(builder.body.treasury_value, Some(42));
+        assert_eq!(server.requests().len(), 1);
+
+        // Neither a pinned value nor a transaction without treasury withdrawals needs a query
+        let mut pinned = proposing_treasury_withdrawal().current_treasury_value(5);
+        pinned
+            .apply_treasury_value(&ogmios)
+            .await
+            .expect("keep pinned value");
+        assert_eq!(pinned.body.treasury_value, Some(5));
+        let mut unrelated = TxBuilder::new(NetworkId::Testnet, dummy_address());
+        unrelated
+            .apply_treasury_value(&ogmios)
+            .await
+            .expect("no treasury withdrawal");
+        assert_eq!(unrelated.body.treasury_value, None);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn treasury_withdrawals_fail_without_a_way_to_get_the_treasury_value() {
+        let server = MockServer::serving(String::new()).await;
+
+        let err = proposing_treasury_withdrawal()
+            .build(&NoIndexer, &ogmios_at(&server), &protocol_params())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<TxBuilderError>(),
+            Some(&TxBuilderError::TreasuryValueUnavailable)
+        );
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pre_resolved_inputs_build_without_the_indexer() {
+        let server = MockServer::serving(String::new()).await;
+        let input = Input::new(Hash([3u8; 32]), 0);
+        let builder = TxBuilder::without_change_address(NetworkId::Testnet)
+            .add_input_resolved(input.clone(), Output::new(dummy_address(), 10_000_000))
+            .add_output(Output::new(dummy_address(), 9_000_000))
+            .no_change_threshold(1_000_000);
+
+        let tx = builder
+            .build(&NoIndexer, &ogmios_at(&server), &protocol_params())
+            .await
+            .expect("build without the indexer");
+        assert_eq!(tx.body().inputs(), &[input]);
+        assert_eq!(tx.body().fee(), Some(1_000_000));
+    }
+
+    #[tokio::test]
+    async fn transactions_without_redeemers_are_not_evaluated() {
+        let server = MockServer::serving(String::new()).await;
+        let ogmios = ogmios_at(&server);
+        let pparams = protocol_params();
+        let input = Input::new(Hash([3u8; 32]), 0);
+        let resolved = Output::new(dummy_address(), 10_000_000);
+
+        TxBuilder::without_change_address(NetworkId::Testnet)
+            .add_input_resolved(input.clone(), resolved.clone())
+            .add_output(Output::new(dummy_address(), 9_000_000))
+            .no_change_threshold(1_000_000)
+            .build(&NoIndexer, &ogmios, &pparams)
+            .await
+            .expect("build without evaluation");
+        assert!(server.requests().is_empty());
+
+        // The same fee calculation with a redeemer does reach the evaluator
+        let policy = Hash([5u8; 28]);
+        let minting = StagingTransaction::new()
+            .input(input.clone())
+            .output(Output::new(dummy_address(), 9_000_000))
+            .mint_asset(policy, b"TOKEN".to_vec(), 1)
+            .expect("mint asset")
+            .add_mint_redeemer(policy, vec![0u8], None);
+        let known_utxos = HashMap::from([(input.clone(), resolved.to_tx_output(&input))]);
+        let evaluated = TxBuilder::min_fee(
+            &minting,
+            &NoIndexer,
+            &known_utxos,
+            &HashMap::new(),
+            &ogmios,
+            &pparams,
+            None,
+        )
+        .await;
+        assert!(evaluated.is_err());
+        assert!(!server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn build_accepts_an_evaluator_not_backed_by_ogmios() {
+        let policy = Hash([4u8; 28]);
+        let collateral = Input::new(Hash([3u8; 32]), 1);
+        let budget = ExUnits {
+            mem: 1_700,
+            steps: 476_468,
+        };
+        let evaluator = FixedBudgets(vec![ValidatorBudget {
+            purpose: ValidatorPurpose::Mint,
+            index: 0,
+            ex_units: budget.clone(),
+        }]);
+
+        let tx = TxBuilder::without_change_address(NetworkId::Testnet)
+            .add_input_resolved(
+                Input::new(Hash([3u8; 32]), 0),
+                Output::new(dummy_address(), 10_000_000),
+            )
+            .add_known_utxo(
+                TxOutputPointer::from(&collateral),
+                Output::new(dummy_address(), 5_000_000),
+            )
+            .add_collateral_input(collateral)
+            .mint_asset(
+                Asset::new(policy, b"EVALUATED".to_vec(), 1),
+                ScriptKind::PlutusV3,
+                vec![0x05],
+            )
+            .expect("mint")
+            .add_output(
+                Output::new(dummy_address(), 9_000_000)
+                    .add_asset(policy, b"EVALUATED".to_vec(), 1)
+                    .expect("add asset"),
+            )
+            .no_change_threshold(1_000_000)
+            .build(&NoIndexer, &evaluator, &protocol_params())
+            .await
+            .expect("build with a custom evaluator");
+        assert_eq!(
+            tx.body().redeemers().expect("redeemers")[&RedeemerPurpose::Mint(policy)].1,
+            Some(budget)
+        );
+    }
+
+    #[tokio::test]
+    async fn stored_budgets_skip_the_evaluator() {
+        let server = MockServer::serving(String::new()).await;
+        let pparams = protocol_params();
+        let policy = Hash([4u8; 28]);
+        let path =
+            std::env::temp_dir().join(format!("hose-ex-units-{}.json", rand::random::<u64>()));
+        let store = PersistentExUnits::new(FileExUnitsStore::new(&path));
+        let input = Input::new(Hash([3u8; 32]), 0);
+        let collateral = Input::new(Hash([3u8; 32]), 1);
+        let builder = TxBuilder::without_change_address(NetworkId::Testnet)
+            .add_input_resolved(input, Output::new(dummy_address(), 10_000_000))
+            .add_known_utxo(
+                TxOutputPointer::from(&collateral),
+                Output::new(dummy_address(), 5_000_000),
+            )
+            .add_collateral_input(collateral)
+            .mint_asset(
+                Asset::new(policy, b"STORED".to_vec(), 1),
+                ScriptKind::PlutusV3,
+                vec![0x05],
+            )
+            .expect("mint")
+            .add_output(
+                Output::new(dummy_address(), 9_000_000)
+                    .add_asset(policy, b"STORED".to_vec(), 1)
+                    .expect("add asset"),
+            )
+            .no_change_threshold(1_000_000)
+            .ex_units_store(store.clone());
+        let evaluated = HashMap::from([(
+            RedeemerPurpose::Mint(policy),
+            ExUnits {
+                mem: 100,
+                steps: 1_000,
+            },
+        )]);
+        store
+            .record_all(
+                &builder.body,
+                &evaluated,
+                &HashMap::new(),
+                super::cost_models_fingerprint(&pparams),
+            )
+            .unwrap();
+
+        let tx = builder
+            .build(&NoIndexer, &ogmios_at(&server), &pparams)
+            .await
+            .expect("build with stored budgets");
+        assert!(server.requests().is_empty());
+        assert_eq!(
+            tx.body().redeemers().expect("redeemers")[&RedeemerPurpose::Mint(policy)].1,
+            Some(ExUnits {
+                mem: 105,
+                steps: 1_050
+            })
+        );
+
+        std::fs::remove_file(&path).ok();
     }
 }
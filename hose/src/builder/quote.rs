@@ -0,0 +1,363 @@
+//! Fee estimates for previews
+//!
+//! `TxBuilder::quote` estimates the fee of a transaction as it is being composed, without coin
+//! selection or balancing. Script budgets come from explicit ex units or from an `ExUnitsCache`
+//! filled by earlier builds, so that a warm cache needs no round trip to Ogmios.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use anyhow::{Context, Result};
+use hydrant::primitives::TxOutput;
+use num::BigRational;
+use ogmios_client::OgmiosHttpClient;
+use ogmios_client::method::pparams::ProtocolParams;
+use pallas::ledger::addresses::{Address, ShelleyPaymentPart};
+
+use super::fee::{fee_for, reference_script_size, required_signers, with_dummy_witnesses};
+use super::resolve::{UtxoSource, resolve_utxos};
+use super::tx::StagingTransaction;
+use super::{TxBuilder, apply_deposits, language_view_for_script_kind, max_tx_ex_units};
+use crate::primitives::{ExUnits, Hash, Input, Output, RedeemerPurpose};
+
+/// Script budgets observed in earlier builds, keyed by script hash and redeemer.
+///
+/// Clones share the same entries, so a single cache can be handed to every builder with
+/// `TxBuilder::ex_units_cache`. `build` records the evaluated budgets, which `quote` then uses
+/// instead of evaluating the transaction.
+#[derive(Default, Debug, Clone)]
+pub struct ExUnitsCache {
+    entries: Arc<StdMutex<HashMap<(Hash<28>, Vec<u8>), ExUnits>>>,
+}
+
+impl ExUnitsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Budget recorded for `script_hash` running with `redeemer`.
+    pub fn get(&self, script_hash: Hash<28>, redeemer: &[u8]) -> Option<ExUnits> {
+        self.entries
+            .lock()
+            .expect("ex units cache lock poisoned")
+            .get(&(script_hash, redeemer.to_vec()))
+            .cloned()
+    }
+
+    /// Records a budget, keeping the largest memory and steps seen for the same key.
+    pub fn record(&self, script_hash: Hash<28>, redeemer: Vec<u8>, ex_units: ExUnits) {
+        let mut entries = self.entries.lock().expect("ex units cache lock poisoned");
+        let entry = entries
+            .entry((script_hash, redeemer))
+            .or_insert(ExUnits { mem: 0, steps: 0 });
+        entry.mem = entry.mem.max(ex_units.mem);
+        entry.steps = entry.steps.max(ex_units.steps);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("ex units cache lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records the budget of every redeemer of `tx` found in `budgets`.
+    pub(crate) fn record_all(
+        &self,
+        tx: &StagingTransaction,
+        budgets: &HashMap<RedeemerPurpose, ExUnits>,
+        known_utxos: &HashMap<Input, TxOutput>,
+    ) {
+        for (purpose, (redeemer, _)) in tx.redeemers.iter().flat_map(|rdmrs| rdmrs.iter()) {
+            if let (Some(script_hash), Some(ex_units)) = (
+                redeemer_script_hash(purpose, tx, known_utxos),
+                budgets.get(purpose),
+            ) {
+                self.record(script_hash, redeemer.clone(), ex_units.clone());
+            }
+        }
+    }
+}
+
+/// Estimated cost of a transaction, see `TxBuilder::quote`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct FeeQuote {
+    /// Lowest and highest fee expected once the transaction is built, padding included
+    pub estimated_fee_range: (u64, u64),
+    /// Size in bytes of the signed transaction, assuming a single selected input
+    pub estimated_size: usize,
+    pub requires_collateral: bool,
+    /// Total budget of the scripts, `None` if some budget is unknown. The upper end of the fee
+    /// range then assumes the per transaction limit.
+    pub script_budget: Option<ExUnits>,
+}
+
+impl TxBuilder {
+    /// Estimates the fee of the transaction as staged so far, without coin selection.
+    ///
+    /// Instead of selecting inputs, the largest UTxOs of the change address stand in for them:
+    /// the low end of the range assumes one more input (none if inputs were added manually),
+    /// the high end two, along with a change output and collateral if needed.
+    ///
+    /// Script budgets are taken from explicit ex units, then from the `ExUnitsCache`. When some
+    /// are missing, the transaction is evaluated through `ogmios` if one is given. Otherwise
+    /// the quote has no `script_budget`, and the high end of the range assumes the per
+    /// transaction limit.
+    pub async fn quote(
+        &self,
+        indexer: &impl UtxoSource,
+        ogmios: Option<&OgmiosHttpClient>,
+        pparams: &ProtocolParams,
+    ) -> Result<FeeQuote> {
+        let mut body = self.body.clone();
+        for script_kind in self.used_script_kinds().iter() {
+            if let Some(language_view) = language_view_for_script_kind(*script_kind, pparams) {
+                body = body.language_view(*script_kind, language_view.1);
+            }
+        }
+        body = apply_deposits(body, pparams);
+
+        let address_utxos = self.spendable_utxos(indexer).await?;
+        self.ensure_fundable(&address_utxos)?;
+        let mut candidates = address_utxos
+            .into_iter()
+            .filter(|utxo| {
+                !body
+                    .inputs
+                    .iter()
+                    .chain(body.reference_inputs.iter())
+                    .any(|input| input == utxo)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|utxo| Reverse(utxo.lovelace));
+        candidates.truncate(2);
+
+        let mut known_utxos = self.known_utxos.clone();
+        for utxo in resolve_utxos(indexer, &self.known_utxos, &body.inputs).await? {
+            known_utxos.insert(Input::from(&utxo), utxo);
+        }
+        for utxo in &candidates {
+            known_utxos.insert(Input::from(utxo), utxo.clone());
+        }
+
+        let fewest_inputs = if body.inputs.is_empty() { 1 } else { 0 };
+        let lower_body =
+            self.assume_selection(&body, &candidates[..fewest_inputs.min(candidates.len())]);
+        let upper_body = self.assume_selection(&body, &candidates);
+
+        let mut budgets = HashMap::new();
+        let mut missing = vec![];
+        for (purpose, (redeemer, ex_units)) in body.redeemers.iter().flat_map(|rdmrs| rdmrs.iter())
+        {
+            let cached = || {
+                let script_hash = redeemer_script_hash(purpose, &body, &known_utxos)?;
+                self.ex_units_cache.as_ref()?.get(script_hash, redeemer)
+            };
+            match ex_units.clone().or_else(cached) {
+                Some(ex_units) => {
+                    budgets.insert(purpose.clone(), ex_units);
+                }
+                None => missing.push(purpose.clone()),
+            }
+        }
+
+        if !missing.is_empty()
+            && let Some(ogmios) = ogmios
+        {
+            let mut zero_budgets = budgets.clone();
+            for purpose in &missing {
+                zero_budgets.insert(purpose.clone(), ExUnits { mem: 0, steps: 0 });
+            }
+            let tx = lower_body
+                .clone()
+                .build_with_budgets(Some(zero_budgets))
+                .context("Failed to build transaction for evaluation")?;
+            let evaluation = ogmios.evaluate(&tx.bytes).await.with_context(|| {
+                format!(
+                    "Failed to evaluate transaction, redeemers:\n{}",
+                    lower_body.redeemer_legend()
+                )
+            })?;
+            let evaluated = lower_body.evaluated_ex_units(&evaluation)?;
+            if let Some(cache) = &self.ex_units_cache {
+                cache.record_all(&lower_body, &evaluated, &known_utxos);
+            }
+            missing.retain(|purpose| match evaluated.get(purpose) {
+                Some(ex_units) => {
+                    budgets.insert(purpose.clone(), ex_units.clone());
+                    false
+                }
+                None => true,
+            });
+        }
+
+        let limit = max_tx_ex_units(pparams);
+        let known_total: ExUnits = budgets.values().sum();
+        let (script_budget, upper_total) = if missing.is_empty() {
+            (Some(known_total.clone()), known_total.clone())
+        } else {
+            (None, limit.clone())
+        };
+        let with_missing = |ex_units: ExUnits| {
+            let mut budgets = budgets.clone();
+            for purpose in &missing {
+                budgets.insert(purpose.clone(), ex_units.clone());
+            }
+            budgets
+        };
+
+        let (lower_fee, estimated_size) = self
+            .quote_fee(
+                lower_body,
+                with_missing(ExUnits { mem: 0, steps: 0 }),
+                known_total,
+                indexer,
+                &known_utxos,
+                pparams,
+            )
+            .await?;
+        let (upper_fee, _) = self
+            .quote_fee(
+                upper_body,
+                with_missing(limit),
+                upper_total,
+                indexer,
+                &known_utxos,
+                pparams,
+            )
+            .await?;
+
+        Ok(FeeQuote {
+            estimated_fee_range: (self.pad_fee(lower_fee), self.pad_fee(upper_fee)),
+            estimated_size,
+            requires_collateral: self.requires_collateral(),
+            script_budget,
+        })
+    }
+
+    /// `body` as it could look after `build` selected `inputs`: with a change output holding
+    /// them unless built without one, and collateral taken from the first one if needed.
+    fn assume_selection(
+        &self,
+        body: &StagingTransaction,
+        inputs: &[TxOutput],
+    ) -> StagingTransaction {
+        let mut body = body.clone();
+        let mut change = self
+            .change_target()
+            .map(|address| Output::new(address.clone(), 0));
+        for utxo in inputs {
+            body = body.input(Input::from(utxo));
+            if let Some(change) = &mut change {
+                change.lovelace += utxo.lovelace;
+                change.assets =
+                    Some(change.assets.take().unwrap_or_default() + utxo.assets.clone());
+            }
+        }
+
+        if self.requires_collateral()
+            && body.collateral_inputs.is_empty()
+            && let Some(collateral) = inputs.first()
+        {
+            body = body.collateral_input(Input::from(collateral));
+            if let Some(address) = &self.collateral_address {
+                let mut collateral_return = Output::new(address.clone(), collateral.lovelace);
+                collateral_return.assets = Some(collateral.assets.clone());
+                body = body.collateral_output(collateral_return);
+            }
+        }
+
+        if let Some(mut change) = change {
+            change.datum = self.change_datum.clone();
+            body = body.output(change);
+        }
+        // A fee in the usual range takes as many bytes as the final one
+        body.fee(u32::MAX as u64)
+    }
+
+    /// Fee of `body` with `budgets` set in its redeemers, charging scripts for `total`.
+    async fn quote_fee(
+        &self,
+        body: StagingTransaction,
+        budgets: HashMap<RedeemerPurpose, ExUnits>,
+        total: ExUnits,
+        indexer: &impl UtxoSource,
+        known_utxos: &HashMap<Input, TxOutput>,
+        pparams: &ProtocolParams,
+    ) -> Result<(u64, usize)> {
+        let witness_count = required_signers(&body, indexer, known_utxos)
+            .await?
+            .len()
+            .max(1);
+        let ref_script_size = reference_script_size(&body, indexer, known_utxos).await?;
+        let tx = body
+            .build_with_budgets(Some(budgets))
+            .context("Failed to build transaction for fee estimation")?;
+        let tx = with_dummy_witnesses(tx, witness_count)?;
+        let fee = fee_for(
+            tx.bytes.len() as u64,
+            BigRational::from_integer(total.steps.into()),
+            BigRational::from_integer(total.mem.into()),
+            ref_script_size,
+            pparams,
+        )?;
+        Ok((fee, tx.bytes.len()))
+    }
+}
+
+/// Hash of the script run by the redeemer for `purpose`, if it can be told from `tx`. Spent
+/// inputs must be in `known_utxos`.
+pub(super) fn redeemer_script_hash(
+    purpose: &RedeemerPurpose,
+    tx: &StagingTransaction,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> Option<Hash<28>> {
+    match purpose {
+        RedeemerPurpose::Spend(input) => {
+            let utxo = known_utxos.get(input)?;
+            match Address::from_bytes(&utxo.address).ok()? {
+                Address::Shelley(address) => match address.payment() {
+                    ShelleyPaymentPart::Script(hash) => Some(Hash::from(*hash)),
+                    ShelleyPaymentPart::Key(_) => None,
+                },
+                _ => None,
+            }
+        }
+        RedeemerPurpose::Mint(policy) => Some(*policy),
+        RedeemerPurpose::Cert(script_hash) => Some(*script_hash),
+        RedeemerPurpose::Reward(account) => {
+            let bytes: [u8; 28] = account.as_ref().get(1..29)?.try_into().ok()?;
+            Some(Hash(bytes))
+        }
+        RedeemerPurpose::Propose(index) => tx.proposals.get(*index)?.gov_action.guardrails_script(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExUnitsCache;
+    use crate::primitives::{ExUnits, Hash};
+
+    #[test]
+    fn cache_keeps_the_largest_budget_and_is_shared_by_clones() {
+        let cache = ExUnitsCache::new();
+        let shared = cache.clone();
+        let script = Hash([1u8; 28]);
+
+        shared.record(script, vec![0x80], ExUnits { mem: 10, steps: 50 });
+        shared.record(script, vec![0x80], ExUnits { mem: 20, steps: 40 });
+        shared.record(script, vec![0x01], ExUnits { mem: 1, steps: 1 });
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.get(script, &[0x80]),
+            Some(ExUnits { mem: 20, steps: 50 })
+        );
+        assert_eq!(cache.get(Hash([2u8; 28]), &[0x80]), None);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Result, ensure};
+use hydrant::UtxoIndexer;
+use hydrant::primitives::{TxOutput, TxOutputPointer};
+use tokio::sync::Mutex;
+
+use super::TxBuilder;
+use crate::primitives::Input;
+
+/// Where `build` looks up UTxOs: the inputs it spends, and those at the addresses it selects
+/// from. Implemented for the hydrant indexer.
+pub trait UtxoSource: Sync {
+    /// The UTxOs `pointers` point to, leaving out those that don't exist (anymore).
+    fn utxos(
+        &self,
+        pointers: &[TxOutputPointer],
+    ) -> impl Future<Output = Result<Vec<TxOutput>>> + Send;
+
+    /// The UTxOs at the serialized `address`.
+    fn address_utxos(&self, address: &[u8]) -> impl Future<Output = Result<Vec<TxOutput>>> + Send;
+}
+
+impl UtxoSource for Arc<Mutex<UtxoIndexer>> {
+    async fn utxos(&self, pointers: &[TxOutputPointer]) -> Result<Vec<TxOutput>> {
+        Ok(self.lock().await.utxos(pointers)?)
+    }
+
+    async fn address_utxos(&self, address: &[u8]) -> Result<Vec<TxOutput>> {
+        Ok(self.lock().await.address_utxos(&address.to_vec())?)
+    }
+}
+
+impl TxBuilder {
+    /// Resolves the given inputs to the outputs they point to, preferring the outputs supplied
+    /// through `add_input_resolved` and only querying the indexer for the remaining ones.
+    pub(crate) async fn resolve_utxos(
+        &self,
+        indexer: &impl UtxoSource,
+        inputs: &[Input],
+    ) -> Result<Vec<TxOutput>> {
+        resolve_utxos(indexer, &self.known_utxos, inputs).await
+    }
+
+    /// Resolves, once, the UTxOs `build` keeps looking up: the inputs, reference inputs and
+    /// collateral inputs staged so far, and `address_utxos` that coin selection picks from. They
+    /// are kept along with the ones supplied through `add_input_resolved`, so that the balancing
+    /// loop works from memory instead of locking the indexer on every iteration.
+    pub(crate) async fn cache_utxos(
+        &mut self,
+        indexer: &impl UtxoSource,
+        address_utxos: &[TxOutput],
+    ) -> Result<()> {
+        let staged_inputs = self
+            .body
+            .inputs
+            .iter()
+            .chain(self.body.reference_inputs.iter())
+            .chain(self.body.collateral_inputs.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let staged_utxos = self.resolve_utxos(indexer, &staged_inputs).await?;
+        merge_utxos(
+            &mut self.known_utxos,
+            staged_utxos
+                .into_iter()
+                .chain(address_utxos.iter().cloned()),
+        )
+    }
+}
+
+/// Resolves `inputs` using `known_utxos` first, falling back to the indexer.
+///
+/// When every input is already known, the indexer isn't touched at all. Otherwise, the indexer is
+/// queried once for all inputs, and any known UTxO the indexer disagrees with is reported as an
+/// error showing both versions.
+pub(crate) async fn resolve_utxos(
+    indexer: &impl UtxoSource,
+    known_utxos: &HashMap<Input, TxOutput>,
+    inputs: &[Input],
+) -> Result<Vec<TxOutput>> {
+    if let Some(resolved) = resolve_known_utxos(known_utxos, inputs) {
+        return Ok(resolved);
+    }
+
+    let pointers = inputs.iter().map(TxOutputPointer::from).collect::<Vec<_>>();
+    let indexed_utxos = indexer.utxos(&pointers).await?;
+
+    let mut resolved = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let indexed = indexed_utxos.iter().find(|utxo| input == *utxo);
+        match (known_utxos.get(input), indexed) {
+            (Some(known), Some(indexed)) => {
+                ensure_agrees(input, known, indexed)?;
+                resolved.push(known.clone());
+            }
+            (Some(utxo), None) | (None, Some(utxo)) => resolved.push(utxo.clone()),
+            (None, None) => {}
+        }
+    }
+    Ok(resolved)
+}
+
+/// Adds `indexed_utxos` to `known_utxos`, keeping the UTxOs already known as long as the indexer
+/// agrees with them.
+fn merge_utxos(
+    known_utxos: &mut HashMap<Input, TxOutput>,
+    indexed_utxos: impl IntoIterator<Item = TxOutput>,
+) -> Result<()> {
+    for indexed in indexed_utxos {
+        let input = Input::from(&indexed);
+        match known_utxos.get(&input) {
+            Some(known) => ensure_agrees(&input, known, &indexed)?,
+            None => {
+                known_utxos.insert(input, indexed);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ensure_agrees(input: &Input, known: &TxOutput, indexed: &TxOutput) -> Result<()> {
+    ensure!(
+        known.address == indexed.address
+            && known.lovelace == indexed.lovelace
+            && known.assets == indexed.assets
+            && known.datum_hash == indexed.datum_hash
+            && known.script == indexed.script,
+        "pre-resolved input {}#{} disagrees with the indexer\n  supplied: {}\n  indexer:  {}",
+        input.hash,
+        input.index,
+        describe(known),
+        describe(indexed),
+    );
+    Ok(())
+}
+
+/// Every field of `utxo`, so that whichever differs shows up in the error.
+fn describe(utxo: &TxOutput) -> String {
+    format!(
+        "address {}, {} lovelace, assets {:?}, datum hash {:?}, script {:?}",
+        hex::encode(&utxo.address),
+        utxo.lovelace,
+        utxo.assets,
+        utxo.datum_hash,
+        utxo.script,
+    )
+}
+
+/// Returns the resolved inputs if all of them are known, without touching the indexer.
+fn resolve_known_utxos(
+    known_utxos: &HashMap<Input, TxOutput>,
+    inputs: &[Input],
+) -> Option<Vec<TxOutput>> {
+    inputs
+        .iter()
+        .map(|input| known_utxos.get(input).cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::*;
+    use crate::primitives::{Hash, Output};
+
+    fn dummy_output(lovelace: u64) -> Output {
+        let address = Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ));
+        Output::new(address, lovelace)
+    }
+
+    #[test]
+    fn fully_known_inputs_skip_the_indexer() {
+        let input_a = Input::new(Hash([1u8; 32]), 0);
+        let input_b = Input::new(Hash([2u8; 32]), 1);
+        let known_utxos = HashMap::from([
+            (input_a.clone(), dummy_output(1).to_tx_output(&input_a)),
+            (input_b.clone(), dummy_output(2).to_tx_output(&input_b)),
+        ]);
+
+        let resolved = resolve_known_utxos(&known_utxos, &[input_b.clone(), input_a.clone()])
+            .expect("all inputs are known");
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|utxo| utxo.lovelace)
+                .collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert!(input_b == resolved[0]);
+    }
+
+    #[test]
+    fn partially_known_inputs_need_the_indexer() {
+        let input_a = Input::new(Hash([1u8; 32]), 0);
+        let input_b = Input::new(Hash([2u8; 32]), 1);
+        let known_utxos =
+            HashMap::from([(input_a.clone(), dummy_output(1).to_tx_output(&input_a))]);
+
+        assert!(resolve_known_utxos(&known_utxos, &[input_a, input_b]).is_none());
+    }
+
+    #[test]
+    fn merged_utxos_resolve_without_the_indexer() {
+        let input_a = Input::new(Hash([1u8; 32]), 0);
+        let input_b = Input::new(Hash([2u8; 32]), 1);
+        let mut known_utxos =
+            HashMap::from([(input_a.clone(), dummy_output(1).to_tx_output(&input_a))]);
+
+        merge_utxos(
+            &mut known_utxos,
+            [
+                dummy_output(1).to_tx_output(&input_a),
+                dummy_output(2).to_tx_output(&input_b),
+            ],
+        )
+        .expect("the indexer agrees with the known utxo");
+        let resolved = resolve_known_utxos(&known_utxos, &[input_a.clone(), input_b])
+            .expect("all inputs are known");
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|utxo| utxo.lovelace)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let disagreeing = merge_utxos(&mut known_utxos, [dummy_output(3).to_tx_output(&input_a)]);
+        assert!(disagreeing.is_err());
+        assert_eq!(known_utxos[&input_a].lovelace, 1);
+    }
+
+    #[test]
+    fn disagreements_show_both_utxos_in_full() {
+        let input = Input::new(Hash([1u8; 32]), 0);
+        let known = dummy_output(1).to_tx_output(&input);
+        let mut indexed = known.clone();
+        indexed.address = vec![0x70; 29];
+
+        let err = ensure_agrees(&input, &known, &indexed)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&hex::encode(&known.address)), "{err}");
+        assert!(err.contains(&hex::encode(&indexed.address)), "{err}");
+    }
+
+    #[test]
+    fn disagreeing_datums_are_caught() {
+        let input = Input::new(Hash([1u8; 32]), 0);
+        let known = dummy_output(1)
+            .set_datum_hash(Hash([0xaa; 32]))
+            .to_tx_output(&input);
+        let indexed = dummy_output(1)
+            .set_datum_hash(Hash([0xbb; 32]))
+            .to_tx_output(&input);
+
+        let err = ensure_agrees(&input, &known, &indexed)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&format!("{:?}", known.datum_hash)), "{err}");
+        assert!(err.contains(&format!("{:?}", indexed.datum_hash)), "{err}");
+        assert!(ensure_agrees(&input, &known, &known).is_ok());
+    }
+}
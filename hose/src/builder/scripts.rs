@@ -0,0 +1,266 @@
+use std::collections::{HashMap, HashSet};
+
+use hydrant::primitives::TxOutput;
+
+use super::quote::redeemer_script_hash;
+use super::tx::{StagingTransaction, TxBuilderError};
+use crate::primitives::{Hash, Input, RedeemerPurpose};
+
+/// Drops the attached scripts that a reference or spent input already carries, unless `keep` is
+/// set, then checks that every redeemer's script is available one way or the other.
+///
+/// Inputs missing from `known_utxos` are skipped, as nothing can be told about their scripts.
+pub(crate) fn dedupe_scripts(
+    mut body: StagingTransaction,
+    known_utxos: &HashMap<Input, TxOutput>,
+    keep: bool,
+) -> Result<StagingTransaction, TxBuilderError> {
+    let referenced = body
+        .reference_inputs
+        .iter()
+        .chain(body.inputs.iter())
+        .filter_map(|input| known_utxos.get(input)?.script.as_ref())
+        .map(|script| script.hash)
+        .collect::<HashSet<Hash<28>>>();
+
+    if !keep {
+        let duplicates = body
+            .scripts
+            .keys()
+            .filter(|hash| referenced.contains(*hash))
+            .copied()
+            .collect::<Vec<_>>();
+        for hash in duplicates {
+            tracing::info!(
+                "dropping attached script {hash}, an input of the transaction already carries it"
+            );
+            body = body.remove_script_by_hash(hash);
+        }
+    }
+
+    for purpose in body.redeemers.iter().flat_map(|redeemers| redeemers.keys()) {
+        let Some(hash) = redeemer_script_hash(purpose, &body, known_utxos) else {
+            continue;
+        };
+        if !body.scripts.contains_key(&hash) && !referenced.contains(&hash) {
+            return Err(TxBuilderError::MissingScript {
+                hash,
+                needed_for: purpose.clone(),
+            });
+        }
+    }
+
+    Ok(body)
+}
+
+/// Drops the attached scripts of `policies`, whose mints and burns cancelled out so the ledger
+/// would reject them as extraneous, unless a redeemer or a spent input still needs them.
+pub(crate) fn drop_cancelled_policies(
+    mut body: StagingTransaction,
+    policies: &HashSet<Hash<28>>,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> StagingTransaction {
+    // Inputs locked by native scripts need them without having a redeemer
+    let spends = body
+        .inputs
+        .iter()
+        .map(|input| RedeemerPurpose::Spend(input.clone()));
+    let needed = body
+        .redeemers
+        .iter()
+        .flat_map(|redeemers| redeemers.keys().cloned())
+        .chain(spends)
+        .filter_map(|purpose| redeemer_script_hash(&purpose, &body, known_utxos))
+        .collect::<HashSet<_>>();
+
+    for policy in policies {
+        if body.scripts.contains_key(policy) && !needed.contains(policy) {
+            tracing::info!("dropping attached script {policy}, its mints and burns cancel out");
+            body = body.remove_script_by_hash(*policy);
+        }
+    }
+    body
+}
+
+/// Checks that the inputs of each `add_script_inputs` batch are all locked by the same script,
+/// so that a single attached or referenced script covers them.
+///
+/// Inputs missing from `known_utxos` are skipped, as nothing can be told about their scripts.
+pub(crate) fn check_script_batches(
+    batches: &[Vec<Input>],
+    body: &StagingTransaction,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> Result<(), TxBuilderError> {
+    for batch in batches {
+        let locking_scripts = batch
+            .iter()
+            .filter(|input| known_utxos.contains_key(input))
+            .map(|input| {
+                let purpose = RedeemerPurpose::Spend(input.clone());
+                (input, redeemer_script_hash(&purpose, body, known_utxos))
+            })
+            .collect::<Vec<_>>();
+        let Some(expected) = locking_scripts.iter().find_map(|(_, hash)| *hash) else {
+            continue;
+        };
+        if let Some((input, found)) = locking_scripts
+            .into_iter()
+            .find(|(_, hash)| *hash != Some(expected))
+        {
+            return Err(TxBuilderError::MixedScriptBatch {
+                input: input.clone(),
+                expected,
+                found,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::*;
+    use crate::primitives::{Output, ScriptKind};
+
+    const SCRIPT: [u8; 3] = [0x46, 0x01, 0x00];
+
+    fn key_address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    /// Body minting with `SCRIPT` as policy, with the script attached and also carried by a
+    /// reference input
+    fn minting_body() -> (StagingTransaction, HashMap<Input, TxOutput>) {
+        let policy = ScriptKind::PlutusV3.hash(&SCRIPT);
+        let reference = Input::new(Hash([2u8; 32]), 0);
+        let reference_utxo = Output::new(key_address(), 5_000_000)
+            .set_script(ScriptKind::PlutusV3, SCRIPT.to_vec())
+            .to_tx_output(&reference);
+        let body = StagingTransaction::new()
+            .input(Input::new(Hash([1u8; 32]), 0))
+            .reference_input(reference.clone())
+            .script(ScriptKind::PlutusV3, SCRIPT.to_vec())
+            .add_mint_redeemer(policy, vec![0x80], None);
+        (body, HashMap::from([(reference, reference_utxo)]))
+    }
+
+    #[test]
+    fn referenced_scripts_are_not_attached_twice() {
+        let (body, known_utxos) = minting_body();
+
+        let deduped = dedupe_scripts(body, &known_utxos, false).unwrap();
+        assert!(deduped.scripts.is_empty());
+    }
+
+    #[test]
+    fn duplicate_scripts_can_be_kept() {
+        let (body, known_utxos) = minting_body();
+
+        let kept = dedupe_scripts(body.clone(), &known_utxos, true).unwrap();
+        assert_eq!(kept, body);
+    }
+
+    #[test]
+    fn redeemers_without_a_script_fail_early() {
+        let (body, _) = minting_body();
+        let policy = ScriptKind::PlutusV3.hash(&SCRIPT);
+        let body = body.remove_script_by_hash(policy);
+
+        assert_eq!(
+            dedupe_scripts(body, &HashMap::new(), false),
+            Err(TxBuilderError::MissingScript {
+                hash: policy,
+                needed_for: RedeemerPurpose::Mint(policy),
+            })
+        );
+    }
+
+    #[test]
+    fn cancelled_policy_scripts_are_kept_while_spending_with_them() {
+        let policy = ScriptKind::PlutusV3.hash(&SCRIPT);
+        let input = Input::new(Hash([3u8; 32]), 0);
+        let locked = Output::new(
+            Address::Shelley(ShelleyAddress::new(
+                Network::Testnet,
+                ShelleyPaymentPart::Script(policy.into()),
+                ShelleyDelegationPart::Null,
+            )),
+            2_000_000,
+        )
+        .to_tx_output(&input);
+        let body = StagingTransaction::new()
+            .input(input.clone())
+            .script(ScriptKind::PlutusV3, SCRIPT.to_vec());
+        let policies = HashSet::from([policy]);
+
+        let kept =
+            drop_cancelled_policies(body.clone(), &policies, &HashMap::from([(input, locked)]));
+        assert_eq!(kept, body);
+
+        let dropped = drop_cancelled_policies(body, &policies, &HashMap::new());
+        assert!(dropped.scripts.is_empty());
+    }
+
+    #[test]
+    fn script_batches_must_share_their_script() {
+        let script_address = |seed: u8| {
+            Address::Shelley(ShelleyAddress::new(
+                Network::Testnet,
+                ShelleyPaymentPart::Script(Hash([seed; 28]).into()),
+                ShelleyDelegationPart::Null,
+            ))
+        };
+        let utxo = |index: u64, seed: u8| {
+            let input = Input::new(Hash([3u8; 32]), index);
+            let utxo = Output::new(script_address(seed), 2_000_000).to_tx_output(&input);
+            (input, utxo)
+        };
+        let mut known_utxos = HashMap::from([utxo(0, 7), utxo(1, 7), utxo(2, 8)]);
+        let batch = |indices: &[u64]| {
+            indices
+                .iter()
+                .map(|index| Input::new(Hash([3u8; 32]), *index))
+                .collect::<Vec<_>>()
+        };
+        let body = StagingTransaction::new();
+
+        // The input missing from `known_utxos` can't be told apart
+        let same_script = vec![batch(&[0, 1, 9])];
+        assert_eq!(
+            check_script_batches(&same_script, &body, &known_utxos),
+            Ok(())
+        );
+
+        let mixed = vec![batch(&[0, 1]), batch(&[0, 2])];
+        assert_eq!(
+            check_script_batches(&mixed, &body, &known_utxos),
+            Err(TxBuilderError::MixedScriptBatch {
+                input: Input::new(Hash([3u8; 32]), 2),
+                expected: Hash([7u8; 28]),
+                found: Some(Hash([8u8; 28])),
+            })
+        );
+
+        let key_locked = Input::new(Hash([3u8; 32]), 3);
+        known_utxos.insert(
+            key_locked.clone(),
+            Output::new(key_address(), 2_000_000).to_tx_output(&key_locked),
+        );
+        assert_eq!(
+            check_script_batches(&[batch(&[0, 3])], &body, &known_utxos),
+            Err(TxBuilderError::MixedScriptBatch {
+                input: key_locked,
+                expected: Hash([7u8; 28]),
+                found: None,
+            })
+        );
+    }
+}
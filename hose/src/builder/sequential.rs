@@ -0,0 +1,372 @@
+//! Strictly ordered transactions from a single wallet
+//!
+//! Cardano has no nonces, the only way to force an order between two transactions is to have
+//! the second one spend an output of the first. A `SequentialSpender` keeps a chain of such
+//! links: every transaction it builds spends the current tip of the chain, and its change output
+//! becomes the next tip.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use hydrant::UtxoIndexer;
+use hydrant::primitives::TxOutputPointer;
+use ogmios_client::OgmiosHttpClient;
+use ogmios_client::method::pparams::ProtocolParams;
+use pallas::codec::minicbor;
+use pallas::codec::utils::Bytes;
+use pallas::ledger::primitives::NetworkId;
+use tokio::sync::{Mutex, Notify};
+
+use super::{BuiltTx, TxBuilder};
+use crate::primitives::{Datum, DatumOption, Input, TxHash};
+use crate::wallet::Wallet;
+
+const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Issues transactions from `wallet` that land on chain in the order they were requested.
+///
+/// Tip outputs are change outputs carrying the chain id as inline datum, which is how `recover`
+/// finds the head of the chain again after a restart.
+///
+/// A link is only built once the previous one is seen by the indexer, since building needs its
+/// inputs to be resolvable. If a link fails to build, submit or confirm, every later link fails
+/// too, until the chain is recovered.
+pub struct SequentialSpender<'a> {
+    wallet: &'a Wallet,
+    network_id: NetworkId,
+    indexer: &'a Arc<Mutex<UtxoIndexer>>,
+    ogmios: &'a OgmiosHttpClient,
+    pparams: &'a ProtocolParams,
+    /// Inline datum marking the tip outputs
+    marker: Vec<u8>,
+    confirmation_timeout: Duration,
+    /// Order of the `next_tx` calls
+    turns: Turns,
+    state: Mutex<ChainState>,
+}
+
+struct ChainState {
+    tip: Input,
+    /// Last submitted link, until the indexer sees it
+    pending: Option<TxHash>,
+    /// Why the chain stopped accepting links
+    blocked: Option<String>,
+}
+
+impl<'a> SequentialSpender<'a> {
+    /// Starts a chain named `chain_id` from `tip`, an UTxO of `wallet` the first link will spend.
+    pub fn new(
+        wallet: &'a Wallet,
+        network_id: NetworkId,
+        indexer: &'a Arc<Mutex<UtxoIndexer>>,
+        ogmios: &'a OgmiosHttpClient,
+        pparams: &'a ProtocolParams,
+        chain_id: &[u8],
+        tip: Input,
+    ) -> Self {
+        Self {
+            wallet,
+            network_id,
+            indexer,
+            ogmios,
+            pparams,
+            marker: marker(chain_id),
+            confirmation_timeout: DEFAULT_CONFIRMATION_TIMEOUT,
+            turns: Turns::default(),
+            state: Mutex::new(ChainState {
+                tip,
+                pending: None,
+                blocked: None,
+            }),
+        }
+    }
+
+    /// Resumes the chain named `chain_id` from the unspent tip output of `wallet`.
+    ///
+    /// Should be called once the last submitted link is either confirmed or known to be lost,
+    /// as a link still in the mempool would spend the tip found here.
+    pub async fn recover(
+        wallet: &'a Wallet,
+        network_id: NetworkId,
+        indexer: &'a Arc<Mutex<UtxoIndexer>>,
+        ogmios: &'a OgmiosHttpClient,
+        pparams: &'a ProtocolParams,
+        chain_id: &[u8],
+    ) -> Result<Self> {
+        let marker_hash = Datum::new(marker(chain_id)).hash;
+        let mut tips = {
+            let indexer = indexer.lock().await;
+            indexer.address_utxos(&wallet.address().to_vec())?
+        }
+        .into_iter()
+        .filter(|utxo| utxo.datum_hash == Some(marker_hash))
+        .map(Input::from)
+        .collect::<Vec<_>>();
+
+        let tip = match tips.len() {
+            1 => tips.remove(0),
+            0 => bail!(
+                "no unspent tip output found for chain {}",
+                hex::encode(chain_id)
+            ),
+            count => bail!(
+                "found {count} unspent tip outputs for chain {}, expected one",
+                hex::encode(chain_id)
+            ),
+        };
+        Ok(Self::new(
+            wallet, network_id, indexer, ogmios, pparams, chain_id, tip,
+        ))
+    }
+
+    /// How long a link may take to show up in the indexer before the chain is blocked. Defaults
+    /// to two minutes.
+    pub fn confirmation_timeout(mut self, timeout: Duration) -> Self {
+        self.confirmation_timeout = timeout;
+        self
+    }
+
+    /// Current tip of the chain, which the next link will spend.
+    pub async fn tip(&self) -> Input {
+        self.state.lock().await.tip.clone()
+    }
+
+    /// Last submitted link, if the indexer hasn't seen it yet.
+    pub async fn pending(&self) -> Result<Option<TxHash>> {
+        let mut state = self.state.lock().await;
+        if state.pending.is_some() && self.is_indexed(&state.tip).await? {
+            state.pending = None;
+        }
+        Ok(state.pending)
+    }
+
+    /// Builds, signs and submits the next link of the chain.
+    ///
+    /// `configure` receives a builder that already spends the current tip and adds the payload
+    /// of the link. Links are issued in the order `next_tx` is called, regardless of the order
+    /// the returned futures are polled in.
+    ///
+    /// Dropping a returned future before its turn skips its link. Dropping it while its link is
+    /// being issued blocks the chain, as the link may have been submitted without the tip being
+    /// updated.
+    pub fn next_tx(
+        &self,
+        configure: impl FnOnce(TxBuilder) -> TxBuilder,
+    ) -> impl Future<Output = Result<BuiltTx>> {
+        let ticket = self.turns.take();
+        async move {
+            ticket.wait().await;
+            let number = ticket.number;
+            let mut state = self.state.lock().await;
+            if let Some(reason) = &state.blocked {
+                return Err(anyhow!("chain is blocked: {reason}"));
+            }
+
+            // Only cleared once the link is issued, so that it sticks if the future is dropped
+            // halfway through
+            state.blocked = Some(format!("link #{number} was cancelled while being issued"));
+            let result = self.extend(&mut state, configure).await;
+            state.blocked = result
+                .as_ref()
+                .err()
+                .map(|err| format!("link #{number} failed: {err:#}"));
+            result
+        }
+    }
+
+    async fn extend(
+        &self,
+        state: &mut ChainState,
+        configure: impl FnOnce(TxBuilder) -> TxBuilder,
+    ) -> Result<BuiltTx> {
+        self.wait_until_indexed(&state.tip).await?;
+        state.pending = None;
+
+        let tx = configure(
+            TxBuilder::new(self.network_id, self.wallet.address()).add_input(state.tip.clone()),
+        )
+        .change_datum(DatumOption::Inline(Datum::new(self.marker.clone())))
+        .build(self.indexer, self.ogmios, self.pparams)
+        .await?
+        .sign(self.wallet)?;
+
+        let hash = tx.hash()?;
+        let marker = Some(DatumOption::Inline(Datum::new(self.marker.clone())));
+        let tip_index = tx
+            .body()
+            .outputs()
+            .iter()
+            .rposition(|output| output.address == self.wallet.address() && output.datum == marker)
+            .context("link has no tip output")?;
+
+        self.ogmios
+            .submit(&tx.cbor())
+            .await
+            .map_err(|err| anyhow!("failed to submit link {hash}: {err:?}"))?;
+
+        state.tip = Input::new(hash, tip_index as u64);
+        state.pending = Some(hash);
+        Ok(tx)
+    }
+
+    async fn is_indexed(&self, input: &Input) -> Result<bool> {
+        let indexer = self.indexer.lock().await;
+        Ok(indexer.utxo(TxOutputPointer::from(input))?.is_some())
+    }
+
+    async fn wait_until_indexed(&self, input: &Input) -> Result<()> {
+        let started = tokio::time::Instant::now();
+        while !self.is_indexed(input).await? {
+            if started.elapsed() > self.confirmation_timeout {
+                bail!(
+                    "tip {}#{} was not seen by the indexer within {:?}",
+                    input.hash,
+                    input.index,
+                    self.confirmation_timeout
+                );
+            }
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+}
+
+/// Serves tickets one at a time, in the order they were taken.
+#[derive(Default)]
+struct Turns {
+    next_ticket: AtomicU64,
+    serving: std::sync::Mutex<Serving>,
+    /// Signalled whenever a ticket has been served
+    turn: Notify,
+}
+
+#[derive(Default)]
+struct Serving {
+    current: u64,
+    /// Tickets dropped before their turn came, to be skipped
+    dropped: BTreeSet<u64>,
+}
+
+/// A place in `Turns`, given up when dropped, whether or not it has been served.
+struct Ticket<'a> {
+    turns: &'a Turns,
+    number: u64,
+}
+
+impl Turns {
+    fn take(&self) -> Ticket<'_> {
+        Ticket {
+            turns: self,
+            number: self.next_ticket.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+}
+
+impl Ticket<'_> {
+    /// Waits until every ticket taken before this one has been dropped.
+    async fn wait(&self) {
+        loop {
+            // Created before checking, so a ticket served in between still wakes us up
+            let turn = self.turns.turn.notified();
+            if self.turns.serving.lock().expect("turns poisoned").current == self.number {
+                return;
+            }
+            turn.await;
+        }
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut serving = self.turns.serving.lock().expect("turns poisoned");
+        if serving.current != self.number {
+            serving.dropped.insert(self.number);
+            return;
+        }
+        serving.current += 1;
+        while serving.dropped.remove(&serving.current) {
+            serving.current += 1;
+        }
+        drop(serving);
+        self.turns.turn.notify_waiters();
+    }
+}
+
+/// Inline datum of the tip outputs: the chain id as a CBOR byte string.
+fn marker(chain_id: &[u8]) -> Vec<u8> {
+    minicbor::to_vec(Bytes::from(chain_id.to_vec())).expect("encoding to a vec is infallible")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Turns;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn a_ticket_dropped_before_its_turn_is_skipped() {
+        let turns = Turns::default();
+        let first = turns.take();
+        let second = turns.take();
+        let third = turns.take();
+
+        // Never polled, like a future that was dropped right away
+        drop(second);
+        drop(first);
+
+        tokio::time::timeout(TIMEOUT, third.wait())
+            .await
+            .expect("third ticket is served");
+    }
+
+    #[tokio::test]
+    async fn a_ticket_dropped_while_waiting_is_skipped() {
+        let turns = Turns::default();
+        let first = turns.take();
+        let second = turns.take();
+        let third = turns.take();
+
+        // Cancelled by a timeout while waiting for its turn
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), second.wait())
+                .await
+                .is_err()
+        );
+        drop(second);
+
+        // Dropped while the third one is waiting, which must then be woken up
+        let release = async move {
+            tokio::task::yield_now().await;
+            drop(first);
+        };
+        let (served, ()) = tokio::join!(tokio::time::timeout(TIMEOUT, third.wait()), release);
+        served.expect("third ticket is served");
+    }
+
+    #[tokio::test]
+    async fn tickets_are_served_in_the_order_they_were_taken() {
+        let turns = Turns::default();
+        let first = turns.take();
+        let second = turns.take();
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), second.wait())
+                .await
+                .is_err()
+        );
+        tokio::time::timeout(TIMEOUT, first.wait())
+            .await
+            .expect("first ticket is served");
+        drop(first);
+        tokio::time::timeout(TIMEOUT, second.wait())
+            .await
+            .expect("second ticket is served");
+    }
+}
@@ -0,0 +1,519 @@
+//! Collecting signatures out of band
+//!
+//! Transactions spending from a multisig script are built once, then circulated among the key
+//! holders. A `SigningRequest` carries what a signer needs to review and sign the transaction,
+//! without the indexer or Ogmios: the body, its hash, a summary and who is expected to sign.
+//! Signers answer with a `SignatureBundle` (see `Wallet::sign_request`), which the holder of the
+//! `BuiltTx` merges with `BuiltTx::apply_signature_bundle` until `BuiltTx::is_fully_signed`.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, bail, ensure};
+use hydrant::primitives::TxOutput;
+use pallas::crypto::hash::Hasher;
+use pallas::crypto::key::ed25519;
+use pallas::ledger::addresses::{Address, ShelleyPaymentPart};
+use pallas::ledger::primitives::Fragment;
+use pallas::ledger::primitives::conway::{NativeScript, Tx};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::BuiltTx;
+use super::assertions::summary;
+use super::tx::StagingTransaction;
+use crate::primitives::{
+    Hash, Input, PubKeyHash, PublicKey, ScriptKind, Signature, timelock_holds,
+};
+use crate::wallet::PrivateKey;
+
+/// Everything needed to sign a transaction, serializable with the `serde` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SigningRequest {
+    /// Hex encoded CBOR of the transaction body
+    pub body: String,
+    /// Hex encoded hash of the body, i.e. the transaction id, which is what gets signed
+    pub body_hash: String,
+    /// 0 for testnets, 1 for mainnet
+    pub network_id: u8,
+    /// Human readable overview of the fee and outputs
+    pub summary: String,
+    pub signers: Vec<RequestedSigner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RequestedSigner {
+    /// Hex encoded key hash
+    pub key_hash: String,
+    /// Whether the key must sign, rather than counting towards a native script threshold
+    pub mandatory: bool,
+    /// Whether the transaction already carries a signature by the key
+    pub signed: bool,
+}
+
+/// A signature over a transaction id, as returned by a signer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SignatureBundle {
+    /// Hex encoded
+    pub key_hash: String,
+    /// Hex encoded
+    pub pub_key: String,
+    /// Hex encoded
+    pub signature: String,
+}
+
+impl SigningRequest {
+    /// Checks that `body_hash` is the hash of `body` and returns it. Signers must sign this
+    /// rather than trusting `body_hash`, or they may sign a transaction other than the one they
+    /// reviewed.
+    pub fn verified_hash(&self) -> Result<[u8; 32]> {
+        let body = hex::decode(&self.body).context("body isn't valid hex")?;
+        let mut hasher = Hasher::<256>::new();
+        hasher.input(&body);
+        let hash = *hasher.finalize();
+        ensure!(
+            hex::encode(hash) == self.body_hash.to_lowercase(),
+            "body hash {} doesn't match the body, which hashes to {}",
+            self.body_hash,
+            hex::encode(hash)
+        );
+        Ok(hash)
+    }
+}
+
+impl BuiltTx {
+    /// Describes the transaction for signers who don't have the builder at hand.
+    ///
+    /// Signers are the keys that must sign (input owners, key certificates and withdrawals,
+    /// disclosed signers) and the keys of the native scripts in the witness set.
+    pub fn to_signing_request(&self) -> Result<SigningRequest> {
+        let tx = Tx::decode_fragment(&self.tx.bytes)
+            .map_err(|err| anyhow::anyhow!("failed to decode transaction: {err}"))?;
+        let signed = self.signed_key_hashes();
+
+        let mut signers = self
+            .required_signers
+            .iter()
+            .map(|key_hash| RequestedSigner {
+                key_hash: hex::encode(key_hash.0),
+                mandatory: true,
+                signed: signed.contains(key_hash),
+            })
+            .collect::<Vec<_>>();
+        for key_hash in self.native_script_keys()? {
+            if !self.required_signers.contains(&key_hash) {
+                signers.push(RequestedSigner {
+                    key_hash: hex::encode(key_hash.0),
+                    mandatory: false,
+                    signed: signed.contains(&key_hash),
+                });
+            }
+        }
+        signers.sort_unstable_by(|a, b| a.key_hash.cmp(&b.key_hash));
+        signers.dedup();
+
+        Ok(SigningRequest {
+            body: hex::encode(tx.transaction_body.raw_cbor()),
+            body_hash: hex::encode(self.tx.hash.0),
+            network_id: self.staging.network_id().unwrap_or_default(),
+            summary: summary(&self.staging.0, None),
+            signers,
+        })
+    }
+
+    /// Adds the signature of `bundle`, after checking it's a valid signature of this transaction
+    /// by one of its signers (see `to_signing_request`).
+    pub fn apply_signature_bundle(mut self, bundle: &SignatureBundle) -> Result<Self> {
+        let key_hash: [u8; 28] = hex::decode(&bundle.key_hash)
+            .context("key hash isn't valid hex")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("key hash isn't 28 bytes long"))?;
+        let pub_key: [u8; 32] = hex::decode(&bundle.pub_key)
+            .context("public key isn't valid hex")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("public key isn't 32 bytes long"))?;
+        let signature: [u8; 64] = hex::decode(&bundle.signature)
+            .context("signature isn't valid hex")?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("signature isn't 64 bytes long"))?;
+
+        let key_hash = Hash(key_hash);
+        ensure!(
+            key_hash_of(&pub_key) == key_hash,
+            "public key {} doesn't hash to {}",
+            bundle.pub_key,
+            bundle.key_hash
+        );
+        ensure!(
+            self.required_signers.contains(&key_hash)
+                || self.native_script_keys()?.contains(&key_hash),
+            "{} isn't a signer of this transaction",
+            bundle.key_hash
+        );
+        let pub_key = ed25519::PublicKey::from(pub_key);
+        if !pub_key.verify(self.tx.hash.0, &ed25519::Signature::from(signature)) {
+            bail!("invalid signature by {}", bundle.key_hash);
+        }
+
+        self.tx = self.tx.add_signature(pub_key, signature)?;
+        Ok(self)
+    }
+
+    /// Whether every mandatory signer has signed and every native script in the witness set is
+    /// satisfied by the signatures so far and the validity interval. Native scripts provided by
+    /// reference inputs aren't checked.
+    pub fn is_fully_signed(&self) -> Result<bool> {
+        let signed = self.signed_key_hashes();
+        if !self.required_signers.is_subset(&signed) {
+            return Ok(false);
+        }
+        Ok(self.native_scripts()?.iter().all(|script| {
+            is_satisfied(
+                script,
+                &signed,
+                self.staging.valid_from_slot(),
+                self.staging.invalid_from_slot(),
+            )
+        }))
+    }
+
+    /// Keys that must sign but haven't yet, e.g. the owner of a collateral wallet. Keys only
+    /// required by native scripts aren't included, see `is_fully_signed`.
+    pub fn missing_signatures(&self) -> HashSet<PubKeyHash> {
+        self.required_signers
+            .difference(&self.signed_key_hashes())
+            .copied()
+            .collect()
+    }
+
+    /// Keys that must sign, known once inputs are resolved. Keys only required by native scripts
+    /// aren't included, see `is_fully_signed`.
+    pub fn required_signers(&self) -> &HashSet<PubKeyHash> {
+        &self.required_signers
+    }
+
+    /// Key hash controlling each spent input, collateral included, for inputs locked by a key
+    /// rather than a script.
+    pub fn input_owners(&self) -> &HashMap<Input, PubKeyHash> {
+        &self.input_owners
+    }
+
+    /// Signs with each of `keys`, see `MultiWallet::sign_all`.
+    pub(crate) fn add_signatures<'a>(
+        mut self,
+        keys: impl IntoIterator<Item = &'a PrivateKey>,
+    ) -> Result<Self> {
+        for key in keys {
+            let signature = key.sign(self.tx.hash.0);
+            let signature = signature.as_ref().try_into().unwrap();
+            self.tx = self.tx.add_signature(key.public_key(), signature)?;
+        }
+        Ok(self)
+    }
+
+    /// Vkey witnesses attached so far, ordered by public key.
+    pub fn vkey_witnesses(&self) -> Vec<(PublicKey, Signature)> {
+        let mut witnesses = self
+            .tx
+            .signatures
+            .iter()
+            .flatten()
+            .map(|(pub_key, signature)| (*pub_key, *signature))
+            .collect::<Vec<_>>();
+        witnesses.sort_unstable_by_key(|(pub_key, _)| pub_key.0);
+        witnesses
+    }
+
+    /// Number of vkey witnesses attached so far.
+    pub fn witness_count(&self) -> usize {
+        self.tx
+            .signatures
+            .as_ref()
+            .map_or(0, |signatures| signatures.len())
+    }
+
+    fn signed_key_hashes(&self) -> HashSet<PubKeyHash> {
+        self.tx
+            .signatures
+            .iter()
+            .flatten()
+            .map(|(pub_key, _)| key_hash_of(&pub_key.0))
+            .collect()
+    }
+
+    fn native_scripts(&self) -> Result<Vec<NativeScript>> {
+        self.staging
+            .scripts()
+            .values()
+            .filter(|script| script.kind == ScriptKind::Native)
+            .map(|script| {
+                NativeScript::decode_fragment(&script.bytes)
+                    .map_err(|err| anyhow::anyhow!("invalid native script {}: {err}", script.hash))
+            })
+            .collect()
+    }
+
+    fn native_script_keys(&self) -> Result<HashSet<PubKeyHash>> {
+        let mut keys = HashSet::new();
+        for script in self.native_scripts()? {
+            collect_keys(&script, &mut keys);
+        }
+        Ok(keys)
+    }
+}
+
+/// Payment key hashes of the inputs and collateral inputs of `tx` that `known_utxos` resolves to
+/// key-locked outputs.
+pub(super) fn input_owners(
+    tx: &StagingTransaction,
+    known_utxos: &HashMap<Input, TxOutput>,
+) -> HashMap<Input, PubKeyHash> {
+    tx.inputs
+        .iter()
+        .chain(tx.collateral_inputs.iter())
+        .filter_map(|input| {
+            let utxo = known_utxos.get(input)?;
+            match Address::from_bytes(&utxo.address).ok()? {
+                Address::Shelley(address) => match address.payment() {
+                    ShelleyPaymentPart::Key(hash) => Some((input.clone(), Hash::from(*hash))),
+                    ShelleyPaymentPart::Script(_) => None,
+                },
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn key_hash_of(pub_key: &[u8]) -> PubKeyHash {
+    let mut hasher = Hasher::<224>::new();
+    hasher.input(pub_key);
+    Hash::from(hasher.finalize())
+}
+
+fn collect_keys(script: &NativeScript, keys: &mut HashSet<PubKeyHash>) {
+    match script {
+        NativeScript::ScriptPubkey(key_hash) => {
+            keys.insert(Hash::from(*key_hash));
+        }
+        NativeScript::ScriptAll(scripts)
+        | NativeScript::ScriptAny(scripts)
+        | NativeScript::ScriptNOfK(_, scripts) => {
+            for script in scripts {
+                collect_keys(script, keys);
+            }
+        }
+        NativeScript::InvalidBefore(_) | NativeScript::InvalidHereafter(_) => {}
+    }
+}
+
+/// Evaluates `script` like the ledger does, given the keys that signed and the validity interval.
+fn is_satisfied(
+    script: &NativeScript,
+    signed: &HashSet<PubKeyHash>,
+    valid_from: Option<u64>,
+    invalid_from: Option<u64>,
+) -> bool {
+    let satisfied = |script: &NativeScript| is_satisfied(script, signed, valid_from, invalid_from);
+    match script {
+        NativeScript::ScriptPubkey(key_hash) => signed.contains(&Hash::from(*key_hash)),
+        NativeScript::ScriptAll(scripts) => scripts.iter().all(satisfied),
+        NativeScript::ScriptAny(scripts) => scripts.iter().any(satisfied),
+        NativeScript::ScriptNOfK(required, scripts) => {
+            scripts.iter().filter(|script| satisfied(script)).count() >= *required as usize
+        }
+        NativeScript::InvalidBefore(_) | NativeScript::InvalidHereafter(_) => {
+            timelock_holds(script, valid_from, invalid_from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::Fragment;
+    use pallas::ledger::primitives::conway::NativeScript;
+
+    use super::key_hash_of;
+    use crate::builder::BuiltTx;
+    use crate::builder::tx::StagingTransaction;
+    use crate::primitives::{Hash, Input, Output, ScriptKind};
+    use crate::wallet::{MultiWallet, PrivateKey, Wallet, WalletBuilder};
+
+    fn wallet(seed: u8) -> Wallet {
+        WalletBuilder::new(Network::Testnet)
+            .from_hex(hex::encode([seed; 32]))
+            .unwrap()
+    }
+
+    /// Spends from a 2-of-3 multisig of the wallets with seeds 1, 2 and 3.
+    fn multisig_staging() -> StagingTransaction {
+        let script = NativeScript::ScriptNOfK(
+            2,
+            (1..=3)
+                .map(|seed| {
+                    NativeScript::ScriptPubkey(PrivateKey::from_bytes([seed; 32]).unwrap().hash())
+                })
+                .collect(),
+        );
+        StagingTransaction::new()
+            .network_id(0)
+            .fee(200_000)
+            .input(Input::new(Hash([9u8; 32]), 0))
+            .output(Output::new(
+                Address::Shelley(ShelleyAddress::new(
+                    Network::Testnet,
+                    ShelleyPaymentPart::Key(Hash([4u8; 28]).into()),
+                    ShelleyDelegationPart::Null,
+                )),
+                5_000_000,
+            ))
+            .script(ScriptKind::Native, script.encode_fragment().unwrap())
+    }
+
+    fn multisig_tx() -> BuiltTx {
+        let staging = multisig_staging();
+        let tx = staging.clone().build_conway(None).expect("build conway");
+        BuiltTx::new(staging, tx)
+    }
+
+    #[test]
+    fn ready_once_the_threshold_is_met() {
+        let tx = multisig_tx();
+        let request = tx.to_signing_request().expect("signing request");
+        assert_eq!(request.signers.len(), 3);
+        assert!(
+            request
+                .signers
+                .iter()
+                .all(|signer| !signer.mandatory && !signer.signed)
+        );
+        assert!(!tx.is_fully_signed().unwrap());
+
+        let tx = tx
+            .apply_signature_bundle(&wallet(1).sign_request(&request).unwrap())
+            .expect("apply first bundle");
+        assert!(!tx.is_fully_signed().unwrap());
+
+        let tx = tx
+            .apply_signature_bundle(&wallet(3).sign_request(&request).unwrap())
+            .expect("apply second bundle");
+        assert!(tx.is_fully_signed().unwrap());
+
+        let request = tx.to_signing_request().unwrap();
+        assert_eq!(
+            request
+                .signers
+                .iter()
+                .filter(|signer| signer.signed)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn missing_signatures_shrink_as_wallets_sign() {
+        let key_hash = |seed: u8| Hash::from(PrivateKey::from_bytes([seed; 32]).unwrap().hash());
+        let tx = multisig_tx();
+        let tx = BuiltTx {
+            required_signers: HashSet::from([key_hash(1), key_hash(2)]),
+            ..tx
+        };
+        assert_eq!(
+            tx.missing_signatures(),
+            HashSet::from([key_hash(1), key_hash(2)])
+        );
+
+        let tx = tx.sign(&wallet(2)).unwrap();
+        assert_eq!(tx.missing_signatures(), HashSet::from([key_hash(1)]));
+
+        let tx = tx.sign(&wallet(1)).unwrap();
+        assert!(tx.missing_signatures().is_empty());
+    }
+
+    #[test]
+    fn vkey_witnesses_list_the_keys_signed_so_far() {
+        let key_hash = |seed: u8| Hash::from(PrivateKey::from_bytes([seed; 32]).unwrap().hash());
+        let tx = multisig_tx();
+        assert_eq!(tx.witness_count(), 0);
+        assert!(tx.vkey_witnesses().is_empty());
+
+        let tx = tx.sign(&wallet(3)).unwrap().sign(&wallet(1)).unwrap();
+        assert_eq!(tx.witness_count(), 2);
+        let witnesses = tx.vkey_witnesses();
+        assert!(witnesses[0].0.0 < witnesses[1].0.0);
+        assert_eq!(
+            witnesses
+                .iter()
+                .map(|(pub_key, _)| key_hash_of(&pub_key.0))
+                .collect::<HashSet<_>>(),
+            HashSet::from([key_hash(1), key_hash(3)])
+        );
+    }
+
+    #[test]
+    fn bundles_from_strangers_or_for_other_transactions_are_rejected() {
+        let tx = multisig_tx();
+        let request = tx.to_signing_request().unwrap();
+
+        let stranger = wallet(4).sign_request(&request).unwrap();
+        assert!(multisig_tx().apply_signature_bundle(&stranger).is_err());
+
+        let other_request = BuiltTx::new(
+            StagingTransaction::new(),
+            multisig_staging().fee(300_000).build_conway(None).unwrap(),
+        )
+        .to_signing_request()
+        .unwrap();
+        let wrong_tx = wallet(1).sign_request(&other_request).unwrap();
+        assert!(tx.apply_signature_bundle(&wrong_tx).is_err());
+    }
+
+    #[test]
+    fn tampered_body_is_not_signed() {
+        let mut request = multisig_tx().to_signing_request().unwrap();
+        request.body_hash = hex::encode([0u8; 32]);
+        assert!(request.verified_hash().is_err());
+        assert!(wallet(1).sign_request(&request).is_err());
+    }
+
+    #[test]
+    fn multi_wallet_signs_each_input_with_its_owner() {
+        let key_hash = |seed: u8| Hash::from(PrivateKey::from_bytes([seed; 32]).unwrap().hash());
+        let input = |seed: u8| Input::new(Hash([seed; 32]), 0);
+        let staging = StagingTransaction::new()
+            .network_id(0)
+            .fee(200_000)
+            .input(input(1))
+            .input(input(2))
+            .output(Output::new(wallet(1).address(), 5_000_000));
+        let tx = staging.clone().build_conway(None).unwrap();
+        let tx = || BuiltTx {
+            required_signers: HashSet::from([key_hash(1), key_hash(2)]),
+            input_owners: HashMap::from([(input(1), key_hash(1)), (input(2), key_hash(2))]),
+            ..BuiltTx::new(staging.clone(), tx.clone())
+        };
+
+        // The third wallet controls nothing, so it doesn't sign
+        let wallets = MultiWallet::new(wallet(2), vec![wallet(1), wallet(3)]).unwrap();
+        let signed = wallets.sign_all(tx()).unwrap();
+        assert_eq!(signed.witness_count(), 2);
+        assert!(signed.missing_signatures().is_empty());
+
+        let err = MultiWallet::new(wallet(1), vec![wallet(3)])
+            .unwrap()
+            .sign_all(tx())
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .contains(&format!("{}#0 (owned by {})", input(2).hash, key_hash(2))),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Where submitted transactions stand
+//!
+//! Neither the indexer nor Ogmios over HTTP can look a transaction up by hash: the indexer only
+//! knows unspent outputs, and Ogmios only exposes its mempool over its websocket. A `TxTracker`
+//! works the status out from the outputs and inputs of the transactions it watches, and from the
+//! height of the chain tip.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use hydrant::primitives::TxOutputPointer;
+
+use super::{BuiltTx, UtxoSource};
+use crate::ogmios::OgmiosQueries;
+use crate::primitives::TxHash;
+
+/// Where a transaction stands.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Status {
+    /// Not seen on chain, nor in the mempool if that can be queried
+    Unknown,
+    /// Waiting in the mempool of the node. Never reported by `TxTracker`, as Ogmios doesn't
+    /// expose the mempool over HTTP: such transactions are `Unknown` until included.
+    InMempool,
+    /// Included in the chain
+    OnChain {
+        /// Height of the including block
+        block: u64,
+        /// Number of blocks from the including one to the tip, 1 for the tip itself
+        confirmations: u64,
+    },
+    /// Was on chain, until a rollback undid it
+    RolledBack,
+}
+
+/// Looks up where transactions stand, e.g. to wait for enough confirmations before crediting a
+/// payment.
+pub trait TxStatus: Sync {
+    fn tx_status(&self, hash: TxHash) -> impl Future<Output = Result<Status>> + Send;
+}
+
+/// Tracks the status of the transactions passed to `watch`, the others are `Unknown`.
+///
+/// A transaction is on chain once the indexer has any of its outputs. It stays on chain when they
+/// are spent later on, and is rolled back when they are gone while its inputs are unspent again.
+/// This can't tell a rollback apart from the inputs being spent by another transaction of the new
+/// fork, which is reported as still on chain.
+///
+/// The including block is the tip when the transaction was first seen on chain, so it's only
+/// exact when polling at least once a block.
+pub struct TxTracker<'a, U> {
+    indexer: &'a U,
+    ogmios: &'a OgmiosQueries,
+    watched: Mutex<HashMap<TxHash, Watched>>,
+}
+
+struct Watched {
+    inputs: Vec<TxOutputPointer>,
+    outputs: Vec<TxOutputPointer>,
+    /// Height of the tip when first seen on chain, until rolled back
+    included: Option<u64>,
+    rolled_back: bool,
+}
+
+impl<'a, U: UtxoSource> TxTracker<'a, U> {
+    pub fn new(indexer: &'a U, ogmios: &'a OgmiosQueries) -> Self {
+        Self {
+            indexer,
+            ogmios,
+            watched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts tracking `tx`, before or after it's submitted.
+    pub fn watch(&self, tx: &BuiltTx) -> Result<()> {
+        let hash = tx.hash()?;
+        let watched = Watched {
+            inputs: tx
+                .body()
+                .inputs()
+                .iter()
+                .map(TxOutputPointer::from)
+                .collect(),
+            outputs: (0..tx.body().outputs().len())
+                .map(|index| TxOutputPointer::new(hash, index as u64))
+                .collect(),
+            included: None,
+            rolled_back: false,
+        };
+        self.watched
+            .lock()
+            .expect("watched transactions poisoned")
+            .entry(hash)
+            .or_insert(watched);
+        Ok(())
+    }
+
+    /// Stops tracking the transaction `hash`, e.g. once it has enough confirmations.
+    pub fn unwatch(&self, hash: TxHash) {
+        self.watched
+            .lock()
+            .expect("watched transactions poisoned")
+            .remove(&hash);
+    }
+
+    fn pointers(&self, hash: TxHash) -> Option<(Vec<TxOutputPointer>, Vec<TxOutputPointer>)> {
+        let watched = self.watched.lock().expect("watched transactions poisoned");
+        let watched = watched.get(&hash)?;
+        Some((watched.inputs.clone(), watched.outputs.clone()))
+    }
+}
+
+impl<U: UtxoSource> TxStatus for TxTracker<'_, U> {
+    async fn tx_status(&self, hash: TxHash) -> Result<Status> {
+        let Some((inputs, outputs)) = self.pointers(hash) else {
+            return Ok(Status::Unknown);
+        };
+        let has_outputs = !self
+            .indexer
+            .utxos(&outputs)
+            .await
+            .with_context(|| format!("failed to look up the outputs of {hash}"))?
+            .is_empty();
+        let has_inputs = !self
+            .indexer
+            .utxos(&inputs)
+            .await
+            .with_context(|| format!("failed to look up the inputs of {hash}"))?
+            .is_empty();
+        let tip = self
+            .ogmios
+            .block_height()
+            .await
+            .context("failed to query the tip")?;
+
+        let mut tracked = self.watched.lock().expect("watched transactions poisoned");
+        let Some(watched) = tracked.get_mut(&hash) else {
+            return Ok(Status::Unknown);
+        };
+        if has_outputs {
+            watched.included.get_or_insert(tip);
+            watched.rolled_back = false;
+        } else if watched.included.is_some() && has_inputs {
+            watched.included = None;
+            watched.rolled_back = true;
+        }
+        Ok(match watched.included {
+            Some(block) => Status::OnChain {
+                block,
+                confirmations: tip.saturating_sub(block) + 1,
+            },
+            None if watched.rolled_back => Status::RolledBack,
+            None => Status::Unknown,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use hydrant::primitives::{TxOutput, TxOutputPointer};
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::{Status, TxStatus, TxTracker};
+    use crate::builder::tx::StagingTransaction;
+    use crate::builder::{BuiltTx, UtxoSource};
+    use crate::mock_http::MockServer;
+    use crate::ogmios::OgmiosQueries;
+    use crate::primitives::{Hash, Input, Output};
+
+    /// UTxO set the test changes as blocks come and go.
+    #[derive(Default)]
+    struct Ledger(Mutex<Vec<TxOutput>>);
+
+    impl Ledger {
+        fn set(&self, utxos: Vec<TxOutput>) {
+            *self.0.lock().unwrap() = utxos;
+        }
+    }
+
+    impl UtxoSource for Ledger {
+        async fn utxos(&self, pointers: &[TxOutputPointer]) -> anyhow::Result<Vec<TxOutput>> {
+            let inputs = pointers.iter().map(Input::from).collect::<Vec<_>>();
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|utxo| inputs.contains(&Input::from(*utxo)))
+                .cloned()
+                .collect())
+        }
+
+        async fn address_utxos(&self, _address: &[u8]) -> anyhow::Result<Vec<TxOutput>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    #[tokio::test]
+    async fn status_follows_inclusion_spending_and_rollback() {
+        let server = MockServer::serving(
+            r#"{ "jsonrpc": "2.0", "method": "queryNetwork/blockHeight", "result": 42 }"#
+                .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+        let spent = Input::new(Hash([9u8; 32]), 0);
+        let staging = StagingTransaction::new()
+            .fee(0)
+            .input(spent.clone())
+            .output(Output::new(address(), 1));
+        let tx = BuiltTx::new(staging.clone(), staging.build_conway(None).unwrap());
+        let hash = tx.hash().unwrap();
+        let spent_utxo = Output::new(address(), 1).to_tx_output(&spent);
+        let output = tx.body().outputs()[0].to_tx_output(&Input::new(hash, 0));
+
+        let ledger = Ledger::default();
+        ledger.set(vec![spent_utxo.clone()]);
+        let tracker = TxTracker::new(&ledger, &ogmios);
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), Status::Unknown);
+
+        tracker.watch(&tx).unwrap();
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), Status::Unknown);
+
+        let on_chain = Status::OnChain {
+            block: 42,
+            confirmations: 1,
+        };
+        ledger.set(vec![output]);
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), on_chain);
+
+        // Spent by a later transaction
+        ledger.set(Vec::new());
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), on_chain);
+
+        // Its input is back
+        ledger.set(vec![spent_utxo]);
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), Status::RolledBack);
+
+        tracker.unwatch(hash);
+        assert_eq!(tracker.tx_status(hash).await.unwrap(), Status::Unknown);
+    }
+}
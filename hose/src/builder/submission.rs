@@ -0,0 +1,621 @@
+//! Submission of several transactions at once
+//!
+//! Transactions of a batch may spend outputs of each other, e.g. when built ahead of the indexer
+//! with `add_input_resolved`. They are submitted so that every transaction comes after the ones
+//! it spends from, and a transaction whose parent failed is skipped rather than submitted, since
+//! its inputs won't exist.
+//!
+//! A transaction the node already has, in its mempool or on chain, is rejected over its inputs,
+//! which the first submission spent. Such rejections are reported as `AlreadySubmitted` once the
+//! transaction shows up in the indexer, see `Submitter`.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use hydrant::primitives::TxOutputPointer;
+use ogmios_client::OgmiosHttpClient;
+
+use super::{BuiltTx, TxJournal, TxState, UtxoSource};
+use crate::ogmios::{self, OgmiosQueries};
+use crate::primitives::TxHash;
+
+const DEFAULT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Submissions of a transaction before giving up on transport errors
+const MAX_SUBMISSIONS: usize = 3;
+
+/// Ogmios error code of a transaction spending inputs that don't exist or are spent
+const UNKNOWN_OUTPUT_REFERENCES: i64 = 3117;
+
+/// Where transactions are submitted to.
+///
+/// Implemented for `OgmiosHttpClient`, and for `OgmiosQueries`, which is the one that tells
+/// `Rejection::UnknownInputs` apart. Ogmios only exposes the mempool over its websocket, so a
+/// duplicate still in the mempool can't be recognized right away over HTTP: with `OgmiosQueries`
+/// it's recognized once included, with `OgmiosHttpClient` only if it's included already.
+pub trait Submitter: Sync {
+    /// Submits the serialized `tx`.
+    fn submit(&self, tx: &[u8]) -> impl Future<Output = Result<(), Rejection>> + Send;
+}
+
+/// Why a transaction wasn't submitted.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    /// Some inputs don't exist or are spent, which is also how the node rejects a transaction
+    /// that is in its mempool or on chain already
+    #[error("unknown or spent inputs: {0}")]
+    UnknownInputs(String),
+    /// The node couldn't be reached or its answer was lost, so the transaction may have been
+    /// submitted all the same
+    #[error("transport error: {0}")]
+    Transport(String),
+    /// Any other reason
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Every rejection is `Rejection::Other`, as the client's errors don't carry the Ogmios error
+/// code.
+impl Submitter for OgmiosHttpClient {
+    async fn submit(&self, tx: &[u8]) -> Result<(), Rejection> {
+        let tx = tx.to_vec();
+        OgmiosHttpClient::submit(self, &tx)
+            .await
+            .map(|_| ())
+            .map_err(|err| Rejection::Other(format!("{err:?}")))
+    }
+}
+
+impl Submitter for OgmiosQueries {
+    async fn submit(&self, tx: &[u8]) -> Result<(), Rejection> {
+        OgmiosQueries::submit(self, tx)
+            .await
+            .map(|_| ())
+            .map_err(|err| match err {
+                ogmios::Error::Query {
+                    code: UNKNOWN_OUTPUT_REFERENCES,
+                    ..
+                } => Rejection::UnknownInputs(err.to_string()),
+                ogmios::Error::Http(_) => Rejection::Transport(err.to_string()),
+                _ => Rejection::Other(err.to_string()),
+            })
+    }
+}
+
+/// What happens to the rest of a batch once a transaction fails.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum OnFailure {
+    /// Nothing else is submitted
+    #[default]
+    Stop,
+    /// Transactions that don't depend on the failed one are still submitted
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SubmitError {
+    /// The node refused the transaction
+    #[error("Transaction {hash} was rejected: {reason}")]
+    Rejected { hash: TxHash, reason: String },
+    /// The node rejected the transaction because it was submitted before: it's been seen by the
+    /// indexer. Transactions of the batch spending from it are still submitted.
+    #[error("Transaction {hash} was submitted already")]
+    AlreadySubmitted { hash: TxHash },
+    /// The transaction was submitted, but the indexer didn't see it in time
+    #[error("Transaction {hash} was not seen by the indexer within {timeout:?}")]
+    NotAccepted { hash: TxHash, timeout: Duration },
+    /// The indexer couldn't be queried while waiting for the transaction
+    #[error("Could not look up transaction {hash} in the indexer: {reason}")]
+    Indexer { hash: TxHash, reason: String },
+    /// Not submitted, as it spends from a transaction of the batch that failed
+    #[error("Transaction {hash} was skipped since {dependency}, which it spends from, failed")]
+    DependencyFailed { hash: TxHash, dependency: TxHash },
+    /// Not submitted, as an earlier transaction failed with `OnFailure::Stop`
+    #[error("Transaction {hash} was skipped after an earlier failure")]
+    Skipped { hash: TxHash },
+    /// Not submitted, as the transactions of the batch spend from each other in a cycle
+    #[error("Transaction {hash} depends on itself through other transactions of the batch")]
+    Cycle { hash: TxHash },
+    /// Not submitted, as it couldn't be recorded in the journal
+    #[error("Could not journal transaction {hash}: {reason}")]
+    Journal { hash: TxHash, reason: String },
+}
+
+/// Submits batches of signed transactions, see `submit_batch`.
+pub struct BatchSubmitter<'a, U, S> {
+    indexer: &'a U,
+    submitter: &'a S,
+    on_failure: OnFailure,
+    wait_for_acceptance: bool,
+    confirmation_timeout: Duration,
+    journal: Option<&'a TxJournal>,
+}
+
+impl<'a, U: UtxoSource, S: Submitter> BatchSubmitter<'a, U, S> {
+    pub fn new(indexer: &'a U, submitter: &'a S) -> Self {
+        Self {
+            indexer,
+            submitter,
+            on_failure: OnFailure::default(),
+            wait_for_acceptance: false,
+            confirmation_timeout: DEFAULT_CONFIRMATION_TIMEOUT,
+            journal: None,
+        }
+    }
+
+    /// Records each transaction in `journal` before submitting it, along with what became of it.
+    pub fn journal(mut self, journal: &'a TxJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Whether the rest of the batch is submitted after a failure. Defaults to `Stop`.
+    pub fn on_failure(mut self, on_failure: OnFailure) -> Self {
+        self.on_failure = on_failure;
+        self
+    }
+
+    /// Waits for each transaction to be seen by the indexer before submitting the next one,
+    /// instead of relying on the mempool to accept chained transactions.
+    pub fn wait_for_acceptance(mut self, wait: bool) -> Self {
+        self.wait_for_acceptance = wait;
+        self
+    }
+
+    /// How long a transaction may take to show up in the indexer with `wait_for_acceptance`, or
+    /// after a `Rejection::UnknownInputs` that may be a duplicate. Defaults to two minutes.
+    pub fn confirmation_timeout(mut self, timeout: Duration) -> Self {
+        self.confirmation_timeout = timeout;
+        self
+    }
+
+    /// Submits `txs`, each after the transactions of the batch it spends from.
+    ///
+    /// Returns one result per transaction, in the order of `txs` rather than the order they were
+    /// submitted in.
+    pub async fn submit_batch(&self, txs: &[BuiltTx]) -> Vec<Result<TxHash, SubmitError>> {
+        let hashes = txs.iter().map(|tx| tx.tx.hash).collect::<Vec<_>>();
+        let spent = txs
+            .iter()
+            .map(|tx| {
+                let body = tx.body();
+                body.inputs()
+                    .iter()
+                    .chain(body.reference_inputs())
+                    .chain(body.collateral_inputs())
+                    .map(|input| input.hash)
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        let dependencies = dependencies(&hashes, &spent);
+        let (order, cyclic) = submission_order(&dependencies);
+
+        let mut results = vec![None; txs.len()];
+        for index in cyclic {
+            results[index] = Some(Err(SubmitError::Cycle {
+                hash: hashes[index],
+            }));
+        }
+
+        let mut stopped = false;
+        for index in order {
+            let hash = hashes[index];
+            // Dependencies come first in the order, so their results are in already
+            let failed_dependency = dependencies[index]
+                .iter()
+                .find(|dependency| !results[**dependency].as_ref().is_some_and(is_submitted));
+            let result = if let Some(dependency) = failed_dependency {
+                Err(SubmitError::DependencyFailed {
+                    hash,
+                    dependency: hashes[*dependency],
+                })
+            } else if stopped {
+                Err(SubmitError::Skipped { hash })
+            } else {
+                self.submit(&txs[index], hash).await
+            };
+            if !is_submitted(&result) && self.on_failure == OnFailure::Stop {
+                stopped = true;
+            }
+            results[index] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every transaction is either ordered or in a cycle"))
+            .collect()
+    }
+
+    async fn submit(&self, tx: &BuiltTx, hash: TxHash) -> Result<TxHash, SubmitError> {
+        let Some(journal) = self.journal else {
+            return self.submit_unjournaled(tx, hash).await;
+        };
+        journal.record(tx).map_err(|err| SubmitError::Journal {
+            hash,
+            reason: err.to_string(),
+        })?;
+        let result = self.submit_unjournaled(tx, hash).await;
+        let state = match &result {
+            Ok(_) if self.wait_for_acceptance => Some(TxState::Confirmed),
+            Ok(_) | Err(SubmitError::NotAccepted { .. }) => Some(TxState::Submitted),
+            Err(SubmitError::AlreadySubmitted { .. }) => Some(TxState::Confirmed),
+            Err(SubmitError::Rejected { .. }) => Some(TxState::Failed),
+            Err(_) => None,
+        };
+        // The transaction is journaled as built already, which recovery handles as well
+        if let Some(state) = state
+            && let Err(err) = journal.transition(hash, state)
+        {
+            tracing::warn!("Failed to journal transaction {hash} as {state:?}: {err}");
+        }
+        result
+    }
+
+    async fn submit_unjournaled(&self, tx: &BuiltTx, hash: TxHash) -> Result<TxHash, SubmitError> {
+        tracing::debug!("Submitting transaction {hash} of batch");
+        self.submit_once(tx, hash).await?;
+        if self.wait_for_acceptance {
+            self.wait_until_indexed(tx, hash).await?;
+        }
+        Ok(hash)
+    }
+
+    /// Submits `tx`, and again after a transport error if the indexer doesn't see it within
+    /// `confirmation_timeout`: resubmitting is harmless, as a transaction can't be included twice.
+    async fn submit_once(&self, tx: &BuiltTx, hash: TxHash) -> Result<(), SubmitError> {
+        let mut submissions = 0;
+        loop {
+            submissions += 1;
+            let reason = match self.submitter.submit(&tx.cbor()).await {
+                Ok(()) => return Ok(()),
+                Err(Rejection::Transport(reason)) => reason,
+                Err(rejection) => return Err(self.rejected(tx, hash, rejection).await),
+            };
+            match self.wait_until_indexed(tx, hash).await {
+                Err(SubmitError::NotAccepted { .. }) if submissions < MAX_SUBMISSIONS => {
+                    tracing::warn!("Submitting transaction {hash} again after: {reason}");
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Tells a duplicate submission apart from a transaction the node refused: a duplicate is
+    /// one whose outputs the indexer has, as long as they aren't all spent. Over unknown inputs,
+    /// it may only be in the mempool, and is waited for.
+    async fn rejected(&self, tx: &BuiltTx, hash: TxHash, rejection: Rejection) -> SubmitError {
+        let indexed = match &rejection {
+            Rejection::UnknownInputs(_) => match self.wait_until_indexed(tx, hash).await {
+                Ok(()) => Ok(true),
+                Err(SubmitError::NotAccepted { .. }) => Ok(false),
+                Err(err) => Err(err),
+            },
+            Rejection::Other(_) | Rejection::Transport(_) => self.is_indexed(tx, hash).await,
+        };
+        match indexed {
+            Ok(true) => SubmitError::AlreadySubmitted { hash },
+            Ok(false) => SubmitError::Rejected {
+                hash,
+                reason: rejection.to_string(),
+            },
+            Err(err) => err,
+        }
+    }
+
+    /// Whether any output of `tx` is in the indexer.
+    async fn is_indexed(&self, tx: &BuiltTx, hash: TxHash) -> Result<bool, SubmitError> {
+        let pointers = (0..tx.body().outputs().len())
+            .map(|index| TxOutputPointer::new(hash, index as u64))
+            .collect::<Vec<_>>();
+        let utxos = self
+            .indexer
+            .utxos(&pointers)
+            .await
+            .map_err(|err| SubmitError::Indexer {
+                hash,
+                reason: format!("{err:#}"),
+            })?;
+        Ok(!utxos.is_empty())
+    }
+
+    async fn wait_until_indexed(&self, tx: &BuiltTx, hash: TxHash) -> Result<(), SubmitError> {
+        let started = tokio::time::Instant::now();
+        loop {
+            if self.is_indexed(tx, hash).await? {
+                return Ok(());
+            }
+            if started.elapsed() > self.confirmation_timeout {
+                return Err(SubmitError::NotAccepted {
+                    hash,
+                    timeout: self.confirmation_timeout,
+                });
+            }
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Whether the node has the transaction, submitted by this batch or before.
+fn is_submitted(result: &Result<TxHash, SubmitError>) -> bool {
+    matches!(result, Ok(_) | Err(SubmitError::AlreadySubmitted { .. }))
+}
+
+/// Indices of the transactions each one spends from, given the hashes of the transactions and
+/// the hashes of the transactions their inputs point to.
+fn dependencies(hashes: &[TxHash], spent: &[Vec<TxHash>]) -> Vec<Vec<usize>> {
+    spent
+        .iter()
+        .enumerate()
+        .map(|(index, spent)| {
+            (0..hashes.len())
+                .filter(|other| *other != index && spent.contains(&hashes[*other]))
+                .collect()
+        })
+        .collect()
+}
+
+/// Orders transactions after their dependencies, keeping the original order where there's a
+/// choice. Returns the order and the transactions that can't be ordered because of a cycle.
+fn submission_order(dependencies: &[Vec<usize>]) -> (Vec<usize>, Vec<usize>) {
+    let mut remaining = dependencies
+        .iter()
+        .map(|dependencies| dependencies.len())
+        .collect::<Vec<_>>();
+    let mut ready = (0..dependencies.len())
+        .filter(|index| remaining[*index] == 0)
+        .collect::<BTreeSet<_>>();
+
+    let mut order = Vec::with_capacity(dependencies.len());
+    while let Some(index) = ready.pop_first() {
+        order.push(index);
+        for (dependent, dependencies) in dependencies.iter().enumerate() {
+            if dependencies.contains(&index) {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+    }
+
+    let cyclic = (0..dependencies.len())
+        .filter(|index| remaining[*index] > 0)
+        .collect();
+    (order, cyclic)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use hydrant::primitives::{TxOutput, TxOutputPointer};
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::{
+        BatchSubmitter, OnFailure, Rejection, SubmitError, Submitter, dependencies,
+        submission_order,
+    };
+    use crate::builder::tx::StagingTransaction;
+    use crate::builder::{BuiltTx, TxJournal, TxState, UtxoSource};
+    use crate::primitives::{Hash, Input, Output};
+
+    /// Rejects the listed transactions, accepts any other.
+    struct Node(Vec<(Vec<u8>, Rejection)>);
+
+    impl Submitter for Node {
+        async fn submit(&self, tx: &[u8]) -> Result<(), Rejection> {
+            match self.0.iter().find(|(rejected, _)| rejected == tx) {
+                Some((_, rejection)) => Err(rejection.clone()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    struct Indexed(Vec<TxOutput>);
+
+    impl UtxoSource for Indexed {
+        async fn utxos(&self, pointers: &[TxOutputPointer]) -> anyhow::Result<Vec<TxOutput>> {
+            let inputs = pointers.iter().map(Input::from).collect::<Vec<_>>();
+            Ok(self
+                .0
+                .iter()
+                .filter(|utxo| inputs.contains(&Input::from(*utxo)))
+                .cloned()
+                .collect())
+        }
+
+        async fn address_utxos(&self, _address: &[u8]) -> anyhow::Result<Vec<TxOutput>> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    /// A transaction with two outputs spending `input`.
+    fn spending(input: Input) -> BuiltTx {
+        let staging = StagingTransaction::new()
+            .fee(0)
+            .input(input)
+            .output(Output::new(address(), 1))
+            .output(Output::new(address(), 2));
+        let tx = staging.clone().build_conway(None).unwrap();
+        BuiltTx::new(staging, tx)
+    }
+
+    /// The second output of `tx` as the indexer has it, the first one being spent.
+    fn second_output(tx: &BuiltTx) -> TxOutput {
+        let input = Input::new(tx.hash().unwrap(), 1);
+        tx.body().outputs()[1].to_tx_output(&input)
+    }
+
+    #[tokio::test]
+    async fn rejected_transactions_seen_by_the_indexer_were_submitted_already() {
+        let tx = spending(Input::new(Hash([9u8; 32]), 0));
+        let hash = tx.hash().unwrap();
+        let indexer = Indexed(vec![second_output(&tx)]);
+
+        for rejection in [
+            Rejection::Other("bad inputs".to_string()),
+            Rejection::UnknownInputs("bad inputs".to_string()),
+        ] {
+            let node = Node(vec![(tx.cbor(), rejection)]);
+            let results = BatchSubmitter::new(&indexer, &node)
+                .submit_batch(std::slice::from_ref(&tx))
+                .await;
+            assert_eq!(results, vec![Err(SubmitError::AlreadySubmitted { hash })]);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_transactions_missing_from_the_indexer_stay_rejected() {
+        let tx = spending(Input::new(Hash([9u8; 32]), 0));
+        let hash = tx.hash().unwrap();
+        let rejection = Rejection::UnknownInputs("bad inputs".to_string());
+        let node = Node(vec![(tx.cbor(), rejection.clone())]);
+
+        let results = BatchSubmitter::new(&Indexed(Vec::new()), &node)
+            .confirmation_timeout(Duration::ZERO)
+            .submit_batch(std::slice::from_ref(&tx))
+            .await;
+        assert_eq!(
+            results,
+            vec![Err(SubmitError::Rejected {
+                hash,
+                reason: rejection.to_string(),
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn transactions_spending_from_a_duplicate_are_still_submitted() {
+        let parent = spending(Input::new(Hash([9u8; 32]), 0));
+        let parent_hash = parent.hash().unwrap();
+        let child = spending(Input::new(parent_hash, 1));
+        let child_hash = child.hash().unwrap();
+        let indexer = Indexed(vec![second_output(&parent)]);
+        let node = Node(vec![(
+            parent.cbor(),
+            Rejection::Other("bad inputs".to_string()),
+        )]);
+
+        let results = BatchSubmitter::new(&indexer, &node)
+            .on_failure(OnFailure::Stop)
+            .submit_batch(&[child, parent])
+            .await;
+        assert_eq!(
+            results,
+            vec![
+                Ok(child_hash),
+                Err(SubmitError::AlreadySubmitted { hash: parent_hash })
+            ]
+        );
+    }
+
+    /// Reports a transport error on every submission, having submitted or not.
+    #[derive(Default)]
+    struct Unreachable(AtomicUsize);
+
+    impl Submitter for Unreachable {
+        async fn submit(&self, _tx: &[u8]) -> Result<(), Rejection> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(Rejection::Transport("connection reset".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_settled_by_the_indexer() {
+        let tx = spending(Input::new(Hash([9u8; 32]), 0));
+        let hash = tx.hash().unwrap();
+
+        // The submission went through, it's only submitted once
+        let node = Unreachable::default();
+        let results = BatchSubmitter::new(&Indexed(vec![second_output(&tx)]), &node)
+            .submit_batch(std::slice::from_ref(&tx))
+            .await;
+        assert_eq!(results, vec![Ok(hash)]);
+        assert_eq!(node.0.load(Ordering::Relaxed), 1);
+
+        // It didn't, it's submitted again until giving up
+        let node = Unreachable::default();
+        let results = BatchSubmitter::new(&Indexed(Vec::new()), &node)
+            .confirmation_timeout(Duration::ZERO)
+            .submit_batch(std::slice::from_ref(&tx))
+            .await;
+        assert_eq!(
+            results,
+            vec![Err(SubmitError::NotAccepted {
+                hash,
+                timeout: Duration::ZERO,
+            })]
+        );
+        assert_eq!(node.0.load(Ordering::Relaxed), super::MAX_SUBMISSIONS);
+    }
+
+    #[tokio::test]
+    async fn the_journal_records_what_became_of_each_transaction() {
+        let accepted = spending(Input::new(Hash([7u8; 32]), 0));
+        let duplicate = spending(Input::new(Hash([8u8; 32]), 0));
+        let rejected = spending(Input::new(Hash([9u8; 32]), 0));
+        let hashes = [&accepted, &duplicate, &rejected].map(|tx| tx.hash().unwrap());
+        let indexer = Indexed(vec![second_output(&duplicate)]);
+        let node = Node(vec![
+            (duplicate.cbor(), Rejection::Other("bad inputs".to_string())),
+            (rejected.cbor(), Rejection::Other("bad script".to_string())),
+        ]);
+        let path =
+            std::env::temp_dir().join(format!("hose-journal-{}.json", rand::random::<u64>()));
+        let journal = TxJournal::new(&path);
+
+        BatchSubmitter::new(&indexer, &node)
+            .journal(&journal)
+            .submit_batch(&[accepted, duplicate, rejected])
+            .await;
+        let states = journal
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.hash, entry.state()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                (hashes[0], TxState::Submitted),
+                (hashes[1], TxState::Confirmed),
+                (hashes[2], TxState::Failed),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn transactions_are_submitted_after_what_they_spend_from() {
+        let hashes = (0..4).map(|i| Hash([i; 32])).collect::<Vec<_>>();
+        // 0 spends from 2, 2 from 3 and from an UTxO outside the batch, 1 from nothing
+        let spent = vec![
+            vec![hashes[2]],
+            vec![],
+            vec![hashes[3], Hash([0xff; 32])],
+            vec![],
+        ];
+
+        let dependencies = dependencies(&hashes, &spent);
+        assert_eq!(dependencies, vec![vec![2], vec![], vec![3], vec![]]);
+        assert_eq!(submission_order(&dependencies), (vec![1, 3, 2, 0], vec![]));
+    }
+
+    #[test]
+    fn cycles_are_left_out_of_the_order() {
+        // 0 and 1 spend from each other, 2 from 1, 3 is independent
+        let dependencies = vec![vec![1], vec![0], vec![1], vec![]];
+        assert_eq!(submission_order(&dependencies), (vec![3], vec![0, 1, 2]));
+    }
+}
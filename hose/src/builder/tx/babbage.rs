@@ -0,0 +1,242 @@
+//! Babbage-era serialization
+//!
+//! Babbage transactions use the same format as Conway ones, short of the Conway-only features and
+//! of the tag 258 on sets, which Babbage nodes don't accept. They're therefore encoded from the
+//! Conway transaction, writing every set as a plain array.
+
+use std::convert::Infallible;
+
+use pallas::codec::minicbor::{self, Decoder, Encode, Encoder};
+use pallas::crypto::hash::{Hash as PallasHash, Hasher};
+use pallas::ledger::primitives::conway::{self, Certificate, LanguageView};
+
+use super::TxBuilderError;
+use crate::primitives::{Hash, PublicKey, Signature, TxHash};
+
+type EncodeError = minicbor::encode::Error<Infallible>;
+
+/// Map with small integer keys, as used by transaction bodies and witness sets
+#[derive(Default)]
+struct RawMap(Vec<(u64, Vec<u8>)>);
+
+impl RawMap {
+    fn entry(&mut self, key: u64, value: &impl Encode<()>) -> Result<(), EncodeError> {
+        self.0.push((key, minicbor::to_vec(value)?));
+        Ok(())
+    }
+
+    /// Encodes `items` as a plain array, omitting the entry when there are none.
+    fn array<'a, T: Encode<()> + 'a>(
+        &mut self,
+        key: u64,
+        items: impl IntoIterator<Item = &'a T>,
+    ) -> Result<(), EncodeError> {
+        let items = items.into_iter().collect::<Vec<_>>();
+        if !items.is_empty() {
+            self.0.push((key, minicbor::to_vec(items)?));
+        }
+        Ok(())
+    }
+
+    fn raw(&mut self, key: u64, value: Vec<u8>) {
+        self.0.push((key, value));
+    }
+
+    fn get(&self, key: u64) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find_map(|(k, value)| (*k == key).then_some(value.as_slice()))
+    }
+
+    fn encode(mut self) -> Result<Vec<u8>, EncodeError> {
+        self.0.sort_by_key(|(key, _)| *key);
+        let mut bytes = vec![];
+        let mut encoder = Encoder::new(&mut bytes);
+        encoder.map(self.0.len() as u64)?;
+        for (key, value) in self.0 {
+            encoder.u64(key)?;
+            encoder.writer_mut().extend_from_slice(&value);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Encodes `tx` in the Babbage format, returning its hash along with the bytes and the script
+/// data hash of its witnesses.
+///
+/// Conway registration and deregistration certificates are written as their pre-Conway
+/// counterparts, whose deposit is implied by the protocol parameters. The script data hash is
+/// recomputed over the witnesses as encoded here, and replaced by `pinned_script_data_hash` in
+/// the body when given.
+pub(crate) fn encode_tx(
+    tx: &conway::Tx,
+    language_view: Option<&LanguageView>,
+    pinned_script_data_hash: Option<Hash<32>>,
+) -> Result<(TxHash, Vec<u8>, Option<Hash<32>>), TxBuilderError> {
+    let body = &tx.transaction_body;
+    let witnesses = &tx.transaction_witness_set;
+
+    let encode = || -> Result<(Vec<u8>, Vec<u8>, Option<Hash<32>>), EncodeError> {
+        let mut witness_set = RawMap::default();
+        if let Some(vkeys) = &witnesses.vkeywitness {
+            witness_set.array(0, vkeys.iter())?;
+        }
+        if let Some(scripts) = &witnesses.native_script {
+            witness_set.array(1, scripts.iter())?;
+        }
+        if let Some(scripts) = &witnesses.plutus_v1_script {
+            witness_set.array(3, scripts.iter())?;
+        }
+        if let Some(datums) = &witnesses.plutus_data {
+            witness_set.array(4, datums.iter())?;
+        }
+        if let Some(redeemers) = &witnesses.redeemer {
+            witness_set.entry(5, redeemers)?;
+        }
+        if let Some(scripts) = &witnesses.plutus_v2_script {
+            witness_set.array(6, scripts.iter())?;
+        }
+
+        let mut fields = RawMap::default();
+        fields.array(0, body.inputs.iter())?;
+        fields.entry(1, &body.outputs)?;
+        fields.entry(2, &body.fee)?;
+        if let Some(ttl) = body.ttl {
+            fields.entry(3, &ttl)?;
+        }
+        if let Some(certificates) = &body.certificates {
+            let certificates = certificates
+                .iter()
+                .map(|certificate| match certificate {
+                    Certificate::Reg(credential, _) => {
+                        Certificate::StakeRegistration(credential.clone())
+                    }
+                    Certificate::UnReg(credential, _) => {
+                        Certificate::StakeDeregistration(credential.clone())
+                    }
+                    certificate => certificate.clone(),
+                })
+                .collect::<Vec<_>>();
+            fields.array(4, certificates.iter())?;
+        }
+        if let Some(withdrawals) = &body.withdrawals {
+            fields.entry(5, withdrawals)?;
+        }
+        if let Some(hash) = &body.auxiliary_data_hash {
+            fields.entry(7, hash)?;
+        }
+        if let Some(start) = body.validity_interval_start {
+            fields.entry(8, &start)?;
+        }
+        if let Some(mint) = &body.mint {
+            fields.entry(9, mint)?;
+        }
+        let mut script_data_hash = None;
+        if body.script_data_hash.is_some() {
+            // See `hashScriptIntegrity`: redeemers, then datums if any, then language views
+            let mut preimage = witness_set.get(5).unwrap_or(&[0x80][..]).to_vec();
+            preimage.extend_from_slice(witness_set.get(4).unwrap_or_default());
+            match language_view {
+                Some(language_view) => preimage.extend(minicbor::to_vec(language_view)?),
+                None => preimage.push(0xa0),
+            }
+            script_data_hash = Some(Hash::from(Hasher::<256>::hash(&preimage)));
+        }
+        if let Some(hash) = pinned_script_data_hash.or(script_data_hash) {
+            fields.entry(11, &PallasHash::from(hash))?;
+        }
+        if let Some(collateral) = &body.collateral {
+            fields.array(13, collateral.iter())?;
+        }
+        if let Some(signers) = &body.required_signers {
+            fields.array(14, signers.iter())?;
+        }
+        if let Some(network_id) = &body.network_id {
+            fields.entry(15, network_id)?;
+        }
+        if let Some(collateral_return) = &body.collateral_return {
+            fields.entry(16, collateral_return)?;
+        }
+        if let Some(total_collateral) = body.total_collateral {
+            fields.entry(17, &total_collateral)?;
+        }
+        if let Some(reference_inputs) = &body.reference_inputs {
+            fields.array(18, reference_inputs.iter())?;
+        }
+
+        Ok((fields.encode()?, witness_set.encode()?, script_data_hash))
+    };
+    let (body, witness_set, script_data_hash) =
+        encode().map_err(|_| TxBuilderError::CorruptedTxBytes)?;
+
+    let mut bytes = vec![];
+    let mut encoder = Encoder::new(&mut bytes);
+    let encoded = (|| -> Result<(), EncodeError> {
+        encoder.array(4)?;
+        encoder.writer_mut().extend_from_slice(&body);
+        encoder.writer_mut().extend_from_slice(&witness_set);
+        encoder.bool(true)?;
+        encoder.encode(&tx.auxiliary_data)?;
+        Ok(())
+    })();
+    encoded.map_err(|_| TxBuilderError::CorruptedTxBytes)?;
+
+    Ok((
+        Hash::from(Hasher::<256>::hash(&body)),
+        bytes,
+        script_data_hash,
+    ))
+}
+
+/// Replaces the vkey witnesses of the Babbage transaction in `bytes`, leaving everything else
+/// byte for byte as it was.
+pub(crate) fn with_vkey_witnesses(
+    bytes: &[u8],
+    vkey_witnesses: &[(PublicKey, Signature)],
+) -> Result<Vec<u8>, TxBuilderError> {
+    let corrupted = |_| TxBuilderError::CorruptedTxBytes;
+
+    let mut decoder = Decoder::new(bytes);
+    decoder.array().map_err(corrupted)?;
+    let body_start = decoder.position();
+    decoder.skip().map_err(corrupted)?;
+    let body = &bytes[body_start..decoder.position()];
+
+    let mut witness_set = RawMap::default();
+    let entries = decoder
+        .map()
+        .map_err(corrupted)?
+        .ok_or(TxBuilderError::CorruptedTxBytes)?;
+    for _ in 0..entries {
+        let key = decoder.u64().map_err(corrupted)?;
+        let start = decoder.position();
+        decoder.skip().map_err(corrupted)?;
+        if key != 0 {
+            witness_set.raw(key, bytes[start..decoder.position()].to_vec());
+        }
+    }
+    let rest = &bytes[decoder.position()..];
+
+    let vkey_witnesses = vkey_witnesses
+        .iter()
+        .map(|(pub_key, signature)| conway::VKeyWitness {
+            vkey: pub_key.to_vec().into(),
+            signature: signature.to_vec().into(),
+        })
+        .collect::<Vec<_>>();
+    witness_set
+        .array(0, vkey_witnesses.iter())
+        .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
+    let witness_set = witness_set
+        .encode()
+        .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
+
+    let mut tx = Vec::with_capacity(bytes.len());
+    Encoder::new(&mut tx)
+        .array(4)
+        .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
+    tx.extend_from_slice(body);
+    tx.extend_from_slice(&witness_set);
+    tx.extend_from_slice(rest);
+    Ok(tx)
+}
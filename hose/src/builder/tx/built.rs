@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use pallas::crypto::key::ed25519;
 use pallas::ledger::primitives::{Fragment, NonEmptySet, conway};
 
-use super::TxBuilderError;
+use super::{Era, TxBuilderError, babbage};
 use crate::primitives::{Ed25519Signer, Hash, PublicKey, Signature, TxHash};
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -11,6 +11,8 @@ pub struct BuiltTransaction {
     pub hash: TxHash,
     pub bytes: Vec<u8>,
     pub signatures: Option<HashMap<PublicKey, Signature>>,
+    /// Format of `bytes`
+    pub era: Era,
 }
 
 impl BuiltTransaction {
@@ -26,24 +28,9 @@ impl BuiltTransaction {
 
         let mut new_sigs = self.signatures.unwrap_or_default();
         new_sigs.insert(Hash(pubkey), Hash(signature));
-        self.signatures = Some(new_sigs.clone());
-
-        // TODO: chance for serialisation round trip issues?
-        let mut tx = conway::Tx::decode_fragment(&self.bytes)
-            .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
-
-        let vkey_witnesses = new_sigs
-            .into_iter()
-            .map(|(pk, sig)| conway::VKeyWitness {
-                vkey: pk.to_vec().into(),
-                signature: sig.to_vec().into(),
-            })
-            .collect::<Vec<_>>();
-
-        tx.transaction_witness_set.vkeywitness =
-            Some(NonEmptySet::from_vec(vkey_witnesses).unwrap());
+        self.signatures = Some(new_sigs);
 
-        self.bytes = tx.encode_fragment().unwrap();
+        self.write_vkey_witnesses()?;
 
         Ok(self)
     }
@@ -63,24 +50,9 @@ impl BuiltTransaction {
             ),
             Hash(signature),
         );
-        self.signatures = Some(new_sigs.clone());
-
-        // TODO: chance for serialisation round trip issues?
-        let mut tx = conway::Tx::decode_fragment(&self.bytes)
-            .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
-
-        let vkey_witnesses = new_sigs
-            .into_iter()
-            .map(|(pk, sig)| conway::VKeyWitness {
-                vkey: pk.to_vec().into(),
-                signature: sig.to_vec().into(),
-            })
-            .collect::<Vec<_>>();
-
-        tx.transaction_witness_set.vkeywitness =
-            Some(NonEmptySet::from_vec(vkey_witnesses).unwrap());
+        self.signatures = Some(new_sigs);
 
-        self.bytes = tx.encode_fragment().unwrap();
+        self.write_vkey_witnesses()?;
 
         Ok(self)
     }
@@ -98,23 +70,42 @@ impl BuiltTransaction {
 
         self.signatures = Some(new_sigs);
 
+        self.write_vkey_witnesses()?;
+
+        Ok(self)
+    }
+
+    /// Rewrites the vkey witnesses in `bytes` from `signatures`.
+    fn write_vkey_witnesses(&mut self) -> Result<(), TxBuilderError> {
+        let mut signatures = self
+            .signatures
+            .iter()
+            .flatten()
+            .map(|(pk, sig)| (*pk, *sig))
+            .collect::<Vec<_>>();
+        signatures.sort_by_key(|(pk, _)| pk.0);
+
+        if self.era == Era::Babbage {
+            self.bytes = babbage::with_vkey_witnesses(&self.bytes, &signatures)?;
+            return Ok(());
+        }
+
         // TODO: chance for serialisation round trip issues?
         let mut tx = conway::Tx::decode_fragment(&self.bytes)
             .map_err(|_| TxBuilderError::CorruptedTxBytes)?;
-        let mut vkey_witnesses = tx
-            .transaction_witness_set
-            .vkeywitness
-            .as_ref()
-            .map(|x| x.clone().to_vec())
-            .unwrap_or_default();
 
-        vkey_witnesses.retain(|x| *x.vkey != pk.0.to_vec());
+        let vkey_witnesses = signatures
+            .into_iter()
+            .map(|(pk, sig)| conway::VKeyWitness {
+                vkey: pk.to_vec().into(),
+                signature: sig.to_vec().into(),
+            })
+            .collect::<Vec<_>>();
 
-        tx.transaction_witness_set.vkeywitness =
-            Some(NonEmptySet::from_vec(vkey_witnesses).unwrap());
+        tx.transaction_witness_set.vkeywitness = NonEmptySet::from_vec(vkey_witnesses);
 
         self.bytes = tx.encode_fragment().unwrap();
 
-        Ok(self)
+        Ok(())
     }
 }
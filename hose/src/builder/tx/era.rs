@@ -0,0 +1,9 @@
+/// Ledger era whose transaction format is built, see `TxBuilder::era`.
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Era {
+    /// For networks that haven't hard-forked into Conway yet. Conway-only features (Plutus V3,
+    /// script-witnessed stake registration) are rejected.
+    Babbage,
+    #[default]
+    Conway,
+}
@@ -1,3 +1,9 @@
+use chrono::{DateTime, Utc};
+
+use crate::builder::Phase;
+use crate::builder::lint::LintFinding;
+use crate::primitives::{ExUnits, Hash, Input, RedeemerPurpose};
+
 #[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum TxBuilderError {
     /// Provided bytes could not be decoded into a script
@@ -6,6 +12,9 @@ pub enum TxBuilderError {
     /// Provided bytes could not be decoded into a datum
     #[error("Could not decode datum bytes")]
     MalformedDatum,
+    /// A typed datum or redeemer could not be encoded as plutus data
+    #[error("Could not encode plutus data")]
+    PlutusDataEncoding,
     /// Input, policy, etc pointed to by a redeemer was not found in the
     /// transaction
     #[error("Input/policy pointed to by redeemer not found in tx")]
@@ -28,17 +37,184 @@ pub enum TxBuilderError {
     /// Registration deposit missing
     #[error("Missing stake credential deposit")]
     MissingStakeCredentialDeposit,
+    /// Proposal deposit missing
+    #[error("Missing governance action deposit")]
+    MissingGovActionDeposit,
+    /// Parameter change update isn't a valid `protocol_param_update`
+    #[error("Malformed protocol parameter update")]
+    MalformedParamUpdate,
     /// Mint/burn amount is out of range
     #[error("Invalid mint amount")]
     InvalidMintAmount,
+    /// A single asset doesn't fit in an output value of `max_value_size` bytes
+    #[error("Asset does not fit in an output value of max_value_size bytes")]
+    ValueTooLarge,
+    /// The value of an output takes more than `max_value_size` bytes, see
+    /// `TxBuilder::add_output_split`
+    #[error("Value of output #{index} takes {size} bytes, above max_value_size of {max}")]
+    OutputValueTooLarge {
+        index: usize,
+        size: usize,
+        max: usize,
+    },
+    /// The lovelace of an output isn't enough to fund the outputs it was split into
+    #[error(
+        "Not enough lovelace to split output: {required} lovelace required, {available} available"
+    )]
+    InsufficientLovelaceForSplit { required: u64, available: u64 },
+    /// An output or the mint field contains an asset with a quantity of zero. `output_index` is
+    /// `None` when the asset comes from the mint field or from an output built on its own.
+    #[error(
+        "Asset {}.{} has a quantity of zero (output index: {output_index:?})",
+        hex::encode(policy.0),
+        hex::encode(name)
+    )]
+    ZeroQuantityAsset {
+        policy: Hash<28>,
+        name: Vec<u8>,
+        output_index: Option<usize>,
+    },
     /// Native scripts do not take redeemers
     #[error("Cannot use redeemers with native scripts")]
     RedeemerForNativeScript,
     /// Plutus scripts need a redeemer
     #[error("Plutus scripts must always take a redeemer")]
     RedeemerMissing,
+    /// A balance assertion registered on the builder doesn't hold for the final transaction
+    #[error("Balance assertion failed: {which}, expected {expected}, got {actual}\n{summary}")]
+    AssertionFailed {
+        which: String,
+        expected: String,
+        actual: String,
+        summary: String,
+    },
+    /// Explicit ex units of a redeemer are below its evaluated budget, only returned in strict
+    /// ex units mode
+    #[error(
+        "Ex units for {purpose:?} are below the evaluated budget: provided {provided:?}, required {required:?}"
+    )]
+    ExUnitsBelowEvaluated {
+        purpose: RedeemerPurpose,
+        provided: ExUnits,
+        required: ExUnits,
+    },
+    /// A pinned budget points to a redeemer that isn't part of the transaction
+    #[error("Ex units were pinned for {purpose:?}, but the transaction has no such redeemer")]
+    PinnedExUnitsWithoutRedeemer { purpose: RedeemerPurpose },
+    /// No set of the expected signers satisfies a native script of the transaction within its
+    /// validity interval
+    #[error("Native script {hash} cannot be satisfied by the expected signers")]
+    UnsatisfiableNativeScript { hash: Hash<28> },
+    /// Ex units were pinned, but not for every redeemer
+    #[error("No ex units were pinned for redeemer {purpose:?}")]
+    MissingPinnedExUnits { purpose: RedeemerPurpose },
+    /// Pinned ex units add up to more than the protocol allows in a single transaction
+    #[error("Pinned ex units ({total}) exceed the per transaction limit ({limit})")]
+    ExUnitsAboveLimit { total: ExUnits, limit: ExUnits },
+    /// `lint` reported errors and lints are denied, see `TxBuilder::deny_lints`
+    #[error(
+        "Transaction failed pre-flight lints:\n{}",
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    )]
+    LintFailed { findings: Vec<LintFinding> },
+    /// A hook registered with `TxBuilder::on_phase` returned an error
+    #[error("Hook failed in phase {phase:?}: {message}")]
+    HookFailed { phase: Phase, message: String },
+    /// The same UTxO is both spent and referenced, which the ledger rejects
+    #[error(
+        "UTxO {}#{} is both spent and referenced by the transaction, which the ledger rejects (ConflictingInputsAndReferences)",
+        hex::encode(input.hash.0),
+        input.index
+    )]
+    ConflictingInputsAndReferences { input: Input },
+    /// Nothing was spent explicitly and there is nothing to select from, usually because the
+    /// wallet is unfunded
+    #[error("transaction has no inputs; the change address has no spendable UTxOs")]
+    NoSpendableUtxos,
+    /// The spendable UTxOs don't cover the deposits of the certificates on top of the outputs,
+    /// fee and change
+    #[error(
+        "insufficient funds: need {needed} for outputs, fee and change + {deposit} deposit, have {available}"
+    )]
+    InsufficientFundsForDeposit {
+        needed: u64,
+        deposit: u64,
+        available: u64,
+    },
+    /// The transaction targets Babbage, see `TxBuilder::era`, but uses a feature introduced in
+    /// Conway
+    #[error("{feature} is only available from the Conway era, but the transaction targets Babbage")]
+    ConwayOnlyFeature { feature: &'static str },
+    /// An evaluation doesn't point to any redeemer of the transaction, e.g. because it was made
+    /// for another transaction
+    #[error("Evaluation for {validator} does not point to any redeemer of the transaction")]
+    UnmatchedEvaluation { validator: String },
+    /// A redeemer runs a script that is neither attached nor carried by a reference or spent
+    /// input
+    #[error(
+        "Script {hash} needed for {needed_for:?} is neither attached nor available from a reference or spent input"
+    )]
+    MissingScript {
+        hash: Hash<28>,
+        needed_for: RedeemerPurpose,
+    },
+    /// An input of an `add_script_inputs` batch is locked by another script than the first one
+    #[error(
+        "Script input {}#{} is locked by {found:?}, while the rest of its batch is locked by script {expected}",
+        input.hash,
+        input.index
+    )]
+    MixedScriptBatch {
+        input: Input,
+        expected: Hash<28>,
+        found: Option<Hash<28>>,
+    },
     #[error(
         "Validity interval is disjoint with existing interval, making transaction invalid. This is likely a bug in your code."
     )]
     InvalidValidityInterval,
+    /// A wall-clock validity window was set on a network without a known `SlotConfig`
+    #[error(
+        "No slot config to convert wall-clock validity windows with, set one with `slot_config`"
+    )]
+    MissingSlotConfig,
+    /// A wall-clock validity window doesn't span a whole slot once the margin is applied
+    #[error("Validity window from {from} to {to} does not span a whole slot")]
+    ValidityWindowTooNarrow {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    /// A wall-clock validity window ends at or before the chain tip
+    #[error("Validity window ending at {to} is already over at slot {tip}")]
+    ValidityWindowInPast { to: DateTime<Utc>, tip: u64 },
+    /// A transaction built without change output doesn't balance: `surplus` is the lovelace left
+    /// over beyond the `no_change` threshold, or negative for the lovelace missing
+    #[error(
+        "Transaction without change output has {} lovelace {}",
+        surplus.unsigned_abs(),
+        if *surplus < 0 { "missing" } else { "left over, more than may be added to the fee" }
+    )]
+    UnbalancedWithoutChangeAddress { surplus: i64 },
+    /// The script data hash set with `script_data_hash` isn't the one computed from the
+    /// redeemers, datums and language view of the transaction
+    #[error(
+        "Script data hash {pinned} was set, but the transaction {}",
+        match computed {
+            Some(computed) => format!("hashes to {computed}"),
+            None => "has no script data".to_string(),
+        }
+    )]
+    ScriptDataHashMismatch {
+        pinned: Hash<32>,
+        computed: Option<Hash<32>>,
+    },
+    /// A treasury withdrawal is proposed without a treasury value, and the evaluator `build` was
+    /// given can't query it
+    #[error(
+        "Treasury withdrawals need the current treasury value: set it with `current_treasury_value`, or build with an evaluator that can query it"
+    )]
+    TreasuryValueUnavailable,
+    /// Verification was asked for with `verify_script_data_hash`, but there is no hash to verify
+    #[error("Script data hash verification is on, but no hash was set with `script_data_hash`")]
+    ScriptDataHashNotPinned,
 }
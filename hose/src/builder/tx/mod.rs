@@ -1,7 +1,10 @@
+mod babbage;
 mod built;
+mod era;
 mod error;
 mod staging;
 
 pub use built::BuiltTransaction;
+pub use era::Era;
 pub use error::TxBuilderError;
 pub use staging::StagingTransaction;
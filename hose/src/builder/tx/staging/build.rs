@@ -1,34 +1,187 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Deref as _;
 
-use num::ToPrimitive as _;
 use ogmios_client::codec::RedeemerPurpose as OgmiosRedeemerPurpose;
 use ogmios_client::method::evaluate::Evaluation;
-use pallas::codec::utils::Bytes;
+use pallas::codec::utils::{Bytes, Nullable};
 use pallas::crypto::hash::Hash as PallasHash;
 use pallas::ledger::primitives::conway::{
-    Certificate as PallasCertificate, ExUnits as PallasExUnits, Multiasset, NativeScript,
-    NetworkId, NonZeroInt, PlutusData, PlutusScript, Redeemer, RedeemerTag, ScriptHash,
+    Anchor as PallasAnchor, Certificate as PallasCertificate, ExUnits as PallasExUnits,
+    GovAction as PallasGovAction, GovActionId as PallasGovActionId, Multiasset, NativeScript,
+    NetworkId, NonZeroInt, PlutusData, PlutusScript, ProposalProcedure as PallasProposalProcedure,
+    ProtocolParamUpdate, Redeemer, RedeemerTag, ScriptHash,
     StakeCredential as PallasStakeCredential, TransactionBody, TransactionInput, Tx, WitnessSet,
 };
 use pallas::ledger::primitives::{Fragment, KeepRaw, NonEmptySet};
 use pallas::ledger::traverse::ComputeHash;
 
-use crate::builder::tx::{BuiltTransaction, StagingTransaction, TxBuilderError};
+use crate::builder::tx::{BuiltTransaction, Era, StagingTransaction, TxBuilderError, babbage};
 use crate::primitives::{
-    Certificate, ExUnits, Hash, Output, RedeemerPurpose, RewardAccount, ScriptKind,
+    Certificate, ExUnits, GovAction, GovActionId, Hash, Input, Output, ProposalProcedure,
+    RedeemerPurpose, Script, ScriptEvaluation, ScriptKind, ValidatorBudget,
 };
 
+/// Where a redeemer points to in the transaction, following the ledger ordering of its purpose.
+struct RedeemerPointer {
+    tag: RedeemerTag,
+    index: u32,
+    /// Position among the inputs that have a spend redeemer, which evaluations of spend
+    /// redeemers may be indexed by instead.
+    spend_ordinal: Option<u32>,
+}
+
 impl StagingTransaction {
+    /// Builds the transaction in the format of `self.era`, taking the budget of each redeemer
+    /// without explicit ex units from `evaluations`. See `build_with_budgets`.
+    pub fn build(
+        self,
+        evaluations: Option<Vec<Evaluation>>,
+    ) -> Result<BuiltTransaction, TxBuilderError> {
+        let budgets = match evaluations {
+            Some(evaluations) => Some(self.evaluated_ex_units(&evaluations)?),
+            None => None,
+        };
+        self.build_with_budgets(budgets)
+    }
+
+    /// Builds the transaction in the Conway format, whatever era was set.
     pub fn build_conway(
         self,
         evaluations: Option<Vec<Evaluation>>,
     ) -> Result<BuiltTransaction, TxBuilderError> {
+        self.era(Era::Conway).build(evaluations)
+    }
+
+    /// Builds the transaction in the Babbage format, whatever era was set.
+    pub fn build_babbage(
+        self,
+        evaluations: Option<Vec<Evaluation>>,
+    ) -> Result<BuiltTransaction, TxBuilderError> {
+        self.era(Era::Babbage).build(evaluations)
+    }
+
+    /// Budget of each redeemer according to `evaluations`, keyed by redeemer purpose.
+    ///
+    /// Evaluations that don't point to any redeemer are left out, as they may come from an
+    /// earlier version of the transaction, and redeemers without an evaluation get none.
+    pub(crate) fn evaluated_ex_units(
+        &self,
+        evaluations: &[Evaluation],
+    ) -> Result<HashMap<RedeemerPurpose, ExUnits>, TxBuilderError> {
+        Ok(self
+            .match_evaluations(&validators(evaluations))?
+            .into_iter()
+            .map(|(purpose, index)| (purpose, ex_units_of(&evaluations[index])))
+            .collect())
+    }
+
+    /// Pairs each evaluation with the redeemer it was evaluated for, so that budgets are told
+    /// apart by purpose rather than by their index in the transaction.
+    ///
+    /// Fails with `TxBuilderError::UnmatchedEvaluation` if an evaluation doesn't point to any
+    /// redeemer, i.e. it was made for another transaction.
+    pub fn script_evaluations(
+        &self,
+        evaluations: &[Evaluation],
+    ) -> Result<Vec<ScriptEvaluation>, TxBuilderError> {
+        let matched = self.match_evaluations(&validators(evaluations))?;
+        if let Some(unmatched) = evaluations
+            .iter()
+            .enumerate()
+            .find(|(index, _)| !matched.values().any(|matched| matched == index))
+            .map(|(_, evaluation)| evaluation)
+        {
+            return Err(TxBuilderError::UnmatchedEvaluation {
+                validator: format!(
+                    "{:?}:{}",
+                    unmatched.validator.purpose, unmatched.validator.index
+                ),
+            });
+        }
+
+        let mut matched = matched.into_iter().collect::<Vec<_>>();
+        matched.sort_unstable_by_key(|(_, index)| *index);
+        Ok(matched
+            .into_iter()
+            .map(|(purpose, index)| ScriptEvaluation {
+                purpose,
+                ex_units: ex_units_of(&evaluations[index]),
+            })
+            .collect())
+    }
+
+    /// Index into `validators` of the evaluation of each redeemer. Redeemers without an
+    /// evaluation are left out.
+    ///
+    /// Each redeemer is matched by its tag and index in the built transaction. Evaluations of
+    /// spend redeemers may instead be indexed among the inputs that have one, see
+    /// `RedeemerPointer::spend_ordinal`.
+    pub(super) fn match_evaluations(
+        &self,
+        validators: &[(&OgmiosRedeemerPurpose, u64)],
+    ) -> Result<HashMap<RedeemerPurpose, usize>, TxBuilderError> {
+        Ok(self
+            .redeemer_pointers()?
+            .into_iter()
+            .filter_map(|(purpose, pointer)| {
+                let ogmios_purpose = match pointer.tag {
+                    RedeemerTag::Spend => OgmiosRedeemerPurpose::Spend,
+                    RedeemerTag::Mint => OgmiosRedeemerPurpose::Mint,
+                    RedeemerTag::Cert => OgmiosRedeemerPurpose::Publish,
+                    RedeemerTag::Reward => OgmiosRedeemerPurpose::Withdraw,
+                    RedeemerTag::Vote => OgmiosRedeemerPurpose::Vote,
+                    RedeemerTag::Propose => OgmiosRedeemerPurpose::Propose,
+                };
+                let find = |index: u32| {
+                    validators
+                        .iter()
+                        .position(|(validator_purpose, validator_index)| {
+                            *validator_index == index as u64
+                                && **validator_purpose == ogmios_purpose
+                        })
+                };
+                let index = find(pointer.index).or_else(|| pointer.spend_ordinal.and_then(find))?;
+                Some((purpose, index))
+            })
+            .collect())
+    }
+
+    /// Builds the transaction with evaluated budgets keyed by redeemer purpose.
+    ///
+    /// Explicit ex units always win for their own redeemer, while the others get their evaluated
+    /// budget. When an explicit budget is below the evaluated one, a warning is logged, or
+    /// `TxBuilderError::ExUnitsBelowEvaluated` is returned in strict mode. Without budgets (i.e.
+    /// before the first evaluation), redeemers without explicit ex units get a zero budget.
+    ///
+    /// Optional fields are absent when empty, never encoded as an empty set or map, like
+    /// `cardano-cli` does and hardware wallets expect: certificates, withdrawals, mint,
+    /// collateral inputs, required signers, reference inputs and proposals in the body, and
+    /// scripts, datums and redeemers in the witness set.
+    pub(crate) fn build_with_budgets(
+        self,
+        budgets: Option<HashMap<RedeemerPurpose, ExUnits>>,
+    ) -> Result<BuiltTransaction, TxBuilderError> {
+        let era = self.era;
+        if era == Era::Babbage {
+            self.check_babbage_compatible()?;
+        }
+        let language_view = self.language_view.clone();
+
+        if let Some(input) = self
+            .reference_inputs
+            .iter()
+            .find(|reference_input| self.inputs.contains(reference_input))
+        {
+            return Err(TxBuilderError::ConflictingInputsAndReferences {
+                input: input.clone(),
+            });
+        }
+
         let mut inputs = self
             .inputs
             .iter()
             .map(|x| TransactionInput {
-                transaction_id: x.hash.0.into(),
+                transaction_id: x.hash.into(),
                 index: x.index,
             })
             .collect::<Vec<_>>();
@@ -38,16 +191,33 @@ impl StagingTransaction {
         let outputs = self
             .outputs
             .iter()
-            .map(Output::build_babbage)
+            .enumerate()
+            .map(|(index, output)| {
+                output.build_babbage().map_err(|err| match err {
+                    TxBuilderError::ZeroQuantityAsset { policy, name, .. } => {
+                        TxBuilderError::ZeroQuantityAsset {
+                            policy,
+                            name,
+                            output_index: Some(index),
+                        }
+                    }
+                    err => err,
+                })
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
+        // Same ordering rules as output values, see `Output::build_babbage`.
         let mut mint: BTreeMap<PallasHash<28>, BTreeMap<Bytes, NonZeroInt>> = BTreeMap::new();
 
         for (asset_id, amount) in self.mint.iter() {
             let Ok(amount) = NonZeroInt::try_from(*amount) else {
-                continue;
+                return Err(TxBuilderError::ZeroQuantityAsset {
+                    policy: asset_id.policy,
+                    name: asset_id.name.clone(),
+                    output_index: None,
+                });
             };
-            mint.entry(asset_id.policy.0.into())
+            mint.entry(asset_id.policy.into())
                 .or_default()
                 .insert(asset_id.name.clone().into(), amount);
         }
@@ -55,11 +225,13 @@ impl StagingTransaction {
         let mint: Option<Multiasset<NonZeroInt>> =
             (!mint.is_empty()).then(|| mint.into_iter().collect());
 
+        let redeemer_pointers = self.redeemer_pointers()?;
+
         let collateral = NonEmptySet::from_vec(
             self.collateral_inputs
                 .iter()
                 .map(|x| TransactionInput {
-                    transaction_id: x.hash.0.into(),
+                    transaction_id: x.hash.into(),
                     index: x.index,
                 })
                 .collect(),
@@ -69,7 +241,7 @@ impl StagingTransaction {
             self.disclosed_signers
                 .unwrap_or_default()
                 .iter()
-                .map(|x| x.0.into())
+                .map(|x| (*x).into())
                 .collect(),
         );
 
@@ -82,16 +254,12 @@ impl StagingTransaction {
             None
         };
 
-        let withdrawals = if self.withdrawals.is_empty() {
-            None
-        } else {
-            Some(
-                self.withdrawals
-                    .iter()
-                    .map(|(account, amount)| (account.clone().into(), *amount))
-                    .collect(),
-            )
-        };
+        let withdrawals = (!self.withdrawals.is_empty()).then(|| {
+            self.withdrawals
+                .iter()
+                .map(|(account, amount)| (account.clone().into(), *amount))
+                .collect()
+        });
 
         let collateral_return = self
             .collateral_output
@@ -152,13 +320,6 @@ impl StagingTransaction {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut mint_policies = mint
-            .iter()
-            .flat_map(|x: &Multiasset<NonZeroInt>| x.iter())
-            .map(|(p, _)| *p)
-            .collect::<Vec<_>>();
-        mint_policies.sort();
-
         let certificates = NonEmptySet::from_vec(
             self.certificates
                 .iter()
@@ -251,133 +412,35 @@ impl StagingTransaction {
                 .collect::<Result<Vec<_>, _>>()?,
         );
 
-        let certificate_script_hashes = self
-            .certificates
-            .iter()
-            .flat_map(|cert| cert.script_hash())
-            .collect::<Vec<_>>();
+        let proposal_procedures = NonEmptySet::from_vec(
+            self.proposals
+                .iter()
+                .map(build_proposal)
+                .collect::<Result<Vec<_>, _>>()?,
+        );
 
-        let withdrawal_accounts = self
-            .withdrawals
-            .keys()
-            .cloned()
-            .collect::<Vec<RewardAccount>>();
         let mut redeemers = vec![];
 
         if let Some(rdmrs) = self.redeemers {
-            // Align redeemer indices with the ledger order (purpose + index, with spend ordinals),
-            // not simply the HashMap iteration order.
-            let spend_keys: HashSet<([u8; 32], u64)> = rdmrs
-                .keys()
-                .filter_map(|purpose| match purpose {
-                    RedeemerPurpose::Spend(txin) => Some((txin.hash.0, txin.index)),
-                    _ => None,
-                })
-                .collect();
-            let mut spend_ordinals: HashMap<([u8; 32], u64), u32> = HashMap::new();
-            let mut ordinal = 0u32;
-            for input in &inputs {
-                let key = (*input.transaction_id, input.index);
-                if spend_keys.contains(&key) {
-                    spend_ordinals.insert(key, ordinal);
-                    ordinal += 1;
-                }
-            }
             for (purpose, (pd, ex_units)) in rdmrs.deref().iter() {
-                let (tag, index) = match purpose {
-                    RedeemerPurpose::Spend(txin) => {
-                        let index = inputs
-                            .iter()
-                            .position(|x| (*x.transaction_id, x.index) == (txin.hash.0, txin.index))
-                            .ok_or(TxBuilderError::RedeemerTargetMissing)?
-                            as u32;
-                        (RedeemerTag::Spend, index)
-                    }
-                    RedeemerPurpose::Mint(pid) => {
-                        let index = mint_policies
-                            .iter()
-                            .position(|x| x.as_slice() == pid.0)
-                            .ok_or(TxBuilderError::RedeemerTargetMissing)?
-                            as u32;
-                        (RedeemerTag::Mint, index)
-                    }
-                    RedeemerPurpose::Cert(script_hash) => {
-                        let index = certificate_script_hashes
-                            .iter()
-                            .position(|hash| hash == script_hash)
-                            .ok_or(TxBuilderError::RedeemerTargetMissing)?
-                            as u32;
-                        (RedeemerTag::Cert, index)
-                    }
-                    RedeemerPurpose::Reward(reward_account) => {
-                        let index = withdrawal_accounts
-                            .iter()
-                            .position(|account| account == reward_account)
-                            .ok_or(TxBuilderError::RedeemerTargetMissing)?
-                            as u32;
-                        (RedeemerTag::Reward, index)
-                    }
-                };
-
-                let ex_units = if let Some(ExUnits { mem, steps }) = ex_units {
-                    PallasExUnits {
-                        mem: *mem,
-                        steps: *steps,
-                    }
-                } else if let Some(ref evaluations) = evaluations {
-                    let ogmios_purpose = match tag {
-                        RedeemerTag::Spend => OgmiosRedeemerPurpose::Spend,
-                        RedeemerTag::Mint => OgmiosRedeemerPurpose::Mint,
-                        RedeemerTag::Cert => OgmiosRedeemerPurpose::Publish,
-                        RedeemerTag::Reward => OgmiosRedeemerPurpose::Withdraw,
-                        RedeemerTag::Vote => OgmiosRedeemerPurpose::Vote,
-                        RedeemerTag::Propose => OgmiosRedeemerPurpose::Propose,
-                    };
-                    let mut evaluation = evaluations.iter().find(|e| {
-                        e.validator.index == index as u64 && e.validator.purpose == ogmios_purpose
-                    });
-                    if evaluation.is_none() && matches!(tag, RedeemerTag::Spend) {
-                        if let RedeemerPurpose::Spend(txin) = purpose {
-                            if let Some(ordinal) = spend_ordinals.get(&(txin.hash.0, txin.index)) {
-                                evaluation = evaluations.iter().find(|e| {
-                                    e.validator.index == *ordinal as u64
-                                        && e.validator.purpose == ogmios_purpose
-                                });
-                            }
-                        }
-                    }
-                    let evaluation = evaluation.ok_or(TxBuilderError::RedeemerTargetMissing)?;
-                    PallasExUnits {
-                        mem: evaluation
-                            .budget
-                            .memory
-                            .0
-                            .clone()
-                            .to_integer()
-                            .to_u64()
-                            .unwrap(),
-                        steps: evaluation
-                            .budget
-                            .cpu
-                            .0
-                            .clone()
-                            .to_integer()
-                            .to_u64()
-                            .unwrap(),
-                    }
-                } else {
-                    // FIXME: We shouldn't just assume 0 for the budget, but it will get recalculated later
-                    PallasExUnits { mem: 0, steps: 0 }
-                };
+                let pointer = redeemer_pointers
+                    .get(purpose)
+                    .ok_or(TxBuilderError::RedeemerTargetMissing)?;
+                let ExUnits { mem, steps } = resolve_ex_units(
+                    purpose,
+                    ex_units.as_ref(),
+                    budgets.as_ref(),
+                    self.strict_ex_units,
+                )?;
 
                 let data = PlutusData::decode_fragment(pd.as_ref())
                     .map_err(|_| TxBuilderError::MalformedDatum)?;
 
                 redeemers.push(Redeemer {
-                    tag,
-                    index,
+                    tag: pointer.tag,
+                    index: pointer.index,
                     data,
-                    ex_units,
+                    ex_units: PallasExUnits { mem, steps },
                 })
             }
 
@@ -429,11 +492,11 @@ impl StagingTransaction {
                 network_id,
                 collateral_return,
                 reference_inputs,
-                total_collateral: None,    // TODO
-                voting_procedures: None,   // TODO
-                proposal_procedures: None, // TODO
-                treasury_value: None,      // TODO
-                donation: None,            // TODO
+                total_collateral: None,  // TODO
+                voting_procedures: None, // TODO
+                proposal_procedures,
+                treasury_value: self.treasury_value,
+                donation: None, // TODO
             }
             .into(),
             transaction_witness_set: WitnessSet {
@@ -464,10 +527,310 @@ impl StagingTransaction {
             .map(|ad| ad.compute_hash())
             .into();
 
+        let (hash, bytes, script_data_hash) = match era {
+            Era::Babbage => {
+                babbage::encode_tx(&pallas_tx, language_view.as_ref(), self.script_data_hash)?
+            }
+            Era::Conway => {
+                if let Some(pinned) = self.script_data_hash {
+                    pallas_tx.transaction_body.script_data_hash = Some(pinned.into());
+                }
+                (
+                    Hash::from(pallas_tx.transaction_body.compute_hash()),
+                    pallas_tx.encode_fragment().unwrap(),
+                    script_data_hash.map(Hash::from),
+                )
+            }
+        };
+        if self.verify_script_data_hash {
+            let pinned = self
+                .script_data_hash
+                .ok_or(TxBuilderError::ScriptDataHashNotPinned)?;
+            if script_data_hash != Some(pinned) {
+                return Err(TxBuilderError::ScriptDataHashMismatch {
+                    pinned,
+                    computed: script_data_hash,
+                });
+            }
+        }
+
         Ok(BuiltTransaction {
-            hash: Hash(*pallas_tx.transaction_body.compute_hash()),
-            bytes: pallas_tx.encode_fragment().unwrap(),
+            hash,
+            bytes,
             signatures: None,
+            era,
         })
     }
+
+    /// Fails on the first feature that only exists from the Conway era on.
+    fn check_babbage_compatible(&self) -> Result<(), TxBuilderError> {
+        let conway_only = |feature| Err(TxBuilderError::ConwayOnlyFeature { feature });
+
+        let is_v3 = |script: &Script| script.kind == ScriptKind::PlutusV3;
+        if self.scripts.values().any(is_v3) {
+            return conway_only("Plutus V3 script");
+        }
+        if self
+            .outputs
+            .iter()
+            .chain(&self.collateral_output)
+            .any(|output| output.script.as_ref().is_some_and(is_v3))
+        {
+            return conway_only("Plutus V3 reference script");
+        }
+        if self
+            .language_view
+            .as_ref()
+            .is_some_and(|language_view| language_view.0 == 2)
+        {
+            return conway_only("Plutus V3 language view");
+        }
+        // Without a redeemer, script registrations are built as Babbage certificates already
+        let witnessed_registration = self.certificates.iter().any(|cert| match cert {
+            Certificate::StakeRegistrationScript { script_hash, .. } => self
+                .redeemers
+                .as_ref()
+                .is_some_and(|rdmrs| rdmrs.contains_key(&RedeemerPurpose::Cert(*script_hash))),
+            _ => false,
+        });
+        if witnessed_registration {
+            return conway_only("script-witnessed stake registration");
+        }
+        if !self.proposals.is_empty() {
+            return conway_only("governance proposal");
+        }
+        if self.treasury_value.is_some() {
+            return conway_only("current treasury value");
+        }
+        Ok(())
+    }
+}
+
+impl StagingTransaction {
+    /// One line per redeemer, naming what it points to next to the purpose and index Ogmios
+    /// reports script failures with, e.g. `spend:3 -> input <tx hash>#1`.
+    pub fn redeemer_legend(&self) -> String {
+        let Ok(pointers) = self.redeemer_pointers() else {
+            return String::new();
+        };
+        let mut pointers = pointers.into_iter().collect::<Vec<_>>();
+        pointers.sort_by_key(|(_, pointer)| (pointer.tag, pointer.index));
+        pointers
+            .into_iter()
+            .map(|(purpose, pointer)| {
+                let tag = match pointer.tag {
+                    RedeemerTag::Spend => "spend",
+                    RedeemerTag::Mint => "mint",
+                    RedeemerTag::Cert => "publish",
+                    RedeemerTag::Reward => "withdraw",
+                    RedeemerTag::Vote => "vote",
+                    RedeemerTag::Propose => "propose",
+                };
+                let target = match purpose {
+                    RedeemerPurpose::Spend(input) => {
+                        format!("input {}#{}", input.hash, input.index)
+                    }
+                    RedeemerPurpose::Mint(policy) => format!("policy {policy}"),
+                    RedeemerPurpose::Cert(script_hash) => format!("certificate of {script_hash}"),
+                    RedeemerPurpose::Reward(account) => {
+                        format!("withdrawal from {}", hex::encode(account))
+                    }
+                    RedeemerPurpose::Propose(index) => format!("proposal {index}"),
+                };
+                format!("{tag}:{} -> {target}", pointer.index)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Points each redeemer to its target, as indexed in the built transaction.
+    fn redeemer_pointers(
+        &self,
+    ) -> Result<HashMap<RedeemerPurpose, RedeemerPointer>, TxBuilderError> {
+        let Some(rdmrs) = &self.redeemers else {
+            return Ok(HashMap::new());
+        };
+
+        // Same orderings as the built body: inputs by (tx hash, index), policies by hash, and
+        // certificates, withdrawals and proposals as staged.
+        let mut inputs = self
+            .inputs
+            .iter()
+            .map(|input| (input.hash.0, input.index))
+            .collect::<Vec<_>>();
+        inputs.sort_unstable();
+        let spend_inputs = inputs
+            .iter()
+            .filter(|(hash, index)| {
+                rdmrs.contains_key(&RedeemerPurpose::Spend(Input::new(Hash(*hash), *index)))
+            })
+            .collect::<Vec<_>>();
+
+        let mut mint_policies = self
+            .mint
+            .iter()
+            .map(|(asset_id, _)| asset_id.policy.0)
+            .collect::<Vec<_>>();
+        mint_policies.sort_unstable();
+        mint_policies.dedup();
+
+        let position = |index: Option<usize>| {
+            index
+                .map(|index| index as u32)
+                .ok_or(TxBuilderError::RedeemerTargetMissing)
+        };
+
+        rdmrs
+            .keys()
+            .map(|purpose| {
+                let pointer = match purpose {
+                    RedeemerPurpose::Spend(txin) => {
+                        let key = (txin.hash.0, txin.index);
+                        RedeemerPointer {
+                            tag: RedeemerTag::Spend,
+                            index: position(inputs.iter().position(|input| *input == key))?,
+                            spend_ordinal: spend_inputs
+                                .iter()
+                                .position(|input| **input == key)
+                                .map(|ordinal| ordinal as u32),
+                        }
+                    }
+                    RedeemerPurpose::Mint(pid) => RedeemerPointer {
+                        tag: RedeemerTag::Mint,
+                        index: position(mint_policies.iter().position(|x| *x == pid.0))?,
+                        spend_ordinal: None,
+                    },
+                    RedeemerPurpose::Cert(script_hash) => RedeemerPointer {
+                        tag: RedeemerTag::Cert,
+                        // Indexed among all certificates, key certificates included
+                        index: position(
+                            self.certificates
+                                .iter()
+                                .position(|cert| cert.script_hash() == Some(*script_hash)),
+                        )?,
+                        spend_ordinal: None,
+                    },
+                    RedeemerPurpose::Reward(reward_account) => RedeemerPointer {
+                        tag: RedeemerTag::Reward,
+                        index: position(
+                            self.withdrawals
+                                .keys()
+                                .position(|account| account == reward_account),
+                        )?,
+                        spend_ordinal: None,
+                    },
+                    RedeemerPurpose::Propose(index) => RedeemerPointer {
+                        tag: RedeemerTag::Propose,
+                        index: position((*index < self.proposals.len()).then_some(*index))?,
+                        spend_ordinal: None,
+                    },
+                };
+                Ok((purpose.clone(), pointer))
+            })
+            .collect()
+    }
+}
+
+fn build_proposal(proposal: &ProposalProcedure) -> Result<PallasProposalProcedure, TxBuilderError> {
+    let previous = |id: &Option<GovActionId>| match id {
+        Some(id) => Nullable::Some(PallasGovActionId {
+            transaction_id: id.tx_hash.into(),
+            action_index: id.index,
+        }),
+        None => Nullable::Null,
+    };
+    let guardrails = |hash: &Option<Hash<28>>| match hash {
+        Some(hash) => Nullable::Some((*hash).into()),
+        None => Nullable::Null,
+    };
+
+    let gov_action = match &proposal.gov_action {
+        GovAction::TreasuryWithdrawals {
+            withdrawals,
+            guardrails_script,
+        } => PallasGovAction::TreasuryWithdrawals(
+            withdrawals
+                .iter()
+                .map(|(account, amount)| (account.clone().into(), *amount))
+                .collect(),
+            guardrails(guardrails_script),
+        ),
+        GovAction::ParameterChange {
+            previous: previous_id,
+            update,
+            guardrails_script,
+        } => PallasGovAction::ParameterChange(
+            previous(previous_id),
+            Box::new(
+                ProtocolParamUpdate::decode_fragment(update)
+                    .map_err(|_| TxBuilderError::MalformedParamUpdate)?,
+            ),
+            guardrails(guardrails_script),
+        ),
+        GovAction::HardForkInitiation {
+            previous: previous_id,
+            protocol_version,
+        } => PallasGovAction::HardForkInitiation(previous(previous_id), *protocol_version),
+    };
+
+    Ok(PallasProposalProcedure {
+        deposit: proposal
+            .deposit
+            .ok_or(TxBuilderError::MissingGovActionDeposit)?,
+        reward_account: proposal.return_account.clone().into(),
+        gov_action,
+        anchor: PallasAnchor {
+            url: proposal.anchor.url.clone(),
+            content_hash: proposal.anchor.data_hash.into(),
+        },
+    })
+}
+
+/// Purpose and index of the redeemer each evaluation is for, as reported by Ogmios.
+fn validators(evaluations: &[Evaluation]) -> Vec<(&OgmiosRedeemerPurpose, u64)> {
+    evaluations
+        .iter()
+        .map(|evaluation| (&evaluation.validator.purpose, evaluation.validator.index))
+        .collect()
+}
+
+fn ex_units_of(evaluation: &Evaluation) -> ExUnits {
+    ValidatorBudget::from(evaluation).ex_units
+}
+
+/// Picks the budget of a single redeemer, see `build_with_budgets`.
+fn resolve_ex_units(
+    purpose: &RedeemerPurpose,
+    provided: Option<&ExUnits>,
+    budgets: Option<&HashMap<RedeemerPurpose, ExUnits>>,
+    strict: bool,
+) -> Result<ExUnits, TxBuilderError> {
+    let Some(budgets) = budgets else {
+        // FIXME: We shouldn't just assume 0 for the budget, but it will get recalculated later
+        return Ok(provided.cloned().unwrap_or(ExUnits { mem: 0, steps: 0 }));
+    };
+
+    match (provided, budgets.get(purpose)) {
+        (Some(provided), Some(required)) => {
+            if !required.fits_within(provided) {
+                if strict {
+                    return Err(TxBuilderError::ExUnitsBelowEvaluated {
+                        purpose: purpose.clone(),
+                        provided: provided.clone(),
+                        required: required.clone(),
+                    });
+                }
+                tracing::warn!(
+                    ?purpose,
+                    ?provided,
+                    ?required,
+                    "Provided ex units are below the evaluated budget, the script will likely fail"
+                );
+            }
+            Ok(provided.clone())
+        }
+        (Some(provided), None) => Ok(provided.clone()),
+        (None, Some(required)) => Ok(required.clone()),
+        (None, None) => Err(TxBuilderError::RedeemerTargetMissing),
+    }
 }
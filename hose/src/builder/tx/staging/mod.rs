@@ -4,10 +4,11 @@ use hydrant::primitives::{AssetDelta, AssetId};
 use pallas::codec::minicbor;
 use pallas::ledger::primitives::conway::AuxiliaryData;
 
-use super::TxBuilderError;
+use super::{Era, TxBuilderError};
 use crate::primitives::{
-    Address, AssetsDelta, Certificate, Datum, DatumHash, ExUnits, Hash, Input, Output, PubKeyHash,
-    RedeemerPurpose, Redeemers, RewardAccount, Script, ScriptHash, ScriptKind,
+    Address, AssetsDelta, Certificate, Datum, DatumHash, ExUnits, Hash, Input, Output,
+    ProposalProcedure, PubKeyHash, RedeemerPurpose, Redeemers, RewardAccount, Script, ScriptHash,
+    ScriptKind,
 };
 
 mod build;
@@ -25,6 +26,8 @@ pub struct StagingTransaction {
     pub collateral_inputs: Vec<Input>,
     pub collateral_output: Option<Output>,
     pub disclosed_signers: Option<Vec<PubKeyHash>>,
+    /// Keys native scripts may be signed with, any of their keys when `None`
+    pub expected_signers: Option<Vec<PubKeyHash>>,
     pub scripts: HashMap<ScriptHash, Script>,
     pub datums: HashMap<DatumHash, Datum>,
     pub redeemers: Option<Redeemers>,
@@ -35,6 +38,17 @@ pub struct StagingTransaction {
     pub auxiliary_data: Option<AuxiliaryData>,
     pub certificates: Vec<Certificate>,
     pub withdrawals: BTreeMap<RewardAccount, u64>,
+    pub proposals: Vec<ProposalProcedure>,
+    /// Lovelace in the treasury as the transaction expects it, which the ledger checks
+    pub treasury_value: Option<u64>,
+    pub strict_ex_units: bool,
+    /// Whether building fails when `script_data_hash` differs from the computed one, see
+    /// `verify_script_data_hash`
+    pub verify_script_data_hash: bool,
+    /// Set once every redeemer has been given its ex units up front, see `pin_ex_units`
+    pub ex_units_pinned: bool,
+    /// Format the transaction is serialized in
+    pub era: Era,
     // pub updates: TODO
     // pub phase_2_valid: TODO
 }
@@ -54,8 +68,11 @@ impl StagingTransaction {
         self
     }
 
+    /// Adds a reference input, unless it has already been added.
     pub fn reference_input(mut self, input: Input) -> Self {
-        self.reference_inputs.push(input);
+        if !self.reference_inputs.contains(&input) {
+            self.reference_inputs.push(input);
+        }
         self
     }
 
@@ -156,15 +173,15 @@ impl StagingTransaction {
 
     pub fn disclosed_signer(mut self, pub_key_hash: PubKeyHash) -> Self {
         let mut disclosed_signers = self.disclosed_signers.unwrap_or_default();
-        disclosed_signers.push(Hash(*pub_key_hash));
+        disclosed_signers.push(pub_key_hash);
         self.disclosed_signers = Some(disclosed_signers);
         self
     }
 
     pub fn remove_disclosed_signer(mut self, pub_key_hash: PubKeyHash) -> Self {
         let mut disclosed_signers = self.disclosed_signers.unwrap_or_default();
-        disclosed_signers.retain(|x| *x != Hash(*pub_key_hash));
-        self.disclosed_signers = Some(disclosed_signers);
+        disclosed_signers.retain(|x| *x != pub_key_hash);
+        self.disclosed_signers = Some(disclosed_signers).filter(|signers| !signers.is_empty());
         self
     }
 
@@ -198,7 +215,7 @@ impl StagingTransaction {
     }
 
     pub fn remove_datum_by_hash(mut self, datum_hash: DatumHash) -> Self {
-        self.datums.remove(&Hash(*datum_hash));
+        self.datums.remove(&datum_hash);
         self
     }
 
@@ -219,6 +236,74 @@ impl StagingTransaction {
         self
     }
 
+    /// When enabled, building fails if explicit ex units are below the evaluated budget of their
+    /// redeemer, instead of only logging a warning.
+    pub fn strict_ex_units(mut self, strict: bool) -> Self {
+        self.strict_ex_units = strict;
+        self
+    }
+
+    /// Writes `hash` as the script data hash instead of the one computed from the redeemers,
+    /// datums and language view, e.g. to reproduce a transaction built elsewhere.
+    pub fn script_data_hash(mut self, hash: Hash<32>) -> Self {
+        self.script_data_hash = Some(hash);
+        self
+    }
+
+    pub fn clear_script_data_hash(mut self) -> Self {
+        self.script_data_hash = None;
+        self
+    }
+
+    /// When enabled, building fails with `TxBuilderError::ScriptDataHashMismatch` if the hash set
+    /// with `script_data_hash` isn't the one computed for the transaction, and with
+    /// `TxBuilderError::ScriptDataHashNotPinned` if no hash was set.
+    pub fn verify_script_data_hash(mut self, verify: bool) -> Self {
+        self.verify_script_data_hash = verify;
+        self
+    }
+
+    /// Targets the transaction format of `era`, Conway by default.
+    pub fn era(mut self, era: Era) -> Self {
+        self.era = era;
+        self
+    }
+
+    /// Sets the ex units of every redeemer from `budgets`, so the transaction can be built
+    /// without evaluating it.
+    ///
+    /// Fails if a budget has no matching redeemer, or if a redeemer is left without ex units.
+    pub fn pin_ex_units(
+        mut self,
+        budgets: HashMap<RedeemerPurpose, ExUnits>,
+    ) -> Result<Self, TxBuilderError> {
+        let mut rdmrs = self.redeemers.unwrap_or_default();
+        for (purpose, ex_units) in budgets {
+            let (_, redeemer_ex_units) = rdmrs
+                .get_mut(&purpose)
+                .ok_or(TxBuilderError::PinnedExUnitsWithoutRedeemer { purpose })?;
+            *redeemer_ex_units = Some(ex_units);
+        }
+        if let Some((purpose, _)) = rdmrs.iter().find(|(_, (_, ex_units))| ex_units.is_none()) {
+            return Err(TxBuilderError::MissingPinnedExUnits {
+                purpose: purpose.clone(),
+            });
+        }
+        self.redeemers = Some(rdmrs);
+        self.ex_units_pinned = true;
+
+        Ok(self)
+    }
+
+    /// Sum of the explicit ex units of all redeemers
+    pub fn total_ex_units(&self) -> ExUnits {
+        self.redeemers
+            .iter()
+            .flat_map(|rdmrs| rdmrs.values())
+            .flat_map(|(_, ex_units)| ex_units.as_ref())
+            .sum()
+    }
+
     pub fn add_spend_redeemer(
         mut self,
         input: Input,
@@ -235,7 +320,7 @@ impl StagingTransaction {
     pub fn remove_spend_redeemer(mut self, input: Input) -> Self {
         let mut rdmrs = self.redeemers.unwrap_or_default();
         rdmrs.remove(&RedeemerPurpose::Spend(input));
-        self.redeemers = Some(rdmrs);
+        self.redeemers = Some(rdmrs).filter(|rdmrs| !rdmrs.is_empty());
 
         self
     }
@@ -247,10 +332,7 @@ impl StagingTransaction {
         ex_units: Option<ExUnits>,
     ) -> Self {
         let mut rdmrs = self.redeemers.unwrap_or_default();
-        rdmrs.insert(
-            RedeemerPurpose::Mint(Hash(*policy)),
-            (plutus_data, ex_units),
-        );
+        rdmrs.insert(RedeemerPurpose::Mint(policy), (plutus_data, ex_units));
         self.redeemers = Some(rdmrs);
 
         self
@@ -274,8 +356,8 @@ impl StagingTransaction {
 
     pub fn remove_mint_redeemer(mut self, policy: Hash<28>) -> Self {
         let mut rdmrs = self.redeemers.unwrap_or_default();
-        rdmrs.remove(&RedeemerPurpose::Mint(Hash(*policy)));
-        self.redeemers = Some(rdmrs);
+        rdmrs.remove(&RedeemerPurpose::Mint(policy));
+        self.redeemers = Some(rdmrs).filter(|rdmrs| !rdmrs.is_empty());
 
         self
     }
@@ -359,6 +441,57 @@ impl StagingTransaction {
         self.withdrawals.remove(reward_account);
         self
     }
+
+    /// Adds a governance proposal. Its index in `proposals` identifies the redeemer of its
+    /// guardrails script, see `add_propose_redeemer`.
+    pub fn proposal(mut self, proposal: ProposalProcedure) -> Self {
+        self.proposals.push(proposal);
+        self
+    }
+
+    pub fn remove_proposal(mut self, index: usize) -> Self {
+        self.proposals.remove(index);
+        self
+    }
+
+    pub fn treasury_value(mut self, lovelace: u64) -> Self {
+        self.treasury_value = Some(lovelace);
+        self
+    }
+
+    pub fn add_propose_redeemer(
+        mut self,
+        index: usize,
+        plutus_data: Vec<u8>,
+        ex_units: Option<ExUnits>,
+    ) -> Self {
+        let mut rdmrs = self.redeemers.unwrap_or_default();
+        rdmrs.insert(RedeemerPurpose::Propose(index), (plutus_data, ex_units));
+        self.redeemers = Some(rdmrs);
+
+        self
+    }
+
+    /// Sets the deposit of the proposals that don't have one yet.
+    pub fn apply_gov_action_deposit(mut self, deposit: u64) -> Self {
+        for proposal in &mut self.proposals {
+            proposal.deposit.get_or_insert(deposit);
+        }
+        self
+    }
+
+    /// Limits the keys native scripts are expected to be signed with, which decides how many
+    /// signatures the fee is estimated for.
+    pub fn expected_signers(mut self, signers: Vec<PubKeyHash>) -> Self {
+        self.expected_signers = Some(signers);
+        self
+    }
+
+    pub fn clear_expected_signers(mut self) -> Self {
+        self.expected_signers = None;
+        self
+    }
+
     pub fn signature_amount_override(mut self, amount: u8) -> Self {
         self.signature_amount_override = Some(amount);
         self
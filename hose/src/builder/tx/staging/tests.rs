@@ -1,11 +1,21 @@
+use std::collections::HashMap;
+
+use ogmios_client::codec::RedeemerPurpose as OgmiosRedeemerPurpose;
+use pallas::codec::minicbor::Decoder;
+use pallas::crypto::key::ed25519::SecretKey;
 use pallas::ledger::addresses::{
     Address as PallasAddress, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
 };
 use pallas::ledger::primitives::Fragment;
 use pallas::ledger::primitives::conway::{Certificate as PallasCertificate, RedeemerTag, Tx};
+use pallas::ledger::traverse::{Era as TraverseEra, MultiEraTx};
 
-use super::StagingTransaction;
-use crate::primitives::{Certificate, Hash, Output, RewardAccount};
+use super::{StagingTransaction, TxBuilderError};
+use crate::builder::tx::Era;
+use crate::primitives::{
+    Anchor, Asset, Assets, Certificate, ExUnits, GovAction, Hash, Input, Output, ProposalProcedure,
+    RedeemerPurpose, RewardAccount, ScriptKind,
+};
 
 fn dummy_output() -> Output {
     let payment_hash = Hash([1u8; 28]);
@@ -325,8 +335,6 @@ fn build_includes_legacy_script_registration_certificate() {
 
 #[test]
 fn test_witness_set_determinism() {
-    use crate::primitives::{Input, ScriptKind};
-
     fn local_dummy_output() -> Output {
         let payment_hash = Hash([1u8; 28]);
         let address = PallasAddress::Shelley(ShelleyAddress::new(
@@ -385,3 +393,948 @@ fn test_witness_set_determinism() {
         cbors.len()
     );
 }
+
+fn asset_insertion_order(names: &[&[u8]]) -> Output {
+    names.iter().fold(dummy_output(), |output, name| {
+        let policy = Hash([name[0]; 28]);
+        output
+            .add_asset(policy, name.to_vec(), 1)
+            .expect("add asset")
+            .add_asset(Hash([0u8; 28]), name.to_vec(), 1)
+            .expect("add asset")
+    })
+}
+
+#[test]
+fn output_assets_serialize_canonically() {
+    let abc = asset_insertion_order(&[b"a", b"b", b"c"]);
+    let cba = asset_insertion_order(&[b"c", b"b", b"a"]);
+
+    let abc_bytes = abc
+        .build_babbage()
+        .expect("build abc")
+        .encode_fragment()
+        .expect("encode abc");
+    let cba_bytes = cba
+        .build_babbage()
+        .expect("build cba")
+        .encode_fragment()
+        .expect("encode cba");
+    assert_eq!(abc_bytes, cba_bytes);
+}
+
+#[test]
+fn mint_serializes_canonically() {
+    let build = |names: &[&[u8]]| {
+        names
+            .iter()
+            .fold(
+                StagingTransaction::new().fee(0).output(dummy_output()),
+                |tx, name| {
+                    tx.mint_asset(Hash([name[0]; 28]), name.to_vec(), 1)
+                        .expect("mint asset")
+                        .mint_asset(Hash([0u8; 28]), name.to_vec(), -1)
+                        .expect("mint asset")
+                },
+            )
+            .build_conway(None)
+            .expect("build conway")
+            .bytes
+    };
+
+    assert_eq!(build(&[b"a", b"b", b"c"]), build(&[b"c", b"b", b"a"]));
+}
+
+#[test]
+fn zero_quantity_output_asset_is_rejected() {
+    let policy = Hash([5u8; 28]);
+    assert_eq!(
+        dummy_output().add_asset(policy, b"zero".to_vec(), 0).err(),
+        Some(TxBuilderError::ZeroQuantityAsset {
+            policy,
+            name: b"zero".to_vec(),
+            output_index: None,
+        })
+    );
+
+    let mut assets = Assets::default();
+    assets.add_asset(Asset::new(policy, b"zero".to_vec(), 0));
+    assert_eq!(
+        dummy_output().add_assets(assets).err(),
+        Some(TxBuilderError::ZeroQuantityAsset {
+            policy,
+            name: b"zero".to_vec(),
+            output_index: None,
+        })
+    );
+}
+
+#[test]
+fn zero_quantity_output_asset_fails_the_build() {
+    let policy = Hash([5u8; 28]);
+    let mut output = dummy_output();
+    output
+        .assets
+        .get_or_insert_with(Assets::default)
+        .add_asset(Asset::new(policy, b"zero".to_vec(), 0));
+
+    assert_eq!(
+        output.build_babbage().err(),
+        Some(TxBuilderError::ZeroQuantityAsset {
+            policy,
+            name: b"zero".to_vec(),
+            output_index: None,
+        })
+    );
+
+    let result = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .output(output)
+        .build_conway(None);
+    assert_eq!(
+        result.err(),
+        Some(TxBuilderError::ZeroQuantityAsset {
+            policy,
+            name: b"zero".to_vec(),
+            output_index: Some(1),
+        })
+    );
+}
+
+#[test]
+fn zero_quantity_mint_is_rejected() {
+    let policy = Hash([6u8; 28]);
+    let result = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .mint_asset(policy, b"zero".to_vec(), 0)
+        .expect("mint asset")
+        .build_conway(None);
+
+    assert_eq!(
+        result.err(),
+        Some(TxBuilderError::ZeroQuantityAsset {
+            policy,
+            name: b"zero".to_vec(),
+            output_index: None,
+        })
+    );
+}
+
+#[test]
+fn reference_script_spend_has_script_data_hash() {
+    // The script data hash only covers redeemers, datums and the language view, so spending
+    // with a reference script must yield the same hash as spending with an inline script.
+    let input = Input::new(Hash([7u8; 32]), 0);
+    let reference_input = Input::new(Hash([8u8; 32]), 0);
+    let spend = || {
+        StagingTransaction::new()
+            .fee(0)
+            .input(input.clone())
+            .output(dummy_output())
+            .add_spend_redeemer(input.clone(), vec![0x80], None)
+            .language_view(ScriptKind::PlutusV3, vec![1, 2, 3])
+    };
+
+    let by_reference = spend()
+        .reference_input(reference_input)
+        .build_conway(None)
+        .expect("build by reference");
+    let inline = spend()
+        .script(
+            ScriptKind::PlutusV3,
+            vec![0x46, 0x01, 0x00, 0x00, 0x22, 0x49, 0x9c],
+        )
+        .build_conway(None)
+        .expect("build inline");
+
+    let by_reference = Tx::decode_fragment(&by_reference.bytes).expect("decode by reference");
+    let inline = Tx::decode_fragment(&inline.bytes).expect("decode inline");
+
+    assert!(
+        by_reference
+            .transaction_witness_set
+            .plutus_v3_script
+            .is_none()
+    );
+    assert!(by_reference.transaction_body.script_data_hash.is_some());
+    assert_eq!(
+        by_reference.transaction_body.script_data_hash,
+        inline.transaction_body.script_data_hash
+    );
+}
+
+#[test]
+fn pinned_script_data_hash_is_written_and_verified() {
+    let input = Input::new(Hash([7u8; 32]), 0);
+    let spend = StagingTransaction::new()
+        .fee(0)
+        .input(input.clone())
+        .output(dummy_output())
+        .add_spend_redeemer(input, vec![0x80], Some(ExUnits { mem: 1, steps: 1 }))
+        .language_view(ScriptKind::PlutusV3, vec![1, 2, 3]);
+    let built = spend.clone().build_conway(None).expect("build");
+    let computed = Tx::decode_fragment(&built.bytes)
+        .expect("decode")
+        .transaction_body
+        .script_data_hash
+        .map(Hash::from)
+        .expect("script data hash");
+
+    let pinned = Hash([9u8; 32]);
+    let built = spend
+        .clone()
+        .script_data_hash(pinned)
+        .build_conway(None)
+        .expect("build pinned");
+    let decoded = Tx::decode_fragment(&built.bytes).expect("decode pinned");
+    assert_eq!(
+        decoded.transaction_body.script_data_hash.map(Hash::from),
+        Some(pinned)
+    );
+
+    // Verification only passes for the computed hash
+    spend
+        .clone()
+        .script_data_hash(computed)
+        .verify_script_data_hash(true)
+        .build_conway(None)
+        .expect("verified hash");
+    assert_eq!(
+        spend
+            .script_data_hash(pinned)
+            .verify_script_data_hash(true)
+            .build_conway(None)
+            .unwrap_err(),
+        TxBuilderError::ScriptDataHashMismatch {
+            pinned,
+            computed: Some(computed),
+        }
+    );
+}
+
+#[test]
+fn verifying_without_a_pinned_script_data_hash_fails() {
+    let input = Input::new(Hash([7u8; 32]), 0);
+    let spend = StagingTransaction::new()
+        .fee(0)
+        .input(input.clone())
+        .output(dummy_output())
+        .add_spend_redeemer(input, vec![0x80], Some(ExUnits { mem: 1, steps: 1 }))
+        .language_view(ScriptKind::PlutusV3, vec![1, 2, 3])
+        .verify_script_data_hash(true);
+    assert_eq!(
+        spend.clone().build_conway(None).unwrap_err(),
+        TxBuilderError::ScriptDataHashNotPinned
+    );
+    assert_eq!(
+        spend.build(None).unwrap_err(),
+        TxBuilderError::ScriptDataHashNotPinned
+    );
+}
+
+#[test]
+fn reference_inputs_are_deduplicated() {
+    let input = Input::new(Hash([9u8; 32]), 0);
+    let tx = StagingTransaction::new()
+        .reference_input(input.clone())
+        .reference_input(input.clone());
+
+    assert_eq!(tx.reference_inputs, vec![input]);
+}
+
+#[test]
+fn spending_a_reference_input_is_rejected() {
+    let input = Input::new(Hash([9u8; 32]), 0);
+    let result = StagingTransaction::new()
+        .fee(0)
+        .input(input.clone())
+        .reference_input(input.clone())
+        .output(dummy_output())
+        .build_conway(None);
+
+    let err = result.expect_err("conflicting input and reference input");
+    assert_eq!(
+        err,
+        TxBuilderError::ConflictingInputsAndReferences {
+            input: input.clone()
+        }
+    );
+    assert!(err.to_string().contains("both spent and referenced"));
+}
+
+/// Two spend redeemers (the first with explicit ex units) and a mint redeemer, along with
+/// evaluated budgets for all three.
+fn mixed_budgets_tx(explicit: ExUnits) -> (StagingTransaction, HashMap<RedeemerPurpose, ExUnits>) {
+    let first = Input::new(Hash([1u8; 32]), 0);
+    let second = Input::new(Hash([2u8; 32]), 0);
+    let policy = Hash([9u8; 28]);
+    let unit = vec![0xd8, 0x79, 0x80];
+
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .input(second.clone())
+        .input(first.clone())
+        .output(dummy_output())
+        .mint_asset(policy, b"token".to_vec(), 1)
+        .unwrap()
+        .add_spend_redeemer(first.clone(), unit.clone(), Some(explicit))
+        .add_spend_redeemer(second.clone(), unit.clone(), None)
+        .add_mint_redeemer(policy, unit, None);
+
+    let budgets = HashMap::from([
+        (
+            RedeemerPurpose::Spend(first),
+            ExUnits {
+                mem: 100,
+                steps: 1_000,
+            },
+        ),
+        (
+            RedeemerPurpose::Spend(second),
+            ExUnits {
+                mem: 200,
+                steps: 2_000,
+            },
+        ),
+        (
+            RedeemerPurpose::Mint(policy),
+            ExUnits {
+                mem: 300,
+                steps: 3_000,
+            },
+        ),
+    ]);
+
+    (tx, budgets)
+}
+
+fn built_ex_units(bytes: &[u8]) -> Vec<(RedeemerTag, u32, u64, u64)> {
+    let decoded = Tx::decode_fragment(bytes).expect("decode tx");
+    let redeemers = decoded
+        .transaction_witness_set
+        .redeemer
+        .as_ref()
+        .expect("redeemers missing");
+    let redeemers = match &**redeemers {
+        pallas::ledger::primitives::conway::Redeemers::List(list) => list,
+        _ => panic!("unexpected redeemer format"),
+    };
+    redeemers
+        .iter()
+        .map(|r| (r.tag, r.index, r.ex_units.mem, r.ex_units.steps))
+        .collect()
+}
+
+#[test]
+fn explicit_and_evaluated_ex_units_go_to_their_own_redeemers() {
+    let (tx, budgets) = mixed_budgets_tx(ExUnits {
+        mem: 150,
+        steps: 1_500,
+    });
+    let built = tx
+        .build_with_budgets(Some(budgets))
+        .expect("build with budgets");
+
+    assert_eq!(
+        built_ex_units(&built.bytes),
+        vec![
+            (RedeemerTag::Spend, 0, 150, 1_500),
+            (RedeemerTag::Spend, 1, 200, 2_000),
+            (RedeemerTag::Mint, 0, 300, 3_000),
+        ]
+    );
+}
+
+#[test]
+fn explicit_ex_units_below_evaluated_are_kept_unless_strict() {
+    let explicit = ExUnits {
+        mem: 50,
+        steps: 1_500,
+    };
+
+    let (tx, budgets) = mixed_budgets_tx(explicit.clone());
+    let built = tx
+        .build_with_budgets(Some(budgets))
+        .expect("build with budgets");
+    assert!(built_ex_units(&built.bytes).contains(&(RedeemerTag::Spend, 0, 50, 1_500)));
+
+    let (tx, budgets) = mixed_budgets_tx(explicit.clone());
+    let err = tx
+        .strict_ex_units(true)
+        .build_with_budgets(Some(budgets))
+        .expect_err("explicit ex units below evaluated budget");
+    assert_eq!(
+        err,
+        TxBuilderError::ExUnitsBelowEvaluated {
+            purpose: RedeemerPurpose::Spend(Input::new(Hash([1u8; 32]), 0)),
+            provided: explicit,
+            required: ExUnits {
+                mem: 100,
+                steps: 1_000
+            },
+        }
+    );
+}
+
+/// Key and script certificates interleaved, with cert redeemers for the two script ones.
+fn mixed_certificates_tx() -> StagingTransaction {
+    let unit = vec![0xd8, 0x79, 0x80];
+    StagingTransaction::new()
+        .network_id(0)
+        .fee(0)
+        .output(dummy_output())
+        .add_certificate(Certificate::StakeRegistration {
+            pub_key_hash: Hash([20u8; 28]),
+            deposit: Some(2),
+        })
+        .add_certificate(Certificate::StakeRegistrationScript {
+            script_hash: Hash([21u8; 28]),
+            deposit: Some(2),
+        })
+        .add_certificate(Certificate::StakeDelegation {
+            pub_key_hash: Hash([24u8; 28]),
+            pool_id: Hash([22u8; 28]),
+        })
+        .add_certificate(Certificate::StakeDelegationScript {
+            script_hash: Hash([23u8; 28]),
+            pool_id: Hash([22u8; 28]),
+        })
+        .add_cert_redeemer(Hash([21u8; 28]), unit.clone(), None)
+        .add_cert_redeemer(Hash([23u8; 28]), unit, None)
+}
+
+#[test]
+fn cert_redeemers_are_indexed_among_all_certificates() {
+    let built = mixed_certificates_tx()
+        .build_conway(None)
+        .expect("build conway");
+
+    let mut ex_units = built_ex_units(&built.bytes);
+    ex_units.sort_unstable_by_key(|(_, index, _, _)| *index);
+    assert_eq!(
+        ex_units,
+        vec![(RedeemerTag::Cert, 1, 0, 0), (RedeemerTag::Cert, 3, 0, 0)]
+    );
+}
+
+#[test]
+fn cert_redeemers_without_ex_units_get_their_evaluated_budget() {
+    let budgets = HashMap::from([
+        (
+            RedeemerPurpose::Cert(Hash([21u8; 28])),
+            ExUnits {
+                mem: 100,
+                steps: 1_000,
+            },
+        ),
+        (
+            RedeemerPurpose::Cert(Hash([23u8; 28])),
+            ExUnits {
+                mem: 300,
+                steps: 3_000,
+            },
+        ),
+    ]);
+    let built = mixed_certificates_tx()
+        .build_with_budgets(Some(budgets))
+        .expect("build with budgets");
+
+    let mut ex_units = built_ex_units(&built.bytes);
+    ex_units.sort_unstable_by_key(|(_, index, _, _)| *index);
+    assert_eq!(
+        ex_units,
+        vec![
+            (RedeemerTag::Cert, 1, 100, 1_000),
+            (RedeemerTag::Cert, 3, 300, 3_000),
+        ]
+    );
+}
+
+#[test]
+fn evaluations_are_matched_to_redeemers_by_purpose() {
+    let (tx, _) = mixed_budgets_tx(ExUnits { mem: 1, steps: 1 });
+    let matched = tx
+        .match_evaluations(&[
+            (&OgmiosRedeemerPurpose::Mint, 0),
+            (&OgmiosRedeemerPurpose::Spend, 1),
+            (&OgmiosRedeemerPurpose::Spend, 0),
+            (&OgmiosRedeemerPurpose::Spend, 7),
+        ])
+        .unwrap();
+    assert_eq!(
+        matched,
+        HashMap::from([
+            (RedeemerPurpose::Mint(Hash([9u8; 28])), 0),
+            (RedeemerPurpose::Spend(Input::new(Hash([2u8; 32]), 0)), 1),
+            (RedeemerPurpose::Spend(Input::new(Hash([1u8; 32]), 0)), 2),
+        ])
+    );
+
+    let matched = mixed_certificates_tx()
+        .match_evaluations(&[
+            (&OgmiosRedeemerPurpose::Publish, 3),
+            (&OgmiosRedeemerPurpose::Publish, 0),
+            (&OgmiosRedeemerPurpose::Publish, 1),
+        ])
+        .unwrap();
+    assert_eq!(
+        matched,
+        HashMap::from([
+            (RedeemerPurpose::Cert(Hash([23u8; 28])), 0),
+            (RedeemerPurpose::Cert(Hash([21u8; 28])), 2),
+        ])
+    );
+}
+
+#[test]
+fn spend_evaluations_may_be_indexed_among_spend_redeemers() {
+    // The script input comes second once inputs are sorted, but first among script inputs
+    let script_input = Input::new(Hash([2u8; 32]), 0);
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .input(Input::new(Hash([1u8; 32]), 0))
+        .input(script_input.clone())
+        .output(dummy_output())
+        .add_spend_redeemer(script_input.clone(), vec![0x80], None);
+
+    assert_eq!(
+        tx.match_evaluations(&[(&OgmiosRedeemerPurpose::Spend, 0)])
+            .unwrap(),
+        HashMap::from([(RedeemerPurpose::Spend(script_input), 0)])
+    );
+    assert!(
+        tx.match_evaluations(&[(&OgmiosRedeemerPurpose::Mint, 0)])
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn pinned_ex_units_build_without_evaluation() {
+    let budgets = HashMap::from([
+        (
+            RedeemerPurpose::Cert(Hash([21u8; 28])),
+            ExUnits {
+                mem: 100,
+                steps: 1_000,
+            },
+        ),
+        (
+            RedeemerPurpose::Cert(Hash([23u8; 28])),
+            ExUnits {
+                mem: 300,
+                steps: 3_000,
+            },
+        ),
+    ]);
+    let tx = mixed_certificates_tx()
+        .pin_ex_units(budgets)
+        .expect("pin ex units");
+    assert!(tx.ex_units_pinned);
+    assert_eq!(
+        tx.total_ex_units(),
+        ExUnits {
+            mem: 400,
+            steps: 4_000,
+        }
+    );
+
+    let built = tx.build_conway(None).expect("build conway");
+    let mut ex_units = built_ex_units(&built.bytes);
+    ex_units.sort_unstable_by_key(|(_, index, _, _)| *index);
+    assert_eq!(
+        ex_units,
+        vec![
+            (RedeemerTag::Cert, 1, 100, 1_000),
+            (RedeemerTag::Cert, 3, 300, 3_000),
+        ]
+    );
+}
+
+#[test]
+fn pinned_ex_units_must_match_the_redeemers() {
+    let ex_units = ExUnits {
+        mem: 100,
+        steps: 1_000,
+    };
+
+    let missing = mixed_certificates_tx().pin_ex_units(HashMap::from([(
+        RedeemerPurpose::Cert(Hash([21u8; 28])),
+        ex_units.clone(),
+    )]));
+    assert_eq!(
+        missing,
+        Err(TxBuilderError::MissingPinnedExUnits {
+            purpose: RedeemerPurpose::Cert(Hash([23u8; 28])),
+        })
+    );
+
+    let unknown = mixed_certificates_tx().pin_ex_units(HashMap::from([
+        (RedeemerPurpose::Cert(Hash([21u8; 28])), ex_units.clone()),
+        (RedeemerPurpose::Cert(Hash([23u8; 28])), ex_units.clone()),
+        (RedeemerPurpose::Mint(Hash([25u8; 28])), ex_units),
+    ]));
+    assert_eq!(
+        unknown,
+        Err(TxBuilderError::PinnedExUnitsWithoutRedeemer {
+            purpose: RedeemerPurpose::Mint(Hash([25u8; 28])),
+        })
+    );
+}
+
+fn babbage_spend_tx() -> StagingTransaction {
+    let input = Input::new(Hash([7u8; 32]), 0);
+    StagingTransaction::new()
+        .network_id(0)
+        .fee(200_000)
+        .input(input.clone())
+        .collateral_input(Input::new(Hash([8u8; 32]), 1))
+        .output(dummy_output())
+        .disclosed_signer(Hash([5u8; 28]))
+        .script(
+            ScriptKind::PlutusV2,
+            vec![0x46, 0x01, 0x00, 0x00, 0x22, 0x49, 0x9c],
+        )
+        .add_spend_redeemer(input, vec![0x80], Some(ExUnits { mem: 1, steps: 1 }))
+        .language_view(ScriptKind::PlutusV2, vec![1, 2, 3])
+        .era(Era::Babbage)
+}
+
+#[test]
+fn babbage_build_has_no_set_tags() {
+    let built = babbage_spend_tx().build(None).expect("build babbage");
+    assert_eq!(built.era, Era::Babbage);
+    assert!(
+        !built
+            .bytes
+            .windows(3)
+            .any(|bytes| bytes == [0xd9, 0x01, 0x02])
+    );
+
+    let decoded =
+        MultiEraTx::decode_for_era(TraverseEra::Babbage, &built.bytes).expect("decode babbage");
+    assert_eq!(Hash::from(decoded.hash()), built.hash);
+    assert_eq!(decoded.inputs().len(), 1);
+    assert_eq!(decoded.redeemers().len(), 1);
+
+    let conway = babbage_spend_tx().build_conway(None).expect("build conway");
+    assert_eq!(conway.era, Era::Conway);
+    assert_ne!(conway.hash, built.hash);
+}
+
+#[test]
+fn babbage_script_data_hash_is_verified_against_its_own_encoding() {
+    let pinned = Hash([9u8; 32]);
+    let err = babbage_spend_tx()
+        .script_data_hash(pinned)
+        .verify_script_data_hash(true)
+        .build(None)
+        .unwrap_err();
+    let TxBuilderError::ScriptDataHashMismatch {
+        computed: Some(computed),
+        ..
+    } = err
+    else {
+        panic!("unexpected error: {err}");
+    };
+
+    let built = babbage_spend_tx()
+        .script_data_hash(computed)
+        .verify_script_data_hash(true)
+        .build(None)
+        .expect("verified hash");
+    assert_eq!(
+        built.bytes,
+        babbage_spend_tx().build(None).expect("build").bytes
+    );
+    let pinned = babbage_spend_tx()
+        .script_data_hash(pinned)
+        .build(None)
+        .expect("build pinned");
+    assert!(pinned.bytes.windows(32).any(|bytes| bytes == [9u8; 32]));
+}
+
+#[test]
+fn babbage_rejects_conway_only_features() {
+    let v3_script = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .script(
+            ScriptKind::PlutusV3,
+            vec![0x46, 0x01, 0x00, 0x00, 0x22, 0x49, 0x9c],
+        );
+    assert!(v3_script.clone().build_conway(None).is_ok());
+    assert_eq!(
+        v3_script.build_babbage(None).err(),
+        Some(TxBuilderError::ConwayOnlyFeature {
+            feature: "Plutus V3 script"
+        })
+    );
+
+    let script_hash = Hash([3u8; 28]);
+    let registration = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .add_certificate(Certificate::StakeRegistrationScript {
+            script_hash,
+            deposit: Some(2),
+        });
+    assert!(registration.clone().build_babbage(None).is_ok());
+    assert_eq!(
+        registration
+            .add_cert_redeemer(script_hash, vec![0u8], None)
+            .build_babbage(None)
+            .err(),
+        Some(TxBuilderError::ConwayOnlyFeature {
+            feature: "script-witnessed stake registration"
+        })
+    );
+}
+
+/// Keys of the body map of the transaction in `bytes`, in encoding order.
+fn body_keys(bytes: &[u8]) -> Vec<u64> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.array().unwrap();
+    let entries = decoder.map().unwrap().expect("definite body map");
+    (0..entries)
+        .map(|_| {
+            let key = decoder.u64().unwrap();
+            decoder.skip().unwrap();
+            key
+        })
+        .collect()
+}
+
+#[test]
+fn empty_collections_are_left_out_of_the_body() {
+    let payment = || {
+        StagingTransaction::new()
+            .fee(170_000)
+            .input(Input::new(Hash([9u8; 32]), 0))
+            .output(dummy_output())
+    };
+    let key_hash = Hash([5u8; 28]);
+    let registration = Certificate::StakeRegistration {
+        pub_key_hash: key_hash,
+        deposit: Some(2_000_000),
+    };
+
+    // Fields of the same transactions out of `cardano-cli conway transaction build-raw`:
+    // inputs, outputs and fee, plus certificates for the registration
+    let built = payment().build_conway(None).expect("build payment");
+    assert_eq!(body_keys(&built.bytes), [0, 1, 2]);
+    let certified = payment()
+        .add_certificate(registration.clone())
+        .build_conway(None)
+        .expect("build registration");
+    assert_eq!(body_keys(&certified.bytes), [0, 1, 2, 4]);
+
+    // Collections emptied again encode exactly like they were never set
+    let reward_account = RewardAccount::from_key_hash(Network::Testnet, key_hash);
+    let policy = Hash([3u8; 28]);
+    let emptied = payment()
+        .add_certificate(registration)
+        .remove_certificate_by_pub_key_hash(key_hash)
+        .withdrawal(reward_account.clone(), 0)
+        .remove_withdrawal(&reward_account)
+        .mint_asset(policy, b"A".to_vec(), 1)
+        .unwrap()
+        .remove_mint_asset(policy, b"A".to_vec())
+        .collateral_input(Input::new(Hash([8u8; 32]), 0))
+        .remove_collateral_input(Input::new(Hash([8u8; 32]), 0))
+        .reference_input(Input::new(Hash([7u8; 32]), 0))
+        .remove_reference_input(Input::new(Hash([7u8; 32]), 0))
+        .disclosed_signer(key_hash)
+        .remove_disclosed_signer(key_hash)
+        .add_mint_redeemer(policy, vec![0u8], None)
+        .remove_mint_redeemer(policy);
+    assert_eq!(emptied.disclosed_signers, None);
+    assert_eq!(emptied.redeemers, None);
+    assert_eq!(
+        emptied.clone().build_conway(None).unwrap().bytes,
+        built.bytes
+    );
+    assert_eq!(
+        emptied.build_babbage(None).unwrap().bytes,
+        payment().build_babbage(None).unwrap().bytes
+    );
+}
+
+#[test]
+fn signing_a_babbage_tx_keeps_its_body() {
+    let built = babbage_spend_tx().build(None).expect("build babbage");
+    let body = |bytes: &[u8]| {
+        let mut decoder = Decoder::new(bytes);
+        decoder.array().unwrap();
+        let start = decoder.position();
+        decoder.skip().unwrap();
+        bytes[start..decoder.position()].to_vec()
+    };
+
+    let key = SecretKey::from([4u8; 32]);
+    let signed = built.clone().sign(&key).expect("sign");
+    assert_eq!(body(&signed.bytes), body(&built.bytes));
+    let decoded =
+        MultiEraTx::decode_for_era(TraverseEra::Babbage, &signed.bytes).expect("decode signed");
+    assert_eq!(decoded.vkey_witnesses().len(), 1);
+    assert_eq!(Hash::from(decoded.hash()), built.hash);
+
+    let unsigned = signed.remove_signature(key.public_key()).expect("remove");
+    assert_eq!(unsigned.bytes, built.bytes);
+}
+
+fn treasury_withdrawal_proposal(guardrails_script: Option<Hash<28>>) -> ProposalProcedure {
+    let beneficiary = RewardAccount::from_key_hash(Network::Testnet, Hash([7u8; 28]));
+    ProposalProcedure {
+        gov_action: GovAction::TreasuryWithdrawals {
+            withdrawals: [(beneficiary.clone(), 1_000_000)].into(),
+            guardrails_script,
+        },
+        deposit: Some(100_000_000_000),
+        return_account: beneficiary,
+        anchor: Anchor::new("https://dao.example/p1", Hash([9u8; 32])),
+    }
+}
+
+#[test]
+fn treasury_withdrawal_proposal_matches_the_conway_cddl() {
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .proposal(treasury_withdrawal_proposal(Some(Hash([8u8; 28]))));
+
+    let built = tx.build_conway(None).expect("build conway");
+    let decoded = Tx::decode_fragment(&built.bytes).expect("decode tx");
+    let proposals = decoded
+        .transaction_body
+        .proposal_procedures
+        .as_ref()
+        .expect("proposals missing");
+    assert_eq!(proposals.len(), 1);
+
+    // proposal_procedure = [deposit, reward_account, gov_action, anchor]
+    let expected = [
+        "84".to_string(),
+        "1b000000174876e800".to_string(),
+        format!("581de0{}", "07".repeat(28)),
+        // treasury_withdrawals_action = [2, {reward_account => coin}, policy_hash / nil]
+        format!(
+            "8302a1581de0{}1a000f4240581c{}",
+            "07".repeat(28),
+            "08".repeat(28)
+        ),
+        // anchor = [url, anchor_data_hash]
+        format!(
+            "8276{}5820{}",
+            hex::encode("https://dao.example/p1"),
+            "09".repeat(32)
+        ),
+    ]
+    .concat();
+    assert_eq!(
+        hex::encode(proposals[0].encode_fragment().unwrap()),
+        expected
+    );
+}
+
+#[test]
+fn treasury_value_is_declared_in_the_body() {
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .proposal(treasury_withdrawal_proposal(None))
+        .treasury_value(1_500_000_000_000_000);
+
+    let built = tx.clone().build_conway(None).expect("build conway");
+    let decoded = Tx::decode_fragment(&built.bytes).expect("decode tx");
+    assert_eq!(
+        decoded.transaction_body.treasury_value,
+        Some(1_500_000_000_000_000)
+    );
+
+    let mut without_proposal = tx;
+    without_proposal.proposals.clear();
+    assert_eq!(
+        without_proposal.build_babbage(None).err(),
+        Some(TxBuilderError::ConwayOnlyFeature {
+            feature: "current treasury value"
+        })
+    );
+}
+
+#[test]
+fn propose_redeemers_point_to_their_proposal() {
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .proposal(treasury_withdrawal_proposal(None))
+        .proposal(treasury_withdrawal_proposal(Some(Hash([8u8; 28]))))
+        .add_propose_redeemer(1, vec![0u8], Some(ExUnits { mem: 1, steps: 2 }));
+
+    let built = tx.clone().build_conway(None).expect("build conway");
+    assert_eq!(
+        built_ex_units(&built.bytes),
+        vec![(RedeemerTag::Propose, 1, 1, 2)]
+    );
+
+    assert_eq!(
+        tx.add_propose_redeemer(2, vec![0u8], None)
+            .build_conway(None)
+            .err(),
+        Some(TxBuilderError::RedeemerTargetMissing)
+    );
+}
+
+#[test]
+fn proposal_deposits_default_to_the_protocol_params() {
+    let mut without_deposit = treasury_withdrawal_proposal(None);
+    without_deposit.deposit = None;
+    let tx = StagingTransaction::new()
+        .fee(0)
+        .output(dummy_output())
+        .proposal(without_deposit)
+        .proposal(treasury_withdrawal_proposal(None));
+
+    assert_eq!(
+        tx.clone().build_conway(None).err(),
+        Some(TxBuilderError::MissingGovActionDeposit)
+    );
+
+    let tx = tx.apply_gov_action_deposit(5);
+    let deposits = tx
+        .proposals
+        .iter()
+        .map(|proposal| proposal.deposit)
+        .collect::<Vec<_>>();
+    assert_eq!(deposits, vec![Some(5), Some(100_000_000_000)]);
+    assert_eq!(
+        tx.build_babbage(None).err(),
+        Some(TxBuilderError::ConwayOnlyFeature {
+            feature: "governance proposal"
+        })
+    );
+}
+
+#[test]
+fn redeemer_legend_names_the_redeemer_targets() {
+    let policy = Hash([4u8; 28]);
+    let spent = Input::new(Hash([2u8; 32]), 1);
+    let tx = StagingTransaction::new()
+        .input(Input::new(Hash([1u8; 32]), 0))
+        .input(spent.clone())
+        .add_spend_redeemer(spent, vec![0u8], None)
+        .mint_asset(policy, b"token".to_vec(), 1)
+        .unwrap()
+        .add_mint_redeemer(policy, vec![0u8], None);
+
+    assert_eq!(
+        tx.redeemer_legend(),
+        format!(
+            "spend:1 -> input {}#1\nmint:0 -> policy {}",
+            Hash([2u8; 32]),
+            policy
+        )
+    );
+}
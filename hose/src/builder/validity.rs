@@ -0,0 +1,213 @@
+//! Validity intervals given in wall-clock time
+//!
+//! Windows set with `TxBuilder::valid_for` and `TxBuilder::valid_between` are converted into
+//! slots during `build`, against the tip of the chain at that point and the network's
+//! `SlotConfig`.
+
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use intervals_general::Interval;
+use intervals_general::bound_pair::BoundPair;
+
+use super::TxBuilder;
+use super::tx::TxBuilderError;
+use crate::primitives::SlotConfig;
+
+/// Slack applied to validity windows given in wall-clock time, so that the transaction is
+/// neither rejected nor valid for longer than asked when the clocks of the builder and of the
+/// chain disagree a little.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ValidityMargin {
+    /// Slots the window opens before the requested start
+    pub start_slots: u64,
+    /// Slots the window closes before the requested end
+    pub end_slots: u64,
+}
+
+impl Default for ValidityMargin {
+    fn default() -> Self {
+        Self {
+            start_slots: 1,
+            end_slots: 1,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub(crate) enum WallClockWindow {
+    /// From the tip on
+    For(Duration),
+    Between(DateTime<Utc>, DateTime<Utc>),
+}
+
+impl TxBuilder {
+    /// Slot config to convert wall-clock windows with: the one set with `slot_config`, or
+    /// mainnet's on mainnet.
+    fn effective_slot_config(&self) -> Result<SlotConfig, TxBuilderError> {
+        match (self.slot_config, self.body.network_id) {
+            (Some(config), _) => Ok(config),
+            (None, Some(1)) => Ok(SlotConfig::mainnet()),
+            (None, _) => Err(TxBuilderError::MissingSlotConfig),
+        }
+    }
+
+    /// Converts the wall-clock windows into slots, given the slot of the tip, and bounds the
+    /// validity interval by each of them.
+    pub(crate) fn apply_wall_clock_windows(mut self, tip: u64) -> Result<Self, TxBuilderError> {
+        if self.wall_clock_windows.is_empty() {
+            return Ok(self);
+        }
+        let config = self.effective_slot_config()?;
+        for window in std::mem::take(&mut self.wall_clock_windows) {
+            let interval = slot_interval(window, &config, self.validity_margin, tip)?;
+            self = self.validity_interval(interval)?;
+        }
+        Ok(self)
+    }
+}
+
+/// Slots `[start, end)` covering `window` minus the margins. Fails when the window lies within
+/// a single slot, or when it's over by the `tip`.
+pub(crate) fn slot_interval(
+    window: WallClockWindow,
+    config: &SlotConfig,
+    margin: ValidityMargin,
+    tip: u64,
+) -> Result<Interval<u64>, TxBuilderError> {
+    let (from, to) = match window {
+        WallClockWindow::For(duration) => {
+            let from = config.time_of(tip);
+            let duration = TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX);
+            (
+                from,
+                from.checked_add_signed(duration)
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            )
+        }
+        WallClockWindow::Between(from, to) => (from, to),
+    };
+
+    let (start, end) = (config.slot_at(from), config.slot_at(to));
+    let too_narrow = TxBuilderError::ValidityWindowTooNarrow { from, to };
+    if end <= start {
+        return Err(too_narrow);
+    }
+    let start = start.saturating_sub(margin.start_slots);
+    let end = end.saturating_sub(margin.end_slots);
+    if end <= start {
+        return Err(too_narrow);
+    }
+    if end <= tip {
+        return Err(TxBuilderError::ValidityWindowInPast { to, tip });
+    }
+
+    Ok(Interval::RightHalfOpen {
+        bound_pair: BoundPair::new(start, end).expect("start is below end"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::TimeDelta;
+    use intervals_general::Interval;
+
+    use super::{ValidityMargin, WallClockWindow, slot_interval};
+    use crate::builder::tx::TxBuilderError;
+    use crate::primitives::SlotConfig;
+
+    // One second slots from slot 100 on, with the tip at slot 1000
+    const TIP: u64 = 1_000;
+
+    fn config() -> SlotConfig {
+        SlotConfig::from_millis(1_700_000_000_000, 100, 1_000)
+    }
+
+    /// Bounds of the `[start, end)` interval, if that's what `result` holds.
+    fn bounds(result: Result<Interval<u64>, TxBuilderError>) -> Result<(u64, u64), TxBuilderError> {
+        match result? {
+            Interval::RightHalfOpen { bound_pair } => Ok((*bound_pair.left(), *bound_pair.right())),
+            _ => panic!("expected a right half-open interval"),
+        }
+    }
+
+    #[test]
+    fn margins_widen_the_start_and_pull_in_the_end() {
+        let window = WallClockWindow::For(Duration::from_secs(600));
+        assert_eq!(
+            bounds(slot_interval(
+                window,
+                &config(),
+                ValidityMargin::default(),
+                TIP
+            )),
+            Ok((999, 1_599))
+        );
+
+        let margin = ValidityMargin {
+            start_slots: 5,
+            end_slots: 30,
+        };
+        assert_eq!(
+            bounds(slot_interval(window, &config(), margin, TIP)),
+            Ok((995, 1_570))
+        );
+
+        let from = config().time_of(2_000) + TimeDelta::milliseconds(500);
+        let window = WallClockWindow::Between(from, from + TimeDelta::seconds(60));
+        assert_eq!(
+            bounds(slot_interval(
+                window,
+                &config(),
+                ValidityMargin::default(),
+                TIP
+            )),
+            Ok((1_999, 2_059))
+        );
+    }
+
+    #[test]
+    fn degenerate_windows_are_rejected() {
+        let margin = ValidityMargin::default();
+
+        // Both ends within slot 2000
+        let from = config().time_of(2_000);
+        let to = from + TimeDelta::milliseconds(900);
+        assert_eq!(
+            bounds(slot_interval(
+                WallClockWindow::Between(from, to),
+                &config(),
+                margin,
+                TIP
+            )),
+            Err(TxBuilderError::ValidityWindowTooNarrow { from, to })
+        );
+        let to = from - TimeDelta::seconds(10);
+        assert_eq!(
+            bounds(slot_interval(
+                WallClockWindow::Between(from, to),
+                &config(),
+                margin,
+                TIP
+            )),
+            Err(TxBuilderError::ValidityWindowTooNarrow { from, to })
+        );
+
+        // Over by slot 900, or once the margin is applied
+        let from = config().time_of(800);
+        for end in [900, TIP + 1] {
+            let to = config().time_of(end);
+            assert_eq!(
+                bounds(slot_interval(
+                    WallClockWindow::Between(from, to),
+                    &config(),
+                    margin,
+                    TIP
+                )),
+                Err(TxBuilderError::ValidityWindowInPast { to, tip: TIP })
+            );
+        }
+    }
+}
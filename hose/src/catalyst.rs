@@ -0,0 +1,487 @@
+//! Catalyst vote registrations (CIP-36)
+//!
+//! A registration is transaction metadata under two labels: `REGISTRATION_LABEL` holds the
+//! delegations, stake key, reward address and nonce, `WITNESS_LABEL` holds a signature by the
+//! stake key. The signature covers the blake2b-256 hash of the CBOR map
+//! `{ 61284: <registration> }`, so the registration must be encoded exactly as it was signed:
+//! `RegistrationBuilder` always uses the canonical encoding (definite lengths, ascending keys,
+//! minimal integers), and `parse_registration` verifies against the bytes found on chain rather
+//! than a re-encoding.
+//!
+//! ```ignore
+//! let registration = RegistrationBuilder::new(wallet.address(), tip_slot)
+//!     .delegate(vote_key, 1)
+//!     .sign(&wallet)?;
+//! let tx = TxBuilder::new(network_id, wallet.address())
+//!     .add_auxiliary_data(registration.to_metadata());
+//! ```
+
+use std::convert::Infallible;
+
+use pallas::codec::minicbor::{self, Decoder, Encoder, data::Type};
+use pallas::crypto::hash::Hasher;
+use pallas::crypto::key::ed25519;
+use pallas::ledger::addresses::Address;
+use thiserror::Error;
+
+use crate::primitives::{Hash, PublicKey, Signature};
+use crate::wallet::{PrivateKey, Wallet};
+
+type EncodeError = minicbor::encode::Error<Infallible>;
+
+/// Metadata label of the registration itself
+pub const REGISTRATION_LABEL: u64 = 61284;
+/// Metadata label of the stake key signature
+pub const WITNESS_LABEL: u64 = 61285;
+
+/// Voting purpose of Catalyst, the default
+pub const CATALYST_VOTING_PURPOSE: u64 = 0;
+
+/// Share of the voting power assigned to a vote key, relative to the other delegations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    pub vote_key: PublicKey,
+    pub weight: u32,
+}
+
+/// A signed registration, as built by `RegistrationBuilder` or found on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub delegations: Vec<Delegation>,
+    /// Key whose stake is registered, and which signs the registration
+    pub stake_key: PublicKey,
+    /// Where voting rewards are paid
+    pub payment_address: Address,
+    /// Later registrations supersede earlier ones, usually the current slot
+    pub nonce: u64,
+    pub voting_purpose: u64,
+    pub signature: Signature,
+}
+
+impl Registration {
+    /// Complete transaction metadata, to be attached with `TxBuilder::add_auxiliary_data`.
+    pub fn to_metadata(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(Vec::new());
+        (|| -> Result<(), EncodeError> {
+            encoder.map(2)?.u64(REGISTRATION_LABEL)?;
+            encode_registration(
+                &mut encoder,
+                &self.delegations,
+                &self.stake_key,
+                &self.payment_address,
+                self.nonce,
+                self.voting_purpose,
+            )?;
+            encoder
+                .u64(WITNESS_LABEL)?
+                .map(1)?
+                .u8(1)?
+                .bytes(&self.signature.0)?;
+            Ok(())
+        })()
+        .expect("encoding to a vec is infallible");
+        encoder.into_writer()
+    }
+}
+
+/// Builds and signs a registration.
+#[derive(Debug, Clone)]
+pub struct RegistrationBuilder {
+    delegations: Vec<Delegation>,
+    payment_address: Address,
+    nonce: u64,
+    voting_purpose: u64,
+}
+
+impl RegistrationBuilder {
+    /// Registration paying rewards to `payment_address`. `nonce` must be higher than the nonce of
+    /// any previous registration of the same stake key, the current slot is the usual choice.
+    pub fn new(payment_address: Address, nonce: u64) -> Self {
+        Self {
+            delegations: Vec::new(),
+            payment_address,
+            nonce,
+            voting_purpose: CATALYST_VOTING_PURPOSE,
+        }
+    }
+
+    /// Delegates `weight` shares of the voting power to `vote_key`. Delegations are kept in the
+    /// order they were added.
+    pub fn delegate(mut self, vote_key: PublicKey, weight: u32) -> Self {
+        self.delegations.push(Delegation { vote_key, weight });
+        self
+    }
+
+    /// Defaults to `CATALYST_VOTING_PURPOSE`.
+    pub fn voting_purpose(mut self, voting_purpose: u64) -> Self {
+        self.voting_purpose = voting_purpose;
+        self
+    }
+
+    /// Signs with the stake key of `wallet`, which must have one.
+    pub fn sign(self, wallet: &Wallet) -> Result<Registration, Error> {
+        let stake_key = wallet.stake_key().ok_or(Error::MissingStakeKey)?;
+        self.sign_with(stake_key)
+    }
+
+    /// Signs with `stake_key`, registering its stake.
+    pub fn sign_with(self, stake_key: &PrivateKey) -> Result<Registration, Error> {
+        if self.delegations.is_empty() {
+            return Err(Error::NoDelegations);
+        }
+        let stake_public_key = Hash(
+            stake_key
+                .public_key()
+                .as_ref()
+                .try_into()
+                .expect("ed25519 public keys are 32 bytes"),
+        );
+
+        let mut encoder = Encoder::new(Vec::new());
+        (|| -> Result<(), EncodeError> {
+            encoder.map(1)?.u64(REGISTRATION_LABEL)?;
+            encode_registration(
+                &mut encoder,
+                &self.delegations,
+                &stake_public_key,
+                &self.payment_address,
+                self.nonce,
+                self.voting_purpose,
+            )
+        })()
+        .expect("encoding to a vec is infallible");
+        let signature = stake_key.sign(payload_hash(encoder.writer()));
+
+        Ok(Registration {
+            delegations: self.delegations,
+            stake_key: stake_public_key,
+            payment_address: self.payment_address,
+            nonce: self.nonce,
+            voting_purpose: self.voting_purpose,
+            signature: Hash(
+                signature
+                    .as_ref()
+                    .try_into()
+                    .expect("ed25519 signatures are 64 bytes"),
+            ),
+        })
+    }
+}
+
+/// Reads the registration out of transaction metadata (the auxiliary data of a transaction, in
+/// any era's encoding) and checks its signature.
+///
+/// CIP-15 registrations, with a single vote key and no voting purpose, are read as a delegation
+/// of weight 1 for Catalyst.
+pub fn parse_registration(metadata: &[u8]) -> Result<Registration, Error> {
+    let mut decoder = Decoder::new(metadata);
+    seek_metadata(&mut decoder)?;
+
+    let mut registration = None;
+    let mut witness = None;
+    for _ in 0..definite_map(&mut decoder)? {
+        let label = decoder.u64()?;
+        let start = decoder.position();
+        match label {
+            REGISTRATION_LABEL => {
+                let fields = decode_registration(&mut decoder)?;
+                registration = Some((fields, &metadata[start..decoder.position()]));
+            }
+            WITNESS_LABEL => witness = Some(decode_witness(&mut decoder)?),
+            _ => decoder.skip()?,
+        }
+    }
+    let (fields, raw) = registration.ok_or(Error::MissingRegistration)?;
+    let signature = witness.ok_or(Error::MissingWitness)?;
+
+    // The signed payload is the registration as found on chain, under its label
+    let mut encoder = Encoder::new(Vec::new());
+    encoder
+        .map(1)
+        .and_then(|encoder| encoder.u64(REGISTRATION_LABEL))
+        .expect("encoding to a vec is infallible");
+    let mut payload = encoder.into_writer();
+    payload.extend_from_slice(raw);
+
+    let public_key = ed25519::PublicKey::from(fields.stake_key.0);
+    if !public_key.verify(
+        payload_hash(&payload),
+        &ed25519::Signature::from(signature.0),
+    ) {
+        return Err(Error::InvalidSignature);
+    }
+
+    Ok(Registration {
+        delegations: fields.delegations,
+        stake_key: fields.stake_key,
+        payment_address: fields.payment_address,
+        nonce: fields.nonce,
+        voting_purpose: fields.voting_purpose,
+        signature,
+    })
+}
+
+/// Fields of the registration map, before the signature is checked
+struct RegistrationFields {
+    delegations: Vec<Delegation>,
+    stake_key: PublicKey,
+    payment_address: Address,
+    nonce: u64,
+    voting_purpose: u64,
+}
+
+fn encode_registration(
+    encoder: &mut Encoder<Vec<u8>>,
+    delegations: &[Delegation],
+    stake_key: &PublicKey,
+    payment_address: &Address,
+    nonce: u64,
+    voting_purpose: u64,
+) -> Result<(), EncodeError> {
+    encoder.map(5)?.u8(1)?.array(delegations.len() as u64)?;
+    for delegation in delegations {
+        encoder
+            .array(2)?
+            .bytes(&delegation.vote_key.0)?
+            .u32(delegation.weight)?;
+    }
+    encoder
+        .u8(2)?
+        .bytes(&stake_key.0)?
+        .u8(3)?
+        .bytes(&payment_address.to_vec())?
+        .u8(4)?
+        .u64(nonce)?
+        .u8(5)?
+        .u64(voting_purpose)?;
+    Ok(())
+}
+
+fn payload_hash(payload: &[u8]) -> [u8; 32] {
+    let mut hasher = Hasher::<256>::new();
+    hasher.input(payload);
+    *hasher.finalize()
+}
+
+/// Moves `decoder` to the metadata map, which is the whole of Shelley auxiliary data, the first
+/// element of the Allegra/Mary array and key 0 of the tagged Alonzo map.
+fn seek_metadata(decoder: &mut Decoder) -> Result<(), Error> {
+    match decoder.datatype()? {
+        Type::Map => Ok(()),
+        Type::Array => {
+            decoder.array()?;
+            Ok(())
+        }
+        Type::Tag => {
+            decoder.tag()?;
+            for _ in 0..definite_map(decoder)? {
+                if decoder.u64()? == 0 {
+                    return Ok(());
+                }
+                decoder.skip()?;
+            }
+            Err(Error::MissingRegistration)
+        }
+        other => Err(Error::MalformedMetadata(format!(
+            "unexpected auxiliary data type {other:?}"
+        ))),
+    }
+}
+
+fn decode_registration(decoder: &mut Decoder) -> Result<RegistrationFields, Error> {
+    let mut delegations = None;
+    let mut stake_key = None;
+    let mut payment_address = None;
+    let mut nonce = None;
+    let mut voting_purpose = CATALYST_VOTING_PURPOSE;
+
+    for _ in 0..definite_map(decoder)? {
+        match decoder.u64()? {
+            1 => {
+                delegations = Some(match decoder.datatype()? {
+                    // CIP-15: a single vote key
+                    Type::Bytes => vec![Delegation {
+                        vote_key: key_bytes(decoder.bytes()?)?,
+                        weight: 1,
+                    }],
+                    _ => {
+                        let count = decoder
+                            .array()?
+                            .ok_or_else(|| malformed("indefinite delegation array"))?;
+                        let mut delegations = Vec::with_capacity(count as usize);
+                        for _ in 0..count {
+                            if decoder.array()? != Some(2) {
+                                return Err(malformed("delegation is not a [key, weight] pair"));
+                            }
+                            delegations.push(Delegation {
+                                vote_key: key_bytes(decoder.bytes()?)?,
+                                weight: decoder.u32()?,
+                            });
+                        }
+                        delegations
+                    }
+                })
+            }
+            2 => stake_key = Some(key_bytes(decoder.bytes()?)?),
+            3 => {
+                payment_address = Some(
+                    Address::from_bytes(decoder.bytes()?)
+                        .map_err(|err| malformed(&format!("invalid payment address: {err}")))?,
+                )
+            }
+            4 => nonce = Some(decoder.u64()?),
+            5 => voting_purpose = decoder.u64()?,
+            _ => decoder.skip()?,
+        }
+    }
+
+    Ok(RegistrationFields {
+        delegations: delegations.ok_or_else(|| malformed("missing delegations"))?,
+        stake_key: stake_key.ok_or_else(|| malformed("missing stake key"))?,
+        payment_address: payment_address.ok_or_else(|| malformed("missing payment address"))?,
+        nonce: nonce.ok_or_else(|| malformed("missing nonce"))?,
+        voting_purpose,
+    })
+}
+
+fn decode_witness(decoder: &mut Decoder) -> Result<Signature, Error> {
+    let mut signature = None;
+    for _ in 0..definite_map(decoder)? {
+        match decoder.u64()? {
+            1 => {
+                let bytes = decoder.bytes()?;
+                signature = Some(Hash(bytes.try_into().map_err(|_| {
+                    malformed(&format!("signature of {} bytes", bytes.len()))
+                })?));
+            }
+            _ => decoder.skip()?,
+        }
+    }
+    signature.ok_or_else(|| malformed("missing signature"))
+}
+
+fn definite_map(decoder: &mut Decoder) -> Result<u64, Error> {
+    decoder
+        .map()?
+        .ok_or_else(|| malformed("indefinite length map"))
+}
+
+fn key_bytes(bytes: &[u8]) -> Result<PublicKey, Error> {
+    Ok(Hash(bytes.try_into().map_err(|_| {
+        malformed(&format!("key of {} bytes", bytes.len()))
+    })?))
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::MalformedMetadata(reason.to_string())
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Registrations are signed by the stake key
+    #[error("Wallet has no stake key")]
+    MissingStakeKey,
+    /// A registration must delegate to at least one vote key
+    #[error("Registration has no delegations")]
+    NoDelegations,
+    /// Metadata isn't valid CBOR
+    #[error("Unable to decode metadata: {0}")]
+    Decode(#[from] minicbor::decode::Error),
+    /// Metadata is valid CBOR but not a registration
+    #[error("Malformed registration metadata: {0}")]
+    MalformedMetadata(String),
+    /// No value under `REGISTRATION_LABEL`
+    #[error("Metadata has no registration")]
+    MissingRegistration,
+    /// No value under `WITNESS_LABEL`
+    #[error("Registration is not signed")]
+    MissingWitness,
+    /// Signature doesn't match the registration and its stake key
+    #[error("Registration signature is invalid")]
+    InvalidSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::Address;
+
+    use super::{Error, Registration, RegistrationBuilder, parse_registration};
+    use crate::primitives::{Hash, HashExt, PublicKey};
+    use crate::wallet::{PrivateKey, WalletBuilder};
+
+    /// Registration of the CIP-36 test vector (CIP-0036/test-vector.md in
+    /// cardano-foundation/CIPs): stake key `STAKE_KEY`, all voting power delegated to `VOTE_KEY`,
+    /// rewards to a testnet stake address, nonce 1234, Catalyst voting purpose.
+    const FIXTURE: &str = "a219ef64a501818258200036ef3e1f0d3f5989e2d155ea54bdb2a72c4c456ccb959af4\
+                           c94868f473f5a00102582086870efc99c453a873a16492ce87738ec79a0ebd064379a62e\
+                           2c9cf4e119219e03581de0ae3a0a7aeda4aea522e74e4fe36759fca80789a613a58a4364\
+                           f6ecef041904d2050019ef65a1015840503e1b9e607e32f0d74a93da7261eb603132cf95\
+                           d5405ee36d14431d3212bd49902445eeb47168e48858e295fd495cfad69f9fc1ea6da0ee\
+                           3272c016a0bdef0b";
+    const STAKE_KEY: &str = "f5beaeff7932a4164d270afde7716067582412e8977e67986cd9b456fc082e3a";
+    const VOTE_KEY: &str = "0036ef3e1f0d3f5989e2d155ea54bdb2a72c4c456ccb959af4c94868f473f5a0";
+    const PAYMENT_ADDRESS: &str = "e0ae3a0a7aeda4aea522e74e4fe36759fca80789a613a58a4364f6ecef";
+
+    fn stake_key() -> PrivateKey {
+        PrivateKey::from_bytes(hex::decode(STAKE_KEY).unwrap()).unwrap()
+    }
+
+    fn vote_key() -> PublicKey {
+        Hash::from_hex(VOTE_KEY).unwrap()
+    }
+
+    fn registration() -> Registration {
+        let payment_address = Address::from_bytes(&hex::decode(PAYMENT_ADDRESS).unwrap()).unwrap();
+        RegistrationBuilder::new(payment_address, 1234)
+            .delegate(vote_key(), 1)
+            .sign_with(&stake_key())
+            .expect("sign registration")
+    }
+
+    #[test]
+    fn builds_the_fixture_byte_for_byte() {
+        assert_eq!(hex::encode(registration().to_metadata()), FIXTURE);
+    }
+
+    #[test]
+    fn parses_and_verifies_the_fixture() {
+        let parsed = parse_registration(&hex::decode(FIXTURE).unwrap()).expect("parse fixture");
+        assert_eq!(parsed, registration());
+        assert_eq!(
+            hex::encode(parsed.stake_key.0),
+            "86870efc99c453a873a16492ce87738ec79a0ebd064379a62e2c9cf4e119219e"
+        );
+        assert_eq!(parsed.delegations[0].vote_key, vote_key());
+    }
+
+    #[test]
+    fn parses_alonzo_auxiliary_data() {
+        // #6.259({ 0: metadata })
+        let aux = format!("d90103a100{FIXTURE}");
+        assert_eq!(
+            parse_registration(&hex::decode(aux).unwrap()).expect("parse fixture"),
+            registration()
+        );
+    }
+
+    #[test]
+    fn tampered_registration_is_rejected() {
+        // Nonce 1234 -> 1235
+        let tampered = FIXTURE.replace("041904d2", "041904d3");
+        assert!(matches!(
+            parse_registration(&hex::decode(tampered).unwrap()),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn signing_needs_a_stake_key() {
+        let wallet = WalletBuilder::new(pallas::ledger::addresses::Network::Testnet)
+            .from_hex(hex::encode([1u8; 32]))
+            .unwrap();
+        let result = RegistrationBuilder::new(wallet.address(), 1234)
+            .delegate(vote_key(), 1)
+            .sign(&wallet);
+        assert!(matches!(result, Err(Error::MissingStakeKey)));
+    }
+}
@@ -0,0 +1,255 @@
+//! Typed interactions with on-chain scripts
+//!
+//! A `Contract<D, R>` ties a script to the datum and redeemer types it expects (usually generated
+//! from its blueprint), so that paying to, spending from and minting with the script only takes
+//! a single builder call, and mixing up datums and redeemers of different contracts is caught at
+//! compile time.
+
+use std::marker::PhantomData;
+
+use pallas::codec::minicbor;
+use pallas::ledger::addresses::{
+    Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+};
+
+use crate::builder::TxBuilder;
+use crate::builder::tx::TxBuilderError;
+use crate::primitives::{Asset, Assets, Hash, Input, Output, Script};
+
+/// Where the datum of a script output being spent comes from.
+pub enum DatumSource<D> {
+    /// The output holds its datum inline, so nothing needs to be attached.
+    Inline,
+    /// The output only holds the datum hash, so the datum must be attached as a witness.
+    Witness(D),
+}
+
+/// A script along with the datum and redeemer types it expects.
+pub struct Contract<D, R> {
+    script: Script,
+    network: Network,
+    reference: Option<Input>,
+    _types: PhantomData<fn(D, R)>,
+}
+
+impl<D, R> Contract<D, R>
+where
+    D: minicbor::Encode<()>,
+    R: minicbor::Encode<()>,
+{
+    /// Creates a contract whose script is attached to every transaction interacting with it.
+    pub fn new(script: Script, network: Network) -> Self {
+        Self {
+            script,
+            network,
+            reference: None,
+            _types: PhantomData,
+        }
+    }
+
+    /// Creates a contract whose script is read from the reference script held by the output at
+    /// `reference`, instead of being attached to transactions.
+    pub fn with_reference(script: Script, network: Network, reference: Input) -> Self {
+        Self {
+            script,
+            network,
+            reference: Some(reference),
+            _types: PhantomData,
+        }
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    /// The script hash, which is also the policy id when the contract is a minting policy.
+    pub fn hash(&self) -> Hash<28> {
+        self.script.hash
+    }
+
+    /// The enterprise address of the script.
+    pub fn address(&self) -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            self.network,
+            ShelleyPaymentPart::Script(self.script.hash.into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    /// Pays `lovelace` and `assets` to the script address, with `datum` inlined in the output.
+    pub fn pay(
+        &self,
+        builder: TxBuilder,
+        lovelace: u64,
+        assets: Assets,
+        datum: &D,
+    ) -> Result<TxBuilder, TxBuilderError> {
+        let output = Output::new(self.address(), lovelace)
+            .add_assets(assets)?
+            .set_datum(encode(datum)?);
+        Ok(builder.add_output(output))
+    }
+
+    /// Spends the script output at `input`, attaching the script (or referencing it) and the
+    /// datum witness if needed.
+    pub fn spend(
+        &self,
+        builder: TxBuilder,
+        input: Input,
+        redeemer: &R,
+        datum_source: DatumSource<D>,
+    ) -> Result<TxBuilder, TxBuilderError> {
+        let mut builder = self.attach_script(builder).add_script_input(
+            input,
+            encode(redeemer)?,
+            self.script.kind,
+        );
+        if let DatumSource::Witness(datum) = datum_source {
+            builder = builder.add_datum_typed(&datum)?.0;
+        }
+        Ok(builder)
+    }
+
+    /// Mints (positive amounts) or burns (negative amounts) assets under the contract's policy.
+    pub fn mint(
+        &self,
+        builder: TxBuilder,
+        assets: impl IntoIterator<Item = (Vec<u8>, i64)>,
+        redeemer: &R,
+    ) -> Result<TxBuilder, TxBuilderError> {
+        let redeemer = encode(redeemer)?;
+        let mut builder = self.attach_script(builder);
+        for (name, amount) in assets {
+            let asset = Asset::new(self.script.hash, name, amount.unsigned_abs());
+            builder = if amount < 0 {
+                builder.burn_asset(asset, self.script.kind, redeemer.clone())?
+            } else {
+                builder.mint_asset(asset, self.script.kind, redeemer.clone())?
+            };
+        }
+        Ok(builder)
+    }
+
+    fn attach_script(&self, builder: TxBuilder) -> TxBuilder {
+        match &self.reference {
+            Some(reference) => builder.add_reference_input(reference.clone()),
+            None => builder.add_script(self.script.kind, self.script.bytes.clone()),
+        }
+    }
+}
+
+fn encode<T: minicbor::Encode<()>>(value: &T) -> Result<Vec<u8>, TxBuilderError> {
+    minicbor::to_vec(value).map_err(|_| TxBuilderError::PlutusDataEncoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::Network;
+    use pallas::ledger::primitives::NetworkId;
+    use pallas::ledger::primitives::conway::PlutusData;
+
+    use super::{Contract, DatumSource};
+    use crate::builder::TxBuilder;
+    use crate::primitives::{
+        Assets, Datum, DatumOption, Hash, Input, RedeemerPurpose, Script, ScriptKind,
+    };
+
+    fn always_succeeds() -> Contract<PlutusData, PlutusData> {
+        let bytes = hex::decode("5101010023259800a518a4d136564004ae69").unwrap();
+        Contract::new(Script::new(ScriptKind::PlutusV3, bytes), Network::Testnet)
+    }
+
+    fn unit() -> PlutusData {
+        pallas::codec::minicbor::decode(&[0xd8, 0x79, 0x80]).unwrap()
+    }
+
+    fn builder(contract: &Contract<PlutusData, PlutusData>) -> TxBuilder {
+        TxBuilder::new(NetworkId::Testnet, contract.address())
+    }
+
+    #[test]
+    fn pay_inlines_the_datum() {
+        let contract = always_succeeds();
+        let builder = contract
+            .pay(builder(&contract), 2_000_000, Assets::default(), &unit())
+            .unwrap();
+
+        let output = &builder.body().outputs[0];
+        assert_eq!(output.address, contract.address());
+        assert_eq!(
+            output.datum,
+            Some(DatumOption::Inline(Datum::new(vec![0xd8, 0x79, 0x80])))
+        );
+    }
+
+    #[test]
+    fn spend_attaches_script_redeemer_and_datum() {
+        let contract = always_succeeds();
+        let input = Input::new(Hash([1u8; 32]), 0);
+        let builder = contract
+            .spend(
+                builder(&contract),
+                input.clone(),
+                &unit(),
+                DatumSource::Witness(unit()),
+            )
+            .unwrap();
+
+        let body = builder.body();
+        assert_eq!(body.inputs, vec![input.clone()]);
+        assert!(body.scripts.contains_key(&contract.hash()));
+        assert_eq!(body.datums.len(), 1);
+        assert!(
+            body.redeemers
+                .as_ref()
+                .unwrap()
+                .contains_key(&RedeemerPurpose::Spend(input))
+        );
+    }
+
+    #[test]
+    fn spend_by_reference_does_not_attach_the_script() {
+        let always_succeeds = always_succeeds();
+        let reference = Input::new(Hash([2u8; 32]), 0);
+        let contract: Contract<PlutusData, PlutusData> = Contract::with_reference(
+            always_succeeds.script().clone(),
+            Network::Testnet,
+            reference.clone(),
+        );
+        let builder = contract
+            .spend(
+                builder(&contract),
+                Input::new(Hash([1u8; 32]), 0),
+                &unit(),
+                DatumSource::Inline,
+            )
+            .unwrap();
+
+        let body = builder.body();
+        assert!(body.scripts.is_empty());
+        assert!(body.datums.is_empty());
+        assert_eq!(body.reference_inputs, vec![reference]);
+    }
+
+    #[test]
+    fn mint_and_burn_under_the_contract_policy() {
+        let contract = always_succeeds();
+        let builder = contract
+            .mint(
+                builder(&contract),
+                [(b"minted".to_vec(), 5), (b"burned".to_vec(), -3)],
+                &unit(),
+            )
+            .unwrap();
+
+        let body = builder.body();
+        assert_eq!(body.mint.len(), 2);
+        assert!(body.mint.keys().all(|id| id.policy == contract.hash()));
+        assert!(
+            body.redeemers
+                .as_ref()
+                .unwrap()
+                .contains_key(&RedeemerPurpose::Mint(contract.hash()))
+        );
+    }
+}
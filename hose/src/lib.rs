@@ -1,3 +1,11 @@
 pub mod builder;
+pub mod catalyst;
+pub mod contract;
+#[cfg(test)]
+mod mock_http;
+pub mod ogmios;
+pub mod plutus_json;
+pub mod prelude;
 pub mod primitives;
+pub mod registry;
 pub mod wallet;
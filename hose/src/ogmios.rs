@@ -0,0 +1,1193 @@
+//! Ogmios queries that `ogmios_client` doesn't cover
+//!
+//! Sent as JSON-RPC over HTTP to the same endpoint `OgmiosHttpClient` talks to.
+
+use std::collections::HashMap;
+
+use ogmios_client::method::evaluate::Evaluation;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+#[cfg(feature = "devnet-queries")]
+use pallas::ledger::addresses::Address;
+use pallas::ledger::addresses::Error as AddressError;
+
+use crate::primitives::{
+    DRep, DatumOption, Hash, HashError, HashExt as _, Input, Output, PoolId, PoolIdError,
+    RewardAccount, ScriptKind, StakeCredential, TxHash,
+};
+
+/// Ogmios error code of an evaluation whose additional UTxOs exist in the ledger already
+const OVERLAPPING_ADDITIONAL_UTXO: i64 = 3002;
+
+/// Client for ledger state queries missing from `OgmiosHttpClient`, e.g. to check a pool exists
+/// before delegating to it rather than have the ledger reject the transaction with
+/// `UnknownStakePool`.
+pub struct OgmiosQueries {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// A registered stake pool, as of the current epoch.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct StakePool {
+    pub id: PoolId,
+    /// Lovelace the owners pledged to the pool
+    pub pledge: u64,
+    /// Fixed lovelace the pool takes out of the rewards of each epoch
+    pub cost: u64,
+    /// Share of the remaining rewards the pool takes, as a fraction such as `"1/20"`
+    pub margin: String,
+    /// Reward account of the operator, in bech32
+    pub reward_account: String,
+    /// Where the pool's off-chain metadata is published, if anywhere
+    pub metadata_url: Option<String>,
+}
+
+/// Balance and delegations of a registered reward account.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RewardAccountSummary {
+    /// Lovelace that can be withdrawn from the account
+    pub rewards: u64,
+    /// Lovelace paid when the account was registered, refunded when it's deregistered. `None`
+    /// with Ogmios releases that don't report it.
+    pub deposit: Option<u64>,
+    /// Pool the account delegates its stake to
+    pub pool: Option<PoolId>,
+    /// DRep the account delegates its votes to. Always `None` with Ogmios releases predating
+    /// Conway delegations.
+    pub drep: Option<DRep>,
+}
+
+/// Registration, delegations and rewards of a stake credential.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct StakeInfo {
+    pub credential: StakeCredential,
+    pub registered: bool,
+    /// See `RewardAccountSummary::deposit`, always `None` for unregistered credentials
+    pub deposit: Option<u64>,
+    pub pool: Option<PoolId>,
+    pub drep: Option<DRep>,
+    /// Lovelace that can be withdrawn, zero for unregistered credentials
+    pub rewards: u64,
+}
+
+/// Lovelace held by the treasury and the reserves.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TreasuryAndReserves {
+    pub treasury: u64,
+    pub reserves: u64,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl From<RpcError> for Error {
+    fn from(error: RpcError) -> Self {
+        Error::Query {
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolEntry {
+    id: String,
+    pledge: Lovelace,
+    cost: Lovelace,
+    margin: String,
+    reward_account: String,
+    metadata: Option<PoolMetadata>,
+}
+
+#[derive(Deserialize)]
+struct Lovelace {
+    ada: Ada,
+}
+
+#[derive(Deserialize)]
+struct Ada {
+    lovelace: u64,
+}
+
+#[derive(Deserialize)]
+struct TreasuryAndReservesEntry {
+    treasury: Lovelace,
+    reserves: Lovelace,
+}
+
+#[cfg(feature = "devnet-queries")]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UtxoEntry {
+    transaction: TransactionRef,
+    index: u64,
+    address: String,
+    /// Lovelace under `ada.lovelace`, other assets by hex policy id and hex name
+    value: HashMap<String, HashMap<String, u64>>,
+    datum_hash: Option<String>,
+    /// Inline datum, as hex CBOR
+    datum: Option<String>,
+    script: Option<ScriptEntry>,
+}
+
+#[derive(Deserialize)]
+struct TransactionRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Submitted {
+    transaction: TransactionRef,
+}
+
+#[derive(Deserialize)]
+struct OutputReference {
+    transaction: TransactionRef,
+    index: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OverlappingUtxos {
+    overlapping_output_references: Vec<OutputReference>,
+}
+
+/// The chain tip, either `"origin"` or a point
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TipEntry {
+    Origin(String),
+    Point { slot: u64 },
+}
+
+/// Height of the chain tip, either `"origin"` or a number
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BlockHeightEntry {
+    Origin(String),
+    Height(u64),
+}
+
+#[cfg(feature = "devnet-queries")]
+#[derive(Deserialize)]
+struct ScriptEntry {
+    language: String,
+    cbor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PoolMetadata {
+    url: String,
+}
+
+/// Reward account summaries, in the shape of either the older or the newer Ogmios releases.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SummaryEntries {
+    /// A list of summaries, each naming its credential
+    List(Vec<SummaryEntry>),
+    /// Summaries keyed by credential
+    Map(HashMap<String, SummaryEntry>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryEntry {
+    credential: Option<String>,
+    #[serde(alias = "delegate")]
+    stake_pool: Option<PoolRef>,
+    delegate_representative: Option<DRepEntry>,
+    rewards: Lovelace,
+    deposit: Option<Lovelace>,
+}
+
+#[derive(Deserialize)]
+struct PoolRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum DRepEntry {
+    Registered { id: String, from: Option<String> },
+    Abstain,
+    NoConfidence,
+}
+
+impl SummaryEntry {
+    fn into_summary(self) -> Result<RewardAccountSummary, Error> {
+        let drep = match self.delegate_representative {
+            Some(DRepEntry::Registered { id, from }) => {
+                let hash = Hash::from_hex(&id)?;
+                Some(match from.as_deref() {
+                    Some("script") => DRep::Script(hash),
+                    _ => DRep::Key(hash),
+                })
+            }
+            Some(DRepEntry::Abstain) => Some(DRep::Abstain),
+            Some(DRepEntry::NoConfidence) => Some(DRep::NoConfidence),
+            None => None,
+        };
+        Ok(RewardAccountSummary {
+            rewards: self.rewards.ada.lovelace,
+            deposit: self.deposit.map(|deposit| deposit.ada.lovelace),
+            pool: self
+                .stake_pool
+                .map(|pool| PoolId::from_bech32(&pool.id))
+                .transpose()?,
+            drep,
+        })
+    }
+}
+
+#[cfg(feature = "devnet-queries")]
+impl UtxoEntry {
+    fn into_utxo(self) -> Result<(Input, Output), Error> {
+        let malformed = |what: &str| Error::MalformedResponse(format!("{what} of UTxO"));
+        let input = Input::new(Hash::from_hex(&self.transaction.id)?, self.index);
+        let mut value = self.value;
+        let lovelace = value
+            .remove("ada")
+            .and_then(|ada| ada.get("lovelace").copied())
+            .ok_or_else(|| malformed("lovelace"))?;
+        let mut output = Output::new(Address::from_bech32(&self.address)?, lovelace);
+        for (policy, assets) in value {
+            let policy = Hash::from_hex(&policy)?;
+            for (name, amount) in assets {
+                let name = hex::decode(&name).map_err(|_| malformed("asset name"))?;
+                output = output
+                    .add_asset(policy, name, amount)
+                    .map_err(|error| Error::MalformedResponse(error.to_string()))?;
+            }
+        }
+        if let Some(datum) = self.datum {
+            output = output.set_datum(hex::decode(&datum).map_err(|_| malformed("datum"))?);
+        } else if let Some(datum_hash) = self.datum_hash {
+            output = output.set_datum_hash(Hash::from_hex(&datum_hash)?);
+        }
+        if let Some(script) = self.script {
+            let kind = match script.language.as_str() {
+                "native" => ScriptKind::Native,
+                "plutus:v1" => ScriptKind::PlutusV1,
+                "plutus:v2" => ScriptKind::PlutusV2,
+                "plutus:v3" => ScriptKind::PlutusV3,
+                _ => return Err(malformed("script language")),
+            };
+            let bytes = script
+                .cbor
+                .as_deref()
+                .map(hex::decode)
+                .and_then(Result::ok)
+                .ok_or_else(|| malformed("script"))?;
+            output = output.set_script(kind, bytes);
+        }
+        Ok((input, output))
+    }
+}
+
+/// `output` at `input` in the shape of Ogmios UTxOs, the reverse of `UtxoEntry::into_utxo`.
+fn utxo_json(input: &Input, output: &Output) -> Result<Value, Error> {
+    let mut value = json!({ "ada": { "lovelace": output.lovelace } });
+    for (asset_id, amount) in output.assets.iter().flat_map(|assets| assets.iter()) {
+        value[asset_id.policy.to_hex()][hex::encode(&asset_id.name)] = json!(amount);
+    }
+    let mut utxo = json!({
+        "transaction": { "id": input.hash.to_hex() },
+        "index": input.index,
+        "address": output.address.to_bech32()?,
+        "value": value,
+    });
+    match &output.datum {
+        Some(DatumOption::Hash(hash)) => utxo["datumHash"] = json!(hash.to_hex()),
+        Some(DatumOption::Inline(datum)) => utxo["datum"] = json!(hex::encode(&datum.bytes)),
+        None => {}
+    }
+    if let Some(script) = &output.script {
+        let language = match script.kind {
+            ScriptKind::Native => "native",
+            ScriptKind::PlutusV1 => "plutus:v1",
+            ScriptKind::PlutusV2 => "plutus:v2",
+            ScriptKind::PlutusV3 => "plutus:v3",
+        };
+        utxo["script"] = json!({ "language": language, "cbor": hex::encode(&script.bytes) });
+    }
+    Ok(utxo)
+}
+
+impl OgmiosQueries {
+    /// Client for the Ogmios server at `url`, e.g. `http://localhost:1337`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+
+    /// Every stake pool registered in the ledger.
+    pub async fn stake_pools(&self) -> Result<HashMap<PoolId, StakePool>, Error> {
+        self.query_stake_pools(None).await
+    }
+
+    /// Whether `pool_id` is registered, so that delegating to it won't be rejected.
+    pub async fn pool_exists(&self, pool_id: PoolId) -> Result<bool, Error> {
+        let params = json!({ "stakePools": [{ "id": pool_id.to_bech32() }] });
+        let pools = self.query_stake_pools(Some(params)).await?;
+        Ok(pools.contains_key(&pool_id))
+    }
+
+    /// Budgets of the scripts the serialized `tx` runs.
+    ///
+    /// Unlike `OgmiosHttpClient::evaluate`, UTxOs the node doesn't know yet can be passed as
+    /// `additional_utxos`, so that transactions spending outputs of transactions still in
+    /// flight can be evaluated. Ogmios rejects additional UTxOs that exist in the ledger, e.g.
+    /// once the transaction that created them is included, so those are dropped and the
+    /// evaluation is retried without them.
+    pub async fn evaluate(
+        &self,
+        tx: &[u8],
+        additional_utxos: &[(Input, Output)],
+    ) -> Result<Vec<Evaluation>, Error> {
+        let mut additional = additional_utxos
+            .iter()
+            .map(|(input, output)| Ok((input.clone(), utxo_json(input, output)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        loop {
+            let params = json!({
+                "transaction": { "cbor": hex::encode(tx) },
+                "additionalUtxo": additional.iter().map(|(_, utxo)| utxo).collect::<Vec<_>>(),
+            });
+            let error = match self.call("evaluateTransaction", Some(params)).await? {
+                Ok(evaluation) => return Ok(evaluation),
+                Err(error) => error,
+            };
+            if error.code != OVERLAPPING_ADDITIONAL_UTXO {
+                return Err(error.into());
+            }
+            let Some(overlapping) = error
+                .data
+                .clone()
+                .and_then(|data| serde_json::from_value::<OverlappingUtxos>(data).ok())
+            else {
+                return Err(error.into());
+            };
+            let overlapping = overlapping
+                .overlapping_output_references
+                .into_iter()
+                .map(|reference| {
+                    Ok(Input::new(
+                        Hash::from_hex(&reference.transaction.id)?,
+                        reference.index,
+                    ))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let count = additional.len();
+            additional.retain(|(input, _)| !overlapping.contains(input));
+            if additional.len() == count {
+                return Err(error.into());
+            }
+        }
+    }
+
+    /// Submits the serialized `tx`, returning its hash.
+    ///
+    /// Unlike `OgmiosHttpClient::submit`, rejections keep their Ogmios error code, e.g. 3117 for
+    /// inputs that don't exist or are spent.
+    pub async fn submit(&self, tx: &[u8]) -> Result<TxHash, Error> {
+        let params = json!({ "transaction": { "cbor": hex::encode(tx) } });
+        let submitted: Submitted = self.query("submitTransaction", Some(params)).await?;
+        Ok(Hash::from_hex(&submitted.transaction.id)?)
+    }
+
+    /// Slot of the chain tip, 0 at the origin.
+    pub async fn tip_slot(&self) -> Result<u64, Error> {
+        match self.query("queryNetwork/tip", None).await? {
+            TipEntry::Point { slot } => Ok(slot),
+            TipEntry::Origin(origin) if origin == "origin" => Ok(0),
+            TipEntry::Origin(other) => Err(Error::MalformedResponse(format!("tip {other}"))),
+        }
+    }
+
+    /// Height of the chain tip, i.e. the number of blocks since genesis, 0 at the origin.
+    pub async fn block_height(&self) -> Result<u64, Error> {
+        match self.query("queryNetwork/blockHeight", None).await? {
+            BlockHeightEntry::Height(height) => Ok(height),
+            BlockHeightEntry::Origin(origin) if origin == "origin" => Ok(0),
+            BlockHeightEntry::Origin(other) => {
+                Err(Error::MalformedResponse(format!("block height {other}")))
+            }
+        }
+    }
+
+    /// Every UTxO holding an asset of `policy`, or only the asset `name` of it if given, e.g. to
+    /// check where a devnet test put its state tokens.
+    ///
+    /// Devnets only, hence the `devnet-queries` feature: Ogmios can't filter UTxOs by asset, so
+    /// this fetches the whole UTxO set and filters it here. On mainnet that's a response of
+    /// several gigabytes that takes Ogmios minutes to serve, and the indexer can't look UTxOs up
+    /// by asset either: scanners there need their own index, fed by a chain follower.
+    #[cfg(feature = "devnet-queries")]
+    pub async fn utxos_by_asset(
+        &self,
+        policy: Hash<28>,
+        name: Option<&[u8]>,
+    ) -> Result<Vec<(Input, Output)>, Error> {
+        let policy = policy.to_hex();
+        let name = name.map(hex::encode);
+        let entries: Vec<UtxoEntry> = self.query("queryLedgerState/utxo", None).await?;
+        entries
+            .into_iter()
+            .filter(|entry| {
+                entry.value.get(&policy).is_some_and(|assets| {
+                    name.as_ref().is_none_or(|name| assets.contains_key(name))
+                })
+            })
+            .map(UtxoEntry::into_utxo)
+            .collect()
+    }
+
+    /// Current lovelace of the treasury and the reserves, e.g. to declare the treasury value of
+    /// treasury withdrawal proposals, see `TxBuilder::current_treasury_value_from`.
+    pub async fn treasury_and_reserves(&self) -> Result<TreasuryAndReserves, Error> {
+        let entry: TreasuryAndReservesEntry = self
+            .query("queryLedgerState/treasuryAndReserves", None)
+            .await?;
+        Ok(TreasuryAndReserves {
+            treasury: entry.treasury.ada.lovelace,
+            reserves: entry.reserves.ada.lovelace,
+        })
+    }
+
+    /// Balance and delegations of each of `accounts` that is registered. Unregistered accounts
+    /// are left out of the result.
+    pub async fn reward_account_summaries(
+        &self,
+        accounts: &[RewardAccount],
+    ) -> Result<HashMap<RewardAccount, RewardAccountSummary>, Error> {
+        let credentials = accounts
+            .iter()
+            .map(|account| {
+                let credential = account
+                    .credential()
+                    .ok_or_else(|| Error::NotARewardAccount(account.as_ref().to_vec()))?;
+                Ok((credential, account))
+            })
+            .collect::<Result<HashMap<StakeCredential, &RewardAccount>, Error>>()?;
+        Ok(self
+            .query_reward_accounts(credentials.keys().copied())
+            .await?
+            .into_iter()
+            .map(|(credential, summary)| (credentials[&credential].clone(), summary))
+            .collect())
+    }
+
+    /// Registration, delegations and rewards of each of `credentials`, in the same order.
+    pub async fn stake_info(
+        &self,
+        credentials: &[StakeCredential],
+    ) -> Result<Vec<StakeInfo>, Error> {
+        let mut summaries = self
+            .query_reward_accounts(credentials.iter().copied())
+            .await?;
+        Ok(credentials
+            .iter()
+            .map(|credential| match summaries.remove(credential) {
+                Some(summary) => StakeInfo {
+                    credential: *credential,
+                    registered: true,
+                    deposit: summary.deposit,
+                    pool: summary.pool,
+                    drep: summary.drep,
+                    rewards: summary.rewards,
+                },
+                None => StakeInfo {
+                    credential: *credential,
+                    registered: false,
+                    deposit: None,
+                    pool: None,
+                    drep: None,
+                    rewards: 0,
+                },
+            })
+            .collect())
+    }
+
+    async fn query_reward_accounts(
+        &self,
+        credentials: impl IntoIterator<Item = StakeCredential>,
+    ) -> Result<HashMap<StakeCredential, RewardAccountSummary>, Error> {
+        let credentials = credentials.into_iter().collect::<Vec<_>>();
+        if credentials.is_empty() {
+            // Without any credential, Ogmios would return every reward account
+            return Ok(HashMap::new());
+        }
+        let (keys, scripts): (Vec<_>, Vec<_>) = credentials
+            .iter()
+            .partition(|credential| matches!(credential, StakeCredential::Key(_)));
+        let params = json!({
+            "keys": keys.iter().map(|credential| credential.hash().to_hex()).collect::<Vec<_>>(),
+            "scripts": scripts
+                .iter()
+                .map(|credential| credential.hash().to_hex())
+                .collect::<Vec<_>>(),
+        });
+        let entries: SummaryEntries = self
+            .query("queryLedgerState/rewardAccountSummaries", Some(params))
+            .await?;
+        let entries: Vec<(String, SummaryEntry)> = match entries {
+            SummaryEntries::List(entries) => entries
+                .into_iter()
+                .map(|entry| (entry.credential.clone().unwrap_or_default(), entry))
+                .collect(),
+            SummaryEntries::Map(entries) => entries.into_iter().collect(),
+        };
+
+        entries
+            .into_iter()
+            .map(|(hex_hash, entry)| {
+                let hash = Hash::from_hex(&hex_hash)?;
+                let credential = credentials
+                    .iter()
+                    .find(|requested| requested.hash() == hash)
+                    .copied()
+                    .ok_or_else(|| {
+                        Error::MalformedResponse(format!("summary of unknown account {hex_hash}"))
+                    })?;
+                Ok((credential, entry.into_summary()?))
+            })
+            .collect()
+    }
+
+    async fn query_stake_pools(
+        &self,
+        params: Option<Value>,
+    ) -> Result<HashMap<PoolId, StakePool>, Error> {
+        let entries: HashMap<String, PoolEntry> =
+            self.query("queryLedgerState/stakePools", params).await?;
+        entries
+            .into_values()
+            .map(|entry| {
+                let pool = StakePool {
+                    id: PoolId::from_bech32(&entry.id)?,
+                    pledge: entry.pledge.ada.lovelace,
+                    cost: entry.cost.ada.lovelace,
+                    margin: entry.margin,
+                    reward_account: entry.reward_account,
+                    metadata_url: entry.metadata.map(|metadata| metadata.url),
+                };
+                Ok::<_, Error>((pool.id, pool))
+            })
+            .collect()
+    }
+
+    async fn query<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<T, Error> {
+        Ok(self.call(method, params).await??)
+    }
+
+    /// Sends a request, failing only if Ogmios can't be reached or its response isn't JSON-RPC.
+    /// Errors Ogmios answers with are left to the caller.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<Result<T, RpcError>, Error> {
+        let mut request = json!({ "jsonrpc": "2.0", "method": method });
+        if let Some(params) = params {
+            request["params"] = params;
+        }
+        let response: Response<T> = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(match (response.result, response.error) {
+            (_, Some(error)) => Err(error),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(RpcError {
+                code: 0,
+                message: "response has neither result nor error".to_string(),
+                data: None,
+            }),
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Request to Ogmios failed, or its response isn't the expected JSON
+    #[error("Ogmios request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// Ogmios answered the query with an error
+    #[error("Ogmios query failed with code {code}: {message}")]
+    Query { code: i64, message: String },
+    /// A pool id in the response isn't a valid `pool1...` id
+    #[error("Ogmios returned a malformed pool id: {0}")]
+    MalformedPoolId(#[from] PoolIdError),
+    /// A hash in the response isn't valid hex of the expected length
+    #[error("Ogmios returned a malformed hash: {0}")]
+    MalformedHash(#[from] HashError),
+    /// An address in the response isn't a valid bech32 address
+    #[error("Ogmios returned a malformed address: {0}")]
+    MalformedAddress(#[from] AddressError),
+    /// One of the accounts asked about isn't a valid reward account
+    #[error("Not a reward account: {}", hex::encode(.0))]
+    NotARewardAccount(Vec<u8>),
+    /// The response refers to something that wasn't asked for, or isn't shaped as expected
+    #[error("Unexpected Ogmios response: {0}")]
+    MalformedResponse(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::{
+        Error, OgmiosQueries, RewardAccountSummary, StakeInfo, StakePool, TreasuryAndReserves,
+    };
+    use crate::mock_http::MockServer;
+    use crate::primitives::{DRep, Hash, Input, Output, PoolId, RewardAccount, StakeCredential};
+
+    fn stake_pools_response(pool_id: PoolId) -> String {
+        let id = pool_id.to_bech32();
+        format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/stakePools",
+                "result": {{
+                    "{id}": {{
+                        "id": "{id}",
+                        "vrfVerificationKeyHash": "{vrf}",
+                        "pledge": {{ "ada": {{ "lovelace": 100000000000 }} }},
+                        "cost": {{ "ada": {{ "lovelace": 340000000 }} }},
+                        "margin": "1/20",
+                        "rewardAccount": "stake_test1operator",
+                        "owners": [],
+                        "relays": [{{ "type": "hostname", "hostname": "relay.example.com", "port": 3001 }}],
+                        "metadata": {{ "url": "https://example.com/pool.json", "hash": "{vrf}" }}
+                    }}
+                }}
+            }}"#,
+            vrf = hex::encode([0x11; 32])
+        )
+    }
+
+    #[tokio::test]
+    async fn stake_pools_are_parsed() {
+        let pool_id = PoolId(Hash([0xaa; 28]));
+        let server = MockServer::serving(stake_pools_response(pool_id)).await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let pools = ogmios.stake_pools().await.expect("query stake pools");
+        assert_eq!(
+            pools.get(&pool_id),
+            Some(&StakePool {
+                id: pool_id,
+                pledge: 100_000_000_000,
+                cost: 340_000_000,
+                margin: "1/20".to_string(),
+                reward_account: "stake_test1operator".to_string(),
+                metadata_url: Some("https://example.com/pool.json".to_string()),
+            })
+        );
+        assert_eq!(pools.len(), 1);
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "queryLedgerState/stakePools");
+    }
+
+    #[tokio::test]
+    async fn pool_exists_asks_for_that_pool_only() {
+        let pool_id = PoolId(Hash([0xaa; 28]));
+        let server = MockServer::serving(stake_pools_response(pool_id)).await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        assert!(ogmios.pool_exists(pool_id).await.expect("query pool"));
+        assert!(
+            !ogmios
+                .pool_exists(PoolId(Hash([0xbb; 28])))
+                .await
+                .expect("query pool")
+        );
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(
+            request["params"]["stakePools"][0]["id"],
+            pool_id.to_bech32()
+        );
+    }
+
+    fn reward_accounts() -> (RewardAccount, RewardAccount) {
+        (
+            RewardAccount::from_key_hash(Network::Testnet, Hash([0x01; 28])),
+            RewardAccount::from_script_hash(Network::Testnet, Hash([0x02; 28])),
+        )
+    }
+
+    #[tokio::test]
+    async fn reward_account_summaries_are_parsed() {
+        let pool_id = PoolId(Hash([0xaa; 28]));
+        let (key_account, script_account) = reward_accounts();
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/rewardAccountSummaries",
+                "result": [
+                    {{
+                        "from": "verificationKey",
+                        "credential": "{key}",
+                        "stakePool": {{ "id": "{pool}" }},
+                        "delegateRepresentative": {{
+                            "type": "registered",
+                            "from": "script",
+                            "id": "{drep}"
+                        }},
+                        "rewards": {{ "ada": {{ "lovelace": 1500000 }} }},
+                        "deposit": {{ "ada": {{ "lovelace": 2000000 }} }}
+                    }}
+                ]
+            }}"#,
+            key = hex::encode([0x01; 28]),
+            pool = pool_id.to_bech32(),
+            drep = hex::encode([0x03; 28]),
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let summaries = ogmios
+            .reward_account_summaries(&[key_account.clone(), script_account])
+            .await
+            .expect("query reward accounts");
+        assert_eq!(
+            summaries,
+            HashMap::from([(
+                key_account,
+                RewardAccountSummary {
+                    rewards: 1_500_000,
+                    deposit: Some(2_000_000),
+                    pool: Some(pool_id),
+                    drep: Some(DRep::Script(Hash([0x03; 28]))),
+                }
+            )])
+        );
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "queryLedgerState/rewardAccountSummaries");
+        assert_eq!(
+            request["params"]["keys"],
+            serde_json::json!([hex::encode([0x01; 28])])
+        );
+        assert_eq!(
+            request["params"]["scripts"],
+            serde_json::json!([hex::encode([0x02; 28])])
+        );
+    }
+
+    #[tokio::test]
+    async fn reward_account_summaries_keyed_by_credential_are_parsed() {
+        let pool_id = PoolId(Hash([0xaa; 28]));
+        let (_, script_account) = reward_accounts();
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/rewardAccountSummaries",
+                "result": {{
+                    "{script}": {{
+                        "delegate": {{ "id": "{pool}" }},
+                        "rewards": {{ "ada": {{ "lovelace": 42 }} }}
+                    }}
+                }}
+            }}"#,
+            script = hex::encode([0x02; 28]),
+            pool = pool_id.to_bech32(),
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let summaries = ogmios
+            .reward_account_summaries(std::slice::from_ref(&script_account))
+            .await
+            .expect("query reward accounts");
+        assert_eq!(
+            summaries.get(&script_account),
+            Some(&RewardAccountSummary {
+                rewards: 42,
+                deposit: None,
+                pool: Some(pool_id),
+                drep: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn stake_info_covers_unregistered_credentials() {
+        let registered = StakeCredential::Key(Hash([0x01; 28]));
+        let unregistered = StakeCredential::Script(Hash([0x02; 28]));
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/rewardAccountSummaries",
+                "result": [
+                    {{
+                        "from": "verificationKey",
+                        "credential": "{key}",
+                        "delegateRepresentative": {{ "type": "abstain" }},
+                        "rewards": {{ "ada": {{ "lovelace": 0 }} }},
+                        "deposit": {{ "ada": {{ "lovelace": 2000000 }} }}
+                    }}
+                ]
+            }}"#,
+            key = hex::encode([0x01; 28]),
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let info = ogmios
+            .stake_info(&[unregistered, registered])
+            .await
+            .expect("query stake info");
+        assert_eq!(
+            info,
+            vec![
+                StakeInfo {
+                    credential: unregistered,
+                    registered: false,
+                    deposit: None,
+                    pool: None,
+                    drep: None,
+                    rewards: 0,
+                },
+                StakeInfo {
+                    credential: registered,
+                    registered: true,
+                    deposit: Some(2_000_000),
+                    pool: None,
+                    drep: Some(DRep::Abstain),
+                    rewards: 0,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn treasury_and_reserves_are_parsed() {
+        let server = MockServer::serving(
+            r#"{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/treasuryAndReserves",
+                "result": {
+                    "treasury": { "ada": { "lovelace": 1500000000000000 } },
+                    "reserves": { "ada": { "lovelace": 7500000000000000 } }
+                }
+            }"#
+            .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        assert_eq!(
+            ogmios
+                .treasury_and_reserves()
+                .await
+                .expect("query treasury"),
+            TreasuryAndReserves {
+                treasury: 1_500_000_000_000_000,
+                reserves: 7_500_000_000_000_000,
+            }
+        );
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "queryLedgerState/treasuryAndReserves");
+    }
+
+    #[tokio::test]
+    async fn no_reward_accounts_means_no_query() {
+        let server = MockServer::serving(String::new()).await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let summaries = ogmios
+            .reward_account_summaries(&[])
+            .await
+            .expect("query reward accounts");
+        assert!(summaries.is_empty());
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn query_errors_are_reported() {
+        let server = MockServer::serving(
+            r#"{ "jsonrpc": "2.0", "error": { "code": 2001, "message": "era mismatch" } }"#
+                .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let err = ogmios.stake_pools().await.unwrap_err();
+        assert!(
+            matches!(&err, Error::Query { code: 2001, message } if message == "era mismatch"),
+            "{err}"
+        );
+    }
+
+    #[cfg(feature = "devnet-queries")]
+    #[tokio::test]
+    async fn utxos_are_filtered_by_asset() {
+        use crate::primitives::ScriptKind;
+
+        let address = Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ));
+        let bech32 = address.to_bech32().unwrap();
+        let policy = "aa".repeat(28);
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "queryLedgerState/utxo",
+                "result": [
+                    {{
+                        "transaction": {{ "id": "{tx1}" }},
+                        "index": 0,
+                        "address": "{bech32}",
+                        "value": {{
+                            "ada": {{ "lovelace": 2000000 }},
+                            "{policy}": {{ "{state}": 1 }}
+                        }},
+                        "datum": "d87980",
+                        "script": {{
+                            "language": "plutus:v2",
+                            "cbor": "4e4d01000033222220051200120011"
+                        }}
+                    }},
+                    {{
+                        "transaction": {{ "id": "{tx1}" }},
+                        "index": 1,
+                        "address": "{bech32}",
+                        "value": {{
+                            "ada": {{ "lovelace": 3000000 }},
+                            "{policy}": {{ "{other}": 5 }}
+                        }},
+                        "datumHash": "{datum_hash}"
+                    }},
+                    {{
+                        "transaction": {{ "id": "{tx2}" }},
+                        "index": 0,
+                        "address": "{bech32}",
+                        "value": {{ "ada": {{ "lovelace": 4000000 }} }}
+                    }}
+                ]
+            }}"#,
+            tx1 = "11".repeat(32),
+            tx2 = "22".repeat(32),
+            state = hex::encode("STATE"),
+            other = hex::encode("OTHER"),
+            datum_hash = "33".repeat(32),
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+        let policy = Hash([0xaa; 28]);
+
+        let state = ogmios
+            .utxos_by_asset(policy, Some(b"STATE"))
+            .await
+            .expect("query state token");
+        assert_eq!(
+            state,
+            vec![(
+                Input::new(Hash([0x11; 32]), 0),
+                Output::new(address.clone(), 2_000_000)
+                    .add_asset(policy, b"STATE".to_vec(), 1)
+                    .unwrap()
+                    .set_datum(hex::decode("d87980").unwrap())
+                    .set_script(
+                        ScriptKind::PlutusV2,
+                        hex::decode("4e4d01000033222220051200120011").unwrap()
+                    )
+            )]
+        );
+
+        let mut whole_policy = ogmios
+            .utxos_by_asset(policy, None)
+            .await
+            .expect("query policy");
+        whole_policy.sort_by_key(|(input, _)| input.index);
+        assert_eq!(
+            whole_policy
+                .iter()
+                .map(|(input, _)| input.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Input::new(Hash([0x11; 32]), 0),
+                Input::new(Hash([0x11; 32]), 1)
+            ]
+        );
+        assert_eq!(
+            whole_policy[1].1,
+            Output::new(address, 3_000_000)
+                .add_asset(policy, b"OTHER".to_vec(), 5)
+                .unwrap()
+                .set_datum_hash(Hash([0x33; 32]))
+        );
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "queryLedgerState/utxo");
+        assert!(request.get("params").is_none());
+    }
+
+    #[tokio::test]
+    async fn submission_returns_the_transaction_id() {
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "submitTransaction",
+                "result": {{ "transaction": {{ "id": "{id}" }} }}
+            }}"#,
+            id = "44".repeat(32),
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+
+        let hash = ogmios.submit(&[0x84, 0xa0]).await.expect("submit");
+        assert_eq!(hash, Hash([0x44; 32]));
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "submitTransaction");
+        assert_eq!(request["params"]["transaction"]["cbor"], "84a0");
+    }
+
+    fn script_address() -> Address {
+        Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Script(Hash([2u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ))
+    }
+
+    #[tokio::test]
+    async fn evaluation_passes_additional_utxos() {
+        let server = MockServer::serving(
+            r#"{
+                "jsonrpc": "2.0",
+                "method": "evaluateTransaction",
+                "result": [
+                    {
+                        "validator": { "purpose": "spend", "index": 0 },
+                        "budget": { "memory": 1700, "cpu": 476468 }
+                    }
+                ]
+            }"#
+            .to_string(),
+        )
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+        let input = Input::new(Hash([0x11; 32]), 1);
+        let output = Output::new(script_address(), 5_000_000)
+            .add_asset(Hash([0xaa; 28]), b"STATE".to_vec(), 1)
+            .unwrap()
+            .set_datum(hex::decode("d87980").unwrap());
+
+        let evaluation = ogmios
+            .evaluate(&[0x84], &[(input, output)])
+            .await
+            .expect("evaluate");
+        assert_eq!(evaluation.len(), 1);
+
+        let request: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(request["method"], "evaluateTransaction");
+        assert_eq!(request["params"]["transaction"]["cbor"], "84");
+        assert_eq!(
+            request["params"]["additionalUtxo"],
+            serde_json::json!([{
+                "transaction": { "id": "11".repeat(32) },
+                "index": 1,
+                "address": script_address().to_bech32().unwrap(),
+                "value": {
+                    "ada": { "lovelace": 5_000_000 },
+                    "aa".repeat(28): { hex::encode("STATE"): 1 }
+                },
+                "datum": "d87980"
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn overlapping_additional_utxos_are_dropped() {
+        let server = MockServer::serving(format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "evaluateTransaction",
+                "error": {{
+                    "code": 3002,
+                    "message": "Some user-provided additional UTxO entries overlap.",
+                    "data": {{
+                        "overlappingOutputReferences": [
+                            {{ "transaction": {{ "id": "{}" }}, "index": 0 }}
+                        ]
+                    }}
+                }}
+            }}"#,
+            "11".repeat(32)
+        ))
+        .await;
+        let ogmios = OgmiosQueries::new(server.url.clone());
+        let confirmed = Input::new(Hash([0x11; 32]), 0);
+        let in_flight = Input::new(Hash([0x22; 32]), 0);
+        let output = Output::new(script_address(), 5_000_000);
+
+        let err = ogmios
+            .evaluate(&[0x84], &[(confirmed, output.clone()), (in_flight, output)])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Query { code: 3002, .. }), "{err}");
+
+        // Retried once without the overlapping UTxO, and given up when nothing more overlaps
+        let requests = server
+            .requests()
+            .iter()
+            .map(|request| serde_json::from_str::<serde_json::Value>(request).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0]["params"]["additionalUtxo"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            requests[1]["params"]["additionalUtxo"][0]["transaction"]["id"],
+            "22".repeat(32)
+        );
+        assert_eq!(
+            requests[1]["params"]["additionalUtxo"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
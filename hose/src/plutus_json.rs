@@ -0,0 +1,365 @@
+//! Plutus data as cardano-cli's detailed schema JSON
+//!
+//! Every node is an object: `{"constructor": 0, "fields": [...]}`, `{"int": 42}`,
+//! `{"bytes": "cafe"}`, `{"list": [...]}` or `{"map": [{"k": ..., "v": ...}]}`.
+//!
+//! JSON is always encoded into the CBOR the ledger and Plutus produce, so such CBOR round-trips
+//! byte for byte: non-empty lists are indefinite, byte strings longer than 64 bytes are chunked,
+//! and integers outside of `[-2^64, 2^64 - 1]` are bignums.
+//!
+//! `serde_json::Value` can't hold integers beyond 64 bits, `to_detailed_json_string` and
+//! `from_detailed_json_str` work on the JSON text instead and cover every integer.
+
+use std::collections::BTreeMap;
+use std::str::FromStr as _;
+
+use num::ToPrimitive as _;
+use pallas::codec::minicbor;
+use pallas::ledger::primitives::{
+    BigInt as PallasBigInt, BoundedBytes, Constr, Fragment, KeyValuePairs, MaybeIndefArray,
+    PlutusData,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::primitives::BigInt;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PlutusJsonError {
+    /// Bytes aren't CBOR encoded plutus data
+    #[error("Could not decode plutus data: {0}")]
+    Cbor(String),
+    /// Not JSON, or not shaped like detailed schema plutus data
+    #[error("Invalid detailed schema JSON: {0}")]
+    Json(String),
+    /// `bytes` isn't a hex string
+    #[error("Invalid hex in `bytes`: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    /// `int` isn't an integer, e.g. `1.5` or `"1"`
+    #[error("Not an integer: {0}")]
+    NotAnInteger(String),
+    /// Constructor encoded with a CBOR tag plutus data doesn't use
+    #[error("Unknown constructor tag {0}")]
+    UnknownConstructorTag(u64),
+    /// Integer beyond 64 bits, which `serde_json::Value` can't hold
+    #[error("Integer {0} does not fit a JSON number, use `to_detailed_json_string` instead")]
+    IntegerOutOfRange(String),
+}
+
+/// Detailed schema JSON of CBOR encoded plutus data. Fails on integers beyond 64 bits, see
+/// `to_detailed_json_string`.
+pub fn to_detailed_json(plutus_data: &[u8]) -> Result<Value, PlutusJsonError> {
+    to_value(&decode(plutus_data)?)
+}
+
+/// CBOR encoding of plutus data given as detailed schema JSON.
+pub fn from_detailed_json(json: &Value) -> Result<Vec<u8>, PlutusJsonError> {
+    from_detailed_json_str(&json.to_string())
+}
+
+/// Same as `to_detailed_json`, as compact JSON text holding integers of any size.
+pub fn to_detailed_json_string(plutus_data: &[u8]) -> Result<String, PlutusJsonError> {
+    let mut json = String::new();
+    write_json(&decode(plutus_data)?, &mut json)?;
+    Ok(json)
+}
+
+/// Same as `from_detailed_json`, from JSON text holding integers of any size.
+pub fn from_detailed_json_str(json: &str) -> Result<Vec<u8>, PlutusJsonError> {
+    let data = parse(from_raw(json)?)?;
+    Ok(minicbor::to_vec(data).expect("encoding to a vec is infallible"))
+}
+
+/// Detailed schema JSON of types encoded as plutus data, e.g. generated from a blueprint, going
+/// through their CBOR encoding.
+pub trait PlutusJson: Sized {
+    fn to_detailed_json(&self) -> Result<Value, PlutusJsonError>;
+
+    fn from_detailed_json(json: &Value) -> Result<Self, PlutusJsonError>;
+}
+
+impl<T> PlutusJson for T
+where
+    T: minicbor::Encode<()> + for<'b> minicbor::Decode<'b, ()>,
+{
+    fn to_detailed_json(&self) -> Result<Value, PlutusJsonError> {
+        let bytes = minicbor::to_vec(self).map_err(|err| PlutusJsonError::Cbor(err.to_string()))?;
+        to_detailed_json(&bytes)
+    }
+
+    fn from_detailed_json(json: &Value) -> Result<Self, PlutusJsonError> {
+        minicbor::decode(&from_detailed_json(json)?)
+            .map_err(|err| PlutusJsonError::Cbor(err.to_string()))
+    }
+}
+
+fn decode(plutus_data: &[u8]) -> Result<PlutusData, PlutusJsonError> {
+    PlutusData::decode_fragment(plutus_data).map_err(|err| PlutusJsonError::Cbor(err.to_string()))
+}
+
+fn to_value(data: &PlutusData) -> Result<Value, PlutusJsonError> {
+    Ok(match data {
+        PlutusData::Constr(constr) => json!({
+            "constructor": constructor_index(constr)?,
+            "fields": to_values(&constr.fields)?,
+        }),
+        PlutusData::BigInt(int) => {
+            let int = BigInt::from(int.clone()).into_inner();
+            let number = match (int.to_i64(), int.to_u64()) {
+                (Some(int), _) => Value::from(int),
+                (None, Some(int)) => Value::from(int),
+                (None, None) => return Err(PlutusJsonError::IntegerOutOfRange(int.to_string())),
+            };
+            json!({ "int": number })
+        }
+        PlutusData::BoundedBytes(bytes) => json!({ "bytes": hex::encode(bytes.as_slice()) }),
+        PlutusData::Array(items) => json!({ "list": to_values(items)? }),
+        PlutusData::Map(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(k, v)| Ok(json!({ "k": to_value(k)?, "v": to_value(v)? })))
+                .collect::<Result<Vec<_>, PlutusJsonError>>()?;
+            json!({ "map": entries })
+        }
+    })
+}
+
+fn to_values(items: &[PlutusData]) -> Result<Vec<Value>, PlutusJsonError> {
+    items.iter().map(to_value).collect()
+}
+
+fn write_json(data: &PlutusData, out: &mut String) -> Result<(), PlutusJsonError> {
+    match data {
+        PlutusData::Constr(constr) => {
+            out.push_str(&format!(
+                r#"{{"constructor":{},"fields":"#,
+                constructor_index(constr)?
+            ));
+            write_list(&constr.fields, out)?;
+            out.push('}');
+        }
+        PlutusData::BigInt(int) => {
+            out.push_str(&format!(r#"{{"int":{}}}"#, BigInt::from(int.clone())));
+        }
+        PlutusData::BoundedBytes(bytes) => {
+            out.push_str(&format!(
+                r#"{{"bytes":"{}"}}"#,
+                hex::encode(bytes.as_slice())
+            ));
+        }
+        PlutusData::Array(items) => {
+            out.push_str(r#"{"list":"#);
+            write_list(items, out)?;
+            out.push('}');
+        }
+        PlutusData::Map(entries) => {
+            out.push_str(r#"{"map":["#);
+            for (index, (k, v)) in entries.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(r#"{"k":"#);
+                write_json(k, out)?;
+                out.push_str(r#","v":"#);
+                write_json(v, out)?;
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+    }
+    Ok(())
+}
+
+fn write_list(items: &[PlutusData], out: &mut String) -> Result<(), PlutusJsonError> {
+    out.push('[');
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        write_json(item, out)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapEntry<'a> {
+    #[serde(borrow)]
+    k: &'a RawValue,
+    #[serde(borrow)]
+    v: &'a RawValue,
+}
+
+fn parse(json: &RawValue) -> Result<PlutusData, PlutusJsonError> {
+    let object: BTreeMap<String, &RawValue> = from_raw(json.get())?;
+    let keys = object.keys().map(String::as_str).collect::<Vec<_>>();
+    match keys.as_slice() {
+        ["constructor", "fields"] => Ok(constr(
+            from_raw(object["constructor"].get())?,
+            parse_list(object["fields"])?,
+        )),
+        ["int"] => {
+            let text = object["int"].get();
+            let int = num::BigInt::from_str(text)
+                .map_err(|_| PlutusJsonError::NotAnInteger(text.to_string()))?;
+            Ok(PlutusData::BigInt(PallasBigInt::from(BigInt::from(int))))
+        }
+        ["bytes"] => {
+            let bytes = hex::decode(from_raw::<String>(object["bytes"].get())?)?;
+            Ok(PlutusData::BoundedBytes(BoundedBytes::from(bytes)))
+        }
+        ["list"] => Ok(PlutusData::Array(array(parse_list(object["list"])?))),
+        ["map"] => {
+            let entries = from_raw::<Vec<MapEntry>>(object["map"].get())?
+                .into_iter()
+                .map(|entry| Ok((parse(entry.k)?, parse(entry.v)?)))
+                .collect::<Result<Vec<_>, PlutusJsonError>>()?;
+            Ok(PlutusData::Map(KeyValuePairs::Def(entries)))
+        }
+        _ => Err(PlutusJsonError::Json(format!(
+            "expected `constructor` and `fields`, `int`, `bytes`, `list` or `map`, got {keys:?}"
+        ))),
+    }
+}
+
+fn parse_list(json: &RawValue) -> Result<Vec<PlutusData>, PlutusJsonError> {
+    from_raw::<Vec<&RawValue>>(json.get())?
+        .into_iter()
+        .map(parse)
+        .collect()
+}
+
+fn from_raw<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, PlutusJsonError> {
+    serde_json::from_str(json).map_err(|err| PlutusJsonError::Json(err.to_string()))
+}
+
+/// Plutus encodes non-empty lists as indefinite arrays, and the empty list as a definite one.
+fn array(items: Vec<PlutusData>) -> MaybeIndefArray<PlutusData> {
+    if items.is_empty() {
+        MaybeIndefArray::Def(items)
+    } else {
+        MaybeIndefArray::Indef(items)
+    }
+}
+
+/// Constructors 0 to 6 and 7 to 127 have their own CBOR tags, later ones share tag 102.
+fn constr(index: u64, fields: Vec<PlutusData>) -> PlutusData {
+    let (tag, any_constructor) = match index {
+        0..=6 => (121 + index, None),
+        7..=127 => (1280 + index - 7, None),
+        _ => (102, Some(index)),
+    };
+    PlutusData::Constr(Constr {
+        tag,
+        any_constructor,
+        fields: array(fields),
+    })
+}
+
+fn constructor_index(constr: &Constr<PlutusData>) -> Result<u64, PlutusJsonError> {
+    match constr.tag {
+        121..=127 => Ok(constr.tag - 121),
+        1280..=1400 => Ok(constr.tag - 1280 + 7),
+        102 => constr
+            .any_constructor
+            .ok_or(PlutusJsonError::UnknownConstructorTag(102)),
+        tag => Err(PlutusJsonError::UnknownConstructorTag(tag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        PlutusJson, PlutusJsonError, from_detailed_json, from_detailed_json_str, to_detailed_json,
+        to_detailed_json_string,
+    };
+    use crate::primitives::BigInt;
+
+    /// Datum in the format `cardano-cli` prints, with bignums of both signs, nested maps, empty
+    /// constructors and lists, constructors with each kind of tag and a chunked byte string.
+    const FIXTURE: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/datum_detailed_schema.json"
+    ));
+    const FIXTURE_CBOR: &str = concat!(
+        "d8799f581cb1b2a39f13c4a7a7b0cba3e4c6b3e7c43efbd2fa38b27fb6b2e0ca9dd87a80c24940000000000000",
+        "30393bffffffffffffffffc34affffffffffffffffffffa2424c51a2019f2440ff02a080d905019f00ffd86682",
+        "18c8805f584000112233445566778899aabbccddeeff00112233445566778899aabbccddeeff00112233445566",
+        "778899aabbccddeeff00112233445566778899aabbccddeeff5000112233445566778899aabbccddeeffffff",
+    );
+
+    #[test]
+    fn fixture_round_trips_byte_for_byte() {
+        let cbor = from_detailed_json_str(FIXTURE).unwrap();
+        assert_eq!(hex::encode(&cbor), FIXTURE_CBOR);
+
+        let json = to_detailed_json_string(&cbor).unwrap();
+        assert!(json.contains(r#"{"int":1180591620717411315769}"#), "{json}");
+        assert!(json.contains(r#"{"constructor":1,"fields":[]}"#), "{json}");
+        assert_eq!(from_detailed_json_str(&json).unwrap(), cbor);
+
+        // Only the bignums keep it from fitting a `serde_json::Value`
+        assert_eq!(
+            to_detailed_json(&cbor),
+            Err(PlutusJsonError::IntegerOutOfRange(
+                "1180591620717411315769".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let value = json!({
+            "constructor": 3,
+            "fields": [
+                { "int": i64::MIN },
+                { "int": u64::MAX },
+                { "map": [{ "k": { "bytes": "cafe" }, "v": { "list": [] } }] },
+            ]
+        });
+        let cbor = from_detailed_json(&value).unwrap();
+        assert_eq!(&cbor[..3], [0xd8, 0x7c, 0x9f]);
+        assert_eq!(to_detailed_json(&cbor).unwrap(), value);
+    }
+
+    #[test]
+    fn typed_values_go_through_their_cbor() {
+        let values = vec![BigInt::from(1), BigInt::from(-2)];
+        let json = values.to_detailed_json().unwrap();
+        assert_eq!(json, json!({ "list": [{ "int": 1 }, { "int": -2 }] }));
+        assert_eq!(Vec::<BigInt>::from_detailed_json(&json).unwrap(), values);
+    }
+
+    #[test]
+    fn malformed_json_is_rejected() {
+        assert!(matches!(
+            from_detailed_json(&json!({ "int": 1, "bytes": "00" })),
+            Err(PlutusJsonError::Json(_))
+        ));
+        assert!(matches!(
+            from_detailed_json(&json!({ "constructor": 0 })),
+            Err(PlutusJsonError::Json(_))
+        ));
+        assert_eq!(
+            from_detailed_json(&json!({ "int": 1.5 })),
+            Err(PlutusJsonError::NotAnInteger("1.5".to_string()))
+        );
+        assert_eq!(
+            from_detailed_json(&json!({ "int": "1" })),
+            Err(PlutusJsonError::NotAnInteger(r#""1""#.to_string()))
+        );
+        assert!(matches!(
+            from_detailed_json(&json!({ "bytes": "xyz" })),
+            Err(PlutusJsonError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            from_detailed_json(&json!({ "map": [{ "k": { "int": 1 } }] })),
+            Err(PlutusJsonError::Json(_))
+        ));
+    }
+}
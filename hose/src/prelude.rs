@@ -0,0 +1,21 @@
+//! Commonly used types, re-exported for convenience.
+//!
+//! ```
+//! use hose::prelude::*;
+//! ```
+//!
+//! Only hose's own `Input`/`Output` types are exported here, so this can be glob-imported
+//! alongside pallas without the names colliding.
+
+pub use ogmios_client::OgmiosHttpClient as OgmiosClient;
+pub use ogmios_client::method::pparams::ProtocolParams;
+
+pub use crate::builder::tx::TxBuilderError;
+pub use crate::builder::{BuiltTx, TxBuilder};
+pub use crate::contract::{Contract, DatumSource};
+pub use crate::ogmios::OgmiosQueries;
+pub use crate::primitives::{
+    Address, Asset, AssetId, Certificate, DatumOption, ExUnits, Hash, Input, Output, PoolId,
+    RedeemerPurpose, RewardAccount, Script, ScriptExt, ScriptKind, TxHash,
+};
+pub use crate::wallet::{Wallet, WalletBuilder};
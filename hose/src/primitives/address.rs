@@ -0,0 +1,247 @@
+use bech32::{Bech32, Hrp};
+use pallas::ledger::addresses::{
+    Address, Network, Pointer, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+};
+use thiserror::Error;
+
+use crate::primitives::{Hash, RewardAccount};
+
+const STAKE_MAINNET_HRP: &str = "stake";
+const STAKE_TESTNET_HRP: &str = "stake_test";
+
+/// Credential owning staking rights: the delegation part of base addresses and reward accounts.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub enum StakeCredential {
+    Key(Hash<28>),
+    Script(Hash<28>),
+}
+
+impl StakeCredential {
+    pub fn hash(&self) -> Hash<28> {
+        match self {
+            StakeCredential::Key(hash) | StakeCredential::Script(hash) => *hash,
+        }
+    }
+
+    pub fn reward_account(&self, network: Network) -> RewardAccount {
+        RewardAccount::from_credential(network, *self)
+    }
+
+    /// Encodes the `stake1...`/`stake_test1...` address of the credential.
+    pub fn to_bech32(&self, network: Network) -> String {
+        let hrp = match network {
+            Network::Mainnet => STAKE_MAINNET_HRP,
+            _ => STAKE_TESTNET_HRP,
+        };
+        bech32::encode::<Bech32>(
+            Hrp::parse_unchecked(hrp),
+            self.reward_account(network).as_ref(),
+        )
+        .expect("a stake address is well below the bech32 length limit")
+    }
+
+    /// Decodes a `stake1...`/`stake_test1...` address into its network and credential.
+    pub fn from_bech32(bech32_str: &str) -> Result<(Network, Self), StakeCredentialError> {
+        let (hrp, data) = bech32::decode(bech32_str)?;
+        if hrp.as_str() != STAKE_MAINNET_HRP && hrp.as_str() != STAKE_TESTNET_HRP {
+            return Err(StakeCredentialError::InvalidHrp(hrp.to_string()));
+        }
+        let account = RewardAccount::from(data);
+        match (account.network(), account.credential()) {
+            (Some(network), Some(credential)) => Ok((network, credential)),
+            _ => Err(StakeCredentialError::NotARewardAddress),
+        }
+    }
+}
+
+impl From<StakeCredential> for ShelleyDelegationPart {
+    fn from(credential: StakeCredential) -> Self {
+        match credential {
+            StakeCredential::Key(hash) => ShelleyDelegationPart::Key(hash.into()),
+            StakeCredential::Script(hash) => ShelleyDelegationPart::Script(hash.into()),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StakeCredentialError {
+    /// Unexpected bech32 HRP prefix
+    #[error("Unexpected bech32 HRP prefix for a stake address: {0}")]
+    InvalidHrp(String),
+    /// Unable to decode bech32 string
+    #[error("Unable to decode bech32: {0}")]
+    InvalidBech32(#[from] bech32::DecodeError),
+    /// Payload isn't a reward address header followed by a 28 byte hash
+    #[error("Not a reward address")]
+    NotARewardAddress,
+}
+
+/// How a Shelley address delegates its stake.
+///
+/// Pointer addresses are legacy, they reference the certificate that registered the stake
+/// credential by its position on chain rather than carrying the credential itself.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum StakeReference {
+    Credential(StakeCredential),
+    Pointer {
+        slot: u64,
+        tx_index: u64,
+        cert_index: u64,
+    },
+    /// Enterprise addresses
+    None,
+}
+
+impl StakeReference {
+    /// Stake reference of a Shelley address, `None` for byron and reward addresses.
+    pub fn from_address(address: &Address) -> Option<Self> {
+        let Address::Shelley(address) = address else {
+            return None;
+        };
+        Some(match address.delegation() {
+            ShelleyDelegationPart::Key(hash) => {
+                StakeReference::Credential(StakeCredential::Key(Hash::from(*hash)))
+            }
+            ShelleyDelegationPart::Script(hash) => {
+                StakeReference::Credential(StakeCredential::Script(Hash::from(*hash)))
+            }
+            ShelleyDelegationPart::Pointer(pointer) => StakeReference::Pointer {
+                slot: pointer.slot(),
+                tx_index: pointer.tx_idx(),
+                cert_index: pointer.cert_idx(),
+            },
+            ShelleyDelegationPart::Null => StakeReference::None,
+        })
+    }
+
+    /// Stake credential carried by the reference, pointers have to be resolved on chain.
+    pub fn credential(&self) -> Option<StakeCredential> {
+        match self {
+            StakeReference::Credential(credential) => Some(*credential),
+            _ => None,
+        }
+    }
+}
+
+impl From<StakeReference> for ShelleyDelegationPart {
+    fn from(reference: StakeReference) -> Self {
+        match reference {
+            StakeReference::Credential(credential) => credential.into(),
+            StakeReference::Pointer {
+                slot,
+                tx_index,
+                cert_index,
+            } => ShelleyDelegationPart::Pointer(Pointer::new(slot, tx_index, cert_index)),
+            StakeReference::None => ShelleyDelegationPart::Null,
+        }
+    }
+}
+
+impl From<StakeCredential> for StakeReference {
+    fn from(credential: StakeCredential) -> Self {
+        StakeReference::Credential(credential)
+    }
+}
+
+/// Builds a Shelley address of any kind: base, pointer or enterprise depending on `stake`.
+pub fn shelley_address(
+    network: Network,
+    payment: ShelleyPaymentPart,
+    stake: impl Into<StakeReference>,
+) -> Address {
+    Address::Shelley(ShelleyAddress::new(network, payment, stake.into().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{Address, Network, ShelleyPaymentPart};
+
+    use super::{StakeCredential, StakeCredentialError, StakeReference, shelley_address};
+    use crate::primitives::{Hash, Output};
+
+    // Test vectors from CIP-19.
+    const PAYMENT_KEY_HASH: &str = "9493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e";
+    const STAKE_KEY_HASH: &str = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+    const POINTER_ADDRESS: &str =
+        "addr_test1gz2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer5pnz75xxcrdw5vky";
+    const STAKE_ADDRESS: &str = "stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw";
+    const STAKE_TEST_ADDRESS: &str =
+        "stake_test1uqehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gssrtvn";
+
+    fn hash(hex_str: &str) -> Hash<28> {
+        Hash(hex::decode(hex_str).unwrap().try_into().unwrap())
+    }
+
+    #[test]
+    fn pointer_addresses_are_parsed() {
+        let address = Address::from_bech32(POINTER_ADDRESS).unwrap();
+
+        let reference = StakeReference::from_address(&address).unwrap();
+        assert_eq!(
+            reference,
+            StakeReference::Pointer {
+                slot: 2_498_243,
+                tx_index: 27,
+                cert_index: 3,
+            }
+        );
+        assert_eq!(reference.credential(), None);
+
+        let rebuilt = shelley_address(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(hash(PAYMENT_KEY_HASH).into()),
+            reference,
+        );
+        assert_eq!(rebuilt.to_bech32().unwrap(), POINTER_ADDRESS);
+
+        // Outputs to pointer addresses still build.
+        Output::new(address, 2_000_000).build_babbage().unwrap();
+    }
+
+    #[test]
+    fn every_stake_reference_round_trips_through_an_address() {
+        let payment = ShelleyPaymentPart::Key(hash(PAYMENT_KEY_HASH).into());
+        let references = [
+            StakeReference::Credential(StakeCredential::Key(hash(STAKE_KEY_HASH))),
+            StakeReference::Credential(StakeCredential::Script(hash(STAKE_KEY_HASH))),
+            StakeReference::Pointer {
+                slot: 1,
+                tx_index: 2,
+                cert_index: 3,
+            },
+            StakeReference::None,
+        ];
+
+        for reference in references {
+            let address = shelley_address(Network::Mainnet, payment.clone(), reference);
+            let parsed = Address::from_bytes(&address.to_vec()).unwrap();
+            assert_eq!(StakeReference::from_address(&parsed), Some(reference));
+        }
+    }
+
+    #[test]
+    fn stake_credentials_round_trip_through_bech32() {
+        let credential = StakeCredential::Key(hash(STAKE_KEY_HASH));
+
+        assert_eq!(credential.to_bech32(Network::Mainnet), STAKE_ADDRESS);
+        assert_eq!(credential.to_bech32(Network::Testnet), STAKE_TEST_ADDRESS);
+        assert_eq!(
+            StakeCredential::from_bech32(STAKE_TEST_ADDRESS).unwrap(),
+            (Network::Testnet, credential)
+        );
+
+        let script = StakeCredential::Script(hash(STAKE_KEY_HASH));
+        assert_eq!(
+            StakeCredential::from_bech32(&script.to_bech32(Network::Mainnet)).unwrap(),
+            (Network::Mainnet, script)
+        );
+    }
+
+    #[test]
+    fn payment_addresses_are_not_stake_credentials() {
+        assert!(matches!(
+            StakeCredential::from_bech32(POINTER_ADDRESS),
+            Err(StakeCredentialError::InvalidHrp(_))
+        ));
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bech32::{Bech32, Hrp};
+use pallas::crypto::hash::Hasher;
+
+use super::{Asset, AssetDelta, AssetId, Assets, AssetsDelta, Hash};
+use crate::builder::tx::TxBuilderError;
+
+const ASSET_FINGERPRINT_HRP: &str = "asset";
+
+/// Consistent rendering of assets for logs and UIs.
+pub trait AssetIdExt {
+    /// The asset name as text if it is printable ASCII, otherwise as `0x`-prefixed hex, so binary
+    /// names are never mangled.
+    fn name_display(&self) -> String;
+
+    /// CIP-14 fingerprint of the asset, e.g. `asset1rjklcrnsdzqp65wjgrg55sy9723kw09mlgvlc3`.
+    fn fingerprint(&self) -> String;
+
+    /// The name along with the fingerprint, as `name (fingerprint)`.
+    fn display_with_fingerprint(&self) -> String {
+        format!("{} ({})", self.name_display(), self.fingerprint())
+    }
+}
+
+impl AssetIdExt for AssetId {
+    fn name_display(&self) -> String {
+        name_display(&self.name)
+    }
+
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.policy, &self.name)
+    }
+}
+
+impl AssetIdExt for Asset {
+    fn name_display(&self) -> String {
+        name_display(&self.name)
+    }
+
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.policy, &self.name)
+    }
+}
+
+fn name_display(name: &[u8]) -> String {
+    if name.iter().all(|byte| (b' '..=b'~').contains(byte)) {
+        String::from_utf8_lossy(name).into_owned()
+    } else {
+        format!("0x{}", hex::encode(name))
+    }
+}
+
+/// See CIP-14: bech32 encoding of the blake2b-160 hash of the policy id and asset name.
+fn fingerprint(policy: &Hash<28>, name: &[u8]) -> String {
+    let mut hasher = Hasher::<160>::new();
+    hasher.input(&policy.0);
+    hasher.input(name);
+    bech32::encode::<Bech32>(
+        Hrp::parse_unchecked(ASSET_FINGERPRINT_HRP),
+        hasher.finalize().as_ref(),
+    )
+    .expect("a fingerprint is well below the bech32 length limit")
+}
+
+/// Helpers for working with signed asset amounts, as found in the mint field of a transaction,
+/// where minted assets are positive and burned ones negative.
+///
+/// Constructing deltas through `mint` and `burn` keeps the sign next to the intent, instead of
+/// negating quantities in calling code.
+pub trait AssetsDeltaExt: Sized {
+    /// `asset.quantity` of the asset, minted.
+    fn mint(asset: Asset) -> Result<Self, TxBuilderError>;
+
+    /// `asset.quantity` of the asset, burned.
+    fn burn(asset: Asset) -> Result<Self, TxBuilderError>;
+
+    /// Sum of both deltas, or `None` if an amount overflows. Assets whose amounts cancel out are
+    /// dropped.
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    /// Whether every asset nets out to zero, in which case no minting policy runs.
+    fn is_net_zero(&self) -> bool;
+
+    /// Amounts by asset name, grouped by minting policy.
+    fn by_policy(&self) -> HashMap<Hash<28>, BTreeMap<Vec<u8>, i64>>;
+
+    /// Positive part of the delta, as output-side assets.
+    fn minted(&self) -> Assets;
+
+    /// Negative part of the delta, negated into output-side assets.
+    fn burned(&self) -> Assets;
+}
+
+impl AssetsDeltaExt for AssetsDelta {
+    fn mint(asset: Asset) -> Result<Self, TxBuilderError> {
+        let amount = signed_quantity(&asset)?;
+        let mut delta = AssetsDelta::default();
+        delta.add_asset(AssetDelta::new(asset.policy, asset.name, amount));
+        Ok(delta)
+    }
+
+    fn burn(asset: Asset) -> Result<Self, TxBuilderError> {
+        let amount = signed_quantity(&asset)?;
+        let mut delta = AssetsDelta::default();
+        delta.add_asset(AssetDelta::new(asset.policy, asset.name, -amount));
+        Ok(delta)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut sum = self.clone();
+        for (asset_id, amount) in other.iter() {
+            let current = sum.get(asset_id).copied().unwrap_or(0);
+            let total = current.checked_add(*amount)?;
+            sum.remove(asset_id);
+            if total != 0 {
+                sum.add_asset(AssetDelta::new(
+                    asset_id.policy,
+                    asset_id.name.clone(),
+                    total,
+                ));
+            }
+        }
+        Some(sum)
+    }
+
+    fn is_net_zero(&self) -> bool {
+        self.iter().all(|(_, amount)| *amount == 0)
+    }
+
+    fn by_policy(&self) -> HashMap<Hash<28>, BTreeMap<Vec<u8>, i64>> {
+        let mut grouped: HashMap<Hash<28>, BTreeMap<Vec<u8>, i64>> = HashMap::new();
+        for (asset_id, amount) in self.iter() {
+            grouped
+                .entry(asset_id.policy)
+                .or_default()
+                .insert(asset_id.name.clone(), *amount);
+        }
+        grouped
+    }
+
+    fn minted(&self) -> Assets {
+        let mut assets = Assets::default();
+        for (asset_id, amount) in self.iter().filter(|(_, amount)| **amount > 0) {
+            assets.add_asset(Asset::new(
+                asset_id.policy,
+                asset_id.name.clone(),
+                amount.unsigned_abs(),
+            ));
+        }
+        assets
+    }
+
+    fn burned(&self) -> Assets {
+        let mut assets = Assets::default();
+        for (asset_id, amount) in self.iter().filter(|(_, amount)| **amount < 0) {
+            assets.add_asset(Asset::new(
+                asset_id.policy,
+                asset_id.name.clone(),
+                amount.unsigned_abs(),
+            ));
+        }
+        assets
+    }
+}
+
+/// Quantity of `asset` as a mint amount, which must be non-zero and fit in an `i64`.
+fn signed_quantity(asset: &Asset) -> Result<i64, TxBuilderError> {
+    if asset.quantity == 0 {
+        return Err(TxBuilderError::InvalidMintAmount);
+    }
+    i64::try_from(asset.quantity).map_err(|_| TxBuilderError::InvalidMintAmount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(policy: u8, name: &[u8], quantity: u64) -> Asset {
+        Asset::new(Hash([policy; 28]), name.to_vec(), quantity)
+    }
+
+    fn policy_from_hex(policy: &str) -> Hash<28> {
+        Hash(hex::decode(policy).unwrap().try_into().unwrap())
+    }
+
+    #[test]
+    fn name_display_falls_back_to_hex() {
+        assert_eq!(asset(1, b"qAda", 1).name_display(), "qAda");
+        assert_eq!(asset(1, b"", 1).name_display(), "");
+        assert_eq!(
+            asset(1, &[0x00, 0x0d, 0xe1, 0x40], 1).name_display(),
+            "0x000de140"
+        );
+        assert_eq!(
+            asset(1, b"tab\tname", 1).name_display(),
+            "0x746162096e616d65"
+        );
+        assert_eq!(
+            asset(1, "caf\u{e9}".as_bytes(), 1).name_display(),
+            "0x636166c3a9"
+        );
+    }
+
+    // Test vectors from CIP-14
+    #[test]
+    fn fingerprints_match_cip14() {
+        let policy_a = policy_from_hex("7eae28af2208be856f7a119668ae52a49b73725e326dc16579dcc373");
+        let policy_b = policy_from_hex("1e349c9bdea19fd6c147626a5260bc44b71635f398b67c59881df209");
+        let cases = [
+            (
+                policy_a,
+                vec![],
+                "asset1rjklcrnsdzqp65wjgrg55sy9723kw09mlgvlc3",
+            ),
+            (
+                policy_a,
+                b"PATATE".to_vec(),
+                "asset13n25uv0yaf5kus35fm2k86cqy60z58d9xmde92",
+            ),
+            (
+                policy_b,
+                policy_a.0.to_vec(),
+                "asset1aqrdypg669jgazruv5ah07nuyqe0wxjhe2el6f",
+            ),
+            (
+                policy_a,
+                vec![0; 32],
+                "asset1pkpwyknlvul7az0xx8czhl60pyel45rpje4z8w",
+            ),
+        ];
+        for (policy, name, expected) in cases {
+            assert_eq!(Asset::new(policy, name, 1).fingerprint(), expected);
+        }
+
+        let mut assets = Assets::default();
+        assets.add_asset(asset(1, b"qAda", 5));
+        let asset_id = assets.keys().next().unwrap();
+        assert_eq!(asset_id.fingerprint(), asset(1, b"qAda", 5).fingerprint());
+        assert_eq!(
+            asset_id.display_with_fingerprint(),
+            format!("qAda ({})", asset_id.fingerprint())
+        );
+    }
+
+    #[test]
+    fn mint_and_burn_keep_their_sign() {
+        let minted = AssetsDelta::mint(asset(1, b"a", 5)).unwrap();
+        let burned = AssetsDelta::burn(asset(1, b"a", 3)).unwrap();
+
+        assert_eq!(minted.by_policy()[&Hash([1; 28])][b"a".as_slice()], 5);
+        assert_eq!(burned.by_policy()[&Hash([1; 28])][b"a".as_slice()], -3);
+        assert_eq!(
+            AssetsDelta::mint(asset(1, b"a", 0)).err(),
+            Some(TxBuilderError::InvalidMintAmount)
+        );
+        assert_eq!(
+            AssetsDelta::burn(asset(1, b"a", u64::MAX)).err(),
+            Some(TxBuilderError::InvalidMintAmount)
+        );
+    }
+
+    #[test]
+    fn checked_add_nets_out_and_detects_overflow() {
+        let minted = AssetsDelta::mint(asset(1, b"a", 5)).unwrap();
+        let burned = AssetsDelta::burn(asset(1, b"a", 5)).unwrap();
+        let sum = minted.checked_add(&burned).unwrap();
+        assert!(sum.is_empty());
+        assert!(sum.is_net_zero());
+
+        let partly_burned = AssetsDelta::burn(asset(1, b"a", 2)).unwrap();
+        let sum = minted.checked_add(&partly_burned).unwrap();
+        assert!(!sum.is_net_zero());
+        assert_eq!(sum.by_policy()[&Hash([1; 28])][b"a".as_slice()], 3);
+
+        let max = AssetsDelta::mint(asset(1, b"a", i64::MAX as u64)).unwrap();
+        assert!(max.checked_add(&minted).is_none());
+    }
+
+    #[test]
+    fn grouping_and_conversion_to_assets() {
+        let delta = [
+            AssetsDelta::mint(asset(1, b"a", 5)).unwrap(),
+            AssetsDelta::burn(asset(1, b"b", 2)).unwrap(),
+            AssetsDelta::mint(asset(2, b"a", 7)).unwrap(),
+        ]
+        .iter()
+        .try_fold(AssetsDelta::default(), |sum, delta| sum.checked_add(delta))
+        .unwrap();
+
+        let grouped = delta.by_policy();
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(
+            grouped[&Hash([1; 28])],
+            BTreeMap::from([(b"a".to_vec(), 5), (b"b".to_vec(), -2)])
+        );
+        assert_eq!(
+            grouped[&Hash([2; 28])],
+            BTreeMap::from([(b"a".to_vec(), 7)])
+        );
+
+        let mut minted = Assets::default();
+        minted.add_asset(asset(1, b"a", 5));
+        minted.add_asset(asset(2, b"a", 7));
+        assert_eq!(delta.minted(), minted);
+
+        let mut burned = Assets::default();
+        burned.add_asset(asset(1, b"b", 2));
+        assert_eq!(delta.burned(), burned);
+    }
+}
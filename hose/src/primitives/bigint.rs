@@ -0,0 +1,266 @@
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use num::bigint::{Sign, TryFromBigIntError};
+use num::{One as _, ToPrimitive as _};
+use pallas::codec::minicbor;
+use pallas::codec::utils::Int;
+use pallas::ledger::primitives::BigInt as PallasBigInt;
+
+/// An arbitrary precision integer, matching the semantics of Plutus' `Integer`.
+///
+/// Values in the `[-2^64, 2^64 - 1]` range are encoded as plain CBOR integers (major types 0 and
+/// 1), while anything outside of it is encoded as a bignum (CBOR tags 2 and 3), just like the
+/// ledger does.
+///
+/// Arithmetic is arbitrary precision and never overflows, like Plutus' integer builtins.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Default)]
+pub struct BigInt(num::BigInt);
+
+impl BigInt {
+    pub fn new(value: num::BigInt) -> Self {
+        Self(value)
+    }
+
+    pub fn as_inner(&self) -> &num::BigInt {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> num::BigInt {
+        self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.sign() == Sign::Minus
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+macro_rules! impl_from_primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for BigInt {
+                fn from(value: $ty) -> Self {
+                    Self(num::BigInt::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_primitive!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+impl TryFrom<&BigInt> for i128 {
+    type Error = TryFromBigIntError<()>;
+
+    fn try_from(value: &BigInt) -> Result<Self, Self::Error> {
+        i128::try_from(&value.0)
+    }
+}
+
+impl TryFrom<BigInt> for i128 {
+    type Error = TryFromBigIntError<()>;
+
+    fn try_from(value: BigInt) -> Result<Self, Self::Error> {
+        i128::try_from(&value)
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl $trait for BigInt {
+                type Output = BigInt;
+
+                fn $method(self, rhs: BigInt) -> BigInt {
+                    BigInt(self.0.$method(rhs.0))
+                }
+            }
+
+            impl $trait<&BigInt> for &BigInt {
+                type Output = BigInt;
+
+                fn $method(self, rhs: &BigInt) -> BigInt {
+                    BigInt((&self.0).$method(&rhs.0))
+                }
+            }
+        )*
+    };
+}
+
+impl_binary_op!(Add::add, Sub::sub, Mul::mul);
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt(-self.0)
+    }
+}
+
+impl From<num::BigInt> for BigInt {
+    fn from(value: num::BigInt) -> Self {
+        Self(value)
+    }
+}
+
+impl From<BigInt> for num::BigInt {
+    fn from(value: BigInt) -> Self {
+        value.0
+    }
+}
+
+impl From<PallasBigInt> for BigInt {
+    fn from(value: PallasBigInt) -> Self {
+        match value {
+            PallasBigInt::Int(int) => Self(num::BigInt::from(i128::from(int))),
+            PallasBigInt::BigUInt(bytes) => Self(num::BigInt::from_bytes_be(Sign::Plus, &bytes)),
+            // CBOR negative bignums encode `n` as `-1 - n`
+            PallasBigInt::BigNInt(bytes) => {
+                Self(-num::BigInt::one() - num::BigInt::from_bytes_be(Sign::Plus, &bytes))
+            }
+        }
+    }
+}
+
+impl From<&BigInt> for PallasBigInt {
+    fn from(value: &BigInt) -> Self {
+        // Plain CBOR integers cover [-2^64, 2^64 - 1], everything else must be a bignum.
+        if let Some(int) = value.0.to_i128().and_then(|x| Int::try_from(x).ok()) {
+            return PallasBigInt::Int(int);
+        }
+
+        if value.is_negative() {
+            let magnitude = -value.0.clone() - num::BigInt::one();
+            let (_, bytes) = magnitude.to_bytes_be();
+            PallasBigInt::BigNInt(bytes.into())
+        } else {
+            let (_, bytes) = value.0.to_bytes_be();
+            PallasBigInt::BigUInt(bytes.into())
+        }
+    }
+}
+
+impl From<BigInt> for PallasBigInt {
+    fn from(value: BigInt) -> Self {
+        PallasBigInt::from(&value)
+    }
+}
+
+impl<C> minicbor::Encode<C> for BigInt {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+        ctx: &mut C,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        PallasBigInt::from(self).encode(e, ctx)
+    }
+}
+
+impl<'b, C> minicbor::Decode<'b, C> for BigInt {
+    fn decode(d: &mut minicbor::Decoder<'b>, ctx: &mut C) -> Result<Self, minicbor::decode::Error> {
+        PallasBigInt::decode(d, ctx).map(BigInt::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::Num as _;
+    use pallas::codec::minicbor;
+
+    use super::BigInt;
+
+    fn round_trip(value: BigInt) -> Vec<u8> {
+        let bytes = minicbor::to_vec(&value).expect("encode");
+        let decoded: BigInt = minicbor::decode(&bytes).expect("decode");
+        assert_eq!(decoded, value);
+        bytes
+    }
+
+    fn parse(value: &str) -> BigInt {
+        num::BigInt::from_str_radix(value, 10).unwrap().into()
+    }
+
+    #[test]
+    fn small_values_use_plain_integers() {
+        assert_eq!(round_trip(BigInt::from(42)), vec![0x18, 0x2a]);
+        assert_eq!(round_trip(BigInt::from(-42)), vec![0x38, 0x29]);
+        assert_eq!(
+            round_trip(BigInt::from(u64::MAX)),
+            vec![0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        // -2^64 is the smallest value representable with major type 1
+        assert_eq!(
+            round_trip(parse("-18446744073709551616")),
+            vec![0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn large_values_use_bignums() {
+        // 2^64
+        assert_eq!(
+            round_trip(parse("18446744073709551616")),
+            vec![
+                0xc2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            ]
+        );
+        // -2^64 - 1
+        assert_eq!(
+            round_trip(parse("-18446744073709551617")),
+            vec![
+                0xc3, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn arithmetic_is_arbitrary_precision() {
+        let max = BigInt::from(u64::MAX);
+        let squared = &max * &max;
+        assert_eq!(squared, parse("340282366920938463426481119284349108225"));
+        assert!(i128::try_from(&squared).is_err());
+
+        let supply_diff = BigInt::from(1_594_436_078u32) - BigInt::from(2_000_000_000i64);
+        assert_eq!(supply_diff, BigInt::from(-405_563_922i64));
+        assert_eq!(i128::try_from(supply_diff).unwrap(), -405_563_922);
+        assert_eq!(-BigInt::from(5), BigInt::from(-5));
+    }
+
+    #[test]
+    fn ordering_follows_integer_semantics() {
+        let mut values = vec![
+            parse("18446744073709551616"),
+            BigInt::from(-3),
+            parse("-18446744073709551617"),
+            BigInt::from(7),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                parse("-18446744073709551617"),
+                BigInt::from(-3),
+                BigInt::from(7),
+                parse("18446744073709551616"),
+            ]
+        );
+    }
+
+    #[test]
+    fn datum_with_signed_fields_round_trips() {
+        let fields = vec![
+            BigInt::from(-1_594_436_078i64),
+            parse("-340282366920938463463374607431768211456"),
+            parse("340282366920938463463374607431768211456"),
+        ];
+        let bytes = minicbor::to_vec(&fields).expect("encode");
+        let decoded: Vec<BigInt> = minicbor::decode(&bytes).expect("decode");
+        assert_eq!(decoded, fields);
+    }
+}
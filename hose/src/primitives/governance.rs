@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use crate::primitives::{Hash, RewardAccount, ScriptHash, TxHash};
+
+/// Off-chain document justifying a governance action, see CIP-108.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Anchor {
+    pub url: String,
+    /// Blake2b-256 of the document
+    pub data_hash: Hash<32>,
+}
+
+impl Anchor {
+    pub fn new(url: impl Into<String>, data_hash: Hash<32>) -> Self {
+        Self {
+            url: url.into(),
+            data_hash,
+        }
+    }
+}
+
+/// Who a stake credential delegates its voting power to.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum DRep {
+    Key(Hash<28>),
+    Script(Hash<28>),
+    /// Counted as abstaining on every action
+    Abstain,
+    /// Counted as voting no confidence on every action
+    NoConfidence,
+}
+
+/// Identifier of a governance action: the proposing transaction and the proposal's index in it.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub struct GovActionId {
+    pub tx_hash: TxHash,
+    pub index: u32,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum GovAction {
+    /// Pays out of the treasury into each reward account.
+    TreasuryWithdrawals {
+        withdrawals: BTreeMap<RewardAccount, u64>,
+        /// Hash of the constitution's guardrails script, if the constitution has one
+        guardrails_script: Option<ScriptHash>,
+    },
+    ParameterChange {
+        /// Last enacted parameter change, `None` if there is none yet
+        previous: Option<GovActionId>,
+        /// CBOR encoded `protocol_param_update`
+        update: Vec<u8>,
+        guardrails_script: Option<ScriptHash>,
+    },
+    HardForkInitiation {
+        /// Last enacted hard fork, `None` if there is none yet
+        previous: Option<GovActionId>,
+        /// Major and minor protocol version to fork to
+        protocol_version: (u64, u64),
+    },
+}
+
+impl GovAction {
+    /// Script the ledger runs to check the action against the constitution. Proposing an action
+    /// with one requires a `RedeemerPurpose::Propose` redeemer.
+    pub fn guardrails_script(&self) -> Option<ScriptHash> {
+        match self {
+            GovAction::TreasuryWithdrawals {
+                guardrails_script, ..
+            }
+            | GovAction::ParameterChange {
+                guardrails_script, ..
+            } => *guardrails_script,
+            GovAction::HardForkInitiation { .. } => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ProposalProcedure {
+    pub gov_action: GovAction,
+    // Note: a deposit is always required. A value of None here just means that the value of the
+    // deposit is to be retrieved from the protocol params.
+    pub deposit: Option<u64>,
+    /// Where the deposit is returned once the action is enacted or expires
+    pub return_account: RewardAccount,
+    pub anchor: Anchor,
+}
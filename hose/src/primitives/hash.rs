@@ -0,0 +1,151 @@
+use thiserror::Error;
+
+use crate::primitives::Hash;
+
+/// Parsing and formatting of hashes, which are shown as lowercase hex everywhere (explorers,
+/// `cardano-cli`, Ogmios).
+///
+/// Conversions from and into pallas hashes and `[u8; N]` are provided by `From` already.
+pub trait HashExt: Sized {
+    /// Parses hex of exactly `N` bytes, in either case.
+    fn from_hex(hex_str: &str) -> Result<Self, HashError>;
+
+    /// Lowercase hex, same as `Display`.
+    fn to_hex(&self) -> String;
+
+    /// Copies exactly `N` bytes, e.g. out of a decoded address or witness.
+    fn from_slice(bytes: &[u8]) -> Result<Self, HashError>;
+}
+
+impl<const N: usize> HashExt for Hash<N> {
+    fn from_hex(hex_str: &str) -> Result<Self, HashError> {
+        Self::from_slice(&hex::decode(hex_str)?)
+    }
+
+    fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, HashError> {
+        let bytes: [u8; N] = bytes.try_into().map_err(|_| HashError::UnexpectedLength {
+            expected: N,
+            found: bytes.len(),
+        })?;
+        Ok(Hash(bytes))
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HashError {
+    /// Unable to decode hex string
+    #[error("Unable to decode hex: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    /// Wrong number of bytes for the hash size
+    #[error("Hash of unexpected length: {found} bytes, expected {expected}")]
+    UnexpectedLength { expected: usize, found: usize },
+}
+
+/// Serializes a `Hash<N>` as a lowercase hex string, for use with
+/// `#[serde(with = "hose::primitives::hash_hex_serde")]`.
+pub mod hash_hex_serde {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    use super::HashExt;
+    use crate::primitives::Hash;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        hash: &Hash<N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hash.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<Hash<N>, D::Error> {
+        let hex_str = String::deserialize(deserializer)?;
+        Hash::from_hex(&hex_str).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{HashError, HashExt};
+    use crate::primitives::Hash;
+
+    const TX_HASH: &str = "e5b4a3c4d5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2";
+
+    fn round_trip<const N: usize>(byte: u8) {
+        let hash = Hash([byte; N]);
+        let hex_str = hash.to_hex();
+        assert_eq!(hex_str.len(), 2 * N);
+        assert_eq!(Hash::<N>::from_hex(&hex_str), Ok(hash));
+    }
+
+    #[test]
+    fn hashes_round_trip_through_hex() {
+        round_trip::<28>(0xab);
+        round_trip::<32>(0x01);
+        round_trip::<64>(0xfe);
+
+        let tx_hash = Hash::<32>::from_hex(TX_HASH).unwrap();
+        assert_eq!(tx_hash.to_string(), TX_HASH);
+        let pallas_hash: pallas::crypto::hash::Hash<32> = tx_hash.into();
+        assert_eq!(pallas_hash.to_string(), TX_HASH);
+        assert_eq!(Hash::from(pallas_hash), tx_hash);
+    }
+
+    #[test]
+    fn parsing_rejects_malformed_hex() {
+        assert_eq!(
+            Hash::<28>::from_hex(TX_HASH),
+            Err(HashError::UnexpectedLength {
+                expected: 28,
+                found: 32
+            })
+        );
+        assert_eq!(
+            Hash::<32>::from_hex(&TX_HASH[1..]),
+            Err(HashError::InvalidHex(hex::FromHexError::OddLength))
+        );
+        assert!(matches!(
+            Hash::<32>::from_hex(&TX_HASH.replace('e', "g")),
+            Err(HashError::InvalidHex(
+                hex::FromHexError::InvalidHexCharacter { c: 'g', .. }
+            ))
+        ));
+        assert_eq!(
+            Hash::<28>::from_slice(&[0u8; 27]),
+            Err(HashError::UnexpectedLength {
+                expected: 28,
+                found: 27
+            })
+        );
+    }
+
+    #[test]
+    fn uppercase_hex_is_accepted_and_printed_lowercase() {
+        let hash = Hash::<32>::from_hex(&TX_HASH.to_uppercase()).unwrap();
+        assert_eq!(hash, Hash::<32>::from_hex(TX_HASH).unwrap());
+        assert_eq!(hash.to_hex(), TX_HASH);
+    }
+
+    #[test]
+    fn serde_uses_hex_strings() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Payment {
+            #[serde(with = "super::hash_hex_serde")]
+            tx_hash: Hash<32>,
+        }
+
+        let payment = Payment {
+            tx_hash: Hash::from_hex(TX_HASH).unwrap(),
+        };
+        let json = serde_json::to_string(&payment).unwrap();
+        assert_eq!(json, format!(r#"{{"tx_hash":"{TX_HASH}"}}"#));
+        assert_eq!(serde_json::from_str::<Payment>(&json).unwrap(), payment);
+        assert!(serde_json::from_str::<Payment>(r#"{"tx_hash":"00"}"#).is_err());
+    }
+}
@@ -3,18 +3,32 @@ pub use hydrant::primitives::{
 };
 pub use pallas::ledger::addresses::Address;
 
+mod address;
+mod assets;
+mod bigint;
+mod governance;
+mod hash;
 mod input;
 mod output;
+mod pool;
 mod reward;
 mod script;
 mod signer;
+mod slot;
 mod stake;
 
+pub use address::*;
+pub use assets::*;
+pub use bigint::*;
+pub use governance::*;
+pub use hash::*;
 pub use input::*;
 pub use output::*;
+pub use pool::*;
 pub use reward::*;
 pub use script::*;
 pub use signer::*;
+pub use slot::*;
 pub use stake::*;
 
 pub type PubKeyHash = Hash<28>;
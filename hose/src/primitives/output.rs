@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 
+use hydrant::primitives::TxOutput;
 use ogmios_client::method::pparams::ProtocolParams;
 use pallas::codec::utils::{Bytes, CborWrap};
 use pallas::crypto::hash::Hash as PallasHash;
@@ -9,10 +10,13 @@ use pallas::ledger::primitives::conway::{
 };
 use pallas::ledger::primitives::{Fragment, PositiveCoin};
 
-use super::{Address, Asset, AssetId, Assets, DatumOption, Hash, Script, ScriptKind};
+use super::{
+    Address, Asset, AssetId, Assets, Datum, DatumOption, Hash, Input, Script, ScriptKind, ScriptRef,
+};
 use crate::builder::tx::TxBuilderError;
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+/// Outputs compare by what they serialize to, see `PartialEq`.
+#[derive(Debug, Clone)]
 pub struct Output {
     pub address: Address,
     pub lovelace: u64,
@@ -21,6 +25,21 @@ pub struct Output {
     pub script: Option<Script>,
 }
 
+/// Outputs are equal when they hold the same value, datum and script at the same address,
+/// however their assets were put together: `None` and an empty map are the same, and zero
+/// quantities, which `build_babbage` rejects, don't count.
+impl PartialEq for Output {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+            && self.lovelace == other.lovelace
+            && self.positive_assets() == other.positive_assets()
+            && self.datum == other.datum
+            && self.script == other.script
+    }
+}
+
+impl Eq for Output {}
+
 impl Output {
     pub fn new(address: Address, lovelace: u64) -> Self {
         Self {
@@ -41,6 +60,13 @@ impl Output {
         if name.len() > 32 {
             return Err(TxBuilderError::AssetNameTooLong);
         }
+        if amount == 0 {
+            return Err(TxBuilderError::ZeroQuantityAsset {
+                policy,
+                name,
+                output_index: None,
+            });
+        }
 
         let mut assets = self.assets.unwrap_or_default();
         assets.add_asset(Asset::new(policy, name, amount));
@@ -50,6 +76,13 @@ impl Output {
     }
 
     pub fn add_assets(mut self, assets: Assets) -> Result<Self, TxBuilderError> {
+        if let Some((asset_id, _)) = assets.iter().find(|(_, amount)| **amount == 0) {
+            return Err(TxBuilderError::ZeroQuantityAsset {
+                policy: asset_id.policy,
+                name: asset_id.name.clone(),
+                output_index: None,
+            });
+        }
         self.assets = Some(self.assets.unwrap_or_default() + assets);
         Ok(self)
     }
@@ -57,7 +90,7 @@ impl Output {
     pub fn remove_asset(mut self, policy: Hash<28>, name: Vec<u8>) -> Self {
         let mut assets = self.assets.unwrap_or_default();
         assets.remove(&AssetId::new(policy, name));
-        self.assets = Some(assets);
+        self.assets = Some(assets).filter(|assets| !assets.is_empty());
         self
     }
 
@@ -66,12 +99,12 @@ impl Output {
         for key in assets_to_remove.keys() {
             assets.remove(key);
         }
-        self.assets = Some(assets);
+        self.assets = Some(assets).filter(|assets| !assets.is_empty());
         self
     }
 
     pub fn set_datum(mut self, bytes: Vec<u8>) -> Self {
-        self.datum = Some(DatumOption::Inline(bytes));
+        self.datum = Some(DatumOption::Inline(Datum::new(bytes)));
         self
     }
 
@@ -95,7 +128,44 @@ impl Output {
         self
     }
 
-    pub fn size(&self) -> Result<usize, TxBuilderError> {
+    pub fn datum_option(&self) -> Option<&DatumOption> {
+        self.datum.as_ref()
+    }
+
+    pub fn script_ref(&self) -> Option<&ScriptRef> {
+        self.script.as_ref()
+    }
+
+    /// Replaces an inline datum with its hash, returning the datum so that it can be attached to
+    /// the witness set instead. See `TxBuilder::add_output_with_datum_hashed`.
+    pub fn with_datum_hashed(mut self) -> (Self, Option<Datum>) {
+        match self.datum.take() {
+            Some(DatumOption::Inline(datum)) => {
+                self.datum = Some(DatumOption::Hash(datum.hash));
+                (self, Some(datum))
+            }
+            datum => {
+                self.datum = datum;
+                (self, None)
+            }
+        }
+    }
+
+    /// Converts the output into the indexer's representation, as if it lived at `input`.
+    pub fn to_tx_output(&self, input: &Input) -> TxOutput {
+        TxOutput {
+            hash: input.hash,
+            index: input.index,
+            address: self.address.to_vec(),
+            lovelace: self.lovelace,
+            assets: self.assets.clone().unwrap_or_default(),
+            script: self.script.clone(),
+            datum_hash: self.datum.as_ref().map(DatumOption::hash),
+        }
+    }
+
+    /// Size in bytes of the serialized output, which its minimum deposit is proportional to.
+    pub fn serialized_size(&self) -> Result<usize, TxBuilderError> {
         // TODO: remove unwrap
         Ok(self
             .build_babbage()?
@@ -106,6 +176,63 @@ impl Output {
 
     /// Minimum amount of lovelace required for the UTxO to be considered valid
     pub fn min_deposit(&self, pparams: &ProtocolParams) -> Result<u64, TxBuilderError> {
+        self.min_deposit_with(
+            pparams.min_utxo_deposit_constant.lovelace,
+            pparams.min_utxo_deposit_coefficient,
+        )
+    }
+
+    /// Part of `min_deposit` that is due to the datum, i.e. how much less the output would need
+    /// without it.
+    pub fn datum_deposit(&self, pparams: &ProtocolParams) -> Result<u64, TxBuilderError> {
+        self.datum_deposit_with(
+            pparams.min_utxo_deposit_constant.lovelace,
+            pparams.min_utxo_deposit_coefficient,
+        )
+    }
+
+    /// `datum_deposit` with the two protocol parameters it depends on given explicitly
+    pub(crate) fn datum_deposit_with(
+        &self,
+        deposit_constant: u64,
+        deposit_coefficient: u64,
+    ) -> Result<u64, TxBuilderError> {
+        if self.datum.is_none() {
+            return Ok(0);
+        }
+        let with_datum = self.min_deposit_with(deposit_constant, deposit_coefficient)?;
+        let without_datum = self
+            .clone()
+            .clear_datum()
+            .min_deposit_with(deposit_constant, deposit_coefficient)?;
+        Ok(with_datum.saturating_sub(without_datum))
+    }
+
+    /// Sets the lovelace to exactly the minimum deposit, e.g. for outputs that only carry tokens
+    pub fn with_min_lovelace(self, pparams: &ProtocolParams) -> Result<Self, TxBuilderError> {
+        self.with_min_lovelace_with(
+            pparams.min_utxo_deposit_constant.lovelace,
+            pparams.min_utxo_deposit_coefficient,
+        )
+    }
+
+    fn with_min_lovelace_with(
+        mut self,
+        deposit_constant: u64,
+        deposit_coefficient: u64,
+    ) -> Result<Self, TxBuilderError> {
+        // Starting from zero, since a larger amount could take more bytes than the minimum does
+        self.lovelace = 0;
+        self.lovelace = self.min_deposit_with(deposit_constant, deposit_coefficient)?;
+        Ok(self)
+    }
+
+    /// `min_deposit` with the two protocol parameters it depends on given explicitly
+    pub(crate) fn min_deposit_with(
+        &self,
+        deposit_constant: u64,
+        deposit_coefficient: u64,
+    ) -> Result<u64, TxBuilderError> {
         // See `babbageMinUTxOValue`:
         //   https://github.com/IntersectMBO/cardano-ledger/blob/6ef1bf9fa1ca589e706e781fa8c9b4ad8df1e919/eras/babbage/impl/src/Cardano/Ledger/Babbage/TxOut.hs#L655-L673
         //
@@ -133,8 +260,8 @@ impl Output {
         let mut sized_output = self.clone();
         let mut previous_required_lovelace = 0_u64;
         loop {
-            let next_required_lovelace = pparams.min_utxo_deposit_constant.lovelace
-                + pparams.min_utxo_deposit_coefficient * (sized_output.size()? as u64 + 160);
+            let next_required_lovelace = deposit_constant
+                + deposit_coefficient * (sized_output.serialized_size()? as u64 + 160);
 
             if next_required_lovelace == previous_required_lovelace {
                 return Ok(next_required_lovelace);
@@ -146,7 +273,107 @@ impl Output {
         }
     }
 
-    pub fn build_babbage(&self) -> Result<TransactionOutput<'_>, TxBuilderError> {
+    /// Size in bytes of the serialized value (lovelace and assets), which the ledger bounds by
+    /// the `max_value_size` protocol parameter.
+    pub fn value_size(&self) -> Result<usize, TxBuilderError> {
+        // TODO: remove unwrap
+        Ok(self
+            .build_value()?
+            .encode_fragment()
+            .expect("failed to encode output value")
+            .len())
+    }
+
+    /// Size in bytes of the inline datum, zero when the output holds no datum or only its hash.
+    pub fn datum_size(&self) -> usize {
+        match &self.datum {
+            Some(DatumOption::Inline(datum)) => datum.bytes.len(),
+            _ => 0,
+        }
+    }
+
+    /// Size in bytes of the reference script, zero when the output holds none.
+    pub fn script_size(&self) -> usize {
+        self.script.as_ref().map_or(0, |script| script.bytes.len())
+    }
+
+    /// Splits the output into as few outputs to the same address as needed for each of their
+    /// values to fit in the `max_value_size` protocol parameter.
+    ///
+    /// Every additional output is funded with its minimum deposit, taken from the lovelace of the
+    /// original output, while the first one keeps the remainder along with the datum and script.
+    /// Additional outputs carry the datum as well, so they remain spendable the same way.
+    pub fn split(self, pparams: &ProtocolParams) -> Result<Vec<Output>, TxBuilderError> {
+        self.split_by_value_size(pparams.max_value_size.bytes as usize, |output| {
+            output.min_deposit(pparams)
+        })
+    }
+
+    fn split_by_value_size(
+        self,
+        max_value_size: usize,
+        min_deposit: impl Fn(&Output) -> Result<u64, TxBuilderError>,
+    ) -> Result<Vec<Output>, TxBuilderError> {
+        if self.value_size()? <= max_value_size {
+            return Ok(vec![self]);
+        }
+
+        let mut chunks = vec![Output {
+            assets: None,
+            ..self.clone()
+        }];
+        for (asset_id, amount) in self.assets.clone().unwrap_or_default().iter() {
+            let asset = Asset::new(asset_id.policy, asset_id.name.clone(), *amount);
+            let current = chunks.last_mut().expect("there is always a chunk");
+
+            let mut candidate = current.clone();
+            let mut assets = candidate.assets.take().unwrap_or_default();
+            assets.add_asset(asset.clone());
+            candidate.assets = Some(assets);
+
+            if candidate.value_size()? <= max_value_size {
+                *current = candidate;
+                continue;
+            }
+
+            let mut assets = Assets::default();
+            assets.add_asset(asset);
+            let chunk = Output {
+                address: self.address.clone(),
+                lovelace: self.lovelace,
+                assets: Some(assets),
+                datum: self.datum.clone(),
+                script: None,
+            };
+            if chunk.value_size()? > max_value_size {
+                return Err(TxBuilderError::ValueTooLarge);
+            }
+            chunks.push(chunk);
+        }
+
+        // Fund the additional outputs with their minimum deposit, the first one keeps the rest
+        let mut extra_lovelace = 0;
+        for chunk in chunks.iter_mut().skip(1) {
+            chunk.lovelace = 0;
+            chunk.lovelace = min_deposit(chunk)?;
+            extra_lovelace += chunk.lovelace;
+        }
+        chunks[0].lovelace = self.lovelace.saturating_sub(extra_lovelace);
+        let required = extra_lovelace + min_deposit(&chunks[0])?;
+        if required > self.lovelace {
+            return Err(TxBuilderError::InsufficientLovelaceForSplit {
+                required,
+                available: self.lovelace,
+            });
+        }
+
+        Ok(chunks)
+    }
+
+    /// Assets with a nonzero quantity, grouped by policy.
+    fn positive_assets(&self) -> BTreeMap<PallasHash<28>, BTreeMap<Bytes, PositiveCoin>> {
+        // Policies and asset names are ordered bytewise through their `Ord` impls, and a policy
+        // group is only created once it holds an asset, so empty groups never get serialized.
         let mut assets: BTreeMap<PallasHash<28>, BTreeMap<Bytes, PositiveCoin>> = BTreeMap::new();
 
         for (asset_id, amount) in self.assets.clone().unwrap_or_default().iter() {
@@ -154,22 +381,42 @@ impl Output {
                 continue;
             };
             assets
-                .entry(asset_id.policy.0.into())
+                .entry(asset_id.policy.into())
                 .or_default()
                 .insert(asset_id.name.clone().into(), amount);
         }
 
+        assets
+    }
+
+    fn build_value(&self) -> Result<Value, TxBuilderError> {
+        // The ledger rejects zero quantities in outputs. `add_asset` already refuses them, but
+        // they can still get in through the public `assets` field.
+        let assets = self.assets.clone().unwrap_or_default();
+        if let Some((asset_id, _)) = assets.iter().find(|(_, amount)| **amount == 0) {
+            return Err(TxBuilderError::ZeroQuantityAsset {
+                policy: asset_id.policy,
+                name: asset_id.name.clone(),
+                output_index: None,
+            });
+        }
+
+        let assets = self.positive_assets();
         let assets = (!assets.is_empty()).then(|| assets.into_iter().collect());
 
-        let value = match assets {
+        Ok(match assets {
             Some(assets) => Value::Multiasset(self.lovelace, assets),
             None => Value::Coin(self.lovelace),
-        };
+        })
+    }
+
+    pub fn build_babbage(&self) -> Result<TransactionOutput<'_>, TxBuilderError> {
+        let value = self.build_value()?;
 
         let datum_option = match self.datum.clone() {
-            Some(DatumOption::Hash(dh)) => Some(conway::DatumOption::Hash(dh.0.into())),
-            Some(DatumOption::Inline(pd)) => {
-                let pd = PlutusData::decode_fragment(pd.as_ref())
+            Some(DatumOption::Hash(dh)) => Some(conway::DatumOption::Hash(dh.into())),
+            Some(DatumOption::Inline(datum)) => {
+                let pd = PlutusData::decode_fragment(&datum.bytes)
                     .map_err(|_| TxBuilderError::MalformedDatum)?;
                 Some(conway::DatumOption::Data(CborWrap(pd.into())))
             }
@@ -210,3 +457,281 @@ impl Output {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{
+        Address, Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+    use pallas::ledger::primitives::Fragment;
+
+    use super::Output;
+    use crate::builder::tx::TxBuilderError;
+    use crate::primitives::{Datum, DatumOption, Hash, ScriptKind};
+
+    const MIN_DEPOSIT: u64 = 1_000_000;
+    const COINS_PER_UTXO_BYTE: u64 = 4_310;
+
+    fn enterprise_output(lovelace: u64) -> Output {
+        let address = Address::Shelley(ShelleyAddress::new(
+            Network::Testnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Null,
+        ));
+        Output::new(address, lovelace)
+    }
+
+    fn many_tokens(lovelace: u64, count: u8) -> Output {
+        (0..count).fold(enterprise_output(lovelace), |output, i| {
+            output
+                .add_asset(Hash([i; 28]), format!("token{i}").into_bytes(), 1)
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn small_outputs_are_not_split() {
+        let output = many_tokens(5_000_000, 2);
+        let chunks = output
+            .clone()
+            .split_by_value_size(5000, |_| Ok(MIN_DEPOSIT))
+            .unwrap();
+        assert_eq!(chunks, vec![output]);
+    }
+
+    #[test]
+    fn large_outputs_are_split_within_max_value_size() {
+        let output = many_tokens(300_000_000, 200);
+        let max_value_size = 5000;
+        assert!(output.value_size().unwrap() > max_value_size);
+
+        let chunks = output
+            .clone()
+            .split_by_value_size(max_value_size, |_| Ok(MIN_DEPOSIT))
+            .unwrap();
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.value_size().unwrap() <= max_value_size);
+            assert_eq!(chunk.address, output.address);
+        }
+
+        let total_lovelace: u64 = chunks.iter().map(|chunk| chunk.lovelace).sum();
+        assert_eq!(total_lovelace, output.lovelace);
+        let total_assets = chunks.iter().flat_map(|chunk| chunk.assets.clone()).sum();
+        assert_eq!(output.assets, Some(total_assets));
+    }
+
+    #[test]
+    fn splitting_requires_enough_lovelace() {
+        let output = many_tokens(1_500_000, 200);
+        let result = output.split_by_value_size(5000, |_| Ok(MIN_DEPOSIT));
+        assert!(matches!(
+            result,
+            Err(TxBuilderError::InsufficientLovelaceForSplit {
+                available: 1_500_000,
+                ..
+            })
+        ));
+    }
+
+    fn min_lovelace(output: &Output) -> u64 {
+        output.min_deposit_with(0, COINS_PER_UTXO_BYTE).unwrap()
+    }
+
+    // Expected values are what `cardano-cli conway transaction calculate-min-required-utxo`
+    // computes under mainnet's utxoCostPerByte of 4310 (minUtxoDepositConstant is 0), given a
+    // protocol parameters file from `cardano-cli conway query protocol-parameters --mainnet`:
+    //   cardano-cli conway transaction calculate-min-required-utxo \
+    //     --protocol-params-file mainnet-pparams.json --tx-out "<address>+0[ + <assets>]" \
+    //     [--tx-out-inline-datum-value ...] [--tx-out-reference-script-file ...]
+    //
+    // cardano-cli sizes outputs without an inline datum or reference script in the legacy array
+    // form, two bytes less than the map form hose serializes them in and the ledger charges for:
+    // those expectations add `MAP_FORM_OVERHEAD`.
+    const MAP_FORM_OVERHEAD: u64 = 2 * COINS_PER_UTXO_BYTE;
+
+    /// Address of `enterprise_output`, as passed to cardano-cli
+    const ENTERPRISE_ADDRESS: &str =
+        "addr_test1vqqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgasfzjt";
+
+    #[test]
+    fn enterprise_address_is_the_one_passed_to_cardano_cli() {
+        assert_eq!(
+            enterprise_output(0).address.to_bech32().unwrap(),
+            ENTERPRISE_ADDRESS
+        );
+    }
+
+    #[test]
+    fn min_lovelace_of_ada_only_base_address_output_against_cardano_cli() {
+        // --tx-out "addr1qyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgzqgpqyqszqgpqyqszqgpqyqszq
+        //           gpqyqszqgpqyqszqgpq2u0p5n+0", the figure wallets quote for ADA-only outputs
+        const CARDANO_CLI_MIN_UTXO: u64 = 969_750;
+        let address = Address::Shelley(ShelleyAddress::new(
+            Network::Mainnet,
+            ShelleyPaymentPart::Key(Hash([1u8; 28]).into()),
+            ShelleyDelegationPart::Key(Hash([2u8; 28]).into()),
+        ));
+        assert_eq!(
+            min_lovelace(&Output::new(address, 0)),
+            CARDANO_CLI_MIN_UTXO + MAP_FORM_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn min_lovelace_of_ada_only_output_against_cardano_cli() {
+        // --tx-out "<ENTERPRISE_ADDRESS>+0"
+        const CARDANO_CLI_MIN_UTXO: u64 = 849_070;
+        assert_eq!(
+            min_lovelace(&enterprise_output(0)),
+            CARDANO_CLI_MIN_UTXO + MAP_FORM_OVERHEAD
+        );
+        assert_eq!(
+            min_lovelace(&enterprise_output(50_000_000)),
+            CARDANO_CLI_MIN_UTXO + MAP_FORM_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn min_lovelace_of_outputs_with_assets_against_cardano_cli() {
+        // --tx-out "<ENTERPRISE_ADDRESS>+0 + 1 00...00.746f6b656e30", policy 28 zero bytes and
+        // name "token0"
+        const ONE_ASSET_CARDANO_CLI_MIN_UTXO: u64 = 1_025_780;
+        assert_eq!(
+            min_lovelace(&many_tokens(0, 1)),
+            ONE_ASSET_CARDANO_CLI_MIN_UTXO + MAP_FORM_OVERHEAD
+        );
+        // --tx-out "<ENTERPRISE_ADDRESS>+0 + 1 <i>.<"token{i}"> + ..." for i in 0..20, where
+        // policy i is 28 bytes of i, e.g. "+ 1 1313...13.746f6b656e3139" for i = 19
+        const TWENTY_ASSETS_CARDANO_CLI_MIN_UTXO: u64 = 4_262_590;
+        assert_eq!(
+            min_lovelace(&many_tokens(0, 20)),
+            TWENTY_ASSETS_CARDANO_CLI_MIN_UTXO + MAP_FORM_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn min_lovelace_of_output_with_inline_datum_against_cardano_cli() {
+        // --tx-out "<ENTERPRISE_ADDRESS>+0" --tx-out-inline-datum-value '{"bytes": "deadbeef"}'
+        // Outputs with an inline datum are in the map form for cardano-cli too
+        const CARDANO_CLI_MIN_UTXO: u64 = 905_100;
+        let output = enterprise_output(0).set_datum(vec![0x44, 0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(min_lovelace(&output), CARDANO_CLI_MIN_UTXO);
+    }
+
+    #[test]
+    fn min_lovelace_of_output_with_reference_script_against_cardano_cli() {
+        // --tx-out "<ENTERPRISE_ADDRESS>+0" --tx-out-reference-script-file script.plutus, with
+        // { "type": "PlutusScriptV3", "cborHex": "5864" followed by 100 zero bytes }
+        // Outputs with a reference script are in the map form for cardano-cli too
+        const CARDANO_CLI_MIN_UTXO: u64 = 1_327_480;
+        let output = enterprise_output(0).set_script(ScriptKind::PlutusV3, vec![0; 100]);
+        assert_eq!(min_lovelace(&output), CARDANO_CLI_MIN_UTXO);
+    }
+
+    #[test]
+    fn with_min_lovelace_sets_exactly_the_minimum() {
+        let output = many_tokens(100_000_000_000, 1)
+            .with_min_lovelace_with(0, COINS_PER_UTXO_BYTE)
+            .unwrap();
+        assert_eq!(output.lovelace, 1_034_400);
+        assert_eq!(min_lovelace(&output), output.lovelace);
+    }
+
+    #[test]
+    fn inline_and_hashed_datums_share_their_hash() {
+        let datum = Datum::new(vec![0xd8, 0x79, 0x80]);
+        let inline = DatumOption::from(datum.clone());
+        let hashed = DatumOption::from(datum.hash);
+
+        assert_eq!(inline.hash(), hashed.hash());
+        assert_eq!(inline.as_inline(), Some(&datum));
+        assert_eq!(hashed.as_inline(), None);
+    }
+
+    #[test]
+    fn with_datum_hashed_returns_the_preimage() {
+        let bytes = vec![0xd8, 0x79, 0x80];
+        let inline = enterprise_output(2_000_000).set_datum(bytes.clone());
+
+        let (hashed, datum) = inline.clone().with_datum_hashed();
+        assert_eq!(datum, Some(Datum::new(bytes)));
+        assert_eq!(
+            hashed.datum_option(),
+            Some(&DatumOption::Hash(inline.datum_option().unwrap().hash()))
+        );
+        assert_eq!(hashed.lovelace, inline.lovelace);
+
+        let (unchanged, datum) = hashed.clone().with_datum_hashed();
+        assert_eq!(unchanged, hashed);
+        assert_eq!(datum, None);
+    }
+
+    #[test]
+    fn script_ref_reads_back_the_script() {
+        let output = enterprise_output(0).set_script(ScriptKind::PlutusV3, vec![0; 100]);
+        let script = output.script_ref().unwrap();
+        assert_eq!(script.kind, ScriptKind::PlutusV3);
+        assert_eq!(script.bytes, vec![0; 100]);
+        assert!(enterprise_output(0).script_ref().is_none());
+    }
+
+    #[test]
+    fn datum_deposit_is_what_the_datum_costs() {
+        // The 11 bytes of `min_lovelace_of_output_with_inline_datum`, at 4310 lovelace each
+        let inline = enterprise_output(0).set_datum(vec![0x44, 0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(inline.datum_size(), 5);
+        assert_eq!(
+            inline.datum_deposit_with(0, COINS_PER_UTXO_BYTE).unwrap(),
+            11 * COINS_PER_UTXO_BYTE
+        );
+        assert_eq!(
+            min_lovelace(&inline) - min_lovelace(&enterprise_output(0)),
+            11 * COINS_PER_UTXO_BYTE
+        );
+
+        // + 1 (key) + 1 (array) + 1 (tag) + 2 + 32 (hash)
+        let (hashed, _) = inline.with_datum_hashed();
+        assert_eq!(hashed.datum_size(), 0);
+        assert_eq!(
+            hashed.datum_deposit_with(0, COINS_PER_UTXO_BYTE).unwrap(),
+            37 * COINS_PER_UTXO_BYTE
+        );
+        assert_eq!(
+            enterprise_output(0)
+                .datum_deposit_with(0, COINS_PER_UTXO_BYTE)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn equality_ignores_how_assets_were_put_together() {
+        let tokens = |order: &[u8]| {
+            order
+                .iter()
+                .fold(enterprise_output(2_000_000), |output, i| {
+                    output
+                        .add_asset(Hash([*i; 28]), vec![*i], u64::from(*i))
+                        .unwrap()
+                })
+        };
+        let forward = tokens(&[1, 2, 3]);
+        let backward = tokens(&[3, 2, 1]);
+        assert_eq!(forward, backward);
+        assert_eq!(
+            forward.build_babbage().unwrap().encode_fragment().unwrap(),
+            backward.build_babbage().unwrap().encode_fragment().unwrap()
+        );
+        assert_ne!(forward, tokens(&[1, 2]));
+
+        // Emptied assets are the same as none at all
+        let emptied = tokens(&[1]).remove_asset(Hash([1; 28]), vec![1]);
+        assert_eq!(emptied.assets, None);
+        let mut empty = enterprise_output(2_000_000);
+        empty.assets = Some(Default::default());
+        assert_eq!(emptied, empty);
+        assert_eq!(emptied, enterprise_output(2_000_000));
+        assert_ne!(emptied, enterprise_output(2_000_001));
+    }
+}
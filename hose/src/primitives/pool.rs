@@ -0,0 +1,339 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use bech32::{Bech32, Hrp};
+use pallas::crypto::hash::Hasher;
+use pallas::crypto::key::ed25519::PublicKey;
+use thiserror::Error;
+
+use crate::primitives::Hash;
+
+const POOL_ID_HRP: &str = "pool";
+/// Longest metadata url and relay dns name the ledger accepts, in bytes, see `url` and `dns_name`
+/// in the Conway CDDL.
+pub const MAX_POOL_TEXT_LENGTH: usize = 128;
+
+/// Identifier of a stake pool: the hash of its cold verification key.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct PoolId(pub Hash<28>);
+
+impl PoolId {
+    pub fn from_cold_key(cold_key: &PublicKey) -> Self {
+        let mut hasher = Hasher::<224>::new();
+        hasher.input(cold_key.as_ref());
+        Self(hasher.finalize().into())
+    }
+
+    /// Decodes a `pool1...` id, as shown by explorers and `cardano-cli`.
+    pub fn from_bech32(bech32_str: &str) -> Result<Self, PoolIdError> {
+        let (hrp, data) = bech32::decode(bech32_str)?;
+        if hrp.as_str() != POOL_ID_HRP {
+            return Err(PoolIdError::InvalidHrp(hrp.to_string()));
+        }
+        Self::from_bytes(&data)
+    }
+
+    pub fn to_bech32(&self) -> String {
+        bech32::encode::<Bech32>(Hrp::parse_unchecked(POOL_ID_HRP), &self.0.0)
+            .expect("a pool id is well below the bech32 length limit")
+    }
+
+    pub fn from_hex(hex_str: &str) -> Result<Self, PoolIdError> {
+        Self::from_bytes(&hex::decode(hex_str)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PoolIdError> {
+        let bytes: [u8; 28] = bytes
+            .try_into()
+            .map_err(|_| PoolIdError::UnexpectedLength(bytes.len()))?;
+        Ok(Self(Hash(bytes)))
+    }
+}
+
+impl From<Hash<28>> for PoolId {
+    fn from(hash: Hash<28>) -> Self {
+        Self(hash)
+    }
+}
+
+impl From<PoolId> for Hash<28> {
+    fn from(pool_id: PoolId) -> Self {
+        pool_id.0
+    }
+}
+
+impl fmt::Display for PoolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_bech32())
+    }
+}
+
+/// Parses either the bech32 or the hex form.
+impl FromStr for PoolId {
+    type Err = PoolIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(POOL_ID_HRP) {
+            Self::from_bech32(s)
+        } else {
+            Self::from_hex(s)
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PoolIdError {
+    /// Unexpected bech32 HRP prefix
+    #[error("Unexpected bech32 HRP prefix for a pool id: {0}")]
+    InvalidHrp(String),
+    /// Unable to decode bech32 string
+    #[error("Unable to decode bech32: {0}")]
+    InvalidBech32(#[from] bech32::DecodeError),
+    /// Unable to decode hex string
+    #[error("Unable to decode hex: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    /// Pool ids are 28 bytes long
+    #[error("Pool id of unexpected length: {0} bytes")]
+    UnexpectedLength(usize),
+}
+
+/// Off-chain metadata of a pool, referenced from its registration certificate.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PoolMetadata {
+    pub url: String,
+    pub hash: Hash<32>,
+}
+
+impl PoolMetadata {
+    pub fn new(url: impl Into<String>, hash: Hash<32>) -> Result<Self, PoolMetadataError> {
+        let url = url.into();
+        if url.len() > MAX_POOL_TEXT_LENGTH {
+            return Err(PoolMetadataError::UrlTooLong(url.len()));
+        }
+        Ok(Self { url, hash })
+    }
+
+    /// Metadata with the hash in hex, as found in registration JSON files.
+    pub fn from_hex(url: impl Into<String>, hash_hex: &str) -> Result<Self, PoolMetadataError> {
+        let bytes = hex::decode(hash_hex)?;
+        let hash: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| PoolMetadataError::UnexpectedHashLength(bytes.len()))?;
+        Self::new(url, Hash(hash))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PoolMetadataError {
+    /// Metadata url above `MAX_POOL_TEXT_LENGTH`
+    #[error("Pool metadata url is {0} bytes long, at most {MAX_POOL_TEXT_LENGTH} are allowed")]
+    UrlTooLong(usize),
+    /// Unable to decode hex string
+    #[error("Unable to decode hex: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    /// Metadata hashes are 32 bytes long
+    #[error("Pool metadata hash of unexpected length: {0} bytes")]
+    UnexpectedHashLength(usize),
+}
+
+/// Where a pool can be reached, as announced in its registration certificate.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Relay {
+    SingleHostAddr {
+        port: Option<u16>,
+        ipv4: Option<Ipv4Addr>,
+        ipv6: Option<Ipv6Addr>,
+    },
+    SingleHostName {
+        port: Option<u16>,
+        hostname: String,
+    },
+    /// Resolved through DNS SRV records, which carry the port
+    MultiHostName {
+        hostname: String,
+    },
+}
+
+impl Relay {
+    pub fn single_host_addr(ip: &str, port: Option<u16>) -> Result<Self, RelayError> {
+        let ip = ip
+            .parse::<IpAddr>()
+            .map_err(|_| RelayError::InvalidIp(ip.to_string()))?;
+        let (ipv4, ipv6) = match ip {
+            IpAddr::V4(ipv4) => (Some(ipv4), None),
+            IpAddr::V6(ipv6) => (None, Some(ipv6)),
+        };
+        Ok(Self::SingleHostAddr { port, ipv4, ipv6 })
+    }
+
+    pub fn single_host_name(
+        hostname: impl Into<String>,
+        port: Option<u16>,
+    ) -> Result<Self, RelayError> {
+        Ok(Self::SingleHostName {
+            port,
+            hostname: validate_hostname(hostname.into())?,
+        })
+    }
+
+    pub fn multi_host_name(hostname: impl Into<String>) -> Result<Self, RelayError> {
+        Ok(Self::MultiHostName {
+            hostname: validate_hostname(hostname.into())?,
+        })
+    }
+}
+
+/// Labels of at most 63 letters, digits, hyphens or underscores (for SRV records), not starting
+/// or ending with a hyphen. A trailing dot is allowed.
+fn validate_hostname(hostname: String) -> Result<String, RelayError> {
+    if hostname.len() > MAX_POOL_TEXT_LENGTH {
+        return Err(RelayError::HostnameTooLong(hostname.len()));
+    }
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if !hostname
+        .strip_suffix('.')
+        .unwrap_or(&hostname)
+        .split('.')
+        .all(valid_label)
+    {
+        return Err(RelayError::InvalidHostname(hostname));
+    }
+    Ok(hostname)
+}
+
+#[derive(Error, Debug)]
+pub enum RelayError {
+    /// Not an IPv4 or IPv6 address
+    #[error("Invalid relay IP address: {0}")]
+    InvalidIp(String),
+    /// Hostname above `MAX_POOL_TEXT_LENGTH`
+    #[error("Relay hostname is {0} bytes long, at most {MAX_POOL_TEXT_LENGTH} are allowed")]
+    HostnameTooLong(usize),
+    /// Hostname with empty labels or characters not allowed in DNS names
+    #[error("Invalid relay hostname: {0}")]
+    InvalidHostname(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use pallas::crypto::key::ed25519::PublicKey;
+
+    use super::{
+        MAX_POOL_TEXT_LENGTH, PoolId, PoolIdError, PoolMetadata, PoolMetadataError, Relay,
+        RelayError,
+    };
+    use crate::primitives::Hash;
+
+    const POOL_HEX: &str = "8a219b698d3b6e034391ae84cee62f1d76b6fbc45ddfe4e31e0d4b60";
+    const POOL_BECH32: &str = "pool13gsek6vd8dhqxsu346zvae30r4mtd77yth07fcc7p49kqc3fd09";
+
+    #[test]
+    fn bech32_round_trip() {
+        let pool_id = PoolId::from_hex(POOL_HEX).unwrap();
+        assert_eq!(pool_id.to_bech32(), POOL_BECH32);
+        assert_eq!(PoolId::from_bech32(POOL_BECH32).unwrap(), pool_id);
+        assert_eq!(POOL_BECH32.parse::<PoolId>().unwrap(), pool_id);
+        assert_eq!(POOL_HEX.parse::<PoolId>().unwrap(), pool_id);
+    }
+
+    #[test]
+    fn from_cold_key() {
+        let cold_key = PublicKey::from(std::array::from_fn::<u8, 32, _>(|i| i as u8));
+        assert_eq!(
+            PoolId::from_cold_key(&cold_key),
+            PoolId::from_hex("491112dd01155c07dab485f71b572e0cae759e2cd38b1c0e97554297").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_other_prefixes_and_lengths() {
+        let stake_vkh =
+            bech32::encode::<bech32::Bech32>(bech32::Hrp::parse_unchecked("stake_vkh"), &[0u8; 28])
+                .unwrap();
+        assert!(matches!(
+            PoolId::from_bech32(&stake_vkh),
+            Err(PoolIdError::InvalidHrp(_))
+        ));
+        assert!(matches!(
+            PoolId::from_hex("8a219b"),
+            Err(PoolIdError::UnexpectedLength(3))
+        ));
+    }
+
+    #[test]
+    fn pool_metadata_validation() {
+        let hash = "0f118a34e20bd77f8a9ba5e27481eba54d063630c4c1c017bad11a2fba615501";
+        let metadata = PoolMetadata::from_hex("https://example.com/pool.json", hash).unwrap();
+        assert_eq!(hex::encode(metadata.hash.0), hash);
+
+        let long_url = format!("https://{}", "a".repeat(MAX_POOL_TEXT_LENGTH));
+        assert!(matches!(
+            PoolMetadata::new(long_url, Hash([0; 32])),
+            Err(PoolMetadataError::UrlTooLong(136))
+        ));
+        assert!(matches!(
+            PoolMetadata::from_hex("https://example.com/pool.json", "0f118a"),
+            Err(PoolMetadataError::UnexpectedHashLength(3))
+        ));
+        assert!(matches!(
+            PoolMetadata::from_hex("https://example.com/pool.json", "not hex"),
+            Err(PoolMetadataError::InvalidHex(_))
+        ));
+    }
+
+    #[test]
+    fn relay_validation() {
+        assert_eq!(
+            Relay::single_host_addr("192.168.0.1", Some(3001)).unwrap(),
+            Relay::SingleHostAddr {
+                port: Some(3001),
+                ipv4: Some(Ipv4Addr::new(192, 168, 0, 1)),
+                ipv6: None,
+            }
+        );
+        assert_eq!(
+            Relay::single_host_addr("::1", None).unwrap(),
+            Relay::SingleHostAddr {
+                port: None,
+                ipv4: None,
+                ipv6: Some(Ipv6Addr::LOCALHOST),
+            }
+        );
+        assert!(matches!(
+            Relay::single_host_addr("300.0.0.1", None),
+            Err(RelayError::InvalidIp(_))
+        ));
+
+        assert!(Relay::single_host_name("relay-1.example.com", Some(3001)).is_ok());
+        assert!(Relay::multi_host_name("_cardano._tcp.example.com.").is_ok());
+        for hostname in [
+            "",
+            "relay..example.com",
+            "-relay.example.com",
+            "relay example.com",
+        ] {
+            assert!(
+                matches!(
+                    Relay::single_host_name(hostname, None),
+                    Err(RelayError::InvalidHostname(_))
+                ),
+                "{hostname:?} should be rejected"
+            );
+        }
+        assert!(matches!(
+            Relay::multi_host_name("a.".repeat(65)),
+            Err(RelayError::HostnameTooLong(130))
+        ));
+    }
+}
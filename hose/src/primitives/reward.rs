@@ -1,7 +1,7 @@
 use pallas::codec::utils::Bytes;
 use pallas::ledger::addresses::Network;
 
-use crate::primitives::Hash;
+use crate::primitives::{Hash, StakeCredential};
 
 // Conway CDDL: "reward addresses: bits 7-5: 111; bit 4: credential is keyhash/scripthash; bits 3-0: network id"
 const REWARD_ADDRESS_PREFIX: u8 = 0b1110_0000;
@@ -63,9 +63,40 @@ impl RewardAccount {
         Self::from_key_hash(network, pub_key_hash)
     }
 
+    pub fn from_credential(network: Network, credential: StakeCredential) -> Self {
+        match credential {
+            StakeCredential::Key(hash) => Self::from_key_hash(network, hash),
+            StakeCredential::Script(hash) => Self::from_script_hash(network, hash),
+        }
+    }
+
     pub fn as_bytes(&self) -> &Bytes {
         &self.0
     }
+
+    /// Network of the account, `None` if the bytes aren't a reward address.
+    pub fn network(&self) -> Option<Network> {
+        self.header()
+            .map(|header| network_from_network_id(header & REWARD_ADDRESS_NETWORK_MASK))
+    }
+
+    /// Credential of the account, `None` if the bytes aren't a reward address.
+    pub fn credential(&self) -> Option<StakeCredential> {
+        let header = self.header()?;
+        let hash = Hash(self.0[1..].try_into().ok()?);
+        if header & REWARD_ADDRESS_CREDENTIAL_SCRIPT != 0 {
+            Some(StakeCredential::Script(hash))
+        } else {
+            Some(StakeCredential::Key(hash))
+        }
+    }
+
+    fn header(&self) -> Option<u8> {
+        let header = *self.0.first()?;
+        (header & !(REWARD_ADDRESS_CREDENTIAL_SCRIPT | REWARD_ADDRESS_NETWORK_MASK)
+            == REWARD_ADDRESS_PREFIX)
+            .then_some(header)
+    }
 }
 
 impl From<Bytes> for RewardAccount {
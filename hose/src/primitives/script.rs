@@ -1,14 +1,56 @@
-use std::collections::HashMap;
-use std::ops::{Deref, DerefMut};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Deref, DerefMut};
 
+use anyhow::{Context, bail, ensure};
 pub use hydrant::primitives::{Datum, DatumHash, Script, ScriptHash, ScriptKind};
+use num::ToPrimitive as _;
+use ogmios_client::codec::RedeemerPurpose as OgmiosRedeemerPurpose;
+use ogmios_client::method::evaluate::Evaluation;
+use pallas::codec::minicbor;
+use pallas::ledger::primitives::Fragment;
+use pallas::ledger::primitives::conway::NativeScript;
+use uplc::ast::{DeBruijn, Program};
 
-use super::{Hash, Input, Policy, RewardAccount};
+use super::{Hash, Input, Policy, PubKeyHash, RewardAccount};
+
+/// Script carried by an output, for other transactions to use as a reference script
+pub type ScriptRef = Script;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum DatumOption {
     Hash(DatumHash),
-    Inline(Vec<u8>),
+    Inline(Datum),
+}
+
+impl DatumOption {
+    /// Hash of the datum, whether it's inlined or not.
+    pub fn hash(&self) -> DatumHash {
+        match self {
+            DatumOption::Hash(hash) => *hash,
+            DatumOption::Inline(datum) => datum.hash,
+        }
+    }
+
+    pub fn as_inline(&self) -> Option<&Datum> {
+        match self {
+            DatumOption::Hash(_) => None,
+            DatumOption::Inline(datum) => Some(datum),
+        }
+    }
+}
+
+impl From<Datum> for DatumOption {
+    fn from(datum: Datum) -> Self {
+        DatumOption::Inline(datum)
+    }
+}
+
+impl From<DatumHash> for DatumOption {
+    fn from(hash: DatumHash) -> Self {
+        DatumOption::Hash(hash)
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -17,6 +59,8 @@ pub enum RedeemerPurpose {
     Mint(Policy),
     Cert(Hash<28>),
     Reward(RewardAccount),
+    /// Guardrails script of the proposal at this index, see `StagingTransaction::proposal`
+    Propose(usize),
 }
 
 impl std::hash::Hash for RedeemerPurpose {
@@ -25,6 +69,7 @@ impl std::hash::Hash for RedeemerPurpose {
         let tag_mint: u8 = 1;
         let tag_cert: u8 = 2;
         let tag_reward: u8 = 3;
+        let tag_propose: u8 = 4;
 
         match self {
             RedeemerPurpose::Spend(input) => {
@@ -43,6 +88,10 @@ impl std::hash::Hash for RedeemerPurpose {
                 std::hash::Hash::hash(&tag_reward, state);
                 std::hash::Hash::hash(account, state);
             }
+            RedeemerPurpose::Propose(index) => {
+                std::hash::Hash::hash(&tag_propose, state);
+                std::hash::Hash::hash(index, state);
+            }
         }
     }
 }
@@ -53,6 +102,125 @@ pub struct ExUnits {
     pub steps: u64,
 }
 
+impl ExUnits {
+    /// Whether both memory and steps are within `max`, e.g. the per transaction limit.
+    pub fn fits_within(&self, max: &ExUnits) -> bool {
+        self.mem <= max.mem && self.steps <= max.steps
+    }
+}
+
+/// Saturating, budgets are compared against limits well below `u64::MAX` anyway.
+impl Add for ExUnits {
+    type Output = ExUnits;
+
+    fn add(self, other: ExUnits) -> ExUnits {
+        self + &other
+    }
+}
+
+impl Add<&ExUnits> for ExUnits {
+    type Output = ExUnits;
+
+    fn add(self, other: &ExUnits) -> ExUnits {
+        ExUnits {
+            mem: self.mem.saturating_add(other.mem),
+            steps: self.steps.saturating_add(other.steps),
+        }
+    }
+}
+
+impl Sum for ExUnits {
+    fn sum<I: Iterator<Item = ExUnits>>(iter: I) -> Self {
+        iter.fold(ExUnits { mem: 0, steps: 0 }, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a ExUnits> for ExUnits {
+    fn sum<I: Iterator<Item = &'a ExUnits>>(iter: I) -> Self {
+        iter.fold(ExUnits { mem: 0, steps: 0 }, Add::add)
+    }
+}
+
+impl fmt::Display for ExUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mem: {}, steps: {}", self.mem, self.steps)
+    }
+}
+
+/// Budget a script was evaluated to, along with the redeemer it was evaluated for. See
+/// `StagingTransaction::script_evaluations`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ScriptEvaluation {
+    pub purpose: RedeemerPurpose,
+    pub ex_units: ExUnits,
+}
+
+/// Kind of redeemer a script ran for, as evaluators report it.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum ValidatorPurpose {
+    Spend,
+    Mint,
+    Cert,
+    Reward,
+    Vote,
+    Propose,
+}
+
+/// Budget of the script run for the redeemer at `index` among those of `purpose`, as reported by
+/// an evaluator, before it's matched to a redeemer of the transaction like a `ScriptEvaluation`.
+///
+/// Converts from and to Ogmios `Evaluation`s, which `Evaluator`s return: one that isn't backed by
+/// Ogmios can work out its budgets as `ValidatorBudget`s and convert them.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ValidatorBudget {
+    pub purpose: ValidatorPurpose,
+    pub index: u64,
+    pub ex_units: ExUnits,
+}
+
+impl From<&Evaluation> for ValidatorBudget {
+    fn from(evaluation: &Evaluation) -> Self {
+        let purpose = match evaluation.validator.purpose {
+            OgmiosRedeemerPurpose::Spend => ValidatorPurpose::Spend,
+            OgmiosRedeemerPurpose::Mint => ValidatorPurpose::Mint,
+            OgmiosRedeemerPurpose::Publish => ValidatorPurpose::Cert,
+            OgmiosRedeemerPurpose::Withdraw => ValidatorPurpose::Reward,
+            OgmiosRedeemerPurpose::Vote => ValidatorPurpose::Vote,
+            OgmiosRedeemerPurpose::Propose => ValidatorPurpose::Propose,
+        };
+        let budget = &evaluation.budget;
+        ValidatorBudget {
+            purpose,
+            index: evaluation.validator.index,
+            ex_units: ExUnits {
+                mem: budget.memory.0.clone().to_integer().to_u64().unwrap(),
+                steps: budget.cpu.0.clone().to_integer().to_u64().unwrap(),
+            },
+        }
+    }
+}
+
+/// Goes through the JSON Ogmios answers evaluations with, the only way `ogmios_client` builds
+/// them.
+impl TryFrom<&ValidatorBudget> for Evaluation {
+    type Error = serde_json::Error;
+
+    fn try_from(budget: &ValidatorBudget) -> Result<Self, Self::Error> {
+        let purpose = match budget.purpose {
+            ValidatorPurpose::Spend => "spend",
+            ValidatorPurpose::Mint => "mint",
+            ValidatorPurpose::Cert => "publish",
+            ValidatorPurpose::Reward => "withdraw",
+            ValidatorPurpose::Vote => "vote",
+            ValidatorPurpose::Propose => "propose",
+        };
+        serde_json::from_value(serde_json::json!({
+            "validator": { "purpose": purpose, "index": budget.index },
+            "budget": { "memory": budget.ex_units.mem, "cpu": budget.ex_units.steps },
+        }))
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Default, Clone)]
 pub struct Redeemers(HashMap<RedeemerPurpose, (Vec<u8>, Option<ExUnits>)>);
 
@@ -75,3 +243,539 @@ impl Redeemers {
         Self(map)
     }
 }
+
+/// Helpers for working with raw script bytes of unknown origin, e.g. pasted from a blueprint or
+/// an explorer.
+pub trait ScriptExt {
+    /// Returns the kinds under which `bytes` are plausibly a valid script, either because they
+    /// parse as a native script, or because they decode as a UPLC program (with or without CBOR
+    /// bytestring wrappers). Programs using UPLC 1.1.0 are only valid as Plutus V3.
+    fn detect(bytes: &[u8]) -> anyhow::Result<Vec<ScriptKind>>;
+
+    /// Brings `bytes` to the form expected by `Script::new` and the witness set: native scripts
+    /// are left untouched, while plutus scripts are wrapped in exactly one CBOR bytestring,
+    /// stripping or adding layers as needed. Idempotent.
+    fn normalize(kind: ScriptKind, bytes: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    /// Hash of the script once normalized, to compare against an expected on-chain hash before
+    /// building.
+    fn hash_for(kind: ScriptKind, bytes: &[u8]) -> anyhow::Result<ScriptHash>;
+}
+
+impl ScriptExt for Script {
+    fn detect(bytes: &[u8]) -> anyhow::Result<Vec<ScriptKind>> {
+        let mut kinds = vec![];
+        if NativeScript::decode_fragment(bytes).is_ok() {
+            kinds.push(ScriptKind::Native);
+        }
+        if let Ok(program) = Program::<DeBruijn>::from_flat(strip_cbor_wrappers(bytes)) {
+            if program.version < (1, 1, 0) {
+                kinds.extend([ScriptKind::PlutusV1, ScriptKind::PlutusV2]);
+            }
+            kinds.push(ScriptKind::PlutusV3);
+        }
+        ensure!(
+            !kinds.is_empty(),
+            "bytes are neither a native nor a plutus script"
+        );
+        Ok(kinds)
+    }
+
+    fn normalize(kind: ScriptKind, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if kind == ScriptKind::Native {
+            NativeScript::decode_fragment(bytes)
+                .map_err(|err| anyhow::anyhow!("invalid native script: {err}"))?;
+            return Ok(bytes.to_vec());
+        }
+
+        let flat = strip_cbor_wrappers(bytes);
+        let program = Program::<DeBruijn>::from_flat(flat)
+            .map_err(|err| anyhow::anyhow!("invalid plutus script: {err}"))?;
+        if kind != ScriptKind::PlutusV3 && program.version >= (1, 1, 0) {
+            bail!(
+                "UPLC {}.{}.{} programs are only supported by Plutus V3",
+                program.version.0,
+                program.version.1,
+                program.version.2
+            );
+        }
+        minicbor::to_vec(minicbor::bytes::ByteSlice::from(flat))
+            .context("failed to wrap plutus script")
+    }
+
+    fn hash_for(kind: ScriptKind, bytes: &[u8]) -> anyhow::Result<ScriptHash> {
+        Ok(kind.hash(&Self::normalize(kind, bytes)?))
+    }
+}
+
+/// Signatures needed by a native script.
+pub trait NativeScriptExt {
+    /// Fewest of the `available` keys that satisfy the script, assuming its timelocks hold, or
+    /// `None` when those keys can't. Computed branch by branch, so a key appearing in several
+    /// branches may be counted more than once, but the count always suffices.
+    fn min_signatures_with(&self, available: &[PubKeyHash]) -> Option<usize>;
+}
+
+impl NativeScriptExt for NativeScript {
+    fn min_signatures_with(&self, available: &[PubKeyHash]) -> Option<usize> {
+        min_signers(self, &|key| available.contains(key), &|_| true).map(|keys| keys.len())
+    }
+}
+
+/// Keys that satisfy `script` with as few signatures as possible, taking the cheapest branches
+/// of `any` and `atLeast`, or `None` when it can't be satisfied with the keys `available` accepts
+/// and the timelocks `timelock_holds` accepts.
+pub(crate) fn min_signers(
+    script: &NativeScript,
+    available: &dyn Fn(&PubKeyHash) -> bool,
+    timelock_holds: &dyn Fn(&NativeScript) -> bool,
+) -> Option<HashSet<PubKeyHash>> {
+    let branches = |scripts: &[NativeScript]| {
+        let mut branches = scripts
+            .iter()
+            .filter_map(|script| min_signers(script, available, timelock_holds))
+            .collect::<Vec<_>>();
+        branches.sort_by_key(|keys| keys.len());
+        branches
+    };
+    match script {
+        NativeScript::ScriptPubkey(key_hash) => {
+            let key_hash = Hash::from(*key_hash);
+            available(&key_hash).then(|| HashSet::from([key_hash]))
+        }
+        NativeScript::ScriptAll(scripts) => scripts
+            .iter()
+            .map(|script| min_signers(script, available, timelock_holds))
+            .try_fold(HashSet::new(), |mut keys, branch| {
+                keys.extend(branch?);
+                Some(keys)
+            }),
+        NativeScript::ScriptAny(scripts) => branches(scripts).into_iter().next(),
+        NativeScript::ScriptNOfK(required, scripts) => {
+            let required = *required as usize;
+            let branches = branches(scripts);
+            (branches.len() >= required)
+                .then(|| branches.into_iter().take(required).flatten().collect())
+        }
+        NativeScript::InvalidBefore(_) | NativeScript::InvalidHereafter(_) => {
+            timelock_holds(script).then(HashSet::new)
+        }
+    }
+}
+
+/// Whether a timelock of a native script holds for a transaction valid from `valid_from` until
+/// before `invalid_from`, like the ledger checks it. Other scripts never hold.
+pub(crate) fn timelock_holds(
+    script: &NativeScript,
+    valid_from: Option<u64>,
+    invalid_from: Option<u64>,
+) -> bool {
+    match script {
+        NativeScript::InvalidBefore(slot) => valid_from.is_some_and(|from| from >= *slot),
+        NativeScript::InvalidHereafter(slot) => invalid_from.is_some_and(|until| until <= *slot),
+        _ => false,
+    }
+}
+
+/// Stable names for script kinds, for config files and command line arguments.
+pub trait ScriptKindExt: Sized {
+    /// `Native`, `PlutusV1`, `PlutusV2` or `PlutusV3`.
+    fn name(&self) -> &'static str;
+
+    /// Parses a name as returned by `name`, case insensitively, along with common aliases such
+    /// as `v3`, `plutus_v3` or `timelock`.
+    fn parse(name: &str) -> Result<Self, ParseScriptKindError>;
+}
+
+impl ScriptKindExt for ScriptKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ScriptKind::Native => "Native",
+            ScriptKind::PlutusV1 => "PlutusV1",
+            ScriptKind::PlutusV2 => "PlutusV2",
+            ScriptKind::PlutusV3 => "PlutusV3",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, ParseScriptKindError> {
+        let normalized = name
+            .chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "native" | "nativescript" | "timelock" => Ok(ScriptKind::Native),
+            "plutusv1" | "v1" => Ok(ScriptKind::PlutusV1),
+            "plutusv2" | "v2" => Ok(ScriptKind::PlutusV2),
+            "plutusv3" | "v3" => Ok(ScriptKind::PlutusV3),
+            _ => Err(ParseScriptKindError(name.to_string())),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown script kind: {0}")]
+pub struct ParseScriptKindError(pub String);
+
+/// Serializes a `ScriptKind` as its `ScriptKindExt::name`, for use with
+/// `#[serde(with = "hose::primitives::script_kind_serde")]`. Requires the `serde` feature.
+#[cfg(feature = "serde")]
+pub mod script_kind_serde {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    use super::{ScriptKind, ScriptKindExt};
+
+    pub fn serialize<S: Serializer>(kind: &ScriptKind, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(kind.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ScriptKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ScriptKind::parse(&name).map_err(de::Error::custom)
+    }
+}
+
+/// Strips every layer of CBOR bytestring wrapping, returning the inner flat-encoded program.
+///
+/// Flat programs start with their major version (1), which never decodes as a CBOR bytestring,
+/// so this stops exactly at the flat encoding.
+fn strip_cbor_wrappers(mut bytes: &[u8]) -> &[u8] {
+    loop {
+        let mut decoder = minicbor::Decoder::new(bytes);
+        match decoder.bytes() {
+            Ok(inner) if decoder.position() == bytes.len() => bytes = inner,
+            _ => return bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::primitives::conway::NativeScript;
+
+    use ogmios_client::method::evaluate::Evaluation;
+
+    use super::{
+        ExUnits, NativeScriptExt, ParseScriptKindError, Script, ScriptExt, ScriptKind,
+        ScriptKindExt, ValidatorBudget, ValidatorPurpose,
+    };
+    use crate::primitives::{Hash, PubKeyHash};
+
+    // `\_ _ _ -> ()` style always-succeeds, UPLC 1.0.0
+    const V2_FLAT: &str = "0100002221200101";
+    const V2_WRAPPED: &str = "480100002221200101";
+    const V2_DOUBLE_WRAPPED: &str = "49480100002221200101";
+    const V2_HASH: &str = "3a888d65f16790950a72daee1f63aa05add6d268434107cfa5b67712";
+
+    // Always-succeeds used throughout the devnet tests, UPLC 1.1.0
+    const V3_FLAT: &str = "01010023259800a518a4d136564004ae69";
+    const V3_WRAPPED: &str = "5101010023259800a518a4d136564004ae69";
+    const V3_DOUBLE_WRAPPED: &str = "525101010023259800a518a4d136564004ae69";
+    const V3_HASH: &str = "bd3ae991b5aafccafe5ca70758bd36a9b2f872f57f6d3a1ffa0eb777";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        hex::decode(hex).unwrap()
+    }
+
+    fn hash(hex: &str) -> Hash<28> {
+        Hash(bytes(hex).try_into().unwrap())
+    }
+
+    #[test]
+    fn detects_plutus_versions_regardless_of_wrapping() {
+        for form in [V2_FLAT, V2_WRAPPED, V2_DOUBLE_WRAPPED] {
+            assert_eq!(
+                Script::detect(&bytes(form)).unwrap(),
+                vec![
+                    ScriptKind::PlutusV1,
+                    ScriptKind::PlutusV2,
+                    ScriptKind::PlutusV3
+                ]
+            );
+        }
+        for form in [V3_FLAT, V3_WRAPPED, V3_DOUBLE_WRAPPED] {
+            assert_eq!(
+                Script::detect(&bytes(form)).unwrap(),
+                vec![ScriptKind::PlutusV3]
+            );
+        }
+        assert!(Script::detect(&[0xff, 0x00]).is_err());
+    }
+
+    #[test]
+    fn normalization_is_idempotent() {
+        for (kind, forms, wrapped) in [
+            (
+                ScriptKind::PlutusV2,
+                [V2_FLAT, V2_WRAPPED, V2_DOUBLE_WRAPPED],
+                V2_WRAPPED,
+            ),
+            (
+                ScriptKind::PlutusV3,
+                [V3_FLAT, V3_WRAPPED, V3_DOUBLE_WRAPPED],
+                V3_WRAPPED,
+            ),
+        ] {
+            for form in forms {
+                let normalized = Script::normalize(kind, &bytes(form)).unwrap();
+                assert_eq!(normalized, bytes(wrapped));
+                assert_eq!(Script::normalize(kind, &normalized).unwrap(), normalized);
+            }
+        }
+    }
+
+    #[test]
+    fn hashes_match_known_values() {
+        for form in [V2_FLAT, V2_WRAPPED, V2_DOUBLE_WRAPPED] {
+            assert_eq!(
+                Script::hash_for(ScriptKind::PlutusV2, &bytes(form)).unwrap(),
+                hash(V2_HASH)
+            );
+        }
+        for form in [V3_FLAT, V3_WRAPPED, V3_DOUBLE_WRAPPED] {
+            assert_eq!(
+                Script::hash_for(ScriptKind::PlutusV3, &bytes(form)).unwrap(),
+                hash(V3_HASH)
+            );
+        }
+    }
+
+    /// Script hashes are blake2b-224 over the language tag (0 native, 1 to 3 for plutus V1 to V3)
+    /// followed by the script bytes, as `cardano-cli hash script` computes them.
+    #[test]
+    fn hash_matrix_across_languages() {
+        // `ScriptAll []`, always valid
+        const NATIVE: &str = "820180";
+        const NATIVE_HASH: &str = "d441227553a0f1a965fee7d60a0f724b368dd1bddbc208730fccebcf";
+        // The V2 always-succeeds, reinterpreted as V1
+        const V1_HASH: &str = "62bdc3d04d04376d516d31664944b25ce3affa76d17f8b5e1279b49d";
+
+        for (kind, script, expected) in [
+            (ScriptKind::Native, NATIVE, NATIVE_HASH),
+            (ScriptKind::PlutusV1, V2_WRAPPED, V1_HASH),
+            (ScriptKind::PlutusV2, V2_WRAPPED, V2_HASH),
+            (ScriptKind::PlutusV3, V3_WRAPPED, V3_HASH),
+        ] {
+            assert_eq!(kind.hash(&bytes(script)), hash(expected), "{kind:?}");
+            assert_eq!(
+                Script::hash_for(kind, &bytes(script)).unwrap(),
+                hash(expected),
+                "{kind:?}"
+            );
+            assert_eq!(
+                Script::new(kind, bytes(script)).hash,
+                hash(expected),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn v3_only_programs_are_rejected_for_older_versions() {
+        assert!(Script::normalize(ScriptKind::PlutusV2, &bytes(V3_WRAPPED)).is_err());
+    }
+
+    #[test]
+    fn script_kind_names_round_trip() {
+        for kind in [
+            ScriptKind::Native,
+            ScriptKind::PlutusV1,
+            ScriptKind::PlutusV2,
+            ScriptKind::PlutusV3,
+        ] {
+            assert_eq!(ScriptKind::parse(kind.name()), Ok(kind));
+        }
+        for alias in ["v3", "V3", "plutus_v3", "plutus-v3", "plutusV3"] {
+            assert_eq!(
+                ScriptKind::parse(alias),
+                Ok(ScriptKind::PlutusV3),
+                "{alias}"
+            );
+        }
+        assert_eq!(ScriptKind::parse("timelock"), Ok(ScriptKind::Native));
+        assert_eq!(
+            ScriptKind::parse("v4"),
+            Err(ParseScriptKindError("v4".to_string()))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn script_kind_serde_round_trip() {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Validator {
+            #[serde(with = "super::script_kind_serde")]
+            kind: ScriptKind,
+        }
+
+        let validator = Validator {
+            kind: ScriptKind::PlutusV2,
+        };
+        let json = serde_json::to_string(&validator).unwrap();
+        assert_eq!(json, r#"{"kind":"PlutusV2"}"#);
+        assert_eq!(serde_json::from_str::<Validator>(&json).unwrap(), validator);
+        assert_eq!(
+            serde_json::from_str::<Validator>(r#"{"kind":"plutus_v3"}"#).unwrap(),
+            Validator {
+                kind: ScriptKind::PlutusV3
+            }
+        );
+        assert!(serde_json::from_str::<Validator>(r#"{"kind":"v9"}"#).is_err());
+    }
+
+    #[test]
+    fn redeemer_budgets_add_up_against_the_tx_limit() {
+        let redeemers = [
+            ExUnits {
+                mem: 4_000_000,
+                steps: 1_000_000_000,
+            },
+            ExUnits {
+                mem: 6_000_000,
+                steps: 2_000_000_000,
+            },
+        ];
+        let max = ExUnits {
+            mem: 10_000_000,
+            steps: 2_500_000_000,
+        };
+
+        let total: ExUnits = redeemers.iter().sum();
+        assert_eq!(
+            total,
+            ExUnits {
+                mem: 10_000_000,
+                steps: 3_000_000_000,
+            }
+        );
+        assert_eq!(total.to_string(), "mem: 10000000, steps: 3000000000");
+        assert!(redeemers[0].fits_within(&max));
+        // Memory fits exactly, but the steps don't
+        assert!(!total.fits_within(&max));
+
+        let saturated = ExUnits {
+            mem: u64::MAX,
+            steps: 1,
+        } + ExUnits { mem: 1, steps: 1 };
+        assert_eq!(
+            saturated,
+            ExUnits {
+                mem: u64::MAX,
+                steps: 2
+            }
+        );
+    }
+
+    fn key(seed: u8) -> PubKeyHash {
+        Hash([seed; 28])
+    }
+
+    fn sig(seed: u8) -> NativeScript {
+        NativeScript::ScriptPubkey(key(seed).into())
+    }
+
+    /// `atLeast(3, [sig 1, sig 2, sig 3, all[sig 4, after 100]])`, shaped like a treasury script
+    fn treasury() -> NativeScript {
+        NativeScript::ScriptNOfK(
+            3,
+            vec![
+                sig(1),
+                sig(2),
+                sig(3),
+                NativeScript::ScriptAll(vec![sig(4), NativeScript::InvalidBefore(100)]),
+            ],
+        )
+    }
+
+    #[test]
+    fn nested_thresholds_need_only_the_cheapest_branches() {
+        let all_keys = (1..=4).map(key).collect::<Vec<_>>();
+        assert_eq!(treasury().min_signatures_with(&all_keys), Some(3));
+        // Without key 2, the nested branch has to make up for it
+        assert_eq!(
+            treasury().min_signatures_with(&[key(1), key(3), key(4)]),
+            Some(3)
+        );
+
+        let nested = NativeScript::ScriptAny(vec![
+            NativeScript::ScriptAll(vec![sig(1), sig(2), sig(3)]),
+            NativeScript::ScriptNOfK(
+                1,
+                vec![sig(4), NativeScript::ScriptAll(vec![sig(5), sig(6)])],
+            ),
+        ]);
+        assert_eq!(nested.min_signatures_with(&all_keys), Some(1));
+        assert_eq!(nested.min_signatures_with(&[key(5), key(6)]), Some(2));
+        assert_eq!(
+            nested.min_signatures_with(&[key(1), key(2), key(3)]),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_scripts_have_no_signature_count() {
+        assert_eq!(treasury().min_signatures_with(&[key(1), key(2)]), None);
+        assert_eq!(treasury().min_signatures_with(&[]), None);
+        assert_eq!(
+            NativeScript::ScriptAny(vec![]).min_signatures_with(&[key(1)]),
+            None
+        );
+        assert_eq!(
+            NativeScript::ScriptNOfK(2, vec![sig(1)]).min_signatures_with(&[key(1)]),
+            None
+        );
+    }
+
+    #[test]
+    fn timelock_branches_need_no_signatures() {
+        let timelocked = NativeScript::ScriptAny(vec![
+            sig(1),
+            NativeScript::ScriptAll(vec![
+                NativeScript::InvalidBefore(100),
+                NativeScript::InvalidHereafter(200),
+            ]),
+        ]);
+        assert_eq!(timelocked.min_signatures_with(&[]), Some(0));
+        assert_eq!(
+            NativeScript::ScriptAll(vec![]).min_signatures_with(&[]),
+            Some(0)
+        );
+        assert_eq!(
+            NativeScript::ScriptNOfK(2, vec![sig(1), NativeScript::InvalidHereafter(200), sig(2)])
+                .min_signatures_with(&[key(1), key(2)]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn ogmios_evaluations_convert_for_every_purpose() {
+        for (name, purpose) in [
+            ("spend", ValidatorPurpose::Spend),
+            ("mint", ValidatorPurpose::Mint),
+            ("publish", ValidatorPurpose::Cert),
+            ("withdraw", ValidatorPurpose::Reward),
+            ("vote", ValidatorPurpose::Vote),
+            ("propose", ValidatorPurpose::Propose),
+        ] {
+            let evaluation: Evaluation = serde_json::from_value(serde_json::json!({
+                "validator": { "purpose": name, "index": 2 },
+                "budget": { "memory": 1700, "cpu": 476468 }
+            }))
+            .unwrap();
+            let budget = ValidatorBudget {
+                purpose,
+                index: 2,
+                ex_units: ExUnits {
+                    mem: 1700,
+                    steps: 476468,
+                },
+            };
+            assert_eq!(ValidatorBudget::from(&evaluation), budget, "{name}");
+
+            let converted = Evaluation::try_from(&budget).unwrap();
+            assert_eq!(ValidatorBudget::from(&converted), budget, "{name}");
+        }
+    }
+}
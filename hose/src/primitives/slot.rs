@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// How slots map to wall-clock time on a network, as set by its Shelley genesis.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SlotConfig {
+    /// Start of `zero_slot`
+    pub zero_time: DateTime<Utc>,
+    /// First slot of the Shelley era
+    pub zero_slot: u64,
+    pub slot_length: Duration,
+}
+
+impl SlotConfig {
+    pub fn mainnet() -> Self {
+        Self::from_millis(1_596_059_091_000, 4_492_800, 1_000)
+    }
+
+    pub fn preprod() -> Self {
+        Self::from_millis(1_655_769_600_000, 86_400, 1_000)
+    }
+
+    pub fn preview() -> Self {
+        Self::from_millis(1_666_656_000_000, 0, 1_000)
+    }
+
+    /// `zero_time` in milliseconds since the Unix epoch, `slot_length` in milliseconds.
+    pub fn from_millis(zero_time: i64, zero_slot: u64, slot_length: u64) -> Self {
+        Self {
+            zero_time: DateTime::from_timestamp_millis(zero_time)
+                .expect("zero time is within the representable range"),
+            zero_slot,
+            slot_length: Duration::from_millis(slot_length),
+        }
+    }
+
+    /// Slot that `time` falls in, `zero_slot` for any time before it.
+    pub fn slot_at(&self, time: DateTime<Utc>) -> u64 {
+        let elapsed = (time - self.zero_time).num_milliseconds().max(0) as u64;
+        self.zero_slot + elapsed / self.slot_length_millis()
+    }
+
+    /// Start of `slot`.
+    pub fn time_of(&self, slot: u64) -> DateTime<Utc> {
+        let elapsed = slot.saturating_sub(self.zero_slot) * self.slot_length_millis();
+        self.zero_time + TimeDelta::milliseconds(elapsed as i64)
+    }
+
+    fn slot_length_millis(&self) -> u64 {
+        (self.slot_length.as_millis() as u64).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta};
+
+    use super::SlotConfig;
+
+    #[test]
+    fn slots_and_times_convert_both_ways() {
+        let config = SlotConfig::mainnet();
+        // The Shelley hard fork
+        let shelley = DateTime::parse_from_rfc3339("2020-07-29T21:44:51Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(config.slot_at(shelley), 4_492_800);
+
+        let time = DateTime::parse_from_rfc3339("2023-12-04T08:20:15Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(config.slot_at(time), 110_111_724);
+        assert_eq!(config.time_of(110_111_724), time);
+
+        // Any time within a slot falls in it
+        assert_eq!(
+            config.slot_at(time + TimeDelta::milliseconds(999)),
+            110_111_724
+        );
+        assert_eq!(
+            config.slot_at(config.zero_time - TimeDelta::days(1)),
+            4_492_800
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+#[cfg(feature = "registry-csv")]
+use serde::Deserialize;
+
+use super::{Error, TokenInfo, TokenRegistry, subject};
+use crate::primitives::AssetId;
+
+/// Token metadata loaded once from a local file, keyed by registry subject (see `subject`).
+///
+/// JSON files map subjects to `TokenInfo` objects:
+/// ```json
+/// { "<policy id><asset name>": { "ticker": "tDJED", "name": "Djed", "decimals": 6 } }
+/// ```
+///
+/// CSV files, read with the `registry-csv` feature, have a `subject,ticker,name,decimals,logo`
+/// header, empty cells are left unset.
+#[derive(Debug, Clone, Default)]
+pub struct FileTokenRegistry {
+    tokens: HashMap<String, TokenInfo>,
+}
+
+#[cfg(feature = "registry-csv")]
+#[derive(Deserialize)]
+struct CsvRow {
+    subject: String,
+    ticker: Option<String>,
+    name: Option<String>,
+    decimals: Option<u8>,
+    logo: Option<String>,
+}
+
+impl FileTokenRegistry {
+    /// Loads a `.json` or `.csv` file, picking the format from the extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json,
+            #[cfg(feature = "registry-csv")]
+            Some("csv") => Self::from_csv,
+            _ => return Err(Error::UnsupportedFormat(path.display().to_string())),
+        };
+        parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let tokens: HashMap<String, TokenInfo> = serde_json::from_str(json)?;
+        Ok(Self {
+            tokens: tokens
+                .into_iter()
+                .map(|(subject, info)| (subject.to_lowercase(), info))
+                .collect(),
+        })
+    }
+
+    #[cfg(feature = "registry-csv")]
+    pub fn from_csv(csv: &str) -> Result<Self, Error> {
+        let mut tokens = HashMap::new();
+        for row in csv::Reader::from_reader(csv.as_bytes()).deserialize() {
+            let row: CsvRow = row?;
+            tokens.insert(
+                row.subject.to_lowercase(),
+                TokenInfo {
+                    ticker: row.ticker,
+                    name: row.name,
+                    decimals: row.decimals.unwrap_or_default(),
+                    logo: row.logo,
+                },
+            );
+        }
+        Ok(Self { tokens })
+    }
+}
+
+impl TokenRegistry for FileTokenRegistry {
+    fn lookup(&self, asset_id: &AssetId) -> Option<TokenInfo> {
+        self.tokens.get(&subject(asset_id)).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileTokenRegistry;
+    use crate::primitives::{AssetId, Hash};
+    use crate::registry::{Error, TokenInfo, TokenRegistry};
+
+    fn djed() -> AssetId {
+        AssetId::new(Hash([0xab; 28]), b"DJED".to_vec())
+    }
+
+    fn djed_info() -> TokenInfo {
+        TokenInfo {
+            ticker: Some("tDJED".to_string()),
+            name: Some("Djed, testnet".to_string()),
+            decimals: 6,
+            logo: None,
+        }
+    }
+
+    #[test]
+    fn json_lookup() {
+        let json = format!(
+            r#"{{ "{}444a4544": {{ "ticker": "tDJED", "name": "Djed, testnet", "decimals": 6 }} }}"#,
+            hex::encode([0xab; 28]).to_uppercase()
+        );
+        let registry = FileTokenRegistry::from_json(&json).expect("parse registry");
+
+        assert_eq!(registry.lookup(&djed()), Some(djed_info()));
+        assert_eq!(
+            registry.lookup(&AssetId::new(Hash([0xab; 28]), b"SHEN".to_vec())),
+            None
+        );
+    }
+
+    #[cfg(feature = "registry-csv")]
+    #[test]
+    fn csv_lookup() {
+        let csv = format!(
+            "subject,ticker,name,decimals,logo\n\
+             {policy}444a4544,tDJED,\"Djed, testnet\",6,\n\
+             {policy}4e4654,,,,\n",
+            policy = hex::encode([0xab; 28])
+        );
+        let registry = FileTokenRegistry::from_csv(&csv).expect("parse registry");
+
+        assert_eq!(registry.lookup(&djed()), Some(djed_info()));
+        assert_eq!(
+            registry.lookup(&AssetId::new(Hash([0xab; 28]), b"NFT".to_vec())),
+            Some(TokenInfo::default())
+        );
+    }
+
+    #[test]
+    fn load_from_file() {
+        let path = std::env::temp_dir().join(format!(
+            "hose-token-registry-{}.json",
+            rand::random::<u64>()
+        ));
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{ "{}444a4544": {{ "ticker": "tDJED", "name": "Djed, testnet", "decimals": 6 }} }}"#,
+                hex::encode([0xab; 28])
+            ),
+        )
+        .expect("write registry");
+
+        let registry = FileTokenRegistry::load(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            registry.expect("load registry").lookup(&djed()),
+            Some(djed_info())
+        );
+        assert!(matches!(
+            FileTokenRegistry::load("registry.toml"),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, TokenInfo, TokenRegistry, subject};
+use crate::primitives::AssetId;
+
+/// Public Cardano token registry (mainnet)
+pub const PUBLIC_REGISTRY_URL: &str = "https://tokens.cardano.org";
+
+/// Client for a CIP-26 token registry server.
+///
+/// Metadata is fetched in batches with `fetch` and cached for the lifetime of the registry,
+/// including the assets the server doesn't know, so each asset is requested at most once. The
+/// total number of requests is capped by a budget, once spent `fetch` fails with
+/// `Error::BudgetExhausted` whenever it would need the network.
+pub struct HttpTokenRegistry {
+    client: reqwest::Client,
+    base_url: String,
+    cache: Mutex<HashMap<String, Option<TokenInfo>>>,
+    remaining_requests: AtomicUsize,
+}
+
+#[derive(Serialize)]
+struct Query<'a> {
+    subjects: Vec<String>,
+    properties: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    subjects: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    subject: String,
+    ticker: Option<Property<String>>,
+    name: Option<Property<String>>,
+    decimals: Option<Property<u8>>,
+    logo: Option<Property<String>>,
+}
+
+#[derive(Deserialize)]
+struct Property<T> {
+    value: T,
+}
+
+impl HttpTokenRegistry {
+    /// Registry at `base_url`, allowed to make up to `request_budget` requests.
+    pub fn new(base_url: impl Into<String>, request_budget: usize) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            cache: Mutex::new(HashMap::new()),
+            remaining_requests: AtomicUsize::new(request_budget),
+        }
+    }
+
+    /// The public mainnet registry at `PUBLIC_REGISTRY_URL`.
+    pub fn public(request_budget: usize) -> Self {
+        Self::new(PUBLIC_REGISTRY_URL, request_budget)
+    }
+
+    /// Number of requests the registry can still make.
+    pub fn remaining_requests(&self) -> usize {
+        self.remaining_requests.load(Ordering::Relaxed)
+    }
+
+    /// Fetches the metadata of every asset in `asset_ids` that isn't cached yet, in a single
+    /// request. Doesn't touch the network when everything is cached.
+    pub async fn fetch(&self, asset_ids: impl IntoIterator<Item = &AssetId>) -> Result<(), Error> {
+        let mut subjects = {
+            let cache = self.cache.lock().expect("token registry cache poisoned");
+            asset_ids
+                .into_iter()
+                .map(subject)
+                .filter(|subject| !cache.contains_key(subject))
+                .collect::<Vec<_>>()
+        };
+        subjects.sort_unstable();
+        subjects.dedup();
+        if subjects.is_empty() {
+            return Ok(());
+        }
+
+        self.remaining_requests
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(1)
+            })
+            .map_err(|_| Error::BudgetExhausted)?;
+
+        let response: QueryResponse = self
+            .client
+            .post(format!("{}/metadata/query", self.base_url))
+            .json(&Query {
+                subjects: subjects.clone(),
+                properties: &["ticker", "name", "decimals", "logo"],
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut cache = self.cache.lock().expect("token registry cache poisoned");
+        // Subjects missing from the response are unknown to the registry, caching them avoids
+        // asking again
+        for subject in subjects {
+            cache.insert(subject, None);
+        }
+        for entry in response.subjects {
+            cache.insert(
+                entry.subject.to_lowercase(),
+                Some(TokenInfo {
+                    ticker: entry.ticker.map(|property| property.value),
+                    name: entry.name.map(|property| property.value),
+                    decimals: entry
+                        .decimals
+                        .map(|property| property.value)
+                        .unwrap_or_default(),
+                    logo: entry.logo.map(|property| property.value),
+                }),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl TokenRegistry for HttpTokenRegistry {
+    /// Only reads the cache, call `fetch` first.
+    fn lookup(&self, asset_id: &AssetId) -> Option<TokenInfo> {
+        self.cache
+            .lock()
+            .expect("token registry cache poisoned")
+            .get(&subject(asset_id))
+            .cloned()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpTokenRegistry;
+    use crate::mock_http::MockServer;
+    use crate::primitives::{AssetId, Hash};
+    use crate::registry::{Error, TokenInfo, TokenRegistry};
+
+    fn djed() -> AssetId {
+        AssetId::new(Hash([0xab; 28]), b"DJED".to_vec())
+    }
+
+    #[tokio::test]
+    async fn fetches_once_then_hits_the_cache() {
+        let body = format!(
+            r#"{{ "subjects": [{{
+                "subject": "{}444a4544",
+                "ticker": {{ "value": "tDJED", "sequenceNumber": 0, "signatures": [] }},
+                "decimals": {{ "value": 6, "sequenceNumber": 0, "signatures": [] }}
+            }}] }}"#,
+            hex::encode([0xab; 28])
+        );
+        let server = MockServer::serving(body).await;
+        let registry = HttpTokenRegistry::new(server.url.clone(), 2);
+        let unknown = AssetId::new(Hash([0xcd; 28]), b"SHEN".to_vec());
+
+        assert_eq!(registry.lookup(&djed()), None);
+        registry
+            .fetch([&djed(), &unknown])
+            .await
+            .expect("fetch metadata");
+        assert_eq!(server.requests().len(), 1);
+
+        let expected = TokenInfo {
+            ticker: Some("tDJED".to_string()),
+            decimals: 6,
+            ..Default::default()
+        };
+        assert_eq!(registry.lookup(&djed()), Some(expected));
+        assert_eq!(registry.lookup(&unknown), None);
+
+        // Both assets are cached, the unknown one included
+        registry
+            .fetch([&djed(), &unknown])
+            .await
+            .expect("fetch metadata");
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(registry.remaining_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn request_budget_is_enforced() {
+        let server = MockServer::serving(r#"{ "subjects": [] }"#.to_string()).await;
+        let registry = HttpTokenRegistry::new(server.url.clone(), 1);
+
+        registry.fetch([&djed()]).await.expect("fetch metadata");
+        let other = AssetId::new(Hash([0xcd; 28]), b"SHEN".to_vec());
+        assert!(matches!(
+            registry.fetch([&other]).await,
+            Err(Error::BudgetExhausted)
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
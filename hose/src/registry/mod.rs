@@ -0,0 +1,187 @@
+//! Human readable metadata for native assets, from a local file or, with the `registry-http`
+//! feature, the Cardano token registry (CIP-26)
+//!
+//! Registries are only used to render values, amounts are never rounded: the scaled amount is
+//! always shown next to the raw one.
+
+use num::Integer as _;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::primitives::{AssetId, Assets, BigInt};
+
+mod file;
+#[cfg(feature = "registry-http")]
+mod http;
+pub use file::FileTokenRegistry;
+#[cfg(feature = "registry-http")]
+pub use http::HttpTokenRegistry;
+
+/// Source of token metadata. Lookups never hit the network, registries backed by a remote
+/// service are expected to fetch ahead of time.
+pub trait TokenRegistry: Send + Sync {
+    fn lookup(&self, asset_id: &AssetId) -> Option<TokenInfo>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct TokenInfo {
+    pub ticker: Option<String>,
+    pub name: Option<String>,
+    /// Number of digits after the decimal point when displaying amounts
+    #[serde(default)]
+    pub decimals: u8,
+    /// Base64 encoded PNG, as served by the token registry
+    pub logo: Option<String>,
+}
+
+impl TokenInfo {
+    /// Renders `amount` in whole units, e.g. 12_500_000 with 6 decimals is "12.5".
+    ///
+    /// The conversion is exact, trailing zeros of the fractional part are dropped.
+    pub fn scale(&self, amount: &BigInt) -> String {
+        let sign = if amount.is_negative() { "-" } else { "" };
+        let unit = num::pow(num::BigInt::from(10), self.decimals as usize);
+        let (whole, fraction) = amount.as_inner().magnitude().div_rem(unit.magnitude());
+
+        let fraction = fraction.to_string();
+        if fraction == "0" {
+            return format!("{sign}{whole}");
+        }
+        let padding = "0".repeat(self.decimals as usize - fraction.len());
+        format!("{sign}{whole}.{padding}{}", fraction.trim_end_matches('0'))
+    }
+}
+
+/// Registry subject of an asset: the hex encoded policy id followed by the asset name.
+pub fn subject(asset_id: &AssetId) -> String {
+    format!(
+        "{}{}",
+        hex::encode(asset_id.policy.0),
+        hex::encode(&asset_id.name)
+    )
+}
+
+/// Renders `amount` of `asset_id`, e.g. "12.5 tDJED (12500000)" when the registry knows the
+/// asset, or the raw amount and asset id otherwise.
+pub fn display_amount(registry: &dyn TokenRegistry, asset_id: &AssetId, amount: &BigInt) -> String {
+    let raw = format!(
+        "{amount} {}.{}",
+        hex::encode(asset_id.policy.0),
+        hex::encode(&asset_id.name)
+    );
+    match registry.lookup(asset_id) {
+        Some(info) => match info.ticker.as_ref().or(info.name.as_ref()) {
+            Some(label) => format!("{} {label} ({amount})", info.scale(amount)),
+            None => format!("{} ({raw})", info.scale(amount)),
+        },
+        None => raw,
+    }
+}
+
+/// Renders every asset of `assets` with `display_amount`, comma separated.
+pub fn display_assets(registry: &dyn TokenRegistry, assets: &Assets) -> String {
+    assets
+        .iter()
+        .map(|(asset_id, amount)| display_amount(registry, asset_id, &BigInt::from(*amount)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Unable to read a registry file
+    #[error("Unable to read token registry: {0}")]
+    Io(#[from] std::io::Error),
+    /// Registry file isn't valid JSON or is missing fields
+    #[error("Malformed JSON token registry: {0}")]
+    MalformedJson(#[from] serde_json::Error),
+    /// Registry file isn't valid CSV or is missing columns
+    #[cfg(feature = "registry-csv")]
+    #[error("Malformed CSV token registry: {0}")]
+    MalformedCsv(#[from] csv::Error),
+    /// Registry file is neither `.json` nor `.csv`
+    #[error("Unsupported token registry format: {0}")]
+    UnsupportedFormat(String),
+    /// Request to a remote registry failed
+    #[cfg(feature = "registry-http")]
+    #[error("Token registry request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// A remote registry was asked for more requests than it's allowed to make
+    #[cfg(feature = "registry-http")]
+    #[error("Token registry request budget exhausted")]
+    BudgetExhausted,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TokenInfo, TokenRegistry, display_amount};
+    use crate::primitives::{AssetId, BigInt, Hash};
+
+    struct Djed;
+
+    impl TokenRegistry for Djed {
+        fn lookup(&self, asset_id: &AssetId) -> Option<TokenInfo> {
+            (asset_id.policy == Hash([1u8; 28])).then(|| TokenInfo {
+                ticker: Some("tDJED".to_string()),
+                decimals: 6,
+                ..Default::default()
+            })
+        }
+    }
+
+    fn scale(decimals: u8, amount: i64) -> String {
+        TokenInfo {
+            decimals,
+            ..Default::default()
+        }
+        .scale(&BigInt::from(amount))
+    }
+
+    #[test]
+    fn scaling_is_exact() {
+        assert_eq!(scale(6, 12_500_000), "12.5");
+        assert_eq!(scale(6, 12_000_000), "12");
+        assert_eq!(scale(6, 1_000_001), "1.000001");
+        assert_eq!(scale(6, -12_500_000), "-12.5");
+
+        let huge = BigInt::from(u128::MAX);
+        let info = TokenInfo {
+            decimals: 18,
+            ..Default::default()
+        };
+        assert_eq!(
+            info.scale(&huge),
+            "340282366920938463463.374607431768211455"
+        );
+    }
+
+    #[test]
+    fn zero_decimals_keep_the_raw_amount() {
+        assert_eq!(scale(0, 0), "0");
+        assert_eq!(scale(0, 42), "42");
+        assert_eq!(scale(0, -42), "-42");
+    }
+
+    #[test]
+    fn amounts_smaller_than_one_unit() {
+        assert_eq!(scale(6, 0), "0");
+        assert_eq!(scale(6, 5), "0.000005");
+        assert_eq!(scale(6, 999_999), "0.999999");
+        assert_eq!(scale(6, -5), "-0.000005");
+    }
+
+    #[test]
+    fn display_preserves_the_raw_amount() {
+        let known = AssetId::new(Hash([1u8; 28]), b"DJED".to_vec());
+        assert_eq!(
+            display_amount(&Djed, &known, &BigInt::from(12_500_000)),
+            "12.5 tDJED (12500000)"
+        );
+
+        let unknown = AssetId::new(Hash([2u8; 28]), b"token".to_vec());
+        assert_eq!(
+            display_amount(&Djed, &unknown, &BigInt::from(7)),
+            format!("7 {}.746f6b656e", hex::encode([2u8; 28]))
+        );
+    }
+}
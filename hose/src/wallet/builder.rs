@@ -1,7 +1,12 @@
+#[cfg(feature = "keystore")]
+use std::path::Path;
+
 use pallas::ledger::addresses::{
     Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
 };
 
+#[cfg(feature = "keystore")]
+use super::Keystore;
 use super::hd_key::PrivateKeyRole;
 use super::{Error, HDPrivateKey, PrivateKey, Wallet};
 
@@ -16,6 +21,7 @@ pub struct WalletBuilder {
     address_type: AddressType,
     account_index: u32,
     address_index: u32,
+    address_pool_size: u32,
 }
 
 impl WalletBuilder {
@@ -26,6 +32,7 @@ impl WalletBuilder {
             address_type: AddressType::Enterprise,
             account_index: 0,
             address_index: 0,
+            address_pool_size: 1,
         }
     }
 
@@ -44,6 +51,16 @@ impl WalletBuilder {
         self
     }
 
+    /// Number of addresses the wallet manages, derived at consecutive address indices starting
+    /// from the address index, 1 by default. See `Wallet::all_addresses`.
+    ///
+    /// Only keys derived from a mnemonic, root or account key can have more than one address.
+    /// Base addresses of the pool all share the stake key of the wallet address.
+    pub fn address_pool_size(mut self, address_pool_size: u32) -> Self {
+        self.address_pool_size = address_pool_size;
+        self
+    }
+
     /// Derives the payment and (optionally, based on `address_type`) stake key from the given
     /// mnemonic and password. The account index and address index are set to 0 by default.
     /// The address will be derived from the keys, unless manually set.
@@ -52,7 +69,18 @@ impl WalletBuilder {
     /// Stake derivation path: `m/1852'/1815'/$account_index'/2/$address_index`
     pub fn from_mnemonic(self, mnemonic: String, password: String) -> Result<Wallet, Error> {
         let private_key = HDPrivateKey::from_bip39_mnenomic(&mnemonic, &password)?;
+        Ok(self.derive_wallet(&private_key))
+    }
 
+    /// Generates a fresh wallet from a random 24-word mnemonic (with an empty password), which is
+    /// returned alongside the wallet so it can be stored and restored with `from_mnemonic`.
+    /// Keys and address are derived just like in `from_mnemonic`.
+    pub fn generate(self) -> Result<(Wallet, String), Error> {
+        let (private_key, mnemonic) = HDPrivateKey::generate_with_mnemonic("")?;
+        Ok((self.derive_wallet(&private_key), mnemonic.to_string()))
+    }
+
+    fn derive_wallet(self, private_key: &HDPrivateKey) -> Wallet {
         let payment_key = private_key
             .derive_key_from_root(
                 self.account_index,
@@ -73,14 +101,46 @@ impl WalletBuilder {
             ),
         };
 
-        Ok(Wallet {
+        let pool = self.derive_pool(
+            |role, address_index| {
+                private_key.derive_key_from_root(self.account_index, role, address_index)
+            },
+            &stake_key,
+        );
+
+        Wallet {
             network: self.network,
             address: self
                 .address
                 .unwrap_or_else(|| address_from_parts(self.network, &payment_key, &stake_key)),
             payment_key,
             stake_key,
-        })
+            pool,
+        }
+    }
+
+    /// Addresses of the pool past the wallet address, along with their payment key.
+    fn derive_pool(
+        &self,
+        derive: impl Fn(PrivateKeyRole, u32) -> HDPrivateKey,
+        stake_key: &Option<PrivateKey>,
+    ) -> Vec<(ShelleyAddress, PrivateKey)> {
+        (1..self.address_pool_size)
+            .map(|offset| {
+                let payment_key: PrivateKey =
+                    derive(PrivateKeyRole::External, self.address_index + offset).into();
+                let address = address_from_parts(self.network, &payment_key, stake_key);
+                (address, payment_key)
+            })
+            .collect()
+    }
+
+    /// Fails if an address pool was requested from a key that can't derive one.
+    fn ensure_no_pool(&self) -> Result<(), Error> {
+        if self.address_pool_size > 1 {
+            return Err(Error::AddressPoolUnavailable);
+        }
+        Ok(())
     }
 
     /// Converts the given bech32 string into a payment and (optionally, based on `address_type`
@@ -97,7 +157,7 @@ impl WalletBuilder {
     /// Stake derivation path: `m/1852'/1815'/$account_index'/2/$address_index`
     pub fn from_bech32(self, bech32: String) -> Result<Wallet, Error> {
         let (hrp, _) = bech32::decode(&bech32)?;
-        let (payment_key, stake_key) = match hrp.as_str() {
+        let (payment_key, stake_key, pool) = match hrp.as_str() {
             // Root key, derive the payment and stake keys
             "root_xsk" | "xprv" => {
                 let private_key = HDPrivateKey::from_bech32(&bech32)?;
@@ -120,7 +180,13 @@ impl WalletBuilder {
                             .into(),
                     ),
                 };
-                (payment_key, stake_key)
+                let pool = self.derive_pool(
+                    |role, address_index| {
+                        private_key.derive_key_from_root(self.account_index, role, address_index)
+                    },
+                    &stake_key,
+                );
+                (payment_key, stake_key, pool)
             }
 
             // Account key, derived from root key: m/1852'/1815'/$account_index'
@@ -137,11 +203,18 @@ impl WalletBuilder {
                             .into(),
                     ),
                 };
-                (payment_key, stake_key)
+                let pool = self.derive_pool(
+                    |role, address_index| account_key.derive_key_from_account(role, address_index),
+                    &stake_key,
+                );
+                (payment_key, stake_key, pool)
             }
 
             // Generic ed25519 key, assume it's the payment key
-            "ed25519_sk" | "ed25519e_sk" | "addr_sk" => (PrivateKey::from_bech32(&bech32)?, None),
+            "ed25519_sk" | "ed25519e_sk" | "addr_sk" => {
+                self.ensure_no_pool()?;
+                (PrivateKey::from_bech32(&bech32)?, None, vec![])
+            }
 
             // Unrecognized
             _ => return Err(Error::InvalidBech32Hrp(hrp.to_string())),
@@ -154,6 +227,24 @@ impl WalletBuilder {
                 .unwrap_or_else(|| address_from_parts(self.network, &payment_key, &stake_key)),
             payment_key,
             stake_key: None,
+            pool,
+        })
+    }
+
+    /// Restores the payment and stake keys from a keystore written by `Wallet::save_keystore`.
+    /// The address will be derived from the keys, unless manually set.
+    #[cfg(feature = "keystore")]
+    pub fn from_keystore(self, path: impl AsRef<Path>, passphrase: &str) -> Result<Wallet, Error> {
+        self.ensure_no_pool()?;
+        let (payment_key, stake_key) = Keystore::load(path)?.decrypt(passphrase)?;
+        Ok(Wallet {
+            network: self.network,
+            address: self
+                .address
+                .unwrap_or_else(|| address_from_parts(self.network, &payment_key, &stake_key)),
+            payment_key,
+            stake_key,
+            pool: vec![],
         })
     }
 
@@ -162,6 +253,7 @@ impl WalletBuilder {
     /// Due to the nature of a raw payment key, we cannot derive the stake key for the address,
     /// so the address will always be an `Enterprise` address (no stake part), unless manually set.
     pub fn from_hex(self, hex_payment_key: String) -> Result<Wallet, Error> {
+        self.ensure_no_pool()?;
         let private_key = PrivateKey::from_hex(hex_payment_key)?;
         Ok(Wallet {
             network: self.network,
@@ -170,6 +262,7 @@ impl WalletBuilder {
                 .unwrap_or_else(|| address_from_parts(self.network, &private_key, &None)),
             payment_key: private_key,
             stake_key: None,
+            pool: vec![],
         })
     }
 }
@@ -186,3 +279,92 @@ fn address_from_parts(
     };
     ShelleyAddress::new(network, payment_part, stake_part)
 }
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::Network;
+
+    use super::WalletBuilder;
+    use crate::wallet::Error;
+
+    #[test]
+    fn generated_wallet_restores_from_mnemonic() {
+        let (wallet, mnemonic) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        assert_eq!(mnemonic.split_whitespace().count(), 24);
+
+        let restored = WalletBuilder::new(Network::Testnet)
+            .from_mnemonic(mnemonic, String::new())
+            .expect("restore from mnemonic");
+        assert_eq!(restored.address(), wallet.address());
+    }
+
+    #[test]
+    fn generated_wallet_restores_from_bech32() {
+        let (wallet, _) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        let bech32 = wallet.to_bech32_private_key().expect("encode key");
+        assert!(bech32.starts_with("ed25519e_sk1"));
+
+        let restored = WalletBuilder::new(Network::Testnet)
+            .from_bech32(bech32)
+            .expect("restore from bech32");
+        assert_eq!(restored.address(), wallet.address());
+    }
+
+    #[test]
+    fn generated_wallets_differ() {
+        let (first, _) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        let (second, _) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        assert_ne!(first.address(), second.address());
+    }
+
+    #[test]
+    fn address_pool_derives_consecutive_addresses() {
+        let (_, mnemonic) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        let wallet = WalletBuilder::new(Network::Testnet)
+            .address_pool_size(3)
+            .from_mnemonic(mnemonic.clone(), String::new())
+            .expect("restore from mnemonic");
+
+        let addresses = wallet.all_addresses();
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(addresses[0], wallet.address());
+        for (index, address) in addresses.iter().enumerate() {
+            let single = WalletBuilder::new(Network::Testnet)
+                .address_index(index as u32)
+                .from_mnemonic(mnemonic.clone(), String::new())
+                .expect("restore from mnemonic");
+            assert_eq!(*address, single.address());
+        }
+    }
+
+    #[test]
+    fn address_pool_needs_a_derivable_key() {
+        assert!(matches!(
+            WalletBuilder::new(Network::Testnet)
+                .address_pool_size(2)
+                .from_hex(hex::encode([1u8; 32])),
+            Err(Error::AddressPoolUnavailable)
+        ));
+    }
+
+    #[test]
+    fn private_key_rejects_unexpected_hrp() {
+        let bech32 =
+            bech32::encode::<bech32::Bech32>(bech32::Hrp::parse_unchecked("foo"), &[0; 32])
+                .expect("encode");
+        assert!(matches!(
+            crate::wallet::PrivateKey::from_bech32(&bech32),
+            Err(Error::InvalidBech32Hrp(hrp)) if hrp == "foo"
+        ));
+    }
+}
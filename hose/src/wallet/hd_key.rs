@@ -29,9 +29,19 @@ impl HDPrivateKey {
         Ok(Self(XPrv::normalize_bytes_force3rd(pbkdf2_result)))
     }
 
+    /// Generates a fresh root key from 256 bits of entropy, returning it along with its 24-word
+    /// mnemonic.
+    pub fn generate_with_mnemonic(password: &str) -> Result<(Self, Mnemonic), Error> {
+        let mut entropy = [0u8; 32];
+        getrandom::getrandom(&mut entropy).expect("the OS random number generator is available");
+        let mnemonic = Mnemonic::from_entropy(&entropy).map_err(Error::Mnemonic)?;
+        let private_key = Self::from_bip39_mnenomic(&mnemonic.to_string(), password)?;
+        Ok((private_key, mnemonic))
+    }
+
     pub fn from_bech32(bech32: &str) -> Result<Self, Error> {
         let (hrp, data) = bech32::decode(bech32)?;
-        if matches!(hrp.as_str(), "xprv" | "root_xsk") {
+        if !matches!(hrp.as_str(), "xprv" | "root_xsk" | "acct_xsk") {
             return Err(Error::InvalidBech32Hrp(hrp.to_string()));
         }
 
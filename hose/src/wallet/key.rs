@@ -1,5 +1,8 @@
+use bech32::{Bech32, Hrp};
 use pallas::crypto::hash::{Hash, Hasher};
 use pallas::crypto::key::ed25519::{PublicKey, SecretKey, SecretKeyExtended, Signature};
+#[cfg(feature = "keystore")]
+use zeroize::Zeroizing;
 
 use super::Error;
 use crate::wallet::HDPrivateKey;
@@ -18,12 +21,39 @@ impl PrivateKey {
 
     pub fn from_bech32(bech32_str: &str) -> Result<Self, Error> {
         let (hrp, data) = bech32::decode(bech32_str)?;
-        if matches!(hrp.as_str(), "ed25519_sk" | "ed25519e_sk") {
+        if !matches!(hrp.as_str(), "ed25519_sk" | "ed25519e_sk" | "addr_sk") {
             return Err(Error::InvalidBech32Hrp(hrp.to_string()));
         }
         Self::from_bytes(&data)
     }
 
+    /// Encodes the key as bech32, using the `ed25519_sk` prefix for standard keys and
+    /// `ed25519e_sk` for extended ones.
+    pub fn to_bech32(&self) -> Result<String, Error> {
+        // SAFETY: the key bytes are only used to produce the encoding handed back to the caller
+        let (hrp, bytes) = match self {
+            Self::Normal(x) => (
+                "ed25519_sk",
+                unsafe { x.clone().leak_into_bytes() }.to_vec(),
+            ),
+            Self::Extended(x) => (
+                "ed25519e_sk",
+                unsafe { x.clone().leak_into_bytes() }.to_vec(),
+            ),
+        };
+        Ok(bech32::encode::<Bech32>(Hrp::parse_unchecked(hrp), &bytes)?)
+    }
+
+    /// The raw key bytes, wiped from memory when dropped.
+    #[cfg(feature = "keystore")]
+    pub(crate) fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        // SAFETY: the leaked bytes are only handed out wrapped in `Zeroizing`
+        Zeroizing::new(match self {
+            Self::Normal(x) => unsafe { x.clone().leak_into_bytes() }.to_vec(),
+            Self::Extended(x) => unsafe { x.clone().leak_into_bytes() }.to_vec(),
+        })
+    }
+
     pub fn from_bytes<T>(bytes: T) -> Result<Self, Error>
     where
         T: AsRef<[u8]>,
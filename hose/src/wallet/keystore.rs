@@ -0,0 +1,340 @@
+//! Passphrase-encrypted storage for wallet keys
+//!
+//! Keys are encrypted with XChaCha20-Poly1305, under a key derived from the passphrase with
+//! PBKDF2-HMAC-SHA512, and stored as versioned JSON along with the KDF and cipher parameters.
+//! Changing the ciphertext, salt, nonce or iteration count makes decryption fail on the
+//! authentication tag. An unknown version, KDF or cipher is rejected as unsupported before that.
+//!
+//! The plaintext is the length of the payment key as a single byte, followed by the payment key
+//! and, if the wallet has one, the stake key.
+
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::Path;
+
+use chacha20poly1305::aead::Aead as _;
+use chacha20poly1305::{Key, KeyInit as _, XChaCha20Poly1305, XNonce};
+use cryptoxide::hmac::Hmac;
+use cryptoxide::pbkdf2::pbkdf2;
+use cryptoxide::sha2::Sha512;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::{Error, PrivateKey, Wallet};
+
+const VERSION: u32 = 1;
+const KDF_FUNCTION: &str = "pbkdf2-hmac-sha512";
+const CIPHER_FUNCTION: &str = "xchacha20-poly1305";
+/// OWASP recommendation for PBKDF2-HMAC-SHA512
+const DEFAULT_ITERATIONS: u32 = 210_000;
+
+/// Encrypted wallet keys, as stored on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Keystore {
+    pub version: u32,
+    pub kdf: KdfParams,
+    pub cipher: CipherParams,
+    /// Hex encoded ciphertext, followed by the authentication tag
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub function: String,
+    pub iterations: u32,
+    /// Hex encoded
+    pub salt: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CipherParams {
+    pub function: String,
+    /// Hex encoded
+    pub nonce: String,
+}
+
+impl Keystore {
+    /// Encrypts the keys of `wallet` with `passphrase`, using a random salt and nonce.
+    pub fn encrypt(wallet: &Wallet, passphrase: &str) -> Result<Self, Error> {
+        Self::encrypt_with(
+            wallet,
+            passphrase,
+            DEFAULT_ITERATIONS,
+            rand::random(),
+            rand::random(),
+        )
+    }
+
+    fn encrypt_with(
+        wallet: &Wallet,
+        passphrase: &str,
+        iterations: u32,
+        salt: [u8; 32],
+        nonce: [u8; 24],
+    ) -> Result<Self, Error> {
+        let payment_key = wallet.payment_key.to_bytes();
+        let stake_key = wallet.stake_key.as_ref().map(PrivateKey::to_bytes);
+        // Reserved up front, growing the buffer would leave a copy of the keys behind
+        let stake_key_len = stake_key.as_ref().map_or(0, |key| key.len());
+        let mut plaintext =
+            Zeroizing::new(Vec::with_capacity(1 + payment_key.len() + stake_key_len));
+        plaintext.push(payment_key.len() as u8);
+        plaintext.extend_from_slice(&payment_key);
+        if let Some(stake_key) = &stake_key {
+            plaintext.extend_from_slice(stake_key);
+        }
+
+        let key = derive_key(passphrase, &salt, iterations);
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| Error::KeystoreEncryption)?;
+
+        Ok(Self {
+            version: VERSION,
+            kdf: KdfParams {
+                function: KDF_FUNCTION.to_string(),
+                iterations,
+                salt: hex::encode(salt),
+            },
+            cipher: CipherParams {
+                function: CIPHER_FUNCTION.to_string(),
+                nonce: hex::encode(nonce),
+            },
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Decrypts the payment key and, if present, the stake key.
+    ///
+    /// A wrong passphrase and a tampered file are indistinguishable, both fail with
+    /// `Error::KeystoreDecryption`.
+    pub fn decrypt(&self, passphrase: &str) -> Result<(PrivateKey, Option<PrivateKey>), Error> {
+        if self.version != VERSION {
+            return Err(Error::UnsupportedKeystore(format!(
+                "version {}",
+                self.version
+            )));
+        }
+        if self.kdf.function != KDF_FUNCTION || self.kdf.iterations == 0 {
+            return Err(Error::UnsupportedKeystore(format!(
+                "key derivation {} with {} iterations",
+                self.kdf.function, self.kdf.iterations
+            )));
+        }
+        if self.cipher.function != CIPHER_FUNCTION {
+            return Err(Error::UnsupportedKeystore(format!(
+                "cipher {}",
+                self.cipher.function
+            )));
+        }
+
+        let salt = hex::decode(&self.kdf.salt)?;
+        let nonce = hex::decode(&self.cipher.nonce)?;
+        if nonce.len() != 24 {
+            return Err(Error::KeystoreDecryption);
+        }
+        let ciphertext = hex::decode(&self.ciphertext)?;
+
+        let key = derive_key(passphrase, &salt, self.kdf.iterations);
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+                .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| Error::KeystoreDecryption)?,
+        );
+
+        let (payment_key_len, keys) = plaintext.split_first().ok_or(Error::UnexpectedKeyLength)?;
+        let (payment_key, stake_key) = keys
+            .split_at_checked(*payment_key_len as usize)
+            .ok_or(Error::UnexpectedKeyLength)?;
+        let payment_key = PrivateKey::from_bytes(payment_key)?;
+        let stake_key = (!stake_key.is_empty())
+            .then(|| PrivateKey::from_bytes(stake_key))
+            .transpose()?;
+        Ok((payment_key, stake_key))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Writes the keystore to `path`. On unix, the file is only readable and writable by its
+    /// owner, including when it already existed with wider permissions.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    let mut mac = Hmac::new(Sha512::new(), passphrase.as_bytes());
+    pbkdf2(&mut mac, salt, iterations, &mut *key);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::{
+        Network, ShelleyAddress, ShelleyDelegationPart, ShelleyPaymentPart,
+    };
+
+    use super::Keystore;
+    use crate::wallet::{Error, HDPrivateKey, PrivateKey, Wallet, WalletBuilder};
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    fn wallet_with_stake_key() -> Wallet {
+        let payment_key = PrivateKey::from_bytes([1u8; 32]).unwrap();
+        let stake_key = PrivateKey::from_bytes([2u8; 32]).unwrap();
+        Wallet {
+            network: Network::Testnet,
+            address: ShelleyAddress::new(
+                Network::Testnet,
+                ShelleyPaymentPart::Key(payment_key.hash()),
+                ShelleyDelegationPart::Key(stake_key.hash()),
+            ),
+            payment_key,
+            stake_key: Some(stake_key),
+            pool: vec![],
+        }
+    }
+
+    /// Cheap KDF parameters so tests don't spend seconds deriving keys.
+    fn encrypt(wallet: &Wallet) -> Keystore {
+        Keystore::encrypt_with(wallet, PASSPHRASE, 1_000, [3u8; 32], [4u8; 24]).unwrap()
+    }
+
+    #[test]
+    fn round_trip_through_file() {
+        let (wallet, _) = WalletBuilder::new(Network::Testnet)
+            .generate()
+            .expect("generate wallet");
+        let path =
+            std::env::temp_dir().join(format!("hose-keystore-{}.json", rand::random::<u64>()));
+        encrypt(&wallet).save(&path).expect("save keystore");
+
+        let restored = WalletBuilder::new(Network::Testnet).from_keystore(&path, PASSPHRASE);
+        std::fs::remove_file(&path).ok();
+
+        let restored = restored.expect("restore from keystore");
+        assert_eq!(restored.address(), wallet.address());
+        assert_eq!(restored.public_key(), wallet.public_key());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_file_is_private_to_its_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path =
+            std::env::temp_dir().join(format!("hose-keystore-{}.json", rand::random::<u64>()));
+        // An existing world-readable file is tightened too
+        std::fs::write(&path, "").expect("create file");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        encrypt(&wallet_with_stake_key())
+            .save(&path)
+            .expect("save keystore");
+        let mode = std::fs::metadata(&path).map(|metadata| metadata.permissions().mode());
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(mode.expect("keystore metadata") & 0o777, 0o600);
+    }
+
+    #[test]
+    fn round_trip_keeps_stake_key() {
+        let wallet = wallet_with_stake_key();
+        let (payment_key, stake_key) = encrypt(&wallet).decrypt(PASSPHRASE).expect("decrypt");
+
+        assert_eq!(payment_key.public_key(), wallet.payment_key.public_key());
+        assert_eq!(
+            stake_key.map(|key| key.public_key()),
+            wallet.stake_key.as_ref().map(|key| key.public_key())
+        );
+    }
+
+    #[test]
+    fn round_trip_keeps_an_extended_stake_key_behind_a_normal_payment_key() {
+        let (root, _) = HDPrivateKey::generate_with_mnemonic("").expect("generate root key");
+        let wallet = Wallet {
+            stake_key: Some(root.private_key()),
+            ..wallet_with_stake_key()
+        };
+        let (payment_key, stake_key) = encrypt(&wallet).decrypt(PASSPHRASE).expect("decrypt");
+
+        assert_eq!(payment_key.public_key(), wallet.payment_key.public_key());
+        assert_eq!(
+            stake_key.map(|key| key.public_key()),
+            wallet.stake_key.as_ref().map(|key| key.public_key())
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase_is_an_error() {
+        let keystore = encrypt(&wallet_with_stake_key());
+        assert!(matches!(
+            keystore.decrypt("hunter2"),
+            Err(Error::KeystoreDecryption)
+        ));
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let keystore = encrypt(&wallet_with_stake_key());
+
+        let mut ciphertext = hex::decode(&keystore.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        let tampered_ciphertext = Keystore {
+            ciphertext: hex::encode(ciphertext),
+            ..keystore.clone()
+        };
+        assert!(matches!(
+            tampered_ciphertext.decrypt(PASSPHRASE),
+            Err(Error::KeystoreDecryption)
+        ));
+
+        let mut tampered_kdf = keystore.clone();
+        tampered_kdf.kdf.iterations += 1;
+        assert!(matches!(
+            tampered_kdf.decrypt(PASSPHRASE),
+            Err(Error::KeystoreDecryption)
+        ));
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        let keystore = Keystore {
+            version: 2,
+            ..encrypt(&wallet_with_stake_key())
+        };
+        assert!(matches!(
+            keystore.decrypt(PASSPHRASE),
+            Err(Error::UnsupportedKeystore(_))
+        ));
+    }
+
+    /// Guards the on-disk format: keystores written by earlier releases must keep decrypting.
+    #[test]
+    fn decrypts_v1_fixture() {
+        let keystore: Keystore = serde_json::from_str(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/keystore_v1.json"
+        )))
+        .expect("parse fixture");
+
+        let (payment_key, stake_key) = keystore.decrypt(PASSPHRASE).expect("decrypt fixture");
+        assert_eq!(
+            hex::encode(payment_key.public_key()),
+            "79b5562e8fe654f94078b112e8a98ba7901f853ae695bed7e0e3910bad049664"
+        );
+        assert!(stake_key.is_none());
+    }
+}
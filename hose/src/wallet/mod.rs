@@ -1,15 +1,28 @@
+use std::collections::HashSet;
+#[cfg(feature = "keystore")]
+use std::path::Path;
+
 use pallas::crypto::key::ed25519::{self, TryFromSecretKeyExtendedError};
 use pallas::ledger::addresses::{Address, Network, ShelleyAddress};
 use thiserror::Error;
 
 use crate::builder::tx::BuiltTransaction;
+use crate::builder::{SignatureBundle, SigningRequest};
+use crate::ogmios::{OgmiosQueries, StakeInfo};
+use crate::primitives::{Hash, PubKeyHash, StakeCredential};
 
 mod builder;
 mod hd_key;
 mod key;
+#[cfg(feature = "keystore")]
+mod keystore;
+mod multi;
 pub use builder::{AddressType, WalletBuilder};
 pub use hd_key::HDPrivateKey;
 pub use key::PrivateKey;
+#[cfg(feature = "keystore")]
+pub use keystore::{CipherParams, KdfParams, Keystore};
+pub use multi::MultiWallet;
 
 pub struct Wallet {
     network: Network,
@@ -17,8 +30,10 @@ pub struct Wallet {
     /// Key used for signing/receiving transactions (derivation path: m/1852'/1815'/0'/0/address_index)
     payment_key: PrivateKey,
     /// Key used for receiving staking rewards (derivation path: m/1852'/1815'/0'/2/address_index)
-    #[allow(dead_code)] // TODO: add staking
     stake_key: Option<PrivateKey>,
+    /// Addresses derived at the following address indices along with their payment key, see
+    /// `WalletBuilder::address_pool_size`
+    pool: Vec<(ShelleyAddress, PrivateKey)>,
 }
 
 impl Wallet {
@@ -34,16 +49,107 @@ impl Wallet {
         Address::Shelley(self.address.clone())
     }
 
+    /// The wallet address followed by the rest of the address pool, in derivation order. Funds
+    /// can be spread across them by selecting inputs with `TxBuilder::address_pool`.
+    pub fn all_addresses(&self) -> Vec<Address> {
+        std::iter::once(&self.address)
+            .chain(self.pool.iter().map(|(address, _)| address))
+            .map(|address| Address::Shelley(address.clone()))
+            .collect()
+    }
+
     pub fn public_key(&self) -> ed25519::PublicKey {
         self.payment_key.public_key()
     }
 
+    pub(crate) fn stake_key(&self) -> Option<&PrivateKey> {
+        self.stake_key.as_ref()
+    }
+
+    /// Credential of the stake key, for wallets with base addresses.
+    pub fn stake_credential(&self) -> Option<StakeCredential> {
+        self.stake_key
+            .as_ref()
+            .map(|key| StakeCredential::Key(Hash::from(key.hash())))
+    }
+
+    /// Registration, delegations and rewards of the wallet stake key, `None` for wallets without
+    /// one.
+    pub async fn stake_info(
+        &self,
+        ogmios: &OgmiosQueries,
+    ) -> Result<Option<StakeInfo>, crate::ogmios::Error> {
+        let Some(credential) = self.stake_credential() else {
+            return Ok(None);
+        };
+        Ok(ogmios.stake_info(&[credential]).await?.pop())
+    }
+
+    /// The wallet payment key followed by those of the address pool.
+    pub(crate) fn payment_keys(&self) -> impl Iterator<Item = &PrivateKey> {
+        std::iter::once(&self.payment_key).chain(self.pool.iter().map(|(_, key)| key))
+    }
+
+    /// Encodes the payment key as bech32, so the wallet can be persisted and restored later with
+    /// `WalletBuilder::from_bech32`.
+    pub fn to_bech32_private_key(&self) -> Result<String, Error> {
+        self.payment_key.to_bech32()
+    }
+
+    /// Encrypts the wallet keys with `passphrase` and writes them to `path`, so the wallet can be
+    /// restored later with `WalletBuilder::from_keystore`.
+    #[cfg(feature = "keystore")]
+    pub fn save_keystore(&self, path: impl AsRef<Path>, passphrase: &str) -> Result<(), Error> {
+        Keystore::encrypt(self, passphrase)?.save(path)
+    }
+
     pub fn sign(&self, tx: &BuiltTransaction) -> anyhow::Result<BuiltTransaction> {
         let signature = self.payment_key.sign(tx.hash.0);
         let signature = signature.as_ref().try_into().unwrap();
         let tx = tx.clone().add_signature(self.public_key(), signature)?;
         Ok(tx)
     }
+
+    /// Signs with every payment key of the address pool whose hash is in `required`, or with the
+    /// wallet key alone if none is.
+    pub fn sign_required(
+        &self,
+        tx: &BuiltTransaction,
+        required: &HashSet<PubKeyHash>,
+    ) -> anyhow::Result<BuiltTransaction> {
+        let keys = self
+            .payment_keys()
+            .filter(|key| required.contains(&Hash::from(key.hash())))
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return self.sign(tx);
+        }
+
+        let mut tx = tx.clone();
+        for key in keys {
+            let signature = key.sign(tx.hash.0);
+            let signature = signature.as_ref().try_into().unwrap();
+            tx = tx.add_signature(key.public_key(), signature)?;
+        }
+        Ok(tx)
+    }
+
+    /// Signs the transaction described by `request`, after checking that it's meant for this
+    /// wallet's network and that its body matches the hash. See `BuiltTx::to_signing_request`.
+    pub fn sign_request(&self, request: &SigningRequest) -> anyhow::Result<SignatureBundle> {
+        anyhow::ensure!(
+            request.network_id == self.network.value(),
+            "signing request is for network {}, the wallet is on network {}",
+            request.network_id,
+            self.network.value()
+        );
+        let hash = request.verified_hash()?;
+        Ok(SignatureBundle {
+            key_hash: hex::encode(self.payment_key.hash()),
+            pub_key: hex::encode(self.public_key()),
+            signature: hex::encode(self.payment_key.sign(hash)),
+        })
+    }
 }
 
 #[derive(Error, Debug)]
@@ -54,6 +160,9 @@ pub enum Error {
     /// Unable to decode bech32 string
     #[error("Unable to decode bech32: {0}")]
     InvalidBech32(#[from] bech32::DecodeError),
+    /// Unable to encode bech32 string
+    #[error("Unable to encode bech32: {0}")]
+    Bech32Encode(#[from] bech32::EncodeError),
     /// Decoded data of unexpected length
     #[error("Decoded data of unexpected length")]
     UnexpectedKeyLength,
@@ -73,4 +182,30 @@ pub enum Error {
     /// which is not valid.
     #[error("Invalid Ed25519 Extended Secret Key: {0}")]
     InvalidSecretKeyExtended(#[from] TryFromSecretKeyExtendedError),
+    /// Unable to read or write a keystore file
+    #[cfg(feature = "keystore")]
+    #[error("Unable to read or write keystore: {0}")]
+    KeystoreIo(#[from] std::io::Error),
+    /// Keystore file isn't valid JSON or is missing fields
+    #[cfg(feature = "keystore")]
+    #[error("Malformed keystore: {0}")]
+    MalformedKeystore(#[from] serde_json::Error),
+    /// Keystore uses a version, key derivation or cipher this release doesn't know about
+    #[cfg(feature = "keystore")]
+    #[error("Unsupported keystore: {0}")]
+    UnsupportedKeystore(String),
+    /// Keys could not be encrypted
+    #[cfg(feature = "keystore")]
+    #[error("Unable to encrypt keystore")]
+    KeystoreEncryption,
+    /// Wrong passphrase, or the keystore was tampered with
+    #[cfg(feature = "keystore")]
+    #[error("Unable to decrypt keystore: wrong passphrase or corrupted file")]
+    KeystoreDecryption,
+    /// More than one address was requested from a key that can't derive others
+    #[error("An address pool can only be derived from a mnemonic, root or account key")]
+    AddressPoolUnavailable,
+    /// Wallets grouped in a `MultiWallet` are on different networks
+    #[error("Wallets of a multi-wallet must share a network, found {0:?} and {1:?}")]
+    MixedNetworks(Network, Network),
 }
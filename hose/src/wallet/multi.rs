@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, ensure};
+use pallas::ledger::addresses::{Address, Network};
+
+use super::{Error, PrivateKey, Wallet};
+use crate::builder::BuiltTx;
+use crate::primitives::{Hash, PubKeyHash};
+
+/// Several wallets operated as one, e.g. while funds move from a raw key to an HD wallet and both
+/// still control UTxOs.
+///
+/// Inputs are selected from the addresses of every member with `TxBuilder::spend_from`, change
+/// goes to the primary wallet's address, and `sign_all` signs each input with the member key that
+/// controls it.
+pub struct MultiWallet {
+    primary: Wallet,
+    others: Vec<Wallet>,
+}
+
+impl MultiWallet {
+    /// Groups `primary`, which receives change, with `others`. They must all be on the same
+    /// network.
+    pub fn new(primary: Wallet, others: Vec<Wallet>) -> Result<Self, Error> {
+        if let Some(other) = others
+            .iter()
+            .find(|wallet| wallet.network() != primary.network())
+        {
+            return Err(Error::MixedNetworks(primary.network(), other.network()));
+        }
+        Ok(Self { primary, others })
+    }
+
+    pub fn network(&self) -> Network {
+        self.primary.network()
+    }
+
+    pub fn primary(&self) -> &Wallet {
+        &self.primary
+    }
+
+    /// Address of the primary wallet, where change goes.
+    pub fn change_address(&self) -> Address {
+        self.primary.address()
+    }
+
+    /// Every address of every member, primary first, without duplicates.
+    pub fn addresses(&self) -> Vec<Address> {
+        let mut addresses = vec![];
+        for address in self.wallets().flat_map(Wallet::all_addresses) {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+        addresses
+    }
+
+    /// Signs with the member keys that must sign, after checking that every key-locked input is
+    /// controlled by one of them. Fails without signing anything otherwise.
+    pub fn sign_all(&self, tx: BuiltTx) -> Result<BuiltTx> {
+        let keys = self.payment_keys();
+        let mut foreign = tx
+            .input_owners()
+            .iter()
+            .filter(|(_, owner)| !keys.iter().any(|(hash, _)| hash == *owner))
+            .map(|(input, owner)| format!("{}#{} (owned by {owner})", input.hash, input.index))
+            .collect::<Vec<_>>();
+        foreign.sort_unstable();
+        ensure!(
+            foreign.is_empty(),
+            "inputs not controlled by any wallet: {}",
+            foreign.join(", ")
+        );
+
+        let required = tx.required_signers();
+        tx.add_signatures(
+            keys.into_iter()
+                .filter(|(hash, _)| required.contains(hash))
+                .map(|(_, key)| key),
+        )
+    }
+
+    fn wallets(&self) -> impl Iterator<Item = &Wallet> {
+        std::iter::once(&self.primary).chain(self.others.iter())
+    }
+
+    /// Payment keys of every member along with their hash, without duplicates.
+    fn payment_keys(&self) -> Vec<(PubKeyHash, &PrivateKey)> {
+        let mut seen = HashSet::new();
+        self.wallets()
+            .flat_map(Wallet::payment_keys)
+            .map(|key| (Hash::from(key.hash()), key))
+            .filter(|(hash, _)| seen.insert(*hash))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::Network;
+
+    use super::MultiWallet;
+    use crate::wallet::{Error, WalletBuilder};
+
+    #[test]
+    fn addresses_span_every_member_primary_first() {
+        let raw = WalletBuilder::new(Network::Testnet)
+            .from_hex(hex::encode([1u8; 32]))
+            .unwrap();
+        let (hd, _) = WalletBuilder::new(Network::Testnet)
+            .address_pool_size(2)
+            .generate()
+            .unwrap();
+        let mut expected = hd.all_addresses();
+        expected.push(raw.address());
+
+        let wallets = MultiWallet::new(hd, vec![raw]).unwrap();
+        assert_eq!(wallets.change_address(), expected[0]);
+        assert_eq!(wallets.addresses(), expected);
+    }
+
+    #[test]
+    fn members_must_share_a_network() {
+        let wallet = |network| {
+            WalletBuilder::new(network)
+                .from_hex(hex::encode([1u8; 32]))
+                .unwrap()
+        };
+        assert!(matches!(
+            MultiWallet::new(wallet(Network::Testnet), vec![wallet(Network::Mainnet)]),
+            Err(Error::MixedNetworks(Network::Testnet, Network::Mainnet))
+        ));
+    }
+}